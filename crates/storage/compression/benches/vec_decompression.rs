@@ -0,0 +1,81 @@
+#![allow(non_snake_case)]
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use alloy_primitives::Bytes;
+use compression::Compressor;
+
+/*
+  Compares decoding a Vec<T> written using the grouped encoding (the lengths of the elements next to
+  each other, decoded in batches) against the interleaved one (each length right before its element,
+  decoded one at a time). Values written before the grouped encoding was introduced are
+  interleaved.
+*/
+
+const ELEMENT_COUNTS: [usize; 3]= [64, 1024, 16384];
+
+fn writeUsize(mut n: usize, buffer: &mut Vec<u8>) {
+  while n >= 0x80 {
+    buffer.push((n as u8) | 0x80);
+    n >>= 7;
+  }
+  buffer.push(n as u8);
+}
+
+// Encodes the Vec using the interleaved encoding.
+fn interleaved<T>(values: &[T]) -> Vec<u8>
+  where
+    T: Compressor + Clone
+{
+  let mut buffer= Vec::new( );
+  writeUsize(values.len( ), &mut buffer);
+
+  for value in values {
+    let mut element= Vec::new( );
+    let len= value.clone( ).compress(&mut element);
+
+    writeUsize(len, &mut buffer);
+    buffer.extend_from_slice(&element);
+  }
+
+  buffer
+}
+
+fn grouped<T>(values: &[T]) -> Vec<u8>
+  where
+    T: Compressor + Clone
+{
+  let mut buffer= Vec::new( );
+  values.to_vec( ).compress(&mut buffer);
+  buffer
+}
+
+fn benchDecompression<T>(criterion: &mut Criterion, name: &str, valueAt: impl Fn(usize) -> T)
+  where
+    T: Compressor + Clone
+{
+  let mut group= criterion.benchmark_group(name);
+
+  for elementCount in ELEMENT_COUNTS {
+    let values: Vec<T>= (0..elementCount).map(&valueAt).collect( );
+    group.throughput(Throughput::Elements(elementCount as u64));
+
+    for (encoding, encoded) in [("interleaved", interleaved(&values)), ("grouped", grouped(&values))] {
+      group.bench_with_input(BenchmarkId::new(encoding, elementCount), &encoded, |bencher, encoded| {
+        bencher.iter(|| Vec::<T>::decompress(black_box(encoded), encoded.len( )))
+      });
+    }
+  }
+
+  group.finish( );
+}
+
+fn vecDecompression(criterion: &mut Criterion) {
+  // e.g. gas used / log indices : mostly 1 to 3 bytes.
+  benchDecompression(criterion, "Vec<u64>", |i| (i as u64 * 7919) % 100_000);
+
+  // e.g. log data.
+  benchDecompression(criterion, "Vec<Bytes>", |i| Bytes::from(vec![i as u8; i % 48]));
+}
+
+criterion_group!(benches, vecDecompression);
+criterion_main!(benches);
@@ -0,0 +1,20 @@
+// Returned by Compressor::tryDecompress, when the buffer doesn't hold a valid encoded value (e.g.
+// it got corrupted on disk).
+#[derive(Debug, thiserror::Error)]
+pub enum DecompressionError {
+
+  #[error("Encoded value is truncated")]
+  Truncated,
+
+  #[error("Unknown bloom encoding {0}")]
+  UnknownBloomEncoding(usize),
+
+  #[error("Bloom bit index {0} is out of bounds")]
+  BloomBitIndexOutOfBounds(usize),
+
+  #[error("Dictionary index {0} is out of bounds")]
+  DictionaryIndexOutOfBounds(usize),
+
+  #[error("Trimmed topic is {0} bytes long")]
+  TrimmedTopicTooLong(usize)
+}
@@ -3,6 +3,14 @@
 use alloy_primitives::{Address, Bloom, Bytes, B256, B512, U256};
 use bytes::{Buf, BufMut};
 
+pub mod serde_bridge;
+mod receipt_logs;
+mod decompression_error;
+#[cfg(feature = "zstd")]
+pub mod zstd_dictionaries;
+
+pub use decompression_error::DecompressionError;
+
 pub trait Compressor: Sized {
 
   // Takes a buffer which can be written to. (Ideally) returns the length written to.
@@ -17,6 +25,45 @@ pub trait Compressor: Sized {
                 // Can either be the buffer remaining length, or the length of the compacted type.
                 len: usize) -> (Self, &[u8]);
 
+  // Same as decompress, but returns an error instead of panicking when the buffer doesn't hold a
+  // valid encoded value. Meant for values read back from disk. The default implementation just
+  // calls decompress, so it still panics : types overriding it implement CheckedDecompressor.
+  fn tryDecompress(buffer: &[u8], len: usize) -> Result<(Self, &[u8]), DecompressionError> {
+    Ok(Self::decompress(buffer, len))
+  }
+
+  // Used by Vec<T> and Option<T>, which record the returned value as the length of the element.
+  // Override implementation when compress returns something else (like Bloom, which returns the
+  // encoding used).
+  fn compressAsElement<B>(self, buffer: &mut B) -> usize
+    where
+      B: BufMut
+  { self.compress(buffer) }
+
+  // Decompresses an element written using compressAsElement, given its length.
+  fn decompressAsElement(buffer: &[u8], len: usize) -> (Self, &[u8]) {
+    Self::decompress(buffer, len)
+  }
+
+  // Same as decompressAsElement, but returns an error instead of panicking (see tryDecompress).
+  fn tryDecompressAsElement(buffer: &[u8], len: usize) -> Result<(Self, &[u8]), DecompressionError> {
+    Ok(Self::decompressAsElement(buffer, len))
+  }
+
+  // Decompresses elements written one after the other, given the length of each of them (returned
+  // by compressAsElement). Used by Vec<T>::decompress. Override implementation when the elements
+  // can be decoded faster as a batch.
+  fn decompressBatch<'a>(mut buffer: &'a [u8], lens: &[usize], elements: &mut Vec<Self>) -> &'a [u8] {
+    for len in lens {
+      let (element, _)= Self::decompressAsElement(buffer, *len);
+      buffer.advance(*len);
+
+      elements.push(element);
+    }
+
+    buffer
+  }
+
   // Override implementation and use when dealing with fixed-size bytes.
   fn compressFixedSizeBytes<B>(self, buffer: &mut B) -> usize
     where
@@ -29,6 +76,10 @@ pub trait Compressor: Sized {
   }
 }
 
+// Implemented by the types whose tryDecompress returns an error, rather than panicking, whatever
+// the buffer holds.
+pub trait CheckedDecompressor: Compressor { }
+
 macro_rules! uint_types_impl_compressor {
   ($($type_name:tt),+) => {
     $(
@@ -60,6 +111,31 @@ macro_rules! uint_types_impl_compressor {
 
           ($type_name::from_be_bytes(uintAsBytes), buffer)
         }
+
+        /*
+          Instead of copying each value into a zeroed array, UINT_TYPE_SIZE bytes are loaded at
+          once from where the value starts, and the bytes following the value are shifted out. The
+          last values, for which there aren't enough bytes left, are decompressed the usual way.
+        */
+        fn decompressBatch<'a>(buffer: &'a [u8], lens: &[usize], elements: &mut Vec<Self>) -> &'a [u8] {
+          const UINT_TYPE_SIZE: usize= core::mem::size_of::<$type_name>( );
+
+          let mut position= 0;
+          for len in lens {
+            let element= match buffer.get(position..(position + UINT_TYPE_SIZE)) {
+              Some(window) if *len <= UINT_TYPE_SIZE =>
+                $type_name::from_be_bytes(window.try_into( ).unwrap( )).checked_shr(((UINT_TYPE_SIZE - *len) * 8) as u32)
+                                                                      .unwrap_or(0),
+
+              _ => Self::decompress(&buffer[position..], *len).0
+            };
+            elements.push(element);
+
+            position += *len;
+          }
+
+          &buffer[position..]
+        }
       }
     )+ // '+' means repeat the contents inside for each match.
   };
@@ -95,6 +171,29 @@ impl Compressor for U256 {
   }
 }
 
+// Marks a Vec<T> written using the grouped encoding. It's a non-canonical encoding of 0 as a
+// compressed usize, which compressUsize never produces. So it can't be mistaken for the element
+// count, which the interleaved encoding starts with.
+const GROUPED_VEC_MARKER: [u8; 2]= [0x80, 0x00];
+
+// Vecs with less elements than this are written using the interleaved encoding.
+pub const GROUPED_VEC_MIN_LEN: usize= 8;
+
+/*
+  A Vec<T> is written in one of these ways :
+
+  (1) Interleaved : the element count, followed by the length and the bytes of each element.
+  (2) Grouped : GROUPED_VEC_MARKER and the element count, followed by the lengths of all the
+      elements, and then the bytes of all the elements.
+
+  Counts and lengths are compressed usizes. Having the lengths next to each other, lets
+  decompress decode them in batches of 8 (see decompressUsizes), rather than one at a time in
+  between the elements.
+
+  Vecs with at least GROUPED_VEC_MIN_LEN elements are written grouped. Smaller ones are written
+  interleaved, avoiding the 2 bytes of the marker. Both encodings are decoded, so values written
+  before the grouped encoding was introduced stay readable.
+*/
 impl<T> Compressor for Vec<T>
   where
     T: Compressor
@@ -105,31 +204,59 @@ impl<T> Compressor for Vec<T>
     where
       B: BufMut
   {
+    if self.len( ) < GROUPED_VEC_MIN_LEN {
+      compressUsize(self.len( ), buffer);
+
+      let mut temp: Vec<u8> = Vec::with_capacity(64);
+      for element in self {
+        temp.clear( );
+
+        let bufferSizeOccupiedByElement= element.compressAsElement(&mut temp);
+        compressUsize(bufferSizeOccupiedByElement, buffer);
+
+        buffer.put_slice(&temp);
+      }
+
+      return 0
+    }
+
+    buffer.put_slice(&GROUPED_VEC_MARKER);
     compressUsize(self.len( ), buffer);
 
-    let mut temp: Vec<u8> = Vec::with_capacity(64);
+    let mut elements: Vec<u8>= Vec::with_capacity(self.len( ) * 8);
     for element in self {
-      temp.clear( );
-
-      let bufferSizeOccupiedByElement= element.compress(&mut temp);
+      let bufferSizeOccupiedByElement= element.compressAsElement(&mut elements);
       compressUsize(bufferSizeOccupiedByElement, buffer);
-
-      buffer.put_slice(&temp);
     }
+    buffer.put_slice(&elements);
 
     0
   }
 
   #[inline]
   fn decompress(buffer: &[u8], _: usize) -> (Self, &[u8]) {
+    if buffer.starts_with(&GROUPED_VEC_MARKER) {
+      let (vecLen, buffer)= decompressUsize(&buffer[GROUPED_VEC_MARKER.len( )..]);
+
+      // Every length takes at least a byte, so a corrupted count can't make us allocate more than
+      // the size of the buffer.
+      let mut elementLens= Vec::with_capacity(vecLen.min(buffer.len( )));
+      let buffer= decompressUsizes(buffer, vecLen, &mut elementLens);
+
+      let mut vec= Vec::with_capacity(elementLens.len( ));
+      let buffer= T::decompressBatch(buffer, &elementLens, &mut vec);
+
+      return (vec, buffer)
+    }
+
     let (vecLen, mut buffer)= decompressUsize(buffer);
 
-    let mut vec= Vec::with_capacity(vecLen);
+    let mut vec= Vec::with_capacity(vecLen.min(buffer.len( )));
     for _ in 0..vecLen {
       let bufferSizeOccupiedByElement;
       (bufferSizeOccupiedByElement, buffer)= decompressUsize(buffer);
 
-      let (element, _) = T::decompress(buffer, bufferSizeOccupiedByElement);
+      let (element, _) = T::decompressAsElement(buffer, bufferSizeOccupiedByElement);
       buffer.advance(bufferSizeOccupiedByElement);
 
       vec.push(element);
@@ -146,7 +273,7 @@ impl<T> Compressor for Vec<T>
     compressUsize(self.len( ), buffer);
 
     for element in self {
-      element.compress(buffer);
+      element.compressFixedSizeBytes(buffer);
     }
 
     0
@@ -159,7 +286,7 @@ impl<T> Compressor for Vec<T>
     let mut vec= Vec::with_capacity(vecLen);
     for _ in 0..vecLen {
       let element;
-      (element, buffer)= T::decompress(buffer, len);
+      (element, buffer)= T::decompressFixedSizeBytes(buffer, len);
 
       vec.push(element);
     }
@@ -184,7 +311,7 @@ impl<T> Compressor for Option<T>
     let element= self.unwrap( );
 
     let mut temp: Vec<u8> = Vec::with_capacity(64);
-    let bufferSizeOccupiedByElement= element.compress(&mut temp);
+    let bufferSizeOccupiedByElement= element.compressAsElement(&mut temp);
 
     compressUsize(bufferSizeOccupiedByElement, buffer);
     buffer.put_slice(&temp);
@@ -199,7 +326,7 @@ impl<T> Compressor for Option<T>
 
     let (bufferSizeOccupiedByElement, mut buffer)= decompressUsize(buffer);
 
-    let (element, _) = T::decompress(&buffer[..bufferSizeOccupiedByElement], bufferSizeOccupiedByElement);
+    let (element, _) = T::decompressAsElement(&buffer[..bufferSizeOccupiedByElement], bufferSizeOccupiedByElement);
     buffer.advance(bufferSizeOccupiedByElement);
 
     (Some(element), buffer)
@@ -212,7 +339,7 @@ impl<T> Compressor for Option<T>
   {
     match self {
       Some(value) => {
-        value.compress(buffer);
+        value.compressFixedSizeBytes(buffer);
         1
       },
 
@@ -226,7 +353,7 @@ impl<T> Compressor for Option<T>
       return (None, buffer)
     }
 
-    let (value, buffer) = T::decompress(buffer, len);
+    let (value, buffer) = T::decompressFixedSizeBytes(buffer, len);
     (Some(value), buffer)
   }
 }
@@ -304,9 +431,179 @@ macro_rules! fixed_size_bytes_types_impl_compressor {
     )+ // '+' means repeat the contents inside for each match.
   };
 }
-fixed_size_bytes_types_impl_compressor!(Address, B256, B512, Bloom);
+fixed_size_bytes_types_impl_compressor!(Address, B256, B512);
+
+const BLOOM_SIZE: usize= 256;
+
+// Values returned by Bloom::compress, which end up in the StructFlags. Bloom::decompress expects
+// one of them in len.
+pub const BLOOM_ENCODING_EMPTY: usize= 0;
+pub const BLOOM_ENCODING_SPARSE: usize= 1;
+pub const BLOOM_ENCODING_RAW: usize= 2;
+
+// Blooms written before the encodings above existed are the raw 256 bytes, with their length in
+// the StructFlags (or recorded as the length of the element, in a Vec<Bloom> or Option<Bloom>).
+// They're still read, so that existing databases don't need to be resynced.
+const LEGACY_BLOOM_LEN: usize= BLOOM_SIZE;
+
+/*
+  Most block and transaction receipt blooms have only a handful of bits set (each log sets at most
+  3 bits per address / topic). Storing all 256 bytes is wasteful in such cases.
+
+  So, depending on the number of set bits, a Bloom is written in one of these ways :
+
+  (1) Empty bloom : nothing is written.
+  (2) Sparse bloom : the count of set bits (as a compressed usize) followed by the index
+      (0..2048) of each set bit, as 2 big-endian bytes.
+  (3) Dense bloom : the raw 256 bytes.
+
+  The sparse encoding is used only when it's smaller than the raw one, i.e. when less than 128 bits
+  are set.
+
+  Elements of a Vec<Bloom> or Option<Bloom> have no place in the StructFlags. So the encoding is
+  written as a byte in front of them instead (see compressAsElement). Such an element never takes
+  exactly 256 bytes, which tells it apart from a legacy one (see LEGACY_BLOOM_LEN).
+*/
+impl Compressor for Bloom {
+  // Returns the encoding used (see BLOOM_ENCODING_*) rather than the length written.
+  #[inline]
+  fn compress<B>(self, buffer: &mut B) -> usize
+    where
+      B: BufMut
+  {
+    let bloomAsBytes: &[u8; BLOOM_SIZE]= &self.0;
+
+    let setBitCount: usize= bloomAsBytes.iter( ).map(|byte| byte.count_ones( ) as usize).sum( );
+
+    if setBitCount == 0 {
+      return BLOOM_ENCODING_EMPTY
+    }
+
+    if (2 * setBitCount) >= BLOOM_SIZE {
+      buffer.put_slice(bloomAsBytes);
+      return BLOOM_ENCODING_RAW
+    }
+
+    compressUsize(setBitCount, buffer);
+
+    for (byteIndex, byte) in bloomAsBytes.iter( ).enumerate( ) {
+      let mut remainingBits= *byte;
+
+      while remainingBits != 0 {
+        let bitPosition= remainingBits.trailing_zeros( ) as usize;
+        buffer.put_u16((byteIndex * 8 + bitPosition) as u16);
+
+        remainingBits &= remainingBits - 1; // Unsets the lowest set bit.
+      }
+    }
+
+    BLOOM_ENCODING_SPARSE
+  }
+
+  #[inline]
+  fn decompress(buffer: &[u8], len: usize) -> (Self, &[u8]) {
+    match Self::tryDecompress(buffer, len) {
+      Ok(result) => result,
+      Err(error) => panic!("could not decode bloom : {}", error)
+    }
+  }
+
+  fn tryDecompress(mut buffer: &[u8], len: usize) -> Result<(Self, &[u8]), DecompressionError> {
+    match len {
+      BLOOM_ENCODING_EMPTY => Ok((Bloom::ZERO, buffer)),
+
+      BLOOM_ENCODING_SPARSE => {
+        let (setBitCount, mut buffer)= tryDecompressUsize(buffer)?;
+        if buffer.len( ) < setBitCount.saturating_mul(2) {
+          return Err(DecompressionError::Truncated)
+        }
+
+        let mut bloomAsBytes= [0u8; BLOOM_SIZE];
+        for _ in 0..setBitCount {
+          let bitIndex= buffer.get_u16( ) as usize;
+          if bitIndex >= BLOOM_SIZE * 8 {
+            return Err(DecompressionError::BloomBitIndexOutOfBounds(bitIndex))
+          }
+
+          bloomAsBytes[bitIndex / 8] |= 1 << (bitIndex % 8);
+        }
+
+        Ok((Bloom::from(bloomAsBytes), buffer))
+      },
+
+      BLOOM_ENCODING_RAW | LEGACY_BLOOM_LEN => {
+        let Some(bloomAsBytes)= buffer.get(..BLOOM_SIZE) else {
+          return Err(DecompressionError::Truncated)
+        };
+        let bloom= Bloom::from_slice(bloomAsBytes);
+        buffer.advance(BLOOM_SIZE);
+
+        Ok((bloom, buffer))
+      },
+
+      _ => Err(DecompressionError::UnknownBloomEncoding(len))
+    }
+  }
+
+  #[inline]
+  fn compressAsElement<B>(self, buffer: &mut B) -> usize
+    where
+      B: BufMut
+  {
+    let mut compressedBloom= Vec::with_capacity(BLOOM_SIZE);
+    let mut encoding= self.compress(&mut compressedBloom);
+
+    // A sparse bloom with 127 set bits would take exactly LEGACY_BLOOM_LEN bytes, with the
+    // encoding in front.
+    if 1 + compressedBloom.len( ) == LEGACY_BLOOM_LEN {
+      compressedBloom.clear( );
+      compressedBloom.put_slice(self.as_slice( ));
+      encoding= BLOOM_ENCODING_RAW;
+    }
+
+    buffer.put_u8(encoding as u8);
+    buffer.put_slice(&compressedBloom);
+
+    1 + compressedBloom.len( )
+  }
+
+  #[inline]
+  fn decompressAsElement(buffer: &[u8], len: usize) -> (Self, &[u8]) {
+    match Self::tryDecompressAsElement(buffer, len) {
+      Ok(result) => result,
+      Err(error) => panic!("could not decode bloom : {}", error)
+    }
+  }
+
+  fn tryDecompressAsElement(buffer: &[u8], len: usize) -> Result<(Self, &[u8]), DecompressionError> {
+    if len == LEGACY_BLOOM_LEN {
+      return Self::tryDecompress(buffer, LEGACY_BLOOM_LEN)
+    }
+
+    let Some((&encoding, buffer))= buffer.split_first( ) else {
+      return Err(DecompressionError::Truncated)
+    };
+    Self::tryDecompress(buffer, encoding as usize)
+  }
+
+  // Vec<Bloom> and Option<Bloom> fields of derived structs are written using these, i.e. as the raw
+  // 256 bytes.
+  #[inline]
+  fn compressFixedSizeBytes<B>(self, buffer: &mut B) -> usize
+    where
+      B: BufMut
+  { self.0.compress(buffer) }
+
+  #[inline]
+  fn decompressFixedSizeBytes(buffer: &[u8], len: usize) -> (Self, &[u8]) {
+    let (value, buffer) = <[u8; BLOOM_SIZE]>::decompress(buffer, len);
+    (Self::from(value), buffer)
+  }
+}
 
-fn compressUsize<B>(mut n: usize, buffer: &mut B)
+impl CheckedDecompressor for Bloom { }
+
+pub(crate) fn compressUsize<B>(mut n: usize, buffer: &mut B)
   where
     B: BufMut
 {
@@ -334,7 +631,102 @@ fn compressUsize<B>(mut n: usize, buffer: &mut B)
   buffer.put_u8(n as u8);
 }
 
-fn decompressUsize(buffer: &[u8]) -> (usize, &[u8]) {
+/*
+  Decompresses a usize compressed using compressUsize. It's called once for every element of an
+  interleaved Vec<T> (and by decompressUsizes, for grouped ones), so it's optimized for decoding
+  large lists :
+
+  (1) Most of the values (like lengths of elements in a Vec) fit in a single byte. That case is
+      checked first.
+  (2) Otherwise, if at least 8 bytes are available in the buffer, they are loaded as a single u64
+      and decoded at once (SWAR - SIMD within a register) : the position of the terminating byte
+      is found using the continuation bits, and the 7-bit groups are packed together using 3 shift
+      and mask steps, instead of one step per byte.
+  (3) If the value is larger than 56 bits or the buffer is near its end, we fallback to decoding
+      one byte at a time.
+
+  The encoded format is unchanged.
+*/
+#[inline]
+pub(crate) fn decompressUsize(buffer: &[u8]) -> (usize, &[u8]) {
+  if let Some(&firstByte)= buffer.first( ) {
+    if firstByte < 0x80 {
+      return (firstByte as usize, &buffer[1..])
+    }
+  }
+
+  if buffer.len( ) >= 8 {
+    let word= u64::from_le_bytes(buffer[..8].try_into( ).unwrap( ));
+
+    // Bytes with the continuation bit (most significant bit) unset, mark the end of the value.
+    let terminatingBytes= !word & 0x8080_8080_8080_8080;
+
+    if terminatingBytes != 0 {
+      let bytesOccupied= (terminatingBytes.trailing_zeros( ) as usize / 8) + 1;
+
+      let mut value= word & 0x7F7F_7F7F_7F7F_7F7F;
+      if bytesOccupied < 8 {
+        value &= (1u64 << (bytesOccupied * 8)) - 1;
+      }
+
+      // Pack the 7-bit groups : 8 x 7 bits -> 4 x 14 bits -> 2 x 28 bits -> 56 bits.
+      value= ((value & 0x7F00_7F00_7F00_7F00) >> 1) | (value & 0x007F_007F_007F_007F);
+      value= ((value & 0x3FFF_0000_3FFF_0000) >> 2) | (value & 0x0000_3FFF_0000_3FFF);
+      value= ((value & 0x0FFF_FFFF_0000_0000) >> 4) | (value & 0x0000_0000_0FFF_FFFF);
+
+      return (value as usize, &buffer[bytesOccupied..])
+    }
+  }
+
+  decompressUsizeByteByByte(buffer)
+}
+
+/*
+  Decompresses count usizes, written one after the other using compressUsize, into values.
+
+  Most of them (like lengths of the elements in a Vec) fit in a single byte. So 8 bytes are loaded
+  at once, as a u64 : the leading bytes without the continuation bit are all single byte values,
+  which get pushed at once (all 8 of them, when none has the continuation bit). Only the value
+  following them goes through decompressUsize.
+*/
+#[inline]
+pub(crate) fn decompressUsizes<'a>(mut buffer: &'a [u8], count: usize, values: &mut Vec<usize>) -> &'a [u8] {
+  let targetLen= values.len( ) + count;
+
+  while values.len( ) < targetLen {
+    if buffer.len( ) >= 8 {
+      let word= u64::from_le_bytes(buffer[..8].try_into( ).unwrap( ));
+
+      let singleByteValueCount= ((word & 0x8080_8080_8080_8080).trailing_zeros( ) as usize / 8)
+                                  .min(targetLen - values.len( ));
+      values.extend(buffer[..singleByteValueCount].iter( ).map(|byte| *byte as usize));
+      buffer= &buffer[singleByteValueCount..];
+
+      if singleByteValueCount == 8 || values.len( ) == targetLen {
+        continue
+      }
+    }
+
+    let value;
+    (value, buffer)= decompressUsize(buffer);
+    values.push(value);
+  }
+
+  buffer
+}
+
+// Same as decompressUsize, but returns an error if the buffer ends before the terminating byte.
+pub(crate) fn tryDecompressUsize(buffer: &[u8]) -> Result<(usize, &[u8]), DecompressionError> {
+  let isTerminated= buffer.iter( ).take(10).any(|byte| *byte < 0x80);
+  if !isTerminated {
+    return Err(DecompressionError::Truncated)
+  }
+
+  Ok(decompressUsize(buffer))
+}
+
+#[inline(never)]
+fn decompressUsizeByteByByte(buffer: &[u8]) -> (usize, &[u8]) {
   let mut value= 0;
 
   for i in 0..33 {
@@ -353,3 +745,187 @@ fn decompressUsize(buffer: &[u8]) -> (usize, &[u8]) {
 const fn usizeDecompressorPanic( ) -> ! {
   panic!("could not decode usize");
 }
+
+#[cfg(test)]
+mod tests {
+  use alloy_primitives::Bloom;
+  use super::*;
+
+  fn bloomWithBits(bitIndices: impl IntoIterator<Item = usize>) -> Bloom {
+    let mut bloomAsBytes= [0u8; BLOOM_SIZE];
+    for bitIndex in bitIndices {
+      bloomAsBytes[bitIndex / 8] |= 1 << (bitIndex % 8);
+    }
+    Bloom::from(bloomAsBytes)
+  }
+
+  fn roundTrip<T>(value: T) -> (usize, usize)
+    where
+      T: Compressor + Clone + PartialEq + std::fmt::Debug
+  {
+    let mut buffer= Vec::new( );
+    let len= value.clone( ).compress(&mut buffer);
+    buffer.extend_from_slice(&[0xAB, 0xCD]);
+
+    let (decompressedValue, remaining)= T::decompress(&buffer, len);
+    assert_eq!(decompressedValue, value);
+    assert_eq!(remaining, [0xAB, 0xCD], "the value must be consumed exactly");
+
+    (len, buffer.len( ) - 2)
+  }
+
+  #[test]
+  fn usizeRoundTrips( ) {
+    let values= [0, 1, 127, 128, 300, 16383, 16384, (1 << 56) - 1, 1 << 56, usize::MAX];
+
+    for value in values {
+      let mut encoded= Vec::new( );
+      compressUsize(value, &mut encoded);
+
+      // With and without enough bytes following, for the 8 bytes at once path.
+      for trailingByteCount in [0, 1, 8] {
+        let mut buffer= encoded.clone( );
+        buffer.extend(std::iter::repeat_n(0xFF, trailingByteCount));
+
+        let (decompressedValue, remaining)= decompressUsize(&buffer);
+        assert_eq!(decompressedValue, value);
+        assert_eq!(remaining.len( ), trailingByteCount);
+
+        assert_eq!(tryDecompressUsize(&buffer).unwrap( ).0, value);
+      }
+    }
+
+    assert!(matches!(tryDecompressUsize(&[0x80, 0x80]), Err(DecompressionError::Truncated)));
+  }
+
+  #[test]
+  fn usizesAreDecompressedInBatches( ) {
+    // Single byte values in between multi byte ones, at every alignment.
+    let values: Vec<usize>= (0..100).map(|i| if i % 11 == 0 { 1000 * i + 200 } else { i }).collect( );
+
+    let mut buffer= Vec::new( );
+    for value in &values {
+      compressUsize(*value, &mut buffer);
+    }
+    buffer.push(0xFF);
+
+    for count in [0, 1, 7, 8, 9, values.len( )] {
+      let mut decompressedValues= vec![42];
+      let remaining= decompressUsizes(&buffer, count, &mut decompressedValues);
+
+      assert_eq!(decompressedValues[1..], values[..count]);
+
+      // The buffer is advanced exactly past the decompressed values.
+      let mut restOfDecompressedValues= Vec::new( );
+      let remaining= decompressUsizes(remaining, values.len( ) - count, &mut restOfDecompressedValues);
+      assert_eq!(restOfDecompressedValues, values[count..]);
+      assert_eq!(remaining, [0xFF]);
+    }
+  }
+
+  #[test]
+  fn vecRoundTrips( ) {
+    for len in [0, 1, GROUPED_VEC_MIN_LEN - 1, GROUPED_VEC_MIN_LEN, 1000] {
+      let values: Vec<u64>= (0..len as u64).map(|i| if i % 5 == 0 { 0 } else { i.pow(3) * 1_000_003 }).collect( );
+      roundTrip(values);
+
+      let values: Vec<u128>= (0..len as u128).map(|i| i << 100).collect( );
+      roundTrip(values);
+
+      let values: Vec<u8>= (0..len).map(|i| i as u8).collect( );
+      roundTrip(values);
+
+      let values: Vec<Bytes>= (0..len).map(|i| Bytes::from(vec![i as u8; i % 40])).collect( );
+      roundTrip(values);
+    }
+
+    let mut buffer= Vec::new( );
+    vec![1u64; GROUPED_VEC_MIN_LEN].compress(&mut buffer);
+    assert!(buffer.starts_with(&GROUPED_VEC_MARKER));
+  }
+
+  #[test]
+  fn interleavedVecsStayReadable( ) {
+    // 9 u64s written with the interleaved encoding : the element count, and then the length and
+    // bytes of each element.
+    let mut buffer= vec![9];
+    for value in 0..9u64 {
+      buffer.extend_from_slice(&[1, value as u8 * 30]);
+    }
+
+    let (values, remaining)= Vec::<u64>::decompress(&buffer, buffer.len( ));
+    assert_eq!(values, (0..9).map(|value| value * 30).collect::<Vec<u64>>( ));
+    assert!(remaining.is_empty( ));
+  }
+
+  #[test]
+  fn bloomRoundTrips( ) {
+    assert_eq!(roundTrip(Bloom::ZERO), (BLOOM_ENCODING_EMPTY, 0));
+
+    let (encoding, size)= roundTrip(bloomWithBits([0, 7, 8, 1000, 2047]));
+    assert_eq!((encoding, size), (BLOOM_ENCODING_SPARSE, 1 + 5 * 2));
+
+    // Sparse up to 127 set bits, raw from 128 on.
+    assert_eq!(roundTrip(bloomWithBits((0..127).map(|i| i * 16))).0, BLOOM_ENCODING_SPARSE);
+    assert_eq!(roundTrip(bloomWithBits((0..128).map(|i| i * 16))), (BLOOM_ENCODING_RAW, BLOOM_SIZE));
+    assert_eq!(roundTrip(Bloom::repeat_byte(0xFF)), (BLOOM_ENCODING_RAW, BLOOM_SIZE));
+  }
+
+  #[test]
+  fn bloomsRoundTripInsideVecsAndOptions( ) {
+    let blooms= [Bloom::ZERO, bloomWithBits([3, 1000]), Bloom::repeat_byte(0xFF), bloomWithBits([2047])];
+
+    for len in [1, blooms.len( ), GROUPED_VEC_MIN_LEN + 1] {
+      roundTrip(blooms.iter( ).cycle( ).take(len).copied( ).collect::<Vec<Bloom>>( ));
+    }
+
+    for bloom in blooms {
+      roundTrip(Some(bloom));
+    }
+    roundTrip(None::<Bloom>);
+  }
+
+  #[test]
+  fn legacyBloomsAreRead( ) {
+    let bloom= bloomWithBits([3, 1000, 2047]);
+
+    // Written as fixed size bytes, both directly and as elements.
+    let mut buffer= bloom.to_vec( );
+    buffer.push(0xAB);
+    assert_eq!(Bloom::decompress(&buffer, LEGACY_BLOOM_LEN), (bloom, [0xAB].as_slice( )));
+    assert_eq!(Bloom::decompressAsElement(&buffer, LEGACY_BLOOM_LEN).0, bloom);
+
+    let mut buffer= vec![2];
+    for _ in 0..2 {
+      compressUsize(LEGACY_BLOOM_LEN, &mut buffer);
+      buffer.extend_from_slice(bloom.as_slice( ));
+    }
+    assert_eq!(Vec::<Bloom>::decompress(&buffer, buffer.len( )).0, vec![bloom; 2]);
+
+    // Elements never take LEGACY_BLOOM_LEN bytes, even when sparse.
+    let sparseBloom= bloomWithBits((0..127).map(|i| i * 16));
+    let mut buffer= Vec::new( );
+    assert_eq!(sparseBloom.compressAsElement(&mut buffer), 1 + BLOOM_SIZE);
+    assert_eq!(buffer[0] as usize, BLOOM_ENCODING_RAW);
+    roundTrip(vec![sparseBloom]);
+  }
+
+  #[test]
+  fn corruptedBloomIsAnError( ) {
+    assert!(matches!(Bloom::tryDecompress(&[ ], 3), Err(DecompressionError::UnknownBloomEncoding(3))));
+
+    // 1 set bit, at index 2048.
+    assert!(matches!(Bloom::tryDecompress(&[1, 0x08, 0x00], BLOOM_ENCODING_SPARSE),
+                     Err(DecompressionError::BloomBitIndexOutOfBounds(2048))));
+
+    // 2 set bits, but only 1 index.
+    assert!(matches!(Bloom::tryDecompress(&[2, 0x00, 0x01], BLOOM_ENCODING_SPARSE), Err(DecompressionError::Truncated)));
+    assert!(matches!(Bloom::tryDecompress(&[0x80], BLOOM_ENCODING_SPARSE), Err(DecompressionError::Truncated)));
+    assert!(matches!(Bloom::tryDecompress(&[0; 255], BLOOM_ENCODING_RAW), Err(DecompressionError::Truncated)));
+
+    // Elements without the encoding in front.
+    assert!(matches!(Bloom::tryDecompressAsElement(&[ ], 0), Err(DecompressionError::Truncated)));
+    assert!(matches!(Bloom::tryDecompressAsElement(&[BLOOM_ENCODING_RAW as u8], 1), Err(DecompressionError::Truncated)));
+    assert!(matches!(Bloom::tryDecompressAsElement(&[0; 255], LEGACY_BLOOM_LEN), Err(DecompressionError::Truncated)));
+  }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use alloy_primitives::{Address, Bytes, Log, B256};
+use bytes::{Buf, BufMut};
+use super::{compressUsize, tryDecompressUsize, CheckedDecompressor, Compressor, DecompressionError};
+
+/*
+  Logs of a transaction receipt repeat the same contract addresses and event signatures (topic0)
+  over and over - e.g. a token swap emits multiple Transfer events from the same few token
+  contracts. And indexed event parameters are mostly addresses / small numbers, padded to 32 bytes
+  with leading zeros.
+
+  So, instead of writing each log as is, a Vec<Log> is written column-wise :
+
+  (1) Dictionary of distinct log emitting contract addresses.
+  (2) Dictionary of distinct topic0 values (event signatures), with leading zeros trimmed.
+  (3) Then for each log :
+      - index of its address in the address dictionary,
+      - count of topics, followed by the index of topic0 in the topic0 dictionary (if any topic is
+        present) and the remaining topics, with leading zeros trimmed,
+      - length of data, followed by the data.
+
+  All counts, lengths and indices are written as compressed usizes. The dictionaries are part of
+  the encoded value, so it can be decoded without any external state.
+*/
+impl Compressor for Vec<Log> {
+  // Returns 0 since we won't include it in the StructFlags.
+  fn compress<B>(self, buffer: &mut B) -> usize
+    where
+      B: BufMut
+  {
+    let mut addressDictionary= Dictionary::<Address>::default( );
+    let mut topic0Dictionary= Dictionary::<B256>::default( );
+
+    let mut logIndices= Vec::with_capacity(self.len( ));
+    for log in &self {
+      let addressIndex= addressDictionary.indexOf(log.address);
+      let topic0Index= log.topics( ).first( ).map(|topic0| topic0Dictionary.indexOf(*topic0));
+
+      logIndices.push((addressIndex, topic0Index));
+    }
+
+    compressUsize(addressDictionary.values.len( ), buffer);
+    for address in &addressDictionary.values {
+      buffer.put_slice(address.as_slice( ));
+    }
+
+    compressUsize(topic0Dictionary.values.len( ), buffer);
+    for topic0 in &topic0Dictionary.values {
+      compressTrimmedTopic(topic0, buffer);
+    }
+
+    compressUsize(self.len( ), buffer);
+    for (log, (addressIndex, topic0Index)) in self.iter( ).zip(logIndices) {
+      compressUsize(addressIndex, buffer);
+
+      let topics= log.topics( );
+      compressUsize(topics.len( ), buffer);
+
+      if let Some(topic0Index)= topic0Index {
+        compressUsize(topic0Index, buffer);
+      }
+      for topic in topics.iter( ).skip(1) {
+        compressTrimmedTopic(topic, buffer);
+      }
+
+      compressUsize(log.data.data.len( ), buffer);
+      buffer.put_slice(&log.data.data);
+    }
+
+    0
+  }
+
+  fn decompress(buffer: &[u8], len: usize) -> (Self, &[u8]) {
+    match Self::tryDecompress(buffer, len) {
+      Ok(result) => result,
+      Err(error) => panic!("could not decode receipt logs : {}", error)
+    }
+  }
+
+  // The indices and lengths are checked against the dictionaries and the remaining buffer, so
+  // corrupted values are reported instead of panicking.
+  fn tryDecompress(buffer: &[u8], _: usize) -> Result<(Self, &[u8]), DecompressionError> {
+    let (addressCount, mut buffer)= tryDecompressUsize(buffer)?;
+
+    let mut addressDictionary= Vec::with_capacity(addressCount.min(buffer.len( ) / 20));
+    for _ in 0..addressCount {
+      let address= buffer.get(..20).ok_or(DecompressionError::Truncated)?;
+      addressDictionary.push(Address::from_slice(address));
+      buffer.advance(20);
+    }
+
+    let topic0Count;
+    (topic0Count, buffer)= tryDecompressUsize(buffer)?;
+
+    let mut topic0Dictionary= Vec::with_capacity(topic0Count.min(buffer.len( )));
+    for _ in 0..topic0Count {
+      let topic0;
+      (topic0, buffer)= decompressTrimmedTopic(buffer)?;
+
+      topic0Dictionary.push(topic0);
+    }
+
+    let logCount;
+    (logCount, buffer)= tryDecompressUsize(buffer)?;
+
+    // Each log takes at least 3 bytes : address index, topic count and data length.
+    let mut logs= Vec::with_capacity(logCount.min(buffer.len( ) / 3));
+    for _ in 0..logCount {
+      let addressIndex;
+      (addressIndex, buffer)= tryDecompressUsize(buffer)?;
+
+      let topicCount;
+      (topicCount, buffer)= tryDecompressUsize(buffer)?;
+
+      let mut topics= Vec::with_capacity(topicCount.min(buffer.len( )));
+      if topicCount > 0 {
+        let topic0Index;
+        (topic0Index, buffer)= tryDecompressUsize(buffer)?;
+
+        let topic0= topic0Dictionary.get(topic0Index)
+                                    .ok_or(DecompressionError::DictionaryIndexOutOfBounds(topic0Index))?;
+        topics.push(*topic0);
+      }
+      for _ in 1..topicCount {
+        let topic;
+        (topic, buffer)= decompressTrimmedTopic(buffer)?;
+
+        topics.push(topic);
+      }
+
+      let dataLen;
+      (dataLen, buffer)= tryDecompressUsize(buffer)?;
+
+      let data= Bytes::copy_from_slice(buffer.get(..dataLen).ok_or(DecompressionError::Truncated)?);
+      buffer.advance(dataLen);
+
+      let address= addressDictionary.get(addressIndex)
+                                    .ok_or(DecompressionError::DictionaryIndexOutOfBounds(addressIndex))?;
+      logs.push(Log::new_unchecked(*address, topics, data));
+    }
+
+    Ok((logs, buffer))
+  }
+}
+
+impl CheckedDecompressor for Vec<Log> { }
+
+// Distinct values, in the order they were first seen.
+struct Dictionary<T> {
+  values: Vec<T>,
+  indices: HashMap<T, usize>
+}
+
+impl<T> Default for Dictionary<T> {
+  fn default( ) -> Self {
+    Self { values: Vec::new( ), indices: HashMap::new( ) }
+  }
+}
+
+impl<T> Dictionary<T>
+  where
+    T: Copy + Eq + std::hash::Hash
+{
+  // Returns the index of the value in the dictionary, inserting it if not present.
+  fn indexOf(&mut self, value: T) -> usize {
+    *self.indices.entry(value).or_insert_with(|| {
+      self.values.push(value);
+      self.values.len( ) - 1
+    })
+  }
+}
+
+// Writes the length of the topic after trimming its leading zeros, followed by the trimmed bytes.
+fn compressTrimmedTopic<B>(topic: &B256, buffer: &mut B)
+  where
+    B: BufMut
+{
+  let leadingZeroByteCount= topic.iter( ).take_while(|byte| **byte == 0).count( );
+
+  compressUsize(32 - leadingZeroByteCount, buffer);
+  buffer.put_slice(&topic[leadingZeroByteCount..]);
+}
+
+fn decompressTrimmedTopic(buffer: &[u8]) -> Result<(B256, &[u8]), DecompressionError> {
+  let (len, mut buffer)= tryDecompressUsize(buffer)?;
+  if len > 32 {
+    return Err(DecompressionError::TrimmedTopicTooLong(len))
+  }
+
+  let mut topic= B256::ZERO;
+  topic[(32 - len)..].copy_from_slice(buffer.get(..len).ok_or(DecompressionError::Truncated)?);
+  buffer.advance(len);
+
+  Ok((topic, buffer))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn logs( ) -> Vec<Log> {
+    let transfer= B256::with_last_byte(0xDD);
+    let token= Address::with_last_byte(1);
+
+    vec![
+      Log::new_unchecked(token, vec![transfer, B256::left_padding_from(&[7; 20]), B256::ZERO], Bytes::from(vec![0, 0, 1])),
+      Log::new_unchecked(Address::with_last_byte(2), vec![ ], Bytes::new( )),
+      Log::new_unchecked(token, vec![transfer, B256::repeat_byte(0xFF)], Bytes::from(vec![9; 100])),
+      Log::new_unchecked(token, vec![B256::repeat_byte(0xAA)], Bytes::new( ))
+    ]
+  }
+
+  #[test]
+  fn roundTrips( ) {
+    for logs in [vec![ ], logs( )] {
+      let mut buffer= Vec::new( );
+      logs.clone( ).compress(&mut buffer);
+      buffer.push(0x42);
+
+      let (decompressed, rest)= Vec::<Log>::tryDecompress(&buffer, 0).unwrap( );
+      assert_eq!((decompressed, rest), (logs, &[0x42][..]));
+    }
+  }
+
+  #[test]
+  fn dictionariesAreShared( ) {
+    let mut buffer= Vec::new( );
+    logs( ).compress(&mut buffer);
+
+    // 2 addresses and 2 topic0s, each written once.
+    assert_eq!(&buffer[..1], &[2]);
+    assert_eq!(buffer[1 + 40], 2);
+  }
+
+  #[test]
+  fn corruptedLogsAreAnError( ) {
+    let mut buffer= Vec::new( );
+    logs( ).compress(&mut buffer);
+
+    for len in 0..buffer.len( ) {
+      assert!(Vec::<Log>::tryDecompress(&buffer[..len], 0).is_err( ), "truncated at {len}");
+    }
+
+    // Address index 9, with only 1 address in the dictionary.
+    let corrupted= [&[1][..], &[0; 20], &[0, 1, 9, 0, 0]].concat( );
+    assert!(matches!(Vec::<Log>::tryDecompress(&corrupted, 0), Err(DecompressionError::DictionaryIndexOutOfBounds(9))));
+
+    // Topic0 index 3, with an empty topic0 dictionary.
+    let corrupted= [0, 0, 1, 0, 1, 3, 0];
+    assert!(matches!(Vec::<Log>::tryDecompress(&corrupted, 0), Err(DecompressionError::DictionaryIndexOutOfBounds(3))));
+
+    // Trimmed topic0 of 33 bytes.
+    let corrupted= [&[0, 1, 33][..], &[1; 33]].concat( );
+    assert!(matches!(Vec::<Log>::tryDecompress(&corrupted, 0), Err(DecompressionError::TrimmedTopicTooLong(33))));
+  }
+}
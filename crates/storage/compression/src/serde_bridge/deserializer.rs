@@ -0,0 +1,365 @@
+use serde::de::{self, value::U32Deserializer, DeserializeSeed, IntoDeserializer, Visitor};
+use crate::tryDecompressUsize;
+use super::SerdeBridgeError;
+
+// Deserializes values from the compact storage encoding (see the serde_bridge module). Strings and
+// byte arrays are borrowed from the input, when the visitor allows it.
+pub struct Deserializer<'de> {
+  buffer: &'de [u8]
+}
+
+impl<'de> Deserializer<'de> {
+  pub fn new(buffer: &'de [u8]) -> Self {
+    Self { buffer }
+  }
+
+  // Returns the part of the input which hasn't been read yet.
+  pub fn remaining(&self) -> &'de [u8] {
+    self.buffer
+  }
+
+  fn readBytes(&mut self, len: usize) -> Result<&'de [u8], SerdeBridgeError> {
+    if self.buffer.len( ) < len {
+      return Err(SerdeBridgeError::UnexpectedEndOfInput)
+    }
+
+    let (bytes, remaining)= self.buffer.split_at(len);
+    self.buffer= remaining;
+
+    Ok(bytes)
+  }
+
+  fn readU8(&mut self) -> Result<u8, SerdeBridgeError> {
+    Ok(self.readBytes(1)?[0])
+  }
+
+  fn readLength(&mut self) -> Result<usize, SerdeBridgeError> {
+    let (len, remaining)= tryDecompressUsize(self.buffer).map_err(|_| SerdeBridgeError::UnexpectedEndOfInput)?;
+    self.buffer= remaining;
+
+    Ok(len)
+  }
+
+  fn readUnsigned(&mut self, maxLen: usize, expected: &'static str) -> Result<u128, SerdeBridgeError> {
+    let len= self.readLength( )?;
+    if len > maxLen {
+      return Err(SerdeBridgeError::InvalidValue { value: len as u128, expected })
+    }
+
+    let mut valueAsBytes= [0u8; 16];
+    valueAsBytes[(16 - len)..].copy_from_slice(self.readBytes(len)?);
+
+    Ok(u128::from_be_bytes(valueAsBytes))
+  }
+
+  fn readSigned(&mut self, maxLen: usize, expected: &'static str) -> Result<i128, SerdeBridgeError> {
+    let zigzagEncodedValue= self.readUnsigned(maxLen, expected)?;
+    Ok(((zigzagEncodedValue >> 1) as i128) ^ -((zigzagEncodedValue & 1) as i128))
+  }
+}
+
+macro_rules! deserialize_unsigned {
+  ($($method_name:ident => $type_name:ty, $visit_method_name:ident);+) => {
+    $(
+      fn $method_name<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+        where
+          V: Visitor<'de>
+      {
+        const TYPE_SIZE: usize= core::mem::size_of::<$type_name>( );
+        let value= self.readUnsigned(TYPE_SIZE, stringify!($type_name))?;
+        visitor.$visit_method_name(value as $type_name)
+      }
+    )+ // '+' means repeat the contents inside for each match.
+  };
+}
+
+macro_rules! deserialize_signed {
+  ($($method_name:ident => $type_name:ty, $visit_method_name:ident);+) => {
+    $(
+      fn $method_name<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+        where
+          V: Visitor<'de>
+      {
+        const TYPE_SIZE: usize= core::mem::size_of::<$type_name>( );
+        let value= self.readSigned(TYPE_SIZE, stringify!($type_name))?;
+        visitor.$visit_method_name(value as $type_name)
+      }
+    )+ // '+' means repeat the contents inside for each match.
+  };
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+  type Error= SerdeBridgeError;
+
+  fn deserialize_any<V>(self, _: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { Err(SerdeBridgeError::DeserializeAnyNotSupported) }
+
+  fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  {
+    match self.readU8( )? {
+      0 => visitor.visit_bool(false),
+      1 => visitor.visit_bool(true),
+      value => Err(SerdeBridgeError::InvalidValue { value: value as u128, expected: "bool" })
+    }
+  }
+
+  fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  {
+    let value= self.readU8( )?;
+    visitor.visit_u8(value)
+  }
+
+  deserialize_unsigned!(
+    deserialize_u16 => u16, visit_u16;
+    deserialize_u32 => u32, visit_u32;
+    deserialize_u64 => u64, visit_u64;
+    deserialize_u128 => u128, visit_u128
+  );
+
+  deserialize_signed!(
+    deserialize_i8 => i8, visit_i8;
+    deserialize_i16 => i16, visit_i16;
+    deserialize_i32 => i32, visit_i32;
+    deserialize_i64 => i64, visit_i64;
+    deserialize_i128 => i128, visit_i128
+  );
+
+  fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  {
+    let bits= u32::from_be_bytes(self.readBytes(4)?.try_into( ).unwrap( ));
+    visitor.visit_f32(f32::from_bits(bits))
+  }
+
+  fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  {
+    let bits= u64::from_be_bytes(self.readBytes(8)?.try_into( ).unwrap( ));
+    visitor.visit_f64(f64::from_bits(bits))
+  }
+
+  fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  {
+    let value= self.readUnsigned(4, "char")?;
+
+    match char::from_u32(value as u32) {
+      Some(value) => visitor.visit_char(value),
+      None => Err(SerdeBridgeError::InvalidValue { value, expected: "char" })
+    }
+  }
+
+  fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  {
+    let len= self.readLength( )?;
+    let value= std::str::from_utf8(self.readBytes(len)?)?;
+
+    visitor.visit_borrowed_str(value)
+  }
+
+  fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { self.deserialize_str(visitor) }
+
+  fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  {
+    let len= self.readLength( )?;
+    visitor.visit_borrowed_bytes(self.readBytes(len)?)
+  }
+
+  fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { self.deserialize_bytes(visitor) }
+
+  fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  {
+    match self.readU8( )? {
+      0 => visitor.visit_none( ),
+      1 => visitor.visit_some(self),
+      value => Err(SerdeBridgeError::InvalidValue { value: value as u128, expected: "option tag" })
+    }
+  }
+
+  fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { visitor.visit_unit( ) }
+
+  fn deserialize_unit_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { visitor.visit_unit( ) }
+
+  fn deserialize_newtype_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { visitor.visit_newtype_struct(self) }
+
+  fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  {
+    let len= self.readLength( )?;
+    visitor.visit_seq(ElementsAccess { deserializer: self, remainingElementCount: len })
+  }
+
+  fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { visitor.visit_seq(ElementsAccess { deserializer: self, remainingElementCount: len }) }
+
+  fn deserialize_tuple_struct<V>(self,
+                                 _: &'static str,
+                                 len: usize,
+                                 visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { self.deserialize_tuple(len, visitor) }
+
+  fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  {
+    let len= self.readLength( )?;
+    visitor.visit_map(ElementsAccess { deserializer: self, remainingElementCount: len })
+  }
+
+  fn deserialize_struct<V>(self,
+                           _: &'static str,
+                           fields: &'static [&'static str],
+                           visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { self.deserialize_tuple(fields.len( ), visitor) }
+
+  fn deserialize_enum<V>(self,
+                         _: &'static str,
+                         _: &'static [&'static str],
+                         visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { visitor.visit_enum(self) }
+
+  fn deserialize_identifier<V>(self, _: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { Err(SerdeBridgeError::DeserializeAnyNotSupported) }
+
+  fn deserialize_ignored_any<V>(self, _: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { Err(SerdeBridgeError::DeserializeAnyNotSupported) }
+
+  fn is_human_readable(&self) -> bool {
+    false
+  }
+}
+
+// Gives visitors access to the elements of a sequence / tuple / struct, or the entries of a map.
+struct ElementsAccess<'a, 'de> {
+  deserializer: &'a mut Deserializer<'de>,
+  remainingElementCount: usize
+}
+
+impl<'a, 'de> de::SeqAccess<'de> for ElementsAccess<'a, 'de> {
+  type Error= SerdeBridgeError;
+
+  fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeBridgeError>
+    where
+      T: DeserializeSeed<'de>
+  {
+    if self.remainingElementCount == 0 {
+      return Ok(None)
+    }
+    self.remainingElementCount -= 1;
+
+    seed.deserialize(&mut *self.deserializer).map(Some)
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.remainingElementCount)
+  }
+}
+
+impl<'a, 'de> de::MapAccess<'de> for ElementsAccess<'a, 'de> {
+  type Error= SerdeBridgeError;
+
+  fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeBridgeError>
+    where
+      K: DeserializeSeed<'de>
+  {
+    if self.remainingElementCount == 0 {
+      return Ok(None)
+    }
+    self.remainingElementCount -= 1;
+
+    seed.deserialize(&mut *self.deserializer).map(Some)
+  }
+
+  fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: DeserializeSeed<'de>
+  { seed.deserialize(&mut *self.deserializer) }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.remainingElementCount)
+  }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Deserializer<'de> {
+  type Error= SerdeBridgeError;
+  type Variant= Self;
+
+  fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self), SerdeBridgeError>
+    where
+      V: DeserializeSeed<'de>
+  {
+    let variantIndex= self.readLength( )? as u32;
+    let variantIndexDeserializer: U32Deserializer<SerdeBridgeError>= variantIndex.into_deserializer( );
+    let variant= seed.deserialize(variantIndexDeserializer)?;
+
+    Ok((variant, self))
+  }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Deserializer<'de> {
+  type Error= SerdeBridgeError;
+
+  fn unit_variant(self) -> Result<( ), SerdeBridgeError> {
+    Ok(( ))
+  }
+
+  fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, SerdeBridgeError>
+    where
+      T: DeserializeSeed<'de>
+  { seed.deserialize(self) }
+
+  fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { de::Deserializer::deserialize_tuple(self, len, visitor) }
+
+  fn struct_variant<V>(self,
+                       fields: &'static [&'static str],
+                       visitor: V) -> Result<V::Value, SerdeBridgeError>
+    where
+      V: Visitor<'de>
+  { de::Deserializer::deserialize_tuple(self, fields.len( ), visitor) }
+}
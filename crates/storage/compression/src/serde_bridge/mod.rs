@@ -0,0 +1,242 @@
+/*
+  A serde bridge to the compact storage encoding. It lets us compact-encode types we don't own (and
+  prototype new tables) without writing a Compressor implementation for them.
+
+  Since there are no StructFlags, every value carries its own length where needed :
+
+  (1) Unsigned integers are written as a compressed usize (the length) followed by the big-endian
+      bytes with leading zeros trimmed - the same way Vec<T> writes its elements. Signed integers
+      are zigzag encoded first, so that small negative numbers stay small. A u8 is written as is.
+  (2) Strings, byte arrays, sequences and maps are prefixed with their length as a compressed usize.
+  (3) Options are prefixed with 0 (None) or 1 (Some).
+  (4) Enum variants are prefixed with their index as a compressed usize.
+  (5) Structs and tuples are written field by field, without any prefix.
+
+  The format is not self-describing, so the type being deserialized must be known in advance (i.e.
+  deserialize_any is not supported). For the same reason, types using #[serde(flatten)] or untagged
+  enums can't be used with it.
+*/
+
+mod serializer;
+mod deserializer;
+pub mod via_compressor;
+
+pub use serializer::Serializer;
+pub use deserializer::Deserializer;
+
+use std::fmt::Display;
+use serde::{de::DeserializeOwned, Serialize};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SerdeBridgeError {
+
+  #[error("{0}")]
+  Custom(String),
+
+  #[error("Unexpected end of input")]
+  UnexpectedEndOfInput,
+
+  #[error("Sequences and maps must have a known length")]
+  UnknownLength,
+
+  #[error("Invalid value {value} for {expected}")]
+  InvalidValue {
+    value: u128,
+    expected: &'static str
+  },
+
+  #[error("The format is not self-describing, so deserialize_any is not supported")]
+  DeserializeAnyNotSupported,
+
+  #[error("{0} bytes remained after deserializing")]
+  TrailingBytes(usize),
+
+  #[error(transparent)]
+  InvalidUtf8(#[from] std::str::Utf8Error)
+}
+
+impl serde::ser::Error for SerdeBridgeError {
+  fn custom<T: Display>(message: T) -> Self {
+    Self::Custom(message.to_string( ))
+  }
+}
+
+impl serde::de::Error for SerdeBridgeError {
+  fn custom<T: Display>(message: T) -> Self {
+    Self::Custom(message.to_string( ))
+  }
+}
+
+// Compact-encodes the given value.
+pub fn toBytes<T>(value: &T) -> Result<Vec<u8>, SerdeBridgeError>
+  where
+    T: Serialize + ?Sized
+{
+  let mut buffer= Vec::with_capacity(64);
+  value.serialize(&mut Serializer::new(&mut buffer))?;
+
+  Ok(buffer)
+}
+
+// Decodes a value which was compact-encoded using toBytes. The whole buffer must be consumed.
+pub fn fromBytes<T>(buffer: &[u8]) -> Result<T, SerdeBridgeError>
+  where
+    T: DeserializeOwned
+{
+  let mut deserializer= Deserializer::new(buffer);
+  let value= T::deserialize(&mut deserializer)?;
+
+  match deserializer.remaining( ).len( ) {
+    0 => Ok(value),
+    remainingByteCount => Err(SerdeBridgeError::TrailingBytes(remainingByteCount))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+  use alloy_primitives::Bloom;
+  use serde::{Deserialize, Serialize};
+  use super::*;
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  enum Kind {
+    Unit,
+    Newtype(u64),
+    Tuple(u8, String),
+    Struct { value: i32 }
+  }
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Everything {
+    byte: u8,
+    small: u16,
+    medium: u32,
+    large: u64,
+    huge: u128,
+    negative: i8,
+    mostNegative: i64,
+    flag: bool,
+    character: char,
+    float: f64,
+    text: String,
+    #[serde(with = "serde_bytes_like")]
+    bytes: Vec<u8>,
+    list: Vec<u64>,
+    absent: Option<u32>,
+    present: Option<u32>,
+    map: BTreeMap<String, u64>,
+    pair: (u8, String),
+    kinds: Vec<Kind>,
+    unit: ( )
+  }
+
+  // Serializes the Vec<u8> as a byte array rather than a sequence.
+  mod serde_bytes_like {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+      serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+      <&[u8]>::deserialize(deserializer).map(|bytes| bytes.to_vec( ))
+    }
+  }
+
+  fn everything( ) -> Everything {
+    Everything {
+      byte: 0xFF,
+      small: 300,
+      medium: 0,
+      large: u64::MAX,
+      huge: 1 << 100,
+      negative: -1,
+      mostNegative: i64::MIN,
+      flag: true,
+      character: 'é',
+      float: -1.5,
+      text: "static files".to_string( ),
+      bytes: vec![0, 1, 2, 0xFF],
+      list: vec![0, 1, 1 << 40],
+      absent: None,
+      present: Some(7),
+      map: BTreeMap::from([("a".to_string( ), 1), ("b".to_string( ), 2)]),
+      pair: (3, "three".to_string( )),
+      kinds: vec![Kind::Unit, Kind::Newtype(9), Kind::Tuple(1, "x".to_string( )), Kind::Struct { value: -300 }],
+      unit: ( )
+    }
+  }
+
+  #[test]
+  fn roundTrips( ) {
+    let value= everything( );
+    assert_eq!(fromBytes::<Everything>(&toBytes(&value).unwrap( )).unwrap( ), value);
+  }
+
+  #[test]
+  fn integersAreCompact( ) {
+    assert_eq!(toBytes(&0u64).unwrap( ), [0]);
+    assert_eq!(toBytes(&5u64).unwrap( ), [1, 5]);
+    assert_eq!(toBytes(&300u32).unwrap( ), [2, 0x01, 0x2C]);
+    assert_eq!(toBytes(&7u8).unwrap( ), [7]);
+
+    // Zigzag encoded : -1 -> 1, 1 -> 2.
+    assert_eq!(toBytes(&-1i64).unwrap( ), [1, 1]);
+    assert_eq!(toBytes(&1i64).unwrap( ), [1, 2]);
+  }
+
+  #[test]
+  fn invalidInputIsAnError( ) {
+    let encoded= toBytes(&everything( )).unwrap( );
+
+    for len in 0..encoded.len( ) {
+      assert!(fromBytes::<Everything>(&encoded[..len]).is_err( ), "truncated to {} bytes", len);
+    }
+
+    let mut withTrailingBytes= encoded.clone( );
+    withTrailingBytes.extend_from_slice(&[1, 2]);
+    assert!(matches!(fromBytes::<Everything>(&withTrailingBytes), Err(SerdeBridgeError::TrailingBytes(2))));
+
+    // A u16 can't take 3 bytes.
+    assert!(matches!(fromBytes::<u16>(&[3, 1, 2, 3]), Err(SerdeBridgeError::InvalidValue { value: 3, .. })));
+  }
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct WithBloom {
+    #[serde(with = "crate::serde_bridge::via_compressor")]
+    bloom: Bloom
+  }
+
+  #[test]
+  fn viaCompressorRoundTrips( ) {
+    let mut bloom= Bloom::ZERO;
+    bloom.0[10]= 0b1001;
+    let value= WithBloom { bloom };
+
+    // Sparse encoding, 2 set bits : | byte array length | encoding | set bit count | 2 x 2 bytes |
+    let encoded= toBytes(&value).unwrap( );
+    assert_eq!(encoded.len( ), 1 + 1 + 1 + 4);
+    assert_eq!(fromBytes::<WithBloom>(&encoded).unwrap( ), value);
+
+    // Human readable formats get the field's own representation.
+    let json= serde_json::to_string(&value).unwrap( );
+    assert!(json.contains("0x"));
+    assert_eq!(serde_json::from_str::<WithBloom>(&json).unwrap( ), value);
+  }
+
+  #[test]
+  fn viaCompressorRejectsCorruptedValues( ) {
+    let mut bloom= Bloom::ZERO;
+    bloom.0[0]= 1;
+
+    // Unknown bloom encoding.
+    let mut encoded= toBytes(&WithBloom { bloom }).unwrap( );
+    encoded[1]= 7;
+    assert!(fromBytes::<WithBloom>(&encoded).is_err( ));
+
+    // Set bit index out of bounds.
+    let encoded= [4, 1, 1, 0xFF, 0xFF];
+    assert!(fromBytes::<WithBloom>(&encoded).is_err( ));
+  }
+}
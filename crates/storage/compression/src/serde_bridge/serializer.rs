@@ -0,0 +1,291 @@
+use bytes::BufMut;
+use serde::{ser, Serialize};
+use crate::compressUsize;
+use super::SerdeBridgeError;
+
+// Serializes values into the compact storage encoding (see the serde_bridge module).
+pub struct Serializer<'b, B: BufMut> {
+  buffer: &'b mut B
+}
+
+impl<'b, B: BufMut> Serializer<'b, B> {
+  pub fn new(buffer: &'b mut B) -> Self {
+    Self { buffer }
+  }
+
+  // Writes the length, followed by the big-endian bytes with leading zeros trimmed.
+  fn writeUnsigned(&mut self, value: u128) {
+    let bytesWithLeadingZeroBits= (value.leading_zeros( ) / 8) as usize;
+
+    compressUsize(16 - bytesWithLeadingZeroBits, self.buffer);
+    self.buffer.put_slice(&value.to_be_bytes( )[bytesWithLeadingZeroBits..]);
+  }
+
+  // Zigzag encoding maps signed integers to unsigned ones : 0 -> 0, -1 -> 1, 1 -> 2, -2 -> 3 ...
+  fn writeSigned(&mut self, value: i128) {
+    self.writeUnsigned(((value << 1) ^ (value >> 127)) as u128);
+  }
+
+  fn writeLength(&mut self, len: Option<usize>) -> Result<( ), SerdeBridgeError> {
+    let len= len.ok_or(SerdeBridgeError::UnknownLength)?;
+    compressUsize(len, self.buffer);
+
+    Ok(( ))
+  }
+}
+
+impl<'a, 'b, B: BufMut> ser::Serializer for &'a mut Serializer<'b, B> {
+  type Ok= ( );
+  type Error= SerdeBridgeError;
+
+  type SerializeSeq= Self;
+  type SerializeTuple= Self;
+  type SerializeTupleStruct= Self;
+  type SerializeTupleVariant= Self;
+  type SerializeMap= Self;
+  type SerializeStruct= Self;
+  type SerializeStructVariant= Self;
+
+  fn serialize_bool(self, value: bool) -> Result<( ), SerdeBridgeError> {
+    self.buffer.put_u8(value as u8);
+    Ok(( ))
+  }
+
+  fn serialize_u8(self, value: u8) -> Result<( ), SerdeBridgeError> {
+    self.buffer.put_u8(value);
+    Ok(( ))
+  }
+
+  fn serialize_u16(self, value: u16) -> Result<( ), SerdeBridgeError> {
+    self.writeUnsigned(value as u128);
+    Ok(( ))
+  }
+
+  fn serialize_u32(self, value: u32) -> Result<( ), SerdeBridgeError> {
+    self.writeUnsigned(value as u128);
+    Ok(( ))
+  }
+
+  fn serialize_u64(self, value: u64) -> Result<( ), SerdeBridgeError> {
+    self.writeUnsigned(value as u128);
+    Ok(( ))
+  }
+
+  fn serialize_u128(self, value: u128) -> Result<( ), SerdeBridgeError> {
+    self.writeUnsigned(value);
+    Ok(( ))
+  }
+
+  fn serialize_i8(self, value: i8) -> Result<( ), SerdeBridgeError> {
+    self.writeSigned(value as i128);
+    Ok(( ))
+  }
+
+  fn serialize_i16(self, value: i16) -> Result<( ), SerdeBridgeError> {
+    self.writeSigned(value as i128);
+    Ok(( ))
+  }
+
+  fn serialize_i32(self, value: i32) -> Result<( ), SerdeBridgeError> {
+    self.writeSigned(value as i128);
+    Ok(( ))
+  }
+
+  fn serialize_i64(self, value: i64) -> Result<( ), SerdeBridgeError> {
+    self.writeSigned(value as i128);
+    Ok(( ))
+  }
+
+  fn serialize_i128(self, value: i128) -> Result<( ), SerdeBridgeError> {
+    self.writeSigned(value);
+    Ok(( ))
+  }
+
+  fn serialize_f32(self, value: f32) -> Result<( ), SerdeBridgeError> {
+    self.buffer.put_u32(value.to_bits( ));
+    Ok(( ))
+  }
+
+  fn serialize_f64(self, value: f64) -> Result<( ), SerdeBridgeError> {
+    self.buffer.put_u64(value.to_bits( ));
+    Ok(( ))
+  }
+
+  fn serialize_char(self, value: char) -> Result<( ), SerdeBridgeError> {
+    self.writeUnsigned(value as u128);
+    Ok(( ))
+  }
+
+  fn serialize_str(self, value: &str) -> Result<( ), SerdeBridgeError> {
+    self.serialize_bytes(value.as_bytes( ))
+  }
+
+  fn serialize_bytes(self, value: &[u8]) -> Result<( ), SerdeBridgeError> {
+    compressUsize(value.len( ), self.buffer);
+    self.buffer.put_slice(value);
+    Ok(( ))
+  }
+
+  fn serialize_none(self) -> Result<( ), SerdeBridgeError> {
+    self.buffer.put_u8(0);
+    Ok(( ))
+  }
+
+  fn serialize_some<T>(self, value: &T) -> Result<( ), SerdeBridgeError>
+    where
+      T: Serialize + ?Sized
+  {
+    self.buffer.put_u8(1);
+    value.serialize(self)
+  }
+
+  fn serialize_unit(self) -> Result<( ), SerdeBridgeError> {
+    Ok(( ))
+  }
+
+  fn serialize_unit_struct(self, _: &'static str) -> Result<( ), SerdeBridgeError> {
+    Ok(( ))
+  }
+
+  fn serialize_unit_variant(self,
+                            _: &'static str,
+                            variantIndex: u32,
+                            _: &'static str) -> Result<( ), SerdeBridgeError>
+  {
+    compressUsize(variantIndex as usize, self.buffer);
+    Ok(( ))
+  }
+
+  fn serialize_newtype_struct<T>(self, _: &'static str, value: &T) -> Result<( ), SerdeBridgeError>
+    where
+      T: Serialize + ?Sized
+  { value.serialize(self) }
+
+  fn serialize_newtype_variant<T>(self,
+                                  _: &'static str,
+                                  variantIndex: u32,
+                                  _: &'static str,
+                                  value: &T) -> Result<( ), SerdeBridgeError>
+    where
+      T: Serialize + ?Sized
+  {
+    compressUsize(variantIndex as usize, self.buffer);
+    value.serialize(self)
+  }
+
+  fn serialize_seq(self, len: Option<usize>) -> Result<Self, SerdeBridgeError> {
+    self.writeLength(len)?;
+    Ok(self)
+  }
+
+  fn serialize_tuple(self, _: usize) -> Result<Self, SerdeBridgeError> {
+    Ok(self)
+  }
+
+  fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self, SerdeBridgeError> {
+    Ok(self)
+  }
+
+  fn serialize_tuple_variant(self,
+                             _: &'static str,
+                             variantIndex: u32,
+                             _: &'static str,
+                             _: usize) -> Result<Self, SerdeBridgeError>
+  {
+    compressUsize(variantIndex as usize, self.buffer);
+    Ok(self)
+  }
+
+  fn serialize_map(self, len: Option<usize>) -> Result<Self, SerdeBridgeError> {
+    self.writeLength(len)?;
+    Ok(self)
+  }
+
+  fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self, SerdeBridgeError> {
+    Ok(self)
+  }
+
+  fn serialize_struct_variant(self,
+                              _: &'static str,
+                              variantIndex: u32,
+                              _: &'static str,
+                              _: usize) -> Result<Self, SerdeBridgeError>
+  {
+    compressUsize(variantIndex as usize, self.buffer);
+    Ok(self)
+  }
+
+  // Keeps the encoding compact, regardless of how the type presents itself to humans.
+  fn is_human_readable(&self) -> bool {
+    false
+  }
+}
+
+// Sequences, tuples and structs are written element by element (the length, if any, has already
+// been written).
+macro_rules! impl_compound_serializer {
+  ($($trait_name:ident :: $method_name:ident),+) => {
+    $(
+      impl<'a, 'b, B: BufMut> ser::$trait_name for &'a mut Serializer<'b, B> {
+        type Ok= ( );
+        type Error= SerdeBridgeError;
+
+        fn $method_name<T>(&mut self, value: &T) -> Result<( ), SerdeBridgeError>
+          where
+            T: Serialize + ?Sized
+        { value.serialize(&mut **self) }
+
+        fn end(self) -> Result<( ), SerdeBridgeError> {
+          Ok(( ))
+        }
+      }
+    )+ // '+' means repeat the contents inside for each match.
+  };
+}
+impl_compound_serializer!(
+  SerializeSeq::serialize_element,
+  SerializeTuple::serialize_element,
+  SerializeTupleStruct::serialize_field,
+  SerializeTupleVariant::serialize_field
+);
+
+impl<'a, 'b, B: BufMut> ser::SerializeMap for &'a mut Serializer<'b, B> {
+  type Ok= ( );
+  type Error= SerdeBridgeError;
+
+  fn serialize_key<T>(&mut self, key: &T) -> Result<( ), SerdeBridgeError>
+    where
+      T: Serialize + ?Sized
+  { key.serialize(&mut **self) }
+
+  fn serialize_value<T>(&mut self, value: &T) -> Result<( ), SerdeBridgeError>
+    where
+      T: Serialize + ?Sized
+  { value.serialize(&mut **self) }
+
+  fn end(self) -> Result<( ), SerdeBridgeError> {
+    Ok(( ))
+  }
+}
+
+// Field names aren't written, since fields are always read back in the same order.
+macro_rules! impl_struct_serializer {
+  ($($trait_name:ident),+) => {
+    $(
+      impl<'a, 'b, B: BufMut> ser::$trait_name for &'a mut Serializer<'b, B> {
+        type Ok= ( );
+        type Error= SerdeBridgeError;
+
+        fn serialize_field<T>(&mut self, _: &'static str, value: &T) -> Result<( ), SerdeBridgeError>
+          where
+            T: Serialize + ?Sized
+        { value.serialize(&mut **self) }
+
+        fn end(self) -> Result<( ), SerdeBridgeError> {
+          Ok(( ))
+        }
+      }
+    )+ // '+' means repeat the contents inside for each match.
+  };
+}
+impl_struct_serializer!(SerializeStruct, SerializeStructVariant);
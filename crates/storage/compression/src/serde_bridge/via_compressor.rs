@@ -0,0 +1,52 @@
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use crate::{compressUsize, tryDecompressUsize, CheckedDecompressor};
+
+/*
+  Use with #[serde(with = "compression::serde_bridge::via_compressor")], to store a field using its
+  Compressor implementation (e.g. the sparse encoding of a Bloom, or the column-aware encoding of
+  receipt logs) when it goes through the compact encoding.
+
+  The field is written as a byte array : the value returned by Compressor::compress (as a compressed
+  usize), followed by the compressed bytes. Human readable formats (like JSON) get the field's own
+  serde representation instead.
+
+  The field's type must implement CheckedDecompressor, since it's read back from disk.
+*/
+
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    T: CheckedDecompressor + Serialize + Clone,
+    S: Serializer
+{
+  if serializer.is_human_readable( ) {
+    return value.serialize(serializer)
+  }
+
+  let mut compressed= Vec::new( );
+  let len= value.clone( ).compress(&mut compressed);
+
+  let mut buffer= Vec::with_capacity(compressed.len( ) + 1);
+  compressUsize(len, &mut buffer);
+  buffer.extend_from_slice(&compressed);
+
+  serializer.serialize_bytes(&buffer)
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+  where
+    T: CheckedDecompressor + Deserialize<'de>,
+    D: Deserializer<'de>
+{
+  if deserializer.is_human_readable( ) {
+    return T::deserialize(deserializer)
+  }
+
+  let buffer= <Vec<u8>>::deserialize(deserializer)?;
+
+  // The value comes from disk, so it's decoded without panicking if corrupted (see
+  // CheckedDecompressor).
+  let (len, compressed)= tryDecompressUsize(&buffer).map_err(D::Error::custom)?;
+  let (value, _)= T::tryDecompress(compressed, len).map_err(D::Error::custom)?;
+
+  Ok(value)
+}
@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use bytes::BufMut;
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+use super::{compressUsize, tryDecompressUsize, DecompressionError};
+
+/*
+  An optional second compression layer, applied on top of the compact encoding.
+
+  Transactions and transaction receipts contain highly repetitive ABI patterns (function selectors,
+  event signatures, padded addresses etc.), which leading zero trimming can't get rid of. zstd works
+  poorly on such small values by itself, but does very well when given a dictionary trained on
+  similar values.
+
+  Each value type gets its own dictionaries. Dictionaries are versioned using their id : once a
+  dictionary is used, it must never change (we'd be unable to decode the values compressed using
+  it), so retraining produces a new dictionary with a new id.
+
+  An encoded value looks like this :
+
+    | dictionary id (compressed usize) | decompressed length (compressed usize) | zstd frame |
+
+  Dictionary id 0 is reserved for values which were stored without zstd compression. Then, the
+  compact encoded value follows the dictionary id directly.
+*/
+
+pub type DictionaryId= u32;
+
+// Dictionary id used for values which aren't zstd compressed.
+pub const NO_DICTIONARY: DictionaryId= 0;
+
+const COMPRESSION_LEVEL: i32= 3;
+
+// Default maximum size of a (decompressed) value. Way above the size of any transaction or receipt
+// seen so far, while keeping a corrupted value from making us allocate gigabytes.
+pub const DEFAULT_MAX_VALUE_SIZE: usize= 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueType {
+  Transaction= 0,
+  TransactionReceipt= 1
+}
+
+impl TryFrom<u8> for ValueType {
+  type Error= ZstdDictionaryError;
+
+  fn try_from(value: u8) -> Result<Self, Self::Error> {
+    match value {
+      0 => Ok(Self::Transaction),
+      1 => Ok(Self::TransactionReceipt),
+      _ => Err(ZstdDictionaryError::UnknownValueType(value))
+    }
+  }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ZstdDictionaryError {
+
+  #[error("Dictionary id {0} is reserved for values which aren't zstd compressed")]
+  ReservedDictionaryId(DictionaryId),
+
+  #[error("Dictionary with id {0} is not known")]
+  UnknownDictionary(DictionaryId),
+
+  #[error("Dictionary id {0} doesn't fit in a DictionaryId")]
+  InvalidDictionaryId(usize),
+
+  #[error("Dictionary id {0} is already registered, with a different dictionary")]
+  DictionaryIdInUse(DictionaryId),
+
+  #[error("Value decompressed to {actualLen} bytes, instead of {expectedLen} bytes")]
+  DecompressedLenMismatch {
+    expectedLen: usize,
+    actualLen: usize
+  },
+
+  #[error("Value of {valueSize} bytes is larger than the maximum value size ({maxValueSize} bytes)")]
+  ValueTooLarge {
+    valueSize: usize,
+    maxValueSize: usize
+  },
+
+  #[error("Unknown value type {0}")]
+  UnknownValueType(u8),
+
+  #[error("Not enough samples to train a dictionary")]
+  NotEnoughSamples,
+
+  #[error(transparent)]
+  Decompression(#[from] DecompressionError),
+
+  #[error(transparent)]
+  Zstd(#[from] std::io::Error)
+}
+
+// A pre-trained zstd dictionary, for a specific value type.
+pub struct ZstdDictionary {
+  id: DictionaryId,
+  valueType: ValueType,
+  bytes: Vec<u8>
+}
+
+impl ZstdDictionary {
+  pub fn new(id: DictionaryId, valueType: ValueType, bytes: Vec<u8>) -> Result<Self, ZstdDictionaryError> {
+    if id == NO_DICTIONARY {
+      return Err(ZstdDictionaryError::ReservedDictionaryId(id))
+    }
+
+    Ok(Self { id, valueType, bytes })
+  }
+
+  /*
+    Trains a dictionary from a sample of existing table values (compact encoded).
+
+    Every sampleInterval-th value is picked, until maxSampleSize bytes are collected. This way, the
+    sample is spread across the table instead of being made of its first rows only. Values which
+    don't fit in the remaining sample size are skipped.
+  */
+  pub fn train<I, V>(id: DictionaryId,
+                     valueType: ValueType,
+                     tableValues: I,
+                     sampleInterval: usize,
+                     maxSampleSize: usize,
+                     maxDictionarySize: usize) -> Result<Self, ZstdDictionaryError>
+    where
+      I: IntoIterator<Item = V>,
+      V: AsRef<[u8]>
+  {
+    let mut samples= Vec::new( );
+    let mut sampleSize= 0;
+
+    for value in tableValues.into_iter( ).step_by(sampleInterval.max(1)) {
+      if sampleSize == maxSampleSize {
+        break
+      }
+
+      let value= value.as_ref( );
+      if sampleSize + value.len( ) > maxSampleSize {
+        continue
+      }
+
+      sampleSize += value.len( );
+      samples.push(value.to_vec( ));
+    }
+
+    if samples.is_empty( ) {
+      return Err(ZstdDictionaryError::NotEnoughSamples)
+    }
+
+    let bytes= zstd::dict::from_samples(&samples, maxDictionarySize)?;
+    Self::new(id, valueType, bytes)
+  }
+
+  pub fn id(&self) -> DictionaryId {
+    self.id
+  }
+
+  pub fn valueType(&self) -> ValueType {
+    self.valueType
+  }
+
+  // Serializes the dictionary, so that it can be persisted / shipped along with the node.
+  // Layout : | id (4 bytes, big-endian) | value type (1 byte) | dictionary bytes |
+  pub fn toBytes(&self) -> Vec<u8> {
+    let mut buffer= Vec::with_capacity(5 + self.bytes.len( ));
+
+    buffer.put_u32(self.id);
+    buffer.put_u8(self.valueType as u8);
+    buffer.put_slice(&self.bytes);
+
+    buffer
+  }
+
+  pub fn fromBytes(buffer: &[u8]) -> Result<Self, ZstdDictionaryError> {
+    if buffer.len( ) < 5 {
+      return Err(DecompressionError::Truncated.into( ))
+    }
+
+    let id= DictionaryId::from_be_bytes(buffer[..4].try_into( ).unwrap( ));
+    let valueType= ValueType::try_from(buffer[4])?;
+
+    Self::new(id, valueType, buffer[5..].to_vec( ))
+  }
+}
+
+// Dictionary, prepared for compression and decompression.
+struct PreparedDictionary {
+  valueType: ValueType,
+  bytes: Vec<u8>,
+
+  encoderDictionary: EncoderDictionary<'static>,
+  decoderDictionary: DecoderDictionary<'static>
+}
+
+// Holds all the known dictionaries. New values of a value type are compressed using the latest
+// registered dictionary for that value type, while any known dictionary can be used to decompress.
+pub struct ZstdDictionaries {
+  dictionaries: HashMap<DictionaryId, PreparedDictionary>,
+  latestDictionaryIds: HashMap<ValueType, DictionaryId>,
+
+  // Values larger than this are neither compressed nor decompressed.
+  maxValueSize: usize
+}
+
+impl Default for ZstdDictionaries {
+  fn default( ) -> Self {
+    Self {
+      dictionaries: HashMap::new( ),
+      latestDictionaryIds: HashMap::new( ),
+      maxValueSize: DEFAULT_MAX_VALUE_SIZE
+    }
+  }
+}
+
+impl ZstdDictionaries {
+  pub fn withMaxValueSize(mut self, maxValueSize: usize) -> Self {
+    self.maxValueSize= maxValueSize;
+    self
+  }
+
+  /*
+    Registers the dictionary. It becomes the one used to compress values of its value type.
+
+    A dictionary id never changes meaning, since values already compressed using it would then be
+    decompressed using the wrong dictionary. So an id can only be registered again along with the
+    very same dictionary.
+  */
+  pub fn register(&mut self, dictionary: ZstdDictionary) -> Result<( ), ZstdDictionaryError> {
+    if let Some(registered)= self.dictionaries.get(&dictionary.id) {
+      if (registered.valueType, &registered.bytes) != (dictionary.valueType, &dictionary.bytes) {
+        return Err(ZstdDictionaryError::DictionaryIdInUse(dictionary.id))
+      }
+    }
+
+    self.dictionaries.insert(dictionary.id, PreparedDictionary {
+      encoderDictionary: EncoderDictionary::copy(&dictionary.bytes, COMPRESSION_LEVEL),
+      decoderDictionary: DecoderDictionary::copy(&dictionary.bytes),
+      valueType: dictionary.valueType,
+      bytes: dictionary.bytes
+    });
+    self.latestDictionaryIds.insert(dictionary.valueType, dictionary.id);
+
+    Ok(( ))
+  }
+
+  // Compresses the compact encoded value, using the latest dictionary registered for the value
+  // type. If there's none, the value is stored as is (prefixed with NO_DICTIONARY).
+  pub fn compress(&self, valueType: ValueType, value: &[u8]) -> Result<Vec<u8>, ZstdDictionaryError> {
+    // It couldn't be decompressed back otherwise.
+    if value.len( ) > self.maxValueSize {
+      return Err(ZstdDictionaryError::ValueTooLarge { valueSize: value.len( ), maxValueSize: self.maxValueSize })
+    }
+
+    let mut buffer= Vec::with_capacity(value.len( ) + 2);
+
+    let Some(&dictionaryId)= self.latestDictionaryIds.get(&valueType) else {
+      compressUsize(NO_DICTIONARY as usize, &mut buffer);
+      buffer.put_slice(value);
+
+      return Ok(buffer)
+    };
+    let dictionary= &self.dictionaries[&dictionaryId];
+
+    let mut compressor= zstd::bulk::Compressor::with_prepared_dictionary(&dictionary.encoderDictionary)?;
+    let frame= compressor.compress(value)?;
+
+    compressUsize(dictionaryId as usize, &mut buffer);
+    compressUsize(value.len( ), &mut buffer);
+    buffer.put_slice(&frame);
+
+    Ok(buffer)
+  }
+
+  // Returns the compact encoded value back.
+  pub fn decompress(&self, buffer: &[u8]) -> Result<Vec<u8>, ZstdDictionaryError> {
+    let (dictionaryId, buffer)= tryDecompressUsize(buffer)?;
+    let dictionaryId= DictionaryId::try_from(dictionaryId)
+                        .map_err(|_| ZstdDictionaryError::InvalidDictionaryId(dictionaryId))?;
+
+    if dictionaryId == NO_DICTIONARY {
+      return Ok(buffer.to_vec( ))
+    }
+
+    let dictionary= self.dictionaries.get(&dictionaryId)
+                                     .ok_or(ZstdDictionaryError::UnknownDictionary(dictionaryId))?;
+
+    // The decompressed length is used as the capacity of the decompression buffer, so a
+    // corrupted one mustn't make us allocate gigabytes.
+    let (decompressedLen, frame)= tryDecompressUsize(buffer)?;
+    if decompressedLen > self.maxValueSize {
+      return Err(ZstdDictionaryError::ValueTooLarge { valueSize: decompressedLen, maxValueSize: self.maxValueSize })
+    }
+
+    let mut decompressor= zstd::bulk::Decompressor::with_prepared_dictionary(&dictionary.decoderDictionary)?;
+    let value= decompressor.decompress(frame, decompressedLen)?;
+
+    // A corrupted frame may decode to fewer bytes.
+    if value.len( ) != decompressedLen {
+      return Err(ZstdDictionaryError::DecompressedLenMismatch { expectedLen: decompressedLen, actualLen: value.len( ) })
+    }
+
+    Ok(value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Looks like ABI encoded calldata : a function selector followed by padded arguments.
+  fn sampleValue(i: usize) -> Vec<u8> {
+    let mut value= vec![0xA9, 0x05, 0x9C, 0xBB];
+    value.extend_from_slice(&[0; 12]);
+    value.extend_from_slice(&(i as u64 * 7919).to_be_bytes( ).repeat(3)[..20]);
+    value.extend_from_slice(&[0; 24]);
+    value.extend_from_slice(&(i as u64 % 1000).to_be_bytes( ));
+    value
+  }
+
+  fn trainedDictionary(id: DictionaryId) -> ZstdDictionary {
+    ZstdDictionary::train(id, ValueType::Transaction, (0..2000).map(sampleValue), 1, 64 * 1024, 4096).unwrap( )
+  }
+
+  #[test]
+  fn roundTripsWithAndWithoutDictionary( ) {
+    let mut dictionaries= ZstdDictionaries::default( );
+
+    // No dictionary registered yet : stored as is.
+    let value= sampleValue(5000);
+    let stored= dictionaries.compress(ValueType::Transaction, &value).unwrap( );
+    assert_eq!(stored[0], NO_DICTIONARY as u8);
+    assert_eq!(dictionaries.decompress(&stored).unwrap( ), value);
+
+    dictionaries.register(trainedDictionary(1)).unwrap( );
+    let compressed= dictionaries.compress(ValueType::Transaction, &value).unwrap( );
+    assert_eq!(compressed[0], 1);
+    assert!(compressed.len( ) < value.len( ), "{} bytes compressed to {}", value.len( ), compressed.len( ));
+    assert_eq!(dictionaries.decompress(&compressed).unwrap( ), value);
+
+    // Values stored before, stay readable.
+    assert_eq!(dictionaries.decompress(&stored).unwrap( ), value);
+
+    // Receipts have no dictionary.
+    assert_eq!(dictionaries.compress(ValueType::TransactionReceipt, &value).unwrap( )[0], NO_DICTIONARY as u8);
+  }
+
+  #[test]
+  fn retrainedDictionaryKeepsOldValuesReadable( ) {
+    let mut dictionaries= ZstdDictionaries::default( );
+    dictionaries.register(trainedDictionary(1)).unwrap( );
+    let compressedWithFirst= dictionaries.compress(ValueType::Transaction, &sampleValue(1)).unwrap( );
+
+    dictionaries.register(ZstdDictionary::fromBytes(&trainedDictionary(2).toBytes( )).unwrap( )).unwrap( );
+    let compressedWithSecond= dictionaries.compress(ValueType::Transaction, &sampleValue(1)).unwrap( );
+
+    assert_eq!((compressedWithFirst[0], compressedWithSecond[0]), (1, 2));
+    assert_eq!(dictionaries.decompress(&compressedWithFirst).unwrap( ), sampleValue(1));
+    assert_eq!(dictionaries.decompress(&compressedWithSecond).unwrap( ), sampleValue(1));
+  }
+
+  #[test]
+  fn invalidValuesAreErrors( ) {
+    let mut dictionaries= ZstdDictionaries::default( ).withMaxValueSize(1024);
+    dictionaries.register(trainedDictionary(1)).unwrap( );
+
+    assert!(matches!(dictionaries.decompress(&[ ]), Err(ZstdDictionaryError::Decompression(_))));
+    assert!(matches!(dictionaries.decompress(&[7, 1, 0]), Err(ZstdDictionaryError::UnknownDictionary(7))));
+
+    // 2^32 would wrap around to NO_DICTIONARY.
+    let mut buffer= Vec::new( );
+    compressUsize(1 << 32, &mut buffer);
+    assert!(matches!(dictionaries.decompress(&buffer), Err(ZstdDictionaryError::InvalidDictionaryId(id)) if id == 1 << 32));
+
+    // Huge decompressed length.
+    let mut buffer= vec![1];
+    compressUsize(1 << 40, &mut buffer);
+    buffer.extend_from_slice(&[0; 16]);
+    assert!(matches!(dictionaries.decompress(&buffer), Err(ZstdDictionaryError::ValueTooLarge { maxValueSize: 1024, .. })));
+
+    assert!(matches!(dictionaries.compress(ValueType::Transaction, &[0; 1025]), Err(ZstdDictionaryError::ValueTooLarge { .. })));
+
+    assert!(matches!(ZstdDictionary::new(NO_DICTIONARY, ValueType::Transaction, vec![ ]), Err(ZstdDictionaryError::ReservedDictionaryId(0))));
+    assert!(matches!(ZstdDictionary::fromBytes(&[0, 0, 0, 1, 9]), Err(ZstdDictionaryError::UnknownValueType(9))));
+    assert!(matches!(ZstdDictionary::fromBytes(&[0, 0, 0, 1]), Err(ZstdDictionaryError::Decompression(DecompressionError::Truncated))));
+
+    // Frame decoding to fewer bytes than the decompressed length says.
+    let value= sampleValue(1);
+    let compressed= dictionaries.compress(ValueType::Transaction, &value).unwrap( );
+    let (_, frame)= tryDecompressUsize(&compressed[1..]).unwrap( );
+    let mut buffer= vec![1];
+    compressUsize(value.len( ) + 1, &mut buffer);
+    buffer.extend_from_slice(frame);
+    assert!(matches!(dictionaries.decompress(&buffer),
+                     Err(ZstdDictionaryError::DecompressedLenMismatch { expectedLen, actualLen }) if (expectedLen, actualLen) == (value.len( ) + 1, value.len( ))));
+  }
+
+  #[test]
+  fn dictionaryIdsCantBeReusedForAnotherDictionary( ) {
+    let mut dictionaries= ZstdDictionaries::default( );
+    dictionaries.register(trainedDictionary(1)).unwrap( );
+    let compressed= dictionaries.compress(ValueType::Transaction, &sampleValue(1)).unwrap( );
+
+    // Registering the very same dictionary again is fine.
+    dictionaries.register(trainedDictionary(1)).unwrap( );
+
+    let otherBytes= ZstdDictionary::train(1, ValueType::Transaction, (0..2000).map(|i| sampleValue(i).repeat(2)), 1, 64 * 1024, 4096).unwrap( );
+    assert!(matches!(dictionaries.register(otherBytes), Err(ZstdDictionaryError::DictionaryIdInUse(1))));
+
+    let otherValueType= ZstdDictionary::new(1, ValueType::TransactionReceipt, trainedDictionary(1).bytes).unwrap( );
+    assert!(matches!(dictionaries.register(otherValueType), Err(ZstdDictionaryError::DictionaryIdInUse(1))));
+
+    assert_eq!(dictionaries.decompress(&compressed).unwrap( ), sampleValue(1));
+    assert_eq!(dictionaries.compress(ValueType::TransactionReceipt, &sampleValue(1)).unwrap( )[0], NO_DICTIONARY as u8);
+  }
+
+  #[test]
+  fn trainingSkipsOversizedValues( ) {
+    // The first value alone is larger than the whole sample.
+    let values= std::iter::once(vec![0; 128 * 1024]).chain((0..2000).map(sampleValue));
+
+    let dictionary= ZstdDictionary::train(1, ValueType::Transaction, values, 1, 64 * 1024, 4096).unwrap( );
+    assert!(!dictionary.bytes.is_empty( ));
+
+    let noValues: Vec<Vec<u8>>= vec![vec![0; 128 * 1024]];
+    assert!(matches!(ZstdDictionary::train(1, ValueType::Transaction, noValues, 1, 64 * 1024, 4096),
+                     Err(ZstdDictionaryError::NotEnoughSamples)));
+  }
+}
@@ -40,4 +40,5 @@ pub trait Db
   }
 }
 
+#[derive(Debug, thiserror::Error)]
 pub enum DbError { }
\ No newline at end of file
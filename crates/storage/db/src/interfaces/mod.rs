@@ -1,3 +1,4 @@
 pub mod db;
 pub mod transaction;
-pub mod table;
\ No newline at end of file
+pub mod table;
+pub mod table_value;
\ No newline at end of file
@@ -0,0 +1,130 @@
+use compression::{
+  serde_bridge::{self, SerdeBridgeError},
+  zstd_dictionaries::{DictionaryId, ValueType, ZstdDictionaries, ZstdDictionary, ZstdDictionaryError}
+};
+use serde::{de::DeserializeOwned, Serialize};
+use crate::models::{BlockBodyIndices, Header, Receipt, TransactionSigned};
+
+/*
+  How values are encoded in the database tables : compact encoded (see compression::serde_bridge),
+  and then, for transactions and receipts, zstd compressed using the latest dictionary trained for
+  their value type (see compression::zstd_dictionaries).
+
+  The id of the dictionary is part of every value. So after a dictionary is retrained and
+  registered, new values use it, while the values written before keep decoding with the old one. If
+  no dictionary is registered for the value type yet, values are stored without zstd compression.
+*/
+pub trait TableValue
+  : Serialize + DeserializeOwned
+{
+  // Value type the zstd dictionaries are trained for. None for values which aren't zstd compressed.
+  const ZSTD_VALUE_TYPE: Option<ValueType>= None;
+
+  fn toTableValue(&self, zstdDictionaries: &ZstdDictionaries) -> Result<Vec<u8>, TableValueError> {
+    let encoded= serde_bridge::toBytes(self)?;
+
+    match Self::ZSTD_VALUE_TYPE {
+      Some(valueType) => Ok(zstdDictionaries.compress(valueType, &encoded)?),
+      None => Ok(encoded)
+    }
+  }
+
+  fn fromTableValue(tableValue: &[u8], zstdDictionaries: &ZstdDictionaries) -> Result<Self, TableValueError> {
+    match Self::ZSTD_VALUE_TYPE {
+      Some(_) => Ok(serde_bridge::fromBytes(&zstdDictionaries.decompress(tableValue)?)?),
+      None => Ok(serde_bridge::fromBytes(tableValue)?)
+    }
+  }
+}
+
+impl TableValue for TransactionSigned {
+  const ZSTD_VALUE_TYPE: Option<ValueType>= Some(ValueType::Transaction);
+}
+
+impl TableValue for Receipt {
+  const ZSTD_VALUE_TYPE: Option<ValueType>= Some(ValueType::TransactionReceipt);
+}
+
+impl TableValue for Header { }
+
+impl TableValue for BlockBodyIndices { }
+
+// Trains a new dictionary for the value type of V, from a sample of the values in its table. It has
+// to be registered (and persisted along with the database) to be used.
+pub fn trainZstdDictionary<'v, V, I>(id: DictionaryId,
+                                     tableValues: I,
+                                     sampleInterval: usize,
+                                     maxSampleSize: usize,
+                                     maxDictionarySize: usize) -> Result<ZstdDictionary, TableValueError>
+  where
+    V: TableValue + 'v,
+    I: IntoIterator<Item = &'v V>
+{
+  let valueType= V::ZSTD_VALUE_TYPE.ok_or(TableValueError::NotZstdCompressed)?;
+
+  let encodedTableValues= tableValues.into_iter( )
+                                     .step_by(sampleInterval.max(1))
+                                     .map(serde_bridge::toBytes)
+                                     .collect::<Result<Vec<_>, _>>( )?;
+
+  Ok(ZstdDictionary::train(id, valueType, encodedTableValues, 1, maxSampleSize, maxDictionarySize)?)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TableValueError {
+
+  #[error("Values of the table aren't zstd compressed")]
+  NotZstdCompressed,
+
+  #[error(transparent)]
+  Encoding(#[from] SerdeBridgeError),
+
+  #[error(transparent)]
+  ZstdDictionary(#[from] ZstdDictionaryError)
+}
+
+#[cfg(test)]
+mod tests {
+  use alloy_primitives::{Address, Bytes, Log, B256};
+  use crate::models::ReceiptOutcome;
+  use super::*;
+
+  fn receipt(i: u64) -> Receipt {
+    Receipt {
+      transactionType: 2,
+      outcome: ReceiptOutcome::Status(!i.is_multiple_of(7)),
+      cumulativeGasUsed: 21_000 * i,
+      logs: vec![Log::new_unchecked(Address::with_last_byte(i as u8 % 4),
+                                    vec![B256::with_last_byte(0xDD), B256::left_padding_from(&i.to_be_bytes( ))],
+                                    Bytes::from(vec![0u8; 32]))]
+    }
+  }
+
+  #[test]
+  fn receiptsAreZstdCompressedOnceADictionaryIsRegistered( ) {
+    let receipts: Vec<Receipt>= (1..2000).map(receipt).collect( );
+    let mut zstdDictionaries= ZstdDictionaries::default( );
+
+    let storedBefore= receipts[5].toTableValue(&zstdDictionaries).unwrap( );
+    assert_eq!(Receipt::fromTableValue(&storedBefore, &zstdDictionaries).unwrap( ), receipts[5]);
+
+    zstdDictionaries.register(trainZstdDictionary(1, &receipts, 1, 64 * 1024, 4096).unwrap( )).unwrap( );
+
+    let stored= receipts[5].toTableValue(&zstdDictionaries).unwrap( );
+    assert!(stored.len( ) < storedBefore.len( ));
+    assert_eq!(Receipt::fromTableValue(&stored, &zstdDictionaries).unwrap( ), receipts[5]);
+    assert_eq!(Receipt::fromTableValue(&storedBefore, &zstdDictionaries).unwrap( ), receipts[5]);
+  }
+
+  #[test]
+  fn otherValuesAreOnlyCompactEncoded( ) {
+    let blockBodyIndices= BlockBodyIndices { firstTxNumber: 10, txCount: 3 };
+    let stored= blockBodyIndices.toTableValue(&ZstdDictionaries::default( )).unwrap( );
+
+    assert_eq!(stored, serde_bridge::toBytes(&blockBodyIndices).unwrap( ));
+    assert_eq!(BlockBodyIndices::fromTableValue(&stored, &ZstdDictionaries::default( )).unwrap( ), blockBodyIndices);
+
+    assert!(matches!(trainZstdDictionary::<BlockBodyIndices, _>(1, &[blockBodyIndices], 1, 1024, 1024),
+                     Err(TableValueError::NotZstdCompressed)));
+  }
+}
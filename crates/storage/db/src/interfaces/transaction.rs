@@ -1,11 +1,54 @@
+use std::ops::RangeInclusive;
+use alloy_primitives::{Address, U256};
+use crate::models::{BlockBodyIndices, BlockHash, BlockNumber, Header, Receipt, TransactionSigned, TxHash, TxNumber};
 use super::db::DbError;
 
 pub trait RoDbTx
   : Send + Sync
 {
   fn commit(self) -> Result<bool, DbError>;
+
+  fn header(&self, blockNumber: BlockNumber) -> Result<Option<Header>, DbError>;
+
+  fn blockHash(&self, blockNumber: BlockNumber) -> Result<Option<BlockHash>, DbError>;
+
+  fn blockNumber(&self, blockHash: BlockHash) -> Result<Option<BlockNumber>, DbError>;
+
+  // Returns the sum of difficulties of all the blocks up to (and including) the given block.
+  fn totalDifficulty(&self, blockNumber: BlockNumber) -> Result<Option<U256>, DbError>;
+
+  fn blockBodyIndices(&self, blockNumber: BlockNumber) -> Result<Option<BlockBodyIndices>, DbError>;
+
+  fn transaction(&self, txNumber: TxNumber) -> Result<Option<TransactionSigned>, DbError>;
+
+  fn txNumber(&self, txHash: TxHash) -> Result<Option<TxNumber>, DbError>;
+
+  fn receipt(&self, txNumber: TxNumber) -> Result<Option<Receipt>, DbError>;
+
+  // Returns the address which signed the given transaction, as recovered from its signature.
+  fn sender(&self, txNumber: TxNumber) -> Result<Option<Address>, DbError>;
+
+  // Returns the headers of the ommers (uncles) included in the given block. Ommers aren't copied to
+  // static files, so they always live in the database.
+  fn ommers(&self, blockNumber: BlockNumber) -> Result<Option<Vec<Header>>, DbError>;
 }
 
 pub trait DbTx
   : Send + Sync
-{ }
\ No newline at end of file
+{
+  // Removes the headers (along with the block hashes and total difficulties) of the blocks in the
+  // given range. Block body indices are kept.
+  fn deleteHeaders(&self, blockRange: RangeInclusive<BlockNumber>) -> Result<( ), DbError>;
+
+  fn deleteTransactions(&self, txRange: RangeInclusive<TxNumber>) -> Result<( ), DbError>;
+
+  fn deleteReceipts(&self, txRange: RangeInclusive<TxNumber>) -> Result<( ), DbError>;
+
+  fn deleteSenders(&self, txRange: RangeInclusive<TxNumber>) -> Result<( ), DbError>;
+
+  // Inserts the block body indices of a block, whose transactions live in static files only (e.g.
+  // when importing history from Era1 files).
+  fn insertBlockBodyIndices(&self, blockNumber: BlockNumber, blockBodyIndices: BlockBodyIndices) -> Result<( ), DbError>;
+
+  fn insertOmmers(&self, blockNumber: BlockNumber, ommers: &[Header]) -> Result<( ), DbError>;
+}
@@ -1,3 +1,7 @@
 #![allow(non_snake_case)]
 
-pub mod interfaces;
\ No newline at end of file
+pub mod interfaces;
+pub mod models;
+
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
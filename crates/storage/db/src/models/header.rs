@@ -0,0 +1,194 @@
+use alloy_primitives::{keccak256, Address, Bloom, Bytes, B256, B64, U256};
+use alloy_rlp::{Buf, BufMut, Decodable, Encodable, EMPTY_STRING_CODE};
+use serde::{Deserialize, Serialize};
+use super::{BlockHash, BlockNumber};
+
+// Block header, as stored in the database (and static files).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+  pub parentHash: B256,
+  pub ommersHash: B256,
+
+  // Address receiving the priority fees (and block rewards, pre-merge).
+  pub beneficiary: Address,
+
+  pub stateRoot: B256,
+  pub transactionsRoot: B256,
+  pub receiptsRoot: B256,
+
+  // Most blooms are nearly empty, so they are stored using the sparse encoding.
+  #[serde(with = "compression::serde_bridge::via_compressor")]
+  pub logsBloom: Bloom,
+
+  // Always 0 post-merge.
+  pub difficulty: U256,
+
+  pub number: BlockNumber,
+  pub gasLimit: u64,
+  pub gasUsed: u64,
+  pub timestamp: u64,
+  pub extraData: Bytes,
+  pub mixHash: B256,
+  pub nonce: u64,
+
+  // Introduced in EIP 1559 (London).
+  pub baseFeePerGas: Option<u64>,
+
+  // Introduced in EIP 4895 (Shanghai).
+  pub withdrawalsRoot: Option<B256>,
+
+  // Introduced in EIP 4844 (Cancun).
+  pub blobGasUsed: Option<u64>,
+  pub excessBlobGas: Option<u64>,
+
+  // Introduced in EIP 4788 (Cancun).
+  pub parentBeaconBlockRoot: Option<B256>
+}
+
+impl Header {
+  // Calculates the block hash, which is the keccak256 hash of the RLP encoded header.
+  pub fn hash(&self) -> BlockHash {
+    keccak256(alloy_rlp::encode(self))
+  }
+
+  fn rlpPayloadLength(&self) -> usize {
+    let mut length= self.parentHash.length( ) +
+                    self.ommersHash.length( ) +
+                    self.beneficiary.length( ) +
+                    self.stateRoot.length( ) +
+                    self.transactionsRoot.length( ) +
+                    self.receiptsRoot.length( ) +
+                    self.logsBloom.length( ) +
+                    self.difficulty.length( ) +
+                    self.number.length( ) +
+                    self.gasLimit.length( ) +
+                    self.gasUsed.length( ) +
+                    self.timestamp.length( ) +
+                    self.extraData.length( ) +
+                    self.mixHash.length( ) +
+                    B64::from(self.nonce).length( );
+
+    for optionalField in self.optionalFields( ) {
+      length += optionalField.map_or(1, |field| field.length( ));
+    }
+    length
+  }
+
+  /*
+    Fields introduced by later forks are appended to the end of the RLP list, in order. A field is
+    only encoded if it (or any field after it) is set - an unset field is encoded as the empty
+    string when a later field is set.
+  */
+  fn optionalFields(&self) -> Vec<Option<&dyn Encodable>> {
+    let mut optionalFields: Vec<Option<&dyn Encodable>>= vec![
+      self.baseFeePerGas.as_ref( ).map(|field| field as &dyn Encodable),
+      self.withdrawalsRoot.as_ref( ).map(|field| field as &dyn Encodable),
+      self.blobGasUsed.as_ref( ).map(|field| field as &dyn Encodable),
+      self.excessBlobGas.as_ref( ).map(|field| field as &dyn Encodable),
+      self.parentBeaconBlockRoot.as_ref( ).map(|field| field as &dyn Encodable)
+    ];
+
+    while optionalFields.last( ).is_some_and(Option::is_none) {
+      optionalFields.pop( );
+    }
+    optionalFields
+  }
+}
+
+impl Encodable for Header {
+  fn encode(&self, out: &mut dyn BufMut) {
+    alloy_rlp::Header { list: true, payload_length: self.rlpPayloadLength( ) }.encode(out);
+
+    self.parentHash.encode(out);
+    self.ommersHash.encode(out);
+    self.beneficiary.encode(out);
+    self.stateRoot.encode(out);
+    self.transactionsRoot.encode(out);
+    self.receiptsRoot.encode(out);
+    self.logsBloom.encode(out);
+    self.difficulty.encode(out);
+    self.number.encode(out);
+    self.gasLimit.encode(out);
+    self.gasUsed.encode(out);
+    self.timestamp.encode(out);
+    self.extraData.encode(out);
+    self.mixHash.encode(out);
+    B64::from(self.nonce).encode(out);
+
+    for optionalField in self.optionalFields( ) {
+      match optionalField {
+        Some(field) => field.encode(out),
+        None => out.put_u8(EMPTY_STRING_CODE)
+      }
+    }
+  }
+
+  fn length(&self) -> usize {
+    let payloadLength= self.rlpPayloadLength( );
+    payloadLength + alloy_rlp::length_of_length(payloadLength)
+  }
+}
+
+impl Decodable for Header {
+  fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+    let rlpHeader= alloy_rlp::Header::decode(buf)?;
+    if !rlpHeader.list {
+      return Err(alloy_rlp::Error::UnexpectedString)
+    }
+    if rlpHeader.payload_length > buf.len( ) {
+      return Err(alloy_rlp::Error::InputTooShort)
+    }
+    let (mut payload, rest)= buf.split_at(rlpHeader.payload_length);
+    *buf= rest;
+
+    let header= Self {
+      parentHash: Decodable::decode(&mut payload)?,
+      ommersHash: Decodable::decode(&mut payload)?,
+      beneficiary: Decodable::decode(&mut payload)?,
+      stateRoot: Decodable::decode(&mut payload)?,
+      transactionsRoot: Decodable::decode(&mut payload)?,
+      receiptsRoot: Decodable::decode(&mut payload)?,
+      logsBloom: Decodable::decode(&mut payload)?,
+      difficulty: Decodable::decode(&mut payload)?,
+      number: Decodable::decode(&mut payload)?,
+      gasLimit: Decodable::decode(&mut payload)?,
+      gasUsed: Decodable::decode(&mut payload)?,
+      timestamp: Decodable::decode(&mut payload)?,
+      extraData: Decodable::decode(&mut payload)?,
+      mixHash: Decodable::decode(&mut payload)?,
+      nonce: u64::from_be_bytes(B64::decode(&mut payload)?.0),
+      baseFeePerGas: decodeOptionalField(&mut payload)?,
+      withdrawalsRoot: decodeOptionalField(&mut payload)?,
+      blobGasUsed: decodeOptionalField(&mut payload)?,
+      excessBlobGas: decodeOptionalField(&mut payload)?,
+      parentBeaconBlockRoot: decodeOptionalField(&mut payload)?
+    };
+
+    if !payload.is_empty( ) {
+      return Err(alloy_rlp::Error::ListLengthMismatch {
+        expected: rlpHeader.payload_length,
+        got: rlpHeader.payload_length - payload.len( )
+      })
+    }
+    Ok(header)
+  }
+}
+
+// Decodes a field introduced by a later fork (see Header::optionalFields). The empty string stands
+// for an unset field, unless it's a valid value of the field (e.g. 0).
+fn decodeOptionalField<T>(payload: &mut &[u8]) -> alloy_rlp::Result<Option<T>>
+  where
+    T: Decodable
+{
+  match payload.first( ) {
+    None => Ok(None),
+
+    Some(&EMPTY_STRING_CODE) => {
+      let field= T::decode(&mut &payload[..]).ok( );
+      payload.advance(1);
+      Ok(field)
+    },
+
+    Some(_) => T::decode(payload).map(Some)
+  }
+}
@@ -0,0 +1,38 @@
+mod header;
+mod transaction;
+mod receipt;
+mod ordered_trie;
+
+pub use header::Header;
+pub use transaction::TransactionSigned;
+pub use receipt::{Receipt, ReceiptOutcome};
+pub use ordered_trie::orderedTrieRoot;
+
+use serde::{Deserialize, Serialize};
+
+pub type BlockNumber= u64;
+pub type BlockHash= alloy_primitives::B256;
+
+// Transactions are numbered sequentially, starting from the first transaction of the genesis
+// block. This way, a transaction can be looked up without knowing which block it belongs to.
+pub type TxNumber= u64;
+pub type TxHash= alloy_primitives::B256;
+
+// Tells where the transactions of a block are, in the sequence of all transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockBodyIndices {
+  pub firstTxNumber: TxNumber,
+  pub txCount: u64
+}
+
+impl BlockBodyIndices {
+  // Returns the transaction numbers of the block's transactions.
+  pub fn txNumbers(&self) -> std::ops::Range<TxNumber> {
+    self.firstTxNumber..(self.firstTxNumber + self.txCount)
+  }
+
+  // Returns the transaction number of the block's last transaction, if the block has any.
+  pub fn lastTxNumber(&self) -> Option<TxNumber> {
+    self.txNumbers( ).last( )
+  }
+}
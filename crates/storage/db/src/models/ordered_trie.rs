@@ -0,0 +1,123 @@
+use alloy_primitives::{keccak256, B256};
+use alloy_rlp::EMPTY_STRING_CODE;
+
+/*
+  Calculates the root of the Merkle Patricia Trie mapping the RLP encoded index of each value to
+  the value, which is how the transactions root (and receipts root) of a block is calculated.
+
+  The whole trie is built in memory, which is fine for the few hundred values of a block.
+*/
+pub fn orderedTrieRoot<V>(values: &[V]) -> B256
+  where
+    V: AsRef<[u8]>
+{
+  let mut entries: Vec<(Vec<u8>, &[u8])>= values.iter( ).enumerate( )
+    .map(|(index, value)| (toNibbles(&alloy_rlp::encode(index)), value.as_ref( )))
+    .collect( );
+
+  // A key may be a prefix of another key (e.g. the RLP encodings of 0 and 128), in which case it
+  // sorts first.
+  entries.sort( );
+
+  keccak256(encodeNode(&entries, 0))
+}
+
+// Encodes the node containing the given (sorted) entries, whose paths share the first depth
+// nibbles.
+fn encodeNode(entries: &[(Vec<u8>, &[u8])], depth: usize) -> Vec<u8> {
+  let [(firstPath, firstValue), ..]= entries else {
+    return vec![EMPTY_STRING_CODE]
+  };
+
+  if entries.len( ) == 1 {
+    return encodeList(&[
+      alloy_rlp::encode(&hexPrefix(&firstPath[depth..], true)[..]),
+      alloy_rlp::encode(*firstValue)
+    ])
+  }
+
+  // Since the entries are sorted, the prefix shared by all of them is the one shared by the first
+  // and the last.
+  let lastPath= &entries[entries.len( ) - 1].0;
+  let sharedPrefixLength= firstPath[depth..].iter( ).zip(&lastPath[depth..])
+                                            .take_while(|(first, last)| first == last)
+                                            .count( );
+  if sharedPrefixLength > 0 {
+    return encodeList(&[
+      alloy_rlp::encode(&hexPrefix(&firstPath[depth..(depth + sharedPrefixLength)], false)[..]),
+      referenceTo(encodeNode(entries, depth + sharedPrefixLength))
+    ])
+  }
+
+  // A branch node : a child for each nibble, followed by the value of the entry ending here.
+  let (mut remainingEntries, value)= match firstPath.len( ) == depth {
+    true => (&entries[1..], alloy_rlp::encode(*firstValue)),
+    false => (entries, vec![EMPTY_STRING_CODE])
+  };
+
+  let mut items= Vec::with_capacity(17);
+  for nibble in 0..16 {
+    let childCount= remainingEntries.iter( ).take_while(|(path, _)| path[depth] == nibble).count( );
+    let (childEntries, rest)= remainingEntries.split_at(childCount);
+
+    items.push(match childEntries.is_empty( ) {
+      true => vec![EMPTY_STRING_CODE],
+      false => referenceTo(encodeNode(childEntries, depth + 1))
+    });
+    remainingEntries= rest;
+  }
+  items.push(value);
+
+  encodeList(&items)
+}
+
+// Nodes shorter than a hash are embedded in their parent. The others are referenced by their hash.
+fn referenceTo(encodedNode: Vec<u8>) -> Vec<u8> {
+  match encodedNode.len( ) < 32 {
+    true => encodedNode,
+    false => alloy_rlp::encode(keccak256(encodedNode))
+  }
+}
+
+// Encodes an RLP list, out of already encoded items.
+fn encodeList(items: &[Vec<u8>]) -> Vec<u8> {
+  let payloadLength= items.iter( ).map(Vec::len).sum( );
+
+  let mut encoded= Vec::with_capacity(payloadLength + 9);
+  alloy_rlp::Header { list: true, payload_length: payloadLength }.encode(&mut encoded);
+  for item in items {
+    encoded.extend_from_slice(item);
+  }
+  encoded
+}
+
+/*
+  Compact (hex prefix) encoding of a path of nibbles. The first nibble is a flag, telling whether
+  the node is a leaf and whether the path has an odd length. For an even length, it's followed by a
+  padding nibble.
+*/
+fn hexPrefix(nibbles: &[u8], isLeaf: bool) -> Vec<u8> {
+  let isOdd= nibbles.len( ) % 2 == 1;
+  let flag= (if isLeaf { 2 } else { 0 }) + (if isOdd { 1 } else { 0 });
+
+  let mut encoded= Vec::with_capacity(nibbles.len( ) / 2 + 1);
+  let remainingNibbles= match isOdd {
+    true => {
+      encoded.push((flag << 4) | nibbles[0]);
+      &nibbles[1..]
+    },
+    false => {
+      encoded.push(flag << 4);
+      nibbles
+    }
+  };
+  for pair in remainingNibbles.chunks(2) {
+    encoded.push((pair[0] << 4) | pair[1]);
+  }
+
+  encoded
+}
+
+fn toNibbles(bytes: &[u8]) -> Vec<u8> {
+  bytes.iter( ).flat_map(|byte| [byte >> 4, byte & 0x0f]).collect( )
+}
@@ -0,0 +1,155 @@
+use alloy_primitives::{Bloom, Log, B256};
+use alloy_rlp::{Decodable, Encodable, EMPTY_LIST_CODE};
+use serde::{Deserialize, Serialize};
+
+// Transaction receipt, as stored in the database (and static files). The bloom isn't stored, since
+// it can be calculated from the logs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+  // Type of the transaction (0 for legacy transactions).
+  pub transactionType: u8,
+
+  pub outcome: ReceiptOutcome,
+
+  pub cumulativeGasUsed: u64,
+
+  // Logs repeat the same contract addresses and event signatures a lot, so they are stored using
+  // the column-aware encoding.
+  #[serde(with = "compression::serde_bridge::via_compressor")]
+  pub logs: Vec<Log>
+}
+
+// Outcome of the transaction, as recorded in its receipt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReceiptOutcome {
+  // Whether or not the transaction succeeded (introduced in EIP 658, with Byzantium).
+  Status(bool),
+
+  // Intermediate state root after the transaction, which pre-Byzantium receipts carry instead.
+  PostStateRoot(B256)
+}
+
+impl Default for ReceiptOutcome {
+  fn default( ) -> Self {
+    Self::Status(false)
+  }
+}
+
+impl Receipt {
+  // Calculates the bloom filter of the logs.
+  pub fn bloom(&self) -> Bloom {
+    let mut bloom= Bloom::ZERO;
+    for log in &self.logs {
+      bloom.accrue_log(log);
+    }
+    bloom
+  }
+
+  // Returns the receipt in its canonical (EIP 2718) encoding - the RLP encoded [status (or post
+  // state root), cumulative gas used, bloom, logs], prefixed by the transaction type for typed
+  // transactions. It's what goes into the receipts trie.
+  pub fn encoded(&self) -> Vec<u8> {
+    let bloom= self.bloom( );
+    let payloadLength= self.outcomeLength( ) + self.cumulativeGasUsed.length( ) + bloom.length( ) + self.logs.length( );
+
+    let mut encoded= Vec::with_capacity(payloadLength + 10);
+    if self.transactionType != 0 {
+      encoded.push(self.transactionType);
+    }
+    alloy_rlp::Header { list: true, payload_length: payloadLength }.encode(&mut encoded);
+    match &self.outcome {
+      ReceiptOutcome::Status(success) => success.encode(&mut encoded),
+      ReceiptOutcome::PostStateRoot(postStateRoot) => postStateRoot.encode(&mut encoded)
+    }
+    self.cumulativeGasUsed.encode(&mut encoded);
+    bloom.encode(&mut encoded);
+    self.logs.encode(&mut encoded);
+
+    encoded
+  }
+
+  // Decodes a receipt from its canonical encoding (see encoded). The bloom is skipped, since it's
+  // calculated from the logs.
+  pub fn fromEncoded(mut encoded: &[u8]) -> alloy_rlp::Result<Self> {
+    let transactionType= match encoded.first( ) {
+      Some(&transactionType) if transactionType < EMPTY_LIST_CODE => {
+        encoded= &encoded[1..];
+        transactionType
+      },
+      _ => 0
+    };
+
+    let header= alloy_rlp::Header::decode(&mut encoded)?;
+    if !header.list {
+      return Err(alloy_rlp::Error::UnexpectedString)
+    }
+    if header.payload_length != encoded.len( ) {
+      return Err(alloy_rlp::Error::UnexpectedLength)
+    }
+
+    // The post state root is a 32 bytes string, prefixed by 0xa0.
+    let outcome= match encoded.first( ) {
+      Some(&0xa0) => ReceiptOutcome::PostStateRoot(B256::decode(&mut encoded)?),
+      _ => ReceiptOutcome::Status(bool::decode(&mut encoded)?)
+    };
+    let cumulativeGasUsed= u64::decode(&mut encoded)?;
+    Bloom::decode(&mut encoded)?;
+    let logs= Vec::<Log>::decode(&mut encoded)?;
+
+    Ok(Self {
+      transactionType,
+      outcome,
+      cumulativeGasUsed,
+      logs
+    })
+  }
+
+  fn outcomeLength(&self) -> usize {
+    match &self.outcome {
+      ReceiptOutcome::Status(success) => success.length( ),
+      ReceiptOutcome::PostStateRoot(postStateRoot) => postStateRoot.length( )
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use alloy_primitives::{Address, Bytes};
+  use super::*;
+
+  fn receipt(transactionType: u8, outcome: ReceiptOutcome) -> Receipt {
+    Receipt {
+      transactionType,
+      outcome,
+      cumulativeGasUsed: 42_000,
+      logs: vec![Log::new_unchecked(Address::with_last_byte(1), vec![B256::with_last_byte(2)], Bytes::from_static(&[3, 4]))]
+    }
+  }
+
+  #[test]
+  fn receiptsRoundTripByteForByte( ) {
+    let receipts= [
+      receipt(0, ReceiptOutcome::PostStateRoot(B256::repeat_byte(0xAB))),
+      receipt(0, ReceiptOutcome::Status(false)),
+      receipt(0, ReceiptOutcome::Status(true)),
+      receipt(2, ReceiptOutcome::Status(true))
+    ];
+
+    for receipt in receipts {
+      let encoded= receipt.encoded( );
+      let decoded= Receipt::fromEncoded(&encoded).unwrap( );
+
+      assert_eq!(decoded, receipt);
+      assert_eq!(decoded.encoded( ), encoded);
+    }
+  }
+
+  #[test]
+  fn postStateRootIsEncodedInPlaceOfTheStatus( ) {
+    let encoded= receipt(0, ReceiptOutcome::PostStateRoot(B256::repeat_byte(0xAB))).encoded( );
+
+    // List header (3 bytes, since the bloom alone is 259 bytes long), then the post state root.
+    assert_eq!(encoded[3], 0xa0);
+    assert_eq!(&encoded[4..36], &[0xAB; 32]);
+  }
+}
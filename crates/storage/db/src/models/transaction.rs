@@ -0,0 +1,120 @@
+use alloy_primitives::{keccak256, Address, Bytes, U256};
+use alloy_rlp::{Decodable, EMPTY_LIST_CODE};
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use super::TxHash;
+
+/*
+  Signed transaction, as stored in the database (and static files).
+
+  Instead of modelling every transaction type, the transaction is kept in its canonical (EIP 2718)
+  encoding - RLP for legacy transactions, and the type byte followed by the RLP encoded payload for
+  typed transactions. It's exactly what's hashed to get the transaction hash, and what goes into
+  the transactions trie.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionSigned {
+  pub hash: TxHash,
+  pub encoded: Bytes
+}
+
+impl TransactionSigned {
+  /*
+    Recovers the address of the account which signed the transaction, from its signature. None is
+    returned if the transaction can't be decoded, or the signature is invalid.
+
+    The signature is always made of the last 3 items of the RLP list. The signed payload is the list
+    without them (prefixed by the type byte, for typed transactions). Legacy transactions protected
+    against replays (EIP 155) sign [chain id, 0, 0] in place of the signature, where the chain id
+    is derived from v.
+  */
+  pub fn recoverSender(&self) -> Option<Address> {
+    let (transactionType, payload)= match self.encoded.first( ) {
+      Some(firstByte) if *firstByte < EMPTY_LIST_CODE => (Some(*firstByte), &self.encoded[1..]),
+      _ => (None, &self.encoded[..])
+    };
+
+    let items= rlpListItems(payload)?;
+    if items.len( ) < 3 {
+      return None
+    }
+    let (unsignedItems, signature)= items.split_at(items.len( ) - 3);
+
+    let v= u64::decode(&mut &*signature[0]).ok( )?;
+    let r= U256::decode(&mut &*signature[1]).ok( )?;
+    let s= U256::decode(&mut &*signature[2]).ok( )?;
+
+    let mut signedPayload= Vec::with_capacity(payload.len( ));
+    let yParity= match transactionType {
+      Some(transactionType) => {
+        signedPayload.push(transactionType);
+        encodeList(&mut signedPayload, unsignedItems.iter( ).copied( ));
+        (v <= 1).then_some(v)?
+      },
+
+      None => match v {
+        27 | 28 => {
+          encodeList(&mut signedPayload, unsignedItems.iter( ).copied( ));
+          v - 27
+        },
+        35.. => {
+          let chainId= alloy_rlp::encode((v - 35) / 2);
+          let replayProtection= [&chainId[..], &[alloy_rlp::EMPTY_STRING_CODE], &[alloy_rlp::EMPTY_STRING_CODE]];
+          encodeList(&mut signedPayload, unsignedItems.iter( ).copied( ).chain(replayProtection));
+          (v - 35) % 2
+        },
+        _ => return None
+      }
+    };
+
+    let mut signature= Signature::from_scalars(r.to_be_bytes::<32>( ), s.to_be_bytes::<32>( )).ok( )?;
+    let mut recoveryId= RecoveryId::from_byte(u8::try_from(yParity).ok( )?)?;
+
+    // Signatures with a high s (valid before EIP 2) are rejected by k256. They are normalized,
+    // which flips the parity of the y coordinate, but recovers the same key.
+    if let Some(normalizedSignature)= signature.normalize_s( ) {
+      signature= normalizedSignature;
+      recoveryId= RecoveryId::new(!recoveryId.is_y_odd( ), recoveryId.is_x_reduced( ));
+    }
+
+    let verifyingKey= VerifyingKey::recover_from_prehash(keccak256(&signedPayload).as_slice( ), &signature, recoveryId).ok( )?;
+    let publicKey= verifyingKey.to_encoded_point(false);
+
+    Some(Address::from_slice(&keccak256(&publicKey.as_bytes( )[1..])[12..]))
+  }
+}
+
+// Splits an RLP list into its (still encoded) items.
+fn rlpListItems(mut encoded: &[u8]) -> Option<Vec<&[u8]>> {
+  let header= alloy_rlp::Header::decode(&mut encoded).ok( )?;
+  if !header.list || header.payload_length != encoded.len( ) {
+    return None
+  }
+
+  let mut items= Vec::new( );
+  while !encoded.is_empty( ) {
+    let mut rest= encoded;
+    let itemHeader= alloy_rlp::Header::decode(&mut rest).ok( )?;
+    let itemLength= encoded.len( ) - rest.len( ) + itemHeader.payload_length;
+    if itemLength > encoded.len( ) {
+      return None
+    }
+
+    let (item, remaining)= encoded.split_at(itemLength);
+    items.push(item);
+    encoded= remaining;
+  }
+  Some(items)
+}
+
+// Encodes an RLP list out of already encoded items.
+fn encodeList<'a, I>(out: &mut Vec<u8>, items: I)
+  where
+    I: Iterator<Item = &'a [u8]> + Clone
+{
+  let payloadLength= items.clone( ).map(<[u8]>::len).sum( );
+  alloy_rlp::Header { list: true, payload_length: payloadLength }.encode(out);
+  for item in items {
+    out.extend_from_slice(item);
+  }
+}
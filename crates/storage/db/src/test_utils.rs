@@ -0,0 +1,213 @@
+use std::{collections::BTreeMap, ops::RangeInclusive, sync::{Arc, Mutex}};
+use alloy_primitives::{keccak256, Address, Bytes, U256};
+use k256::ecdsa::SigningKey;
+use crate::{
+  interfaces::{db::{Db, DbError}, table::TableDuplicater, transaction::{DbTx, RoDbTx}},
+  models::{BlockBodyIndices, BlockHash, BlockNumber, Header, Receipt, TransactionSigned, TxHash, TxNumber}
+};
+
+/*
+  In-memory Db, for the tests of the crates reading from / writing to the database.
+
+  Transactions operate on the tables directly, so there's no isolation between them, and writes
+  can't be rolled back.
+*/
+#[derive(Default, Clone)]
+pub struct MemoryDb {
+  tables: Arc<Mutex<MemoryTables>>
+}
+
+#[derive(Default)]
+pub struct MemoryTables {
+  pub headers: BTreeMap<BlockNumber, Header>,
+  pub blockHashes: BTreeMap<BlockNumber, BlockHash>,
+  pub blockNumbers: BTreeMap<BlockHash, BlockNumber>,
+  pub totalDifficulties: BTreeMap<BlockNumber, U256>,
+  pub blockBodyIndices: BTreeMap<BlockNumber, BlockBodyIndices>,
+  pub ommers: BTreeMap<BlockNumber, Vec<Header>>,
+
+  pub transactions: BTreeMap<TxNumber, TransactionSigned>,
+  pub txNumbers: BTreeMap<TxHash, TxNumber>,
+  pub receipts: BTreeMap<TxNumber, Receipt>,
+  pub senders: BTreeMap<TxNumber, Address>
+}
+
+impl MemoryDb {
+  // Inserts a block, whose transactions get numbered sequentially from firstTxNumber.
+  pub fn insertBlock(&self,
+                     header: &Header,
+                     totalDifficulty: U256,
+                     firstTxNumber: TxNumber,
+                     transactions: &[TransactionSigned],
+                     receipts: &[Receipt],
+                     senders: &[Address])
+  {
+    let mut tables= self.tables( );
+    let blockHash= header.hash( );
+
+    tables.headers.insert(header.number, header.clone( ));
+    tables.blockHashes.insert(header.number, blockHash);
+    tables.blockNumbers.insert(blockHash, header.number);
+    tables.totalDifficulties.insert(header.number, totalDifficulty);
+    tables.blockBodyIndices.insert(header.number, BlockBodyIndices { firstTxNumber, txCount: transactions.len( ) as u64 });
+
+    for (txNumber, transaction) in (firstTxNumber..).zip(transactions) {
+      tables.transactions.insert(txNumber, transaction.clone( ));
+      tables.txNumbers.insert(transaction.hash, txNumber);
+    }
+    tables.receipts.extend((firstTxNumber..).zip(receipts.iter( ).cloned( )));
+    tables.senders.extend((firstTxNumber..).zip(senders.iter( ).copied( )));
+  }
+
+  pub fn tables(&self) -> std::sync::MutexGuard<'_, MemoryTables> {
+    self.tables.lock( ).unwrap( )
+  }
+}
+
+impl Db for MemoryDb {
+  type RoDbTx= MemoryDbTx;
+  type DbTx= MemoryDbTx;
+
+  fn roDbTx(&self) -> Result<Self::RoDbTx, DbError> {
+    Ok(MemoryDbTx { db: self.clone( ) })
+  }
+
+  fn dbTx(&self) -> Result<Self::DbTx, DbError> {
+    Ok(MemoryDbTx { db: self.clone( ) })
+  }
+}
+
+pub struct MemoryDbTx {
+  db: MemoryDb
+}
+
+impl RoDbTx for MemoryDbTx {
+  fn commit(self) -> Result<bool, DbError> {
+    Ok(true)
+  }
+
+  fn header(&self, blockNumber: BlockNumber) -> Result<Option<Header>, DbError> {
+    Ok(self.db.tables( ).headers.get(&blockNumber).cloned( ))
+  }
+
+  fn blockHash(&self, blockNumber: BlockNumber) -> Result<Option<BlockHash>, DbError> {
+    Ok(self.db.tables( ).blockHashes.get(&blockNumber).copied( ))
+  }
+
+  fn blockNumber(&self, blockHash: BlockHash) -> Result<Option<BlockNumber>, DbError> {
+    Ok(self.db.tables( ).blockNumbers.get(&blockHash).copied( ))
+  }
+
+  fn totalDifficulty(&self, blockNumber: BlockNumber) -> Result<Option<U256>, DbError> {
+    Ok(self.db.tables( ).totalDifficulties.get(&blockNumber).copied( ))
+  }
+
+  fn blockBodyIndices(&self, blockNumber: BlockNumber) -> Result<Option<BlockBodyIndices>, DbError> {
+    Ok(self.db.tables( ).blockBodyIndices.get(&blockNumber).copied( ))
+  }
+
+  fn transaction(&self, txNumber: TxNumber) -> Result<Option<TransactionSigned>, DbError> {
+    Ok(self.db.tables( ).transactions.get(&txNumber).cloned( ))
+  }
+
+  fn txNumber(&self, txHash: TxHash) -> Result<Option<TxNumber>, DbError> {
+    Ok(self.db.tables( ).txNumbers.get(&txHash).copied( ))
+  }
+
+  fn receipt(&self, txNumber: TxNumber) -> Result<Option<Receipt>, DbError> {
+    Ok(self.db.tables( ).receipts.get(&txNumber).cloned( ))
+  }
+
+  fn sender(&self, txNumber: TxNumber) -> Result<Option<Address>, DbError> {
+    Ok(self.db.tables( ).senders.get(&txNumber).copied( ))
+  }
+
+  fn ommers(&self, blockNumber: BlockNumber) -> Result<Option<Vec<Header>>, DbError> {
+    Ok(self.db.tables( ).ommers.get(&blockNumber).cloned( ))
+  }
+}
+
+impl DbTx for MemoryDbTx {
+  fn deleteHeaders(&self, blockRange: RangeInclusive<BlockNumber>) -> Result<( ), DbError> {
+    let mut tables= self.db.tables( );
+    for blockNumber in blockRange {
+      tables.headers.remove(&blockNumber);
+      tables.totalDifficulties.remove(&blockNumber);
+      if let Some(blockHash)= tables.blockHashes.remove(&blockNumber) {
+        tables.blockNumbers.remove(&blockHash);
+      }
+    }
+    Ok(( ))
+  }
+
+  fn deleteTransactions(&self, txRange: RangeInclusive<TxNumber>) -> Result<( ), DbError> {
+    let mut tables= self.db.tables( );
+    for txNumber in txRange {
+      if let Some(transaction)= tables.transactions.remove(&txNumber) {
+        tables.txNumbers.remove(&transaction.hash);
+      }
+    }
+    Ok(( ))
+  }
+
+  fn deleteReceipts(&self, txRange: RangeInclusive<TxNumber>) -> Result<( ), DbError> {
+    let mut tables= self.db.tables( );
+    tables.receipts.retain(|txNumber, _| !txRange.contains(txNumber));
+    Ok(( ))
+  }
+
+  fn deleteSenders(&self, txRange: RangeInclusive<TxNumber>) -> Result<( ), DbError> {
+    let mut tables= self.db.tables( );
+    tables.senders.retain(|txNumber, _| !txRange.contains(txNumber));
+    Ok(( ))
+  }
+
+  fn insertBlockBodyIndices(&self, blockNumber: BlockNumber, blockBodyIndices: BlockBodyIndices) -> Result<( ), DbError> {
+    self.db.tables( ).blockBodyIndices.insert(blockNumber, blockBodyIndices);
+    Ok(( ))
+  }
+
+  fn insertOmmers(&self, blockNumber: BlockNumber, ommers: &[Header]) -> Result<( ), DbError> {
+    self.db.tables( ).ommers.insert(blockNumber, ommers.to_vec( ));
+    Ok(( ))
+  }
+}
+
+impl TableDuplicater for MemoryDbTx { }
+
+// Signs a legacy transaction (without replay protection) sending 1 wei to the zero address, with
+// the private key derived from the given seed. Returns it along with the address of the signer.
+pub fn signedTransaction(keySeed: u64, nonce: u64) -> (TransactionSigned, Address) {
+  let signingKey= SigningKey::from_bytes(&keccak256(keySeed.to_be_bytes( )).0.into( )).unwrap( );
+
+  let publicKey= signingKey.verifying_key( ).to_encoded_point(false);
+  let signer= Address::from_slice(&keccak256(&publicKey.as_bytes( )[1..])[12..]);
+
+  let unsignedItems= [
+    alloy_rlp::encode(nonce),
+    alloy_rlp::encode(1_000_000_000u64),
+    alloy_rlp::encode(21_000u64),
+    alloy_rlp::encode(Address::ZERO),
+    alloy_rlp::encode(U256::from(1)),
+    alloy_rlp::encode(Bytes::new( ))
+  ];
+  let (signature, recoveryId)= signingKey.sign_prehash_recoverable(keccak256(encodeList(&unsignedItems)).as_slice( )).unwrap( );
+
+  let signatureItems= [
+    alloy_rlp::encode(27 + recoveryId.is_y_odd( ) as u64),
+    alloy_rlp::encode(U256::from_be_slice(&signature.r( ).to_bytes( ))),
+    alloy_rlp::encode(U256::from_be_slice(&signature.s( ).to_bytes( )))
+  ];
+  let encoded= Bytes::from(encodeList(&[&unsignedItems[..], &signatureItems[..]].concat( )));
+
+  (TransactionSigned { hash: keccak256(&encoded), encoded }, signer)
+}
+
+fn encodeList(items: &[Vec<u8>]) -> Vec<u8> {
+  let mut encoded= Vec::new( );
+  alloy_rlp::Header { list: true, payload_length: items.iter( ).map(Vec::len).sum( ) }.encode(&mut encoded);
+  for item in items {
+    encoded.extend_from_slice(item);
+  }
+  encoded
+}
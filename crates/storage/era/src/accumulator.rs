@@ -0,0 +1,51 @@
+use alloy_primitives::{B256, U256};
+use db::models::BlockHash;
+use sha2::{Digest, Sha256};
+
+// Number of blocks in an epoch, and so in an Era1 file.
+pub const EPOCH_SIZE: u64= 8192;
+
+// Depth of the merkle tree over the header records of an epoch.
+const ACCUMULATOR_TREE_DEPTH: usize= EPOCH_SIZE.trailing_zeros( ) as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderRecord {
+  pub blockHash: BlockHash,
+  pub totalDifficulty: U256
+}
+
+/*
+  Calculates the accumulator root of an epoch : the SSZ hash tree root of the header records of
+  the blocks in the epoch, as a List[HeaderRecord, EPOCH_SIZE]. It's what pins the content of an
+  Era1 file, and what pre-merge history is verified against.
+
+  The hash tree root of a HeaderRecord is the hash of its 2 fields (each being a 32 bytes chunk, the
+  total difficulty in little-endian). The records are merkleized into a tree with EPOCH_SIZE leaves
+  (the missing ones being zero), and the number of records is mixed in at the end.
+*/
+pub fn accumulatorRoot(headerRecords: &[HeaderRecord]) -> B256 {
+  let mut layer: Vec<B256>= headerRecords.iter( )
+    .map(|headerRecord| hashPair(&headerRecord.blockHash, &B256::from(headerRecord.totalDifficulty.to_le_bytes::<32>( ))))
+    .collect( );
+
+  // Hash of a subtree whose leaves are all zero, at the current depth.
+  let mut zeroHash= B256::ZERO;
+
+  for _ in 0..ACCUMULATOR_TREE_DEPTH {
+    if layer.len( ) % 2 == 1 {
+      layer.push(zeroHash);
+    }
+    layer= layer.chunks(2).map(|pair| hashPair(&pair[0], &pair[1])).collect( );
+    zeroHash= hashPair(&zeroHash, &zeroHash);
+  }
+  let treeRoot= layer.pop( ).unwrap_or(zeroHash);
+
+  hashPair(&treeRoot, &B256::from(U256::from(headerRecords.len( )).to_le_bytes::<32>( )))
+}
+
+fn hashPair(left: &B256, right: &B256) -> B256 {
+  let mut hasher= Sha256::new( );
+  hasher.update(left);
+  hasher.update(right);
+  B256::from_slice(&hasher.finalize( ))
+}
@@ -0,0 +1,111 @@
+use std::io::{self, Read, Write};
+use super::era1_error::Era1Error;
+
+/*
+  e2store is the container format Era1 files are built upon. A file is a sequence of entries, each
+  made of an 8 bytes header followed by the data :
+
+    type (2 bytes) | length of the data (4 bytes, little-endian) | reserved (2 bytes, always 0)
+
+  See https://github.com/status-im/nimbus-eth2/blob/stable/docs/e2store.md.
+*/
+
+pub type EntryType= [u8; 2];
+
+pub const VERSION: EntryType= [0x65, 0x32];
+pub const COMPRESSED_HEADER: EntryType= [0x03, 0x00];
+pub const COMPRESSED_BODY: EntryType= [0x04, 0x00];
+pub const COMPRESSED_RECEIPTS: EntryType= [0x05, 0x00];
+pub const TOTAL_DIFFICULTY: EntryType= [0x06, 0x00];
+pub const ACCUMULATOR: EntryType= [0x07, 0x00];
+pub const BLOCK_INDEX: EntryType= [0x66, 0x32];
+
+pub const ENTRY_HEADER_SIZE: usize= 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+  pub entryType: EntryType,
+  pub data: Vec<u8>
+}
+
+// Writes an entry, returning the number of bytes written (including the entry header).
+pub fn writeEntry<W>(writer: &mut W, entryType: EntryType, data: &[u8]) -> Result<u64, Era1Error>
+  where
+    W: Write
+{
+  let length= u32::try_from(data.len( )).map_err(|_| Era1Error::EntryTooLarge(data.len( )))?;
+
+  writer.write_all(&entryType)?;
+  writer.write_all(&length.to_le_bytes( ))?;
+  writer.write_all(&[0, 0])?;
+  writer.write_all(data)?;
+
+  Ok((ENTRY_HEADER_SIZE + data.len( )) as u64)
+}
+
+// Reads the next entry, out of the remaining bytes of the file. None is returned at the end of the
+// file.
+pub fn readEntry<R>(reader: &mut R, remainingLength: u64) -> Result<Option<Entry>, Era1Error>
+  where
+    R: Read
+{
+  let mut entryHeader= [0u8; ENTRY_HEADER_SIZE];
+
+  // Distinguishes the end of the file from a truncated entry header.
+  match reader.read(&mut entryHeader[..1])? {
+    0 => return Ok(None),
+    _ => reader.read_exact(&mut entryHeader[1..]).map_err(truncated)?
+  }
+
+  if entryHeader[6..] != [0, 0] {
+    return Err(Era1Error::InvalidEntry("reserved bytes of the entry header aren't 0"))
+  }
+
+  // The length is checked before allocating, so that a corrupted one can't make us allocate up to
+  // 4GB.
+  let length= u32::from_le_bytes(entryHeader[2..6].try_into( ).unwrap( ));
+  if length as u64 > remainingLength.saturating_sub(ENTRY_HEADER_SIZE as u64) {
+    return Err(Era1Error::InvalidEntry("entry is truncated"))
+  }
+  let mut data= vec![0u8; length as usize];
+  reader.read_exact(&mut data).map_err(truncated)?;
+
+  Ok(Some(Entry {
+    entryType: [entryHeader[0], entryHeader[1]],
+    data
+  }))
+}
+
+fn truncated(error: io::Error) -> Era1Error {
+  match error.kind( ) {
+    io::ErrorKind::UnexpectedEof => Era1Error::InvalidEntry("entry is truncated"),
+    _ => error.into( )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn entriesAreReadBack( ) {
+    let mut file= Vec::new( );
+    writeEntry(&mut file, COMPRESSED_HEADER, &[1, 2, 3]).unwrap( );
+    writeEntry(&mut file, VERSION, &[ ]).unwrap( );
+
+    let mut reader= &file[..];
+    let remainingLength= file.len( ) as u64;
+    assert_eq!(readEntry(&mut reader, remainingLength).unwrap( ), Some(Entry { entryType: COMPRESSED_HEADER, data: vec![1, 2, 3] }));
+    assert_eq!(readEntry(&mut reader, remainingLength - 11).unwrap( ), Some(Entry { entryType: VERSION, data: Vec::new( ) }));
+    assert_eq!(readEntry(&mut reader, 0).unwrap( ), None);
+  }
+
+  #[test]
+  fn entryLongerThanTheFileIsAnError( ) {
+    let mut file= Vec::new( );
+    writeEntry(&mut file, COMPRESSED_HEADER, &[1, 2, 3]).unwrap( );
+    file[2..6].copy_from_slice(&u32::MAX.to_le_bytes( ));
+
+    assert!(matches!(readEntry(&mut &file[..], file.len( ) as u64), Err(Era1Error::InvalidEntry("entry is truncated"))));
+  }
+}
@@ -0,0 +1,77 @@
+use alloy_primitives::B256;
+use db::{interfaces::db::DbError, models::BlockNumber};
+use static_files::static_files_error::StaticFilesError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Era1Error {
+
+  #[error("{data} of block {blockNumber} not found")]
+  MissingBlockData {
+    data: &'static str,
+    blockNumber: BlockNumber
+  },
+
+  #[error("Header of block {blockNumber} doesn't hash to the stored block hash")]
+  BlockHashMismatch {
+    blockNumber: BlockNumber
+  },
+
+  #[error("Epoch {epoch} is past the last block that can be exported")]
+  EpochNotExportable {
+    epoch: u64
+  },
+
+  #[error("Era1 file of epoch {epoch} starts at block {got}, but block {expected} is the next one to be imported")]
+  NonContiguousEpoch {
+    epoch: u64,
+    expected: BlockNumber,
+    got: BlockNumber
+  },
+
+  #[error("Block {blockNumber} is invalid : {reason}")]
+  InvalidBlock {
+    blockNumber: BlockNumber,
+    reason: &'static str
+  },
+
+  #[error("Accumulator root of epoch {epoch} doesn't match the trusted accumulator root")]
+  UntrustedAccumulatorRoot {
+    epoch: u64
+  },
+
+  #[error("Entry of {0} bytes is too large for an e2store entry")]
+  EntryTooLarge(usize),
+
+  #[error("Invalid e2store entry : {0}")]
+  InvalidEntry(&'static str),
+
+  #[error("Expected an e2store entry of type {expected:?}, but got {got:?}")]
+  UnexpectedEntry {
+    expected: [u8; 2],
+    got: [u8; 2]
+  },
+
+  #[error("Invalid block index : {0}")]
+  InvalidBlockIndex(&'static str),
+
+  #[error("Accumulator root {expected} is stored, but the blocks have accumulator root {got}")]
+  AccumulatorMismatch {
+    expected: B256,
+    got: B256
+  },
+
+  #[error("File name {0} doesn't match the accumulator root of the Era1 file")]
+  FileNameMismatch(String),
+
+  #[error(transparent)]
+  Rlp(#[from] alloy_rlp::Error),
+
+  #[error(transparent)]
+  StaticFiles(#[from] StaticFilesError),
+
+  #[error(transparent)]
+  Db(#[from] DbError),
+
+  #[error(transparent)]
+  Io(#[from] std::io::Error)
+}
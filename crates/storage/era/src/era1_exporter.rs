@@ -0,0 +1,258 @@
+use std::{fs::{self, File}, io::BufWriter, path::{Path, PathBuf}, sync::Arc};
+use db::{interfaces::{self, transaction::RoDbTx}, models::BlockNumber};
+use static_files::{segment::SegmentType, static_files_handler::StaticFilesHander};
+use super::{
+  accumulator::EPOCH_SIZE,
+  era1_error::Era1Error,
+  era1_reader::verifyEra1File,
+  era1_writer::{era1FileName, Era1Block, Era1Writer}
+};
+
+// Segments the blocks are read from. Senders aren't stored in Era1 files.
+const EXPORTED_SEGMENT_TYPES: [SegmentType; 3]= [
+  SegmentType::BlockHeaders,
+  SegmentType::Transactions,
+  SegmentType::TransactionReceipts
+];
+
+/*
+  Exports history to Era1 files, one per epoch (of EPOCH_SIZE blocks, starting from genesis).
+
+  Headers (along with the block hashes and total difficulties), transactions and receipts are read
+  through the StaticFilesHandler. Block body indices and ommers are read from the database, since
+  they never get copied to static files.
+
+  Era1 only covers pre-merge history : use withLastBlockNumber( ) to stop at the last pre-merge block.
+  The epoch containing it is then exported partially. Otherwise, only whole epochs get exported.
+
+  Each Era1 file is written to a temporary file, renamed once complete, and then verified against
+  its accumulator root.
+*/
+pub struct Era1Exporter<Db> {
+  db: Arc<Db>,
+  staticFilesHandler: Arc<StaticFilesHander>,
+
+  // Name of the network, used as the prefix of the file names (e.g. mainnet).
+  network: String,
+
+  lastBlockNumber: Option<BlockNumber>
+}
+
+impl<Db> Era1Exporter<Db>
+  where
+    Db: interfaces::db::Db
+{
+  pub fn new(db: Arc<Db>, staticFilesHandler: Arc<StaticFilesHander>, network: &str) -> Self {
+    Self {
+      db,
+      staticFilesHandler,
+      network: network.to_string( ),
+      lastBlockNumber: None
+    }
+  }
+
+  // Sets the last block to be exported (usually the last pre-merge block).
+  pub fn withLastBlockNumber(mut self, lastBlockNumber: BlockNumber) -> Self {
+    self.lastBlockNumber= Some(lastBlockNumber);
+    self
+  }
+
+  // Exports every exportable epoch into the directory, returning the paths of the Era1 files.
+  pub fn export(&self, directory: &Path) -> Result<Vec<PathBuf>, Era1Error> {
+    (0..self.exportableEpochCount( ))
+      .map(|epoch| self.exportEpoch(epoch, directory))
+      .collect( )
+  }
+
+  // Exports an epoch into the directory, returning the path of its Era1 file.
+  pub fn exportEpoch(&self, epoch: u64, directory: &Path) -> Result<PathBuf, Era1Error> {
+    if epoch >= self.exportableEpochCount( ) {
+      return Err(Era1Error::EpochNotExportable { epoch })
+    }
+
+    let firstBlockNumber= epoch * EPOCH_SIZE;
+    let lastBlockNumber= (firstBlockNumber + EPOCH_SIZE - 1).min(self.highestExportableBlockNumber( ).unwrap_or_default( ));
+
+    fs::create_dir_all(directory)?;
+    let temporaryPath= directory.join(format!("{}-{:05}.era1.tmp", self.network, epoch));
+
+    let accumulatorRoot= self.db.withRoDbTx(|roDbTx| -> Result<_, Era1Error> {
+      let mut era1Writer= Era1Writer::new(BufWriter::new(File::create(&temporaryPath)?))?;
+
+      for blockNumber in firstBlockNumber..=lastBlockNumber {
+        era1Writer.appendBlock(&self.block(roDbTx, blockNumber)?)?;
+      }
+      era1Writer.finish( )
+    });
+    let accumulatorRoot= match accumulatorRoot.map_err(Era1Error::from).and_then(|result| result) {
+      Ok(accumulatorRoot) => accumulatorRoot,
+      Err(error) => {
+        let _= fs::remove_file(&temporaryPath);
+        return Err(error)
+      }
+    };
+
+    let path= directory.join(era1FileName(&self.network, epoch, &accumulatorRoot));
+    fs::rename(&temporaryPath, &path)?;
+
+    verifyEra1File(&path)?;
+
+    Ok(path)
+  }
+
+  // Reads a block, checking that its header hashes to the stored block hash.
+  fn block(&self, roDbTx: &Db::RoDbTx, blockNumber: BlockNumber) -> Result<Era1Block, Era1Error> {
+    let missing= |data| Era1Error::MissingBlockData { data, blockNumber };
+
+    let header= self.staticFilesHandler.headerByNumber(blockNumber)?.ok_or(missing("Header"))?;
+    let blockHash= self.staticFilesHandler.blockHash(blockNumber)?.ok_or(missing("Block hash"))?;
+    if header.hash( ) != blockHash {
+      return Err(Era1Error::BlockHashMismatch { blockNumber })
+    }
+    let totalDifficulty= self.staticFilesHandler.totalDifficulty(blockNumber)?.ok_or(missing("Total difficulty"))?;
+
+    let blockBodyIndices= roDbTx.blockBodyIndices(blockNumber)?.ok_or(missing("Block body indices"))?;
+    let ommers= roDbTx.ommers(blockNumber)?.unwrap_or_default( );
+
+    let mut transactions= Vec::with_capacity(blockBodyIndices.txCount as usize);
+    let mut receipts= Vec::with_capacity(blockBodyIndices.txCount as usize);
+
+    for txNumber in blockBodyIndices.txNumbers( ) {
+      transactions.push(self.staticFilesHandler.transactionById(txNumber)?.ok_or(missing("Transaction"))?);
+      receipts.push(self.staticFilesHandler.receiptById(txNumber)?.ok_or(missing("Receipt"))?);
+    }
+
+    Ok(Era1Block {
+      header,
+      transactions,
+      ommers,
+      receipts,
+      totalDifficulty
+    })
+  }
+
+  // Number of epochs (starting from genesis) that can be exported.
+  fn exportableEpochCount(&self) -> u64 {
+    let Some(highestBlockNumber)= self.highestExportableBlockNumber( ) else {
+      return 0
+    };
+
+    match Some(highestBlockNumber) == self.lastBlockNumber {
+      true => highestBlockNumber / EPOCH_SIZE + 1,
+      false => (highestBlockNumber + 1) / EPOCH_SIZE
+    }
+  }
+
+  // The highest block contained in the static files of every exported segment, capped at the last
+  // block to be exported. None if the static files don't start from genesis.
+  fn highestExportableBlockNumber(&self) -> Option<BlockNumber> {
+    let startsFromGenesis= EXPORTED_SEGMENT_TYPES.iter( ).all(|segmentType| {
+      self.staticFilesHandler.segmentHeaders(*segmentType).first( )
+        .is_some_and(|segmentHeader| *segmentHeader.blockRange.start( ) == 0)
+    });
+    if !startsFromGenesis {
+      return None
+    }
+
+    let highestBlockNumber= EXPORTED_SEGMENT_TYPES.map(|segmentType| self.staticFilesHandler.highestBlockNumber(segmentType))
+                                                  .into_iter( )
+                                                  .min( )??;
+
+    Some(match self.lastBlockNumber {
+      Some(lastBlockNumber) => highestBlockNumber.min(lastBlockNumber),
+      None => highestBlockNumber
+    })
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use alloy_primitives::{B256, U256};
+  use db::{
+    models::{BlockBodyIndices, Header, Receipt, TransactionSigned},
+    test_utils::{signedTransaction, MemoryDb}
+  };
+  use static_files::static_files_writer::StaticFilesWriter;
+  use crate::{accumulator::{accumulatorRoot, HeaderRecord}, era1_reader::Era1Reader};
+  use super::*;
+
+  // Writes blocks 0..blockCount to static files (and their block body indices to the database).
+  // Block n has n % 2 transactions. The block hash of the block numbered invalidBlockHashAt (if
+  // any) doesn't match its header.
+  fn writeBlocks(staticFilesHandler: Arc<StaticFilesHander>, db: &MemoryDb, blockCount: u64, invalidBlockHashAt: Option<BlockNumber>) {
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler, db).unwrap( );
+
+    for blockNumber in 0..blockCount {
+      let firstTxNumber= staticFilesWriter.nextTxNumber( );
+      let (transactions, senders): (Vec<TransactionSigned>, Vec<_>)= (firstTxNumber..(firstTxNumber + blockNumber % 2))
+        .map(|txNumber| signedTransaction(txNumber, 0))
+        .unzip( );
+      let receipts= vec![Receipt::default( ); transactions.len( )];
+
+      let header= Header { number: blockNumber, difficulty: U256::from(blockNumber + 1), ..Default::default( ) };
+      let blockHash= match invalidBlockHashAt == Some(blockNumber) {
+        true => B256::ZERO,
+        false => header.hash( )
+      };
+
+      db.tables( ).blockBodyIndices.insert(blockNumber, BlockBodyIndices { firstTxNumber, txCount: transactions.len( ) as u64 });
+      staticFilesWriter.appendBlock(&header, blockHash, U256::from(blockNumber * 10), &transactions, &receipts, &senders).unwrap( );
+    }
+    staticFilesWriter.commit( ).unwrap( );
+  }
+
+  #[test]
+  fn partialEpochsAreOnlyExportedUpToTheLastBlock( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= Arc::new(MemoryDb::default( ));
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).join("static_files")).unwrap( ));
+    writeBlocks(staticFilesHandler.clone( ), &db, 5, None);
+
+    let era1Directory= directory.path( ).join("era1");
+
+    let era1Exporter= Era1Exporter::new(db.clone( ), staticFilesHandler.clone( ), "testnet");
+    assert!(era1Exporter.export(&era1Directory).unwrap( ).is_empty( ));
+    assert!(matches!(era1Exporter.exportEpoch(0, &era1Directory), Err(Era1Error::EpochNotExportable { epoch: 0 })));
+
+    let era1Files= era1Exporter.withLastBlockNumber(3).export(&era1Directory).unwrap( );
+    assert_eq!(era1Files.len( ), 1);
+
+    let mut era1Reader= Era1Reader::open(&era1Files[0]).unwrap( );
+    let mut headerRecords= Vec::new( );
+    while let Some(rawEra1Block)= era1Reader.nextBlock( ).unwrap( ) {
+      let era1Block= rawEra1Block.decode( ).unwrap( );
+      let blockNumber= era1Block.header.number;
+
+      assert_eq!(Some(era1Block.header.clone( )), staticFilesHandler.headerByNumber(blockNumber).unwrap( ));
+      assert_eq!(era1Block.transactions.len( ) as u64, blockNumber % 2);
+      assert_eq!(era1Block.receipts.len( ) as u64, blockNumber % 2);
+      assert_eq!(era1Block.totalDifficulty, U256::from(blockNumber * 10));
+
+      headerRecords.push(HeaderRecord { blockHash: era1Block.header.hash( ), totalDifficulty: era1Block.totalDifficulty });
+    }
+
+    let trailer= era1Reader.trailer( ).unwrap( );
+    assert_eq!((trailer.startingNumber, trailer.blockCount), (0, 4));
+    assert_eq!(trailer.accumulatorRoot, accumulatorRoot(&headerRecords));
+    assert_eq!(era1Files[0], era1Directory.join(era1FileName("testnet", 0, &trailer.accumulatorRoot)));
+
+    // Only the Era1 file is left in the directory.
+    assert_eq!(fs::read_dir(&era1Directory).unwrap( ).count( ), 1);
+  }
+
+  #[test]
+  fn blocksNotMatchingTheirBlockHashAreNotExported( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= Arc::new(MemoryDb::default( ));
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).join("static_files")).unwrap( ));
+    writeBlocks(staticFilesHandler.clone( ), &db, 5, Some(2));
+
+    let era1Directory= directory.path( ).join("era1");
+    let era1Exporter= Era1Exporter::new(db, staticFilesHandler, "testnet").withLastBlockNumber(4);
+    assert!(matches!(era1Exporter.export(&era1Directory), Err(Era1Error::BlockHashMismatch { blockNumber: 2 })));
+
+    // The temporary file got removed.
+    assert_eq!(fs::read_dir(&era1Directory).unwrap( ).count( ), 0);
+  }
+}
@@ -0,0 +1,417 @@
+use std::{collections::HashMap, fs, path::{Path, PathBuf}, sync::Arc};
+use alloy_primitives::{keccak256, Address, B256, U256};
+use db::{
+  interfaces::{self, transaction::{DbTx, RoDbTx}},
+  models::{orderedTrieRoot, BlockBodyIndices, BlockHash, Header, Receipt}
+};
+use static_files::{segment::SegmentType, static_files_handler::StaticFilesHander, static_files_writer::StaticFilesWriter};
+use super::{
+  accumulator::{accumulatorRoot, HeaderRecord, EPOCH_SIZE},
+  era1_error::Era1Error,
+  era1_reader::Era1Reader,
+  era1_writer::parseEra1FileName
+};
+
+/*
+  Seeds history from a directory of Era1 files, without the network. The blocks are written
+  straight to static files (of the block headers, transactions, transaction receipts and senders
+  segments), through the StaticFilesWriter. Block body indices and ommers, which never get copied to static
+  files, are written to the database.
+
+  Each Era1 file is read twice :
+
+  (1) The blocks are verified, before anything gets written : each header must hash to the block
+      hash in the accumulator (so must our own encoding of it), link to the previous block, and sum
+      up to the stored total difficulty. The ommers must match the ommers hash in the header. The
+      sender of each transaction is recovered from its signature (Era1 files don't store them). The
+      block hashes and total difficulties must then add up to the accumulator root stored in the
+      file, and to the trusted one of the epoch. Then the block body indices and ommers are written
+      to the database.
+
+  (2) The blocks are appended to static files, and committed.
+
+  Since the database is written to first, an import interrupted midway can be resumed : blocks
+  already in static files are skipped, and the rest of the epoch gets appended.
+
+  The accumulator root stored in an Era1 file only proves that the file is intact. What proves that
+  its blocks are canonical is the trusted accumulator root : so the trusted accumulator roots must
+  be given (e.g. the pre-merge accumulator of mainnet), and epochs without one can't be imported.
+*/
+pub struct Era1Importer<Db> {
+  db: Arc<Db>,
+  staticFilesHandler: Arc<StaticFilesHander>,
+
+  // Number of blocks in each static file created, for the segments for which it's set.
+  blocksPerStaticFile: HashMap<SegmentType, u64>,
+
+  // Accumulator roots of the epochs (starting from genesis), known beforehand.
+  trustedAccumulatorRoots: Vec<B256>
+}
+
+// What's verified about an Era1 file, before it gets imported.
+struct VerifiedEra1File {
+  blockHashes: Vec<BlockHash>,
+  blockBodyIndices: Vec<BlockBodyIndices>,
+  ommers: Vec<Vec<Header>>,
+  senders: Vec<Vec<Address>>
+}
+
+impl<Db> Era1Importer<Db>
+  where
+    Db: interfaces::db::Db
+{
+  // The trusted accumulator roots are the ones of the epochs, starting from genesis (e.g. the ones
+  // published along with the Era1 files).
+  pub fn new(db: Arc<Db>, staticFilesHandler: Arc<StaticFilesHander>, trustedAccumulatorRoots: Vec<B256>) -> Self {
+    Self {
+      db,
+      staticFilesHandler,
+      blocksPerStaticFile: HashMap::new( ),
+      trustedAccumulatorRoots
+    }
+  }
+
+  // Sets the number of blocks in each static file created for the given segment (see
+  // StaticFilesWriter::withBlocksPerStaticFile).
+  pub fn withBlocksPerStaticFile(mut self, segmentType: SegmentType, blocksPerStaticFile: u64) -> Self {
+    self.blocksPerStaticFile.insert(segmentType, blocksPerStaticFile);
+    self
+  }
+
+  // Imports the Era1 files in the directory, in the order of their epochs. Epochs already in static
+  // files are skipped. Returns the number of blocks imported.
+  pub fn import(&self, directory: &Path) -> Result<u64, Era1Error> {
+    let mut staticFilesWriter= StaticFilesWriter::new(self.staticFilesHandler.clone( ), &*self.db)?;
+    for (segmentType, blocksPerStaticFile) in &self.blocksPerStaticFile {
+      staticFilesWriter= staticFilesWriter.withBlocksPerStaticFile(*segmentType, *blocksPerStaticFile);
+    }
+
+    let mut importedBlockCount= 0;
+    for (epoch, path) in era1FilesIn(directory)? {
+      importedBlockCount += self.importEra1File(&mut staticFilesWriter, epoch, &path)?;
+    }
+
+    Ok(importedBlockCount)
+  }
+
+  fn importEra1File(&self, staticFilesWriter: &mut StaticFilesWriter, epoch: u64, path: &Path) -> Result<u64, Era1Error> {
+    let firstBlockNumber= epoch * EPOCH_SIZE;
+    let nextBlockNumber= staticFilesWriter.nextBlockNumber( );
+
+    if nextBlockNumber >= firstBlockNumber + EPOCH_SIZE {
+      return Ok(0)
+    }
+    if firstBlockNumber > nextBlockNumber {
+      return Err(Era1Error::NonContiguousEpoch { epoch, expected: nextBlockNumber, got: firstBlockNumber })
+    }
+
+    // (1) Verify, and write to the database.
+    let verifiedEra1File= self.verifyEra1File(epoch, path)?;
+
+    let firstTxNumber= match firstBlockNumber == nextBlockNumber {
+      true => staticFilesWriter.nextTxNumber( ),
+
+      // The import of the epoch got interrupted : the database was written to already.
+      false => self.db.withRoDbTx(|roDbTx| roDbTx.blockBodyIndices(firstBlockNumber))??
+                      .ok_or(Era1Error::MissingBlockData { data: "Block body indices", blockNumber: firstBlockNumber })?
+                      .firstTxNumber
+    };
+
+    self.db.withDbTx(|dbTx| -> Result<( ), Era1Error> {
+      let mut nextTxNumber= firstTxNumber;
+
+      for (i, (blockBodyIndices, ommers)) in verifiedEra1File.blockBodyIndices.iter( ).zip(&verifiedEra1File.ommers).enumerate( ) {
+        let blockNumber= firstBlockNumber + i as u64;
+
+        dbTx.insertBlockBodyIndices(blockNumber, BlockBodyIndices { firstTxNumber: nextTxNumber, ..*blockBodyIndices })?;
+        if !ommers.is_empty( ) {
+          dbTx.insertOmmers(blockNumber, ommers)?;
+        }
+        nextTxNumber += blockBodyIndices.txCount;
+      }
+      Ok(( ))
+    })??;
+
+    // (2) Append to static files.
+    let mut era1Reader= Era1Reader::open(path)?;
+    let mut blockNumber= firstBlockNumber;
+    let mut importedBlockCount= 0;
+
+    while let Some(rawBlock)= era1Reader.nextBlock( )? {
+      if blockNumber >= nextBlockNumber {
+        let blockHash= keccak256(&rawBlock.header);
+        if verifiedEra1File.blockHashes.get((blockNumber - firstBlockNumber) as usize) != Some(&blockHash) {
+          return Err(Era1Error::InvalidBlock { blockNumber, reason: "Era1 file changed while being imported" })
+        }
+
+        let block= rawBlock.decode( )?;
+        let senders= &verifiedEra1File.senders[(blockNumber - firstBlockNumber) as usize];
+        staticFilesWriter.appendBlock(&block.header, blockHash, block.totalDifficulty, &block.transactions, &block.receipts, senders)?;
+        importedBlockCount += 1;
+      }
+      blockNumber += 1;
+    }
+    staticFilesWriter.commit( )?;
+
+    Ok(importedBlockCount)
+  }
+
+  // Verifies the blocks of an Era1 file against each other, the blocks before them and the
+  // accumulator root (see (1) above).
+  fn verifyEra1File(&self, epoch: u64, path: &Path) -> Result<VerifiedEra1File, Era1Error> {
+    let firstBlockNumber= epoch * EPOCH_SIZE;
+
+    let (mut parentHash, mut parentTotalDifficulty)= match firstBlockNumber {
+      0 => (B256::ZERO, U256::ZERO),
+      _ => {
+        let parentNumber= firstBlockNumber - 1;
+        let missing= |data| Era1Error::MissingBlockData { data, blockNumber: parentNumber };
+
+        (self.staticFilesHandler.blockHash(parentNumber)?.ok_or(missing("Block hash"))?,
+         self.staticFilesHandler.totalDifficulty(parentNumber)?.ok_or(missing("Total difficulty"))?)
+      }
+    };
+
+    let mut verifiedEra1File= VerifiedEra1File {
+      blockHashes: Vec::new( ),
+      blockBodyIndices: Vec::new( ),
+      ommers: Vec::new( ),
+      senders: Vec::new( )
+    };
+    let mut headerRecords= Vec::new( );
+
+    let mut era1Reader= Era1Reader::open(path)?;
+    while let Some(rawBlock)= era1Reader.nextBlock( )? {
+      let blockNumber= firstBlockNumber + headerRecords.len( ) as u64;
+      let invalid= |reason| Era1Error::InvalidBlock { blockNumber, reason };
+
+      let blockHash= keccak256(&rawBlock.header);
+      let block= rawBlock.decode( )?;
+      let header= &block.header;
+
+      if header.number != blockNumber {
+        return Err(invalid("block number doesn't match its position in the epoch"))
+      }
+      if header.hash( ) != blockHash {
+        return Err(invalid("header doesn't encode back to the same block hash"))
+      }
+      if header.parentHash != parentHash {
+        return Err(invalid("parent hash doesn't match the hash of the previous block"))
+      }
+      if block.totalDifficulty != parentTotalDifficulty + header.difficulty {
+        return Err(invalid("total difficulty doesn't add up"))
+      }
+      if keccak256(alloy_rlp::encode(&block.ommers)) != header.ommersHash {
+        return Err(invalid("ommers don't match the ommers hash"))
+      }
+      if block.transactions.len( ) != block.receipts.len( ) {
+        return Err(invalid("number of receipts doesn't match the number of transactions"))
+      }
+      let encodedTransactions: Vec<&[u8]>= block.transactions.iter( ).map(|transaction| &transaction.encoded[..]).collect( );
+      if orderedTrieRoot(&encodedTransactions) != header.transactionsRoot {
+        return Err(invalid("transactions don't match the transactions root"))
+      }
+      let encodedReceipts: Vec<Vec<u8>>= block.receipts.iter( ).map(Receipt::encoded).collect( );
+      if orderedTrieRoot(&encodedReceipts) != header.receiptsRoot {
+        return Err(invalid("receipts don't match the receipts root"))
+      }
+      let senders= block.transactions.iter( )
+        .map(|transaction| transaction.recoverSender( ).ok_or_else(|| invalid("sender of a transaction can't be recovered")))
+        .collect::<Result<Vec<_>, _>>( )?;
+
+      headerRecords.push(HeaderRecord { blockHash, totalDifficulty: block.totalDifficulty });
+      verifiedEra1File.blockHashes.push(blockHash);
+      verifiedEra1File.blockBodyIndices.push(BlockBodyIndices { firstTxNumber: 0, txCount: block.transactions.len( ) as u64 });
+      verifiedEra1File.ommers.push(block.ommers);
+      verifiedEra1File.senders.push(senders);
+
+      (parentHash, parentTotalDifficulty)= (blockHash, block.totalDifficulty);
+    }
+
+    let trailer= era1Reader.trailer( )?;
+    if trailer.startingNumber != firstBlockNumber {
+      return Err(Era1Error::InvalidBlockIndex("starting block number doesn't match the epoch"))
+    }
+
+    let got= accumulatorRoot(&headerRecords);
+    if got != trailer.accumulatorRoot {
+      return Err(Era1Error::AccumulatorMismatch { expected: trailer.accumulatorRoot, got })
+    }
+    if self.trustedAccumulatorRoots.get(epoch as usize) != Some(&got) {
+      return Err(Era1Error::UntrustedAccumulatorRoot { epoch })
+    }
+
+    Ok(verifiedEra1File)
+  }
+}
+
+// Returns the Era1 files in the directory, along with their epochs, ordered by epoch.
+fn era1FilesIn(directory: &Path) -> Result<Vec<(u64, PathBuf)>, Era1Error> {
+  let mut era1Files= Vec::new( );
+
+  for entry in fs::read_dir(directory)? {
+    let path= entry?.path( );
+
+    let epoch= path.file_name( )
+                   .and_then(|fileName| fileName.to_str( ))
+                   .and_then(parseEra1FileName);
+    if let Some(epoch)= epoch {
+      era1Files.push((epoch, path));
+    }
+  }
+  era1Files.sort( );
+
+  Ok(era1Files)
+}
+
+#[cfg(test)]
+mod tests {
+  use alloy_primitives::{Bytes, Log};
+  use db::{
+    models::{orderedTrieRoot, BlockNumber, Receipt, ReceiptOutcome, TransactionSigned},
+    test_utils::{signedTransaction, MemoryDb}
+  };
+  use crate::{era1_exporter::Era1Exporter, era1_reader::verifyEra1File};
+  use super::*;
+
+  const LAST_BLOCK_NUMBER: BlockNumber= 9;
+
+  /*
+    Writes blocks 0..=LAST_BLOCK_NUMBER to static files (and their block body indices to the
+    database). Block n has n % 3 transactions, and the receipts of blocks before 5 carry a post
+    state root, like pre-Byzantium ones.
+
+    The header of the block numbered invalidReceiptsRootAt (if any) gets a wrong receipts root.
+  */
+  fn writeBlocks(staticFilesHandler: Arc<StaticFilesHander>, db: &MemoryDb, invalidReceiptsRootAt: Option<BlockNumber>) {
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler, db).unwrap( );
+    let (mut parentHash, mut totalDifficulty)= (B256::ZERO, U256::ZERO);
+
+    for blockNumber in 0..=LAST_BLOCK_NUMBER {
+      let firstTxNumber= staticFilesWriter.nextTxNumber( );
+      let (transactions, senders): (Vec<TransactionSigned>, Vec<Address>)= (firstTxNumber..(firstTxNumber + blockNumber % 3))
+        .map(|txNumber| signedTransaction(txNumber % 2, txNumber))
+        .unzip( );
+      let receipts: Vec<Receipt>= (0..transactions.len( ))
+        .map(|i| Receipt {
+          transactionType: 0,
+          outcome: match blockNumber < 5 {
+            true => ReceiptOutcome::PostStateRoot(B256::with_last_byte(i as u8)),
+            false => ReceiptOutcome::Status(i == 0)
+          },
+          cumulativeGasUsed: 21_000 * (i as u64 + 1),
+          logs: vec![Log::new_unchecked(Address::with_last_byte(blockNumber as u8), vec![B256::with_last_byte(1)], Bytes::from_static(&[2]))]
+        })
+        .collect( );
+
+      let encodedTransactions: Vec<&[u8]>= transactions.iter( ).map(|transaction| &transaction.encoded[..]).collect( );
+      let encodedReceipts: Vec<Vec<u8>>= receipts.iter( ).map(Receipt::encoded).collect( );
+      let mut header= Header {
+        parentHash,
+        number: blockNumber,
+        difficulty: U256::from(1000 + blockNumber),
+        gasUsed: 21_000 * transactions.len( ) as u64,
+        ommersHash: keccak256(alloy_rlp::encode(Vec::<Header>::new( ))),
+        transactionsRoot: orderedTrieRoot(&encodedTransactions),
+        receiptsRoot: orderedTrieRoot(&encodedReceipts),
+        ..Default::default( )
+      };
+      if invalidReceiptsRootAt == Some(blockNumber) {
+        header.receiptsRoot= B256::ZERO;
+      }
+      let blockHash= header.hash( );
+      totalDifficulty += header.difficulty;
+
+      db.tables( ).blockBodyIndices.insert(blockNumber, BlockBodyIndices { firstTxNumber, txCount: transactions.len( ) as u64 });
+      staticFilesWriter.appendBlock(&header, blockHash, totalDifficulty, &transactions, &receipts, &senders).unwrap( );
+      parentHash= blockHash;
+    }
+    staticFilesWriter.commit( ).unwrap( );
+  }
+
+  // Exports the blocks in the static files to Era1 files, returning their directory along with
+  // their accumulator roots (trusted, from then on).
+  fn export(staticFilesHandler: Arc<StaticFilesHander>, db: &MemoryDb, directory: &Path) -> (PathBuf, Vec<B256>) {
+    let era1Directory= directory.join("era1");
+
+    let era1Files= Era1Exporter::new(Arc::new(db.clone( )), staticFilesHandler, "testnet")
+      .withLastBlockNumber(LAST_BLOCK_NUMBER)
+      .export(&era1Directory)
+      .unwrap( );
+    assert_eq!(era1Files.len( ), 1);
+
+    let accumulatorRoots= era1Files.iter( ).map(|path| verifyEra1File(path).unwrap( ).accumulatorRoot).collect( );
+    (era1Directory, accumulatorRoots)
+  }
+
+  #[test]
+  fn exportedBlocksAreImportedBackAsTheyWere( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+
+    let (sourceDb, importedDb)= (MemoryDb::default( ), MemoryDb::default( ));
+    let sourceHandler= Arc::new(StaticFilesHander::new(directory.path( ).join("source")).unwrap( ));
+    writeBlocks(sourceHandler.clone( ), &sourceDb, None);
+
+    let (era1Directory, accumulatorRoots)= export(sourceHandler.clone( ), &sourceDb, directory.path( ));
+
+    let importedHandler= Arc::new(StaticFilesHander::new(directory.path( ).join("imported")).unwrap( ));
+    let era1Importer= Era1Importer::new(Arc::new(importedDb.clone( )), importedHandler.clone( ), accumulatorRoots);
+    assert_eq!(era1Importer.import(&era1Directory).unwrap( ), LAST_BLOCK_NUMBER + 1);
+    assert_eq!(era1Importer.import(&era1Directory).unwrap( ), 0);
+
+    for blockNumber in 0..=LAST_BLOCK_NUMBER {
+      assert_eq!(importedHandler.headerByNumber(blockNumber).unwrap( ), sourceHandler.headerByNumber(blockNumber).unwrap( ));
+      assert_eq!(importedHandler.totalDifficulty(blockNumber).unwrap( ), sourceHandler.totalDifficulty(blockNumber).unwrap( ));
+
+      let blockBodyIndices= sourceDb.tables( ).blockBodyIndices.get(&blockNumber).copied( );
+      assert_eq!(importedDb.tables( ).blockBodyIndices.get(&blockNumber).copied( ), blockBodyIndices);
+    }
+
+    let highestTxNumber= sourceHandler.highestTxNumber(SegmentType::Transactions);
+    assert_eq!(importedHandler.highestTxNumber(SegmentType::Transactions), highestTxNumber);
+
+    for txNumber in 0..=highestTxNumber.unwrap( ) {
+      assert_eq!(importedHandler.transactionById(txNumber).unwrap( ), sourceHandler.transactionById(txNumber).unwrap( ));
+      assert_eq!(importedHandler.receiptById(txNumber).unwrap( ), sourceHandler.receiptById(txNumber).unwrap( ));
+      assert_eq!(importedHandler.senderById(txNumber).unwrap( ), sourceHandler.senderById(txNumber).unwrap( ));
+    }
+  }
+
+  #[test]
+  fn blocksNotMatchingTheirReceiptsRootAreRejected( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+
+    let sourceDb= MemoryDb::default( );
+    let sourceHandler= Arc::new(StaticFilesHander::new(directory.path( ).join("source")).unwrap( ));
+    writeBlocks(sourceHandler.clone( ), &sourceDb, Some(7));
+
+    let (era1Directory, accumulatorRoots)= export(sourceHandler, &sourceDb, directory.path( ));
+
+    let importedHandler= Arc::new(StaticFilesHander::new(directory.path( ).join("imported")).unwrap( ));
+    let era1Importer= Era1Importer::new(Arc::new(MemoryDb::default( )), importedHandler.clone( ), accumulatorRoots);
+    assert!(matches!(era1Importer.import(&era1Directory),
+                     Err(Era1Error::InvalidBlock { blockNumber: 7, reason: "receipts don't match the receipts root" })));
+
+    // Nothing got written.
+    assert_eq!(importedHandler.highestBlockNumber(SegmentType::BlockHeaders), None);
+  }
+
+  #[test]
+  fn epochsWithoutATrustedAccumulatorRootAreRejected( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+
+    let sourceDb= MemoryDb::default( );
+    let sourceHandler= Arc::new(StaticFilesHander::new(directory.path( ).join("source")).unwrap( ));
+    writeBlocks(sourceHandler.clone( ), &sourceDb, None);
+
+    // The Era1 file is intact, so only the trusted accumulator roots tell that it's not canonical.
+    let (era1Directory, _)= export(sourceHandler, &sourceDb, directory.path( ));
+
+    let importedHandler= Arc::new(StaticFilesHander::new(directory.path( ).join("imported")).unwrap( ));
+    for trustedAccumulatorRoots in [vec![ ], vec![B256::with_last_byte(1)]] {
+      let era1Importer= Era1Importer::new(Arc::new(MemoryDb::default( )), importedHandler.clone( ), trustedAccumulatorRoots);
+      assert!(matches!(era1Importer.import(&era1Directory), Err(Era1Error::UntrustedAccumulatorRoot { epoch: 0 })));
+    }
+    assert_eq!(importedHandler.highestBlockNumber(SegmentType::BlockHeaders), None);
+  }
+}
@@ -0,0 +1,299 @@
+use std::{fs::File, io::{BufReader, Read}, path::Path};
+use alloy_primitives::{keccak256, Bytes, B256, U256};
+use alloy_rlp::Decodable;
+use db::models::{BlockNumber, Header, Receipt, TransactionSigned};
+use snap::read::FrameDecoder;
+use super::{
+  accumulator::{accumulatorRoot, HeaderRecord, EPOCH_SIZE},
+  e2store::{self, readEntry, Entry, EntryType, ENTRY_HEADER_SIZE},
+  era1_error::Era1Error,
+  era1_writer::{era1FileName, Era1Block}
+};
+
+// A block as read from an Era1 file : the (decompressed) RLP encoded header, body and receipts,
+// along with the total difficulty.
+#[derive(Debug, Clone)]
+pub struct RawEra1Block {
+  pub header: Vec<u8>,
+  pub body: Vec<u8>,
+  pub receipts: Vec<u8>,
+  pub totalDifficulty: U256
+}
+
+impl RawEra1Block {
+  // Decodes the header, body and receipts. The transactions (and receipts) are decoded back into
+  // their canonical encoding.
+  pub fn decode(&self) -> Result<Era1Block, Era1Error> {
+    let header= decodeExactly(&self.header, Header::decode)?;
+
+    let (transactions, ommers)= decodeExactly(&self.body, |body| {
+      let rlpHeader= alloy_rlp::Header::decode(body)?;
+      if !rlpHeader.list {
+        return Err(alloy_rlp::Error::UnexpectedString)
+      }
+      Ok((decodeEnvelopes(body)?, Vec::<Header>::decode(body)?))
+    })?;
+    let transactions= transactions.into_iter( )
+      .map(|encoded| TransactionSigned { hash: keccak256(&encoded), encoded: Bytes::from(encoded) })
+      .collect( );
+
+    let receipts= decodeExactly(&self.receipts, decodeEnvelopes)?
+      .iter( )
+      .map(|encoded| Receipt::fromEncoded(encoded))
+      .collect::<Result<_, _>>( )?;
+
+    Ok(Era1Block {
+      header,
+      transactions,
+      ommers,
+      receipts,
+      totalDifficulty: self.totalDifficulty
+    })
+  }
+}
+
+// What's found after the blocks in an Era1 file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Era1Trailer {
+  pub accumulatorRoot: B256,
+  pub startingNumber: BlockNumber,
+  pub blockCount: u64
+}
+
+/*
+  Reads an Era1 file block by block (see Era1Writer for the layout), so that a whole epoch never has
+  to be held in memory.
+
+  Once the blocks run out, the Accumulator and the BlockIndex get read, and the offsets in the
+  BlockIndex are checked against where the blocks actually were.
+*/
+pub struct Era1Reader<R> {
+  reader: R,
+
+  // Length of the file, and number of bytes read so far.
+  length: u64,
+  position: u64,
+
+  blockPositions: Vec<u64>,
+  trailer: Option<Era1Trailer>
+}
+
+impl Era1Reader<BufReader<File>> {
+  pub fn open(path: &Path) -> Result<Self, Era1Error> {
+    let file= File::open(path)?;
+    let length= file.metadata( )?.len( );
+
+    Self::new(BufReader::new(file), length)
+  }
+}
+
+impl<R> Era1Reader<R>
+  where
+    R: Read
+{
+  // The reader must hold an Era1 file of the given length.
+  pub fn new(reader: R, length: u64) -> Result<Self, Era1Error> {
+    let mut era1Reader= Self {
+      reader,
+      length,
+      position: 0,
+      blockPositions: Vec::new( ),
+      trailer: None
+    };
+    era1Reader.expectEntry(e2store::VERSION)?;
+
+    Ok(era1Reader)
+  }
+
+  // Reads the next block. None is returned once the blocks run out.
+  pub fn nextBlock(&mut self) -> Result<Option<RawEra1Block>, Era1Error> {
+    if self.trailer.is_some( ) {
+      return Ok(None)
+    }
+
+    let blockPosition= self.position;
+    let entry= self.nextEntry( )?;
+
+    match entry.entryType {
+      e2store::COMPRESSED_HEADER => {
+        let body= self.expectEntry(e2store::COMPRESSED_BODY)?;
+        let receipts= self.expectEntry(e2store::COMPRESSED_RECEIPTS)?;
+        let totalDifficulty= self.expectEntry(e2store::TOTAL_DIFFICULTY)?;
+
+        let totalDifficulty: [u8; 32]= totalDifficulty.data.try_into( )
+          .map_err(|_| Era1Error::InvalidEntry("total difficulty isn't 32 bytes long"))?;
+
+        self.blockPositions.push(blockPosition);
+
+        Ok(Some(RawEra1Block {
+          header: decompress(&entry.data)?,
+          body: decompress(&body.data)?,
+          receipts: decompress(&receipts.data)?,
+          totalDifficulty: U256::from_le_bytes(totalDifficulty)
+        }))
+      },
+
+      e2store::ACCUMULATOR => {
+        let accumulatorRoot: [u8; 32]= entry.data.try_into( )
+          .map_err(|_| Era1Error::InvalidEntry("accumulator root isn't 32 bytes long"))?;
+
+        self.readBlockIndex(B256::from(accumulatorRoot))?;
+        Ok(None)
+      },
+
+      got => Err(Era1Error::UnexpectedEntry { expected: e2store::COMPRESSED_HEADER, got })
+    }
+  }
+
+  // Returns the accumulator root, starting block number and number of blocks. Fails unless all the
+  // blocks have been read.
+  pub fn trailer(&self) -> Result<Era1Trailer, Era1Error> {
+    self.trailer.ok_or(Era1Error::InvalidEntry("blocks of the Era1 file haven't all been read"))
+  }
+
+  fn readBlockIndex(&mut self, accumulatorRoot: B256) -> Result<( ), Era1Error> {
+    let indexPosition= self.position;
+    let blockIndex= self.expectEntry(e2store::BLOCK_INDEX)?.data;
+
+    let blockCount= self.blockPositions.len( );
+    if blockIndex.len( ) != 16 + 8 * blockCount {
+      return Err(Era1Error::InvalidBlockIndex("number of offsets doesn't match the number of blocks"))
+    }
+
+    let u64At= |offset: usize| u64::from_le_bytes(blockIndex[offset..(offset + 8)].try_into( ).unwrap( ));
+
+    if u64At(blockIndex.len( ) - 8) != blockCount as u64 {
+      return Err(Era1Error::InvalidBlockIndex("block count doesn't match the number of blocks"))
+    }
+    for (i, blockPosition) in self.blockPositions.iter( ).enumerate( ) {
+      if u64At(8 + 8 * i) as i64 != *blockPosition as i64 - indexPosition as i64 {
+        return Err(Era1Error::InvalidBlockIndex("offset doesn't point to the block"))
+      }
+    }
+
+    if self.readEntry( )?.is_some( ) {
+      return Err(Era1Error::InvalidEntry("unexpected entry after the block index"))
+    }
+
+    self.trailer= Some(Era1Trailer {
+      accumulatorRoot,
+      startingNumber: u64At(0),
+      blockCount: blockCount as u64
+    });
+    Ok(( ))
+  }
+
+  fn nextEntry(&mut self) -> Result<Entry, Era1Error> {
+    let entry= self.readEntry( )?.ok_or(Era1Error::InvalidEntry("Era1 file ends before the block index"))?;
+    self.position += (ENTRY_HEADER_SIZE + entry.data.len( )) as u64;
+
+    Ok(entry)
+  }
+
+  // Reads the next entry (if any), out of the bytes left in the file.
+  fn readEntry(&mut self) -> Result<Option<Entry>, Era1Error> {
+    let remainingLength= self.length.saturating_sub(self.position);
+    readEntry(&mut self.reader, remainingLength)
+  }
+
+  fn expectEntry(&mut self, expected: EntryType) -> Result<Entry, Era1Error> {
+    let entry= self.nextEntry( )?;
+
+    match entry.entryType == expected {
+      true => Ok(entry),
+      false => Err(Era1Error::UnexpectedEntry { expected, got: entry.entryType })
+    }
+  }
+}
+
+/*
+  Verifies an Era1 file : the block hashes (the keccak256 of the stored header RLP) and total
+  difficulties must add up to the stored accumulator root, which the file name must match as well.
+
+  Returns the trailer of the file.
+*/
+pub fn verifyEra1File(path: &Path) -> Result<Era1Trailer, Era1Error> {
+  let mut era1Reader= Era1Reader::open(path)?;
+  let mut headerRecords= Vec::new( );
+
+  while let Some(block)= era1Reader.nextBlock( )? {
+    headerRecords.push(HeaderRecord {
+      blockHash: keccak256(&block.header),
+      totalDifficulty: block.totalDifficulty
+    });
+  }
+  let trailer= era1Reader.trailer( )?;
+
+  let got= accumulatorRoot(&headerRecords);
+  if got != trailer.accumulatorRoot {
+    return Err(Era1Error::AccumulatorMismatch { expected: trailer.accumulatorRoot, got })
+  }
+
+  verifyFileName(path, &trailer)?;
+
+  Ok(trailer)
+}
+
+// The file name must end with the epoch and the short accumulator root.
+fn verifyFileName(path: &Path, trailer: &Era1Trailer) -> Result<( ), Era1Error> {
+  let fileName= path.file_name( ).and_then(|fileName| fileName.to_str( )).unwrap_or_default( );
+
+  let expectedSuffix= era1FileName("", trailer.startingNumber / EPOCH_SIZE, &trailer.accumulatorRoot);
+
+  match fileName.ends_with(&expectedSuffix) {
+    true => Ok(( )),
+    false => Err(Era1Error::FileNameMismatch(fileName.to_string( )))
+  }
+}
+
+fn decompress(data: &[u8]) -> Result<Vec<u8>, Era1Error> {
+  let mut decompressed= Vec::new( );
+  FrameDecoder::new(data).read_to_end(&mut decompressed)?;
+
+  Ok(decompressed)
+}
+
+// Decodes a value, which must take up all the data.
+fn decodeExactly<T, F>(mut data: &[u8], decode: F) -> Result<T, Era1Error>
+  where
+    F: FnOnce(&mut &[u8]) -> alloy_rlp::Result<T>
+{
+  let value= decode(&mut data)?;
+
+  match data.is_empty( ) {
+    true => Ok(value),
+    false => Err(alloy_rlp::Error::UnexpectedLength.into( ))
+  }
+}
+
+// Decodes a list of transactions (or receipts) back into their canonical encoding (see
+// Era1Writer) : legacy ones are RLP lists, while typed ones are wrapped into an RLP string.
+fn decodeEnvelopes(buf: &mut &[u8]) -> alloy_rlp::Result<Vec<Vec<u8>>> {
+  let rlpHeader= alloy_rlp::Header::decode(buf)?;
+  if !rlpHeader.list {
+    return Err(alloy_rlp::Error::UnexpectedString)
+  }
+  if rlpHeader.payload_length > buf.len( ) {
+    return Err(alloy_rlp::Error::InputTooShort)
+  }
+  let (mut payload, rest)= buf.split_at(rlpHeader.payload_length);
+  *buf= rest;
+
+  let mut envelopes= Vec::new( );
+  while !payload.is_empty( ) {
+    let mut item= payload;
+    let itemHeader= alloy_rlp::Header::decode(&mut item)?;
+    if itemHeader.payload_length > item.len( ) {
+      return Err(alloy_rlp::Error::InputTooShort)
+    }
+    let itemLength= (payload.len( ) - item.len( )) + itemHeader.payload_length;
+
+    envelopes.push(match itemHeader.list {
+      true => payload[..itemLength].to_vec( ),
+      false => item[..itemHeader.payload_length].to_vec( )
+    });
+    payload= &payload[itemLength..];
+  }
+
+  Ok(envelopes)
+}
@@ -0,0 +1,176 @@
+use std::io::Write;
+use alloy_primitives::{hex, B256, U256};
+use db::models::{BlockNumber, Header, Receipt, TransactionSigned};
+use snap::write::FrameEncoder;
+use super::{
+  accumulator::{accumulatorRoot, HeaderRecord, EPOCH_SIZE},
+  e2store::{self, writeEntry},
+  era1_error::Era1Error
+};
+
+// A block, along with everything stored about it in an Era1 file.
+#[derive(Debug, Clone, Default)]
+pub struct Era1Block {
+  pub header: Header,
+  pub transactions: Vec<TransactionSigned>,
+  pub ommers: Vec<Header>,
+  pub receipts: Vec<Receipt>,
+  pub totalDifficulty: U256
+}
+
+/*
+  Writes an Era1 file : an e2store file containing, in order,
+
+    Version
+    CompressedHeader | CompressedBody | CompressedReceipts | TotalDifficulty    (for each block)
+    Accumulator
+    BlockIndex
+
+  The header, body and receipts are RLP encoded and then snappy compressed (using the framing
+  format). The BlockIndex holds the block number of the first block, the offset of each block (of
+  its CompressedHeader entry, relative to the BlockIndex entry) and the number of blocks.
+
+  Receipts are written in their canonical encoding, with the status or (for pre-Byzantium blocks)
+  the post state root they were stored with.
+
+  See https://github.com/eth-clients/e2store-format-specs/blob/main/formats/era1.md.
+*/
+pub struct Era1Writer<W> {
+  writer: W,
+
+  // Number of bytes written so far.
+  position: u64,
+
+  startingNumber: Option<BlockNumber>,
+  blockPositions: Vec<u64>,
+  headerRecords: Vec<HeaderRecord>
+}
+
+impl<W> Era1Writer<W>
+  where
+    W: Write
+{
+  pub fn new(mut writer: W) -> Result<Self, Era1Error> {
+    let position= writeEntry(&mut writer, e2store::VERSION, &[ ])?;
+
+    Ok(Self {
+      writer,
+      position,
+      startingNumber: None,
+      blockPositions: Vec::new( ),
+      headerRecords: Vec::new( )
+    })
+  }
+
+  // Appends the next block. Blocks must be appended in order, and an Era1 file holds at most
+  // EPOCH_SIZE blocks.
+  pub fn appendBlock(&mut self, block: &Era1Block) -> Result<( ), Era1Error> {
+    let startingNumber= *self.startingNumber.get_or_insert(block.header.number);
+    if block.header.number != startingNumber + self.blockPositions.len( ) as u64 {
+      return Err(Era1Error::InvalidBlockIndex("blocks aren't appended in order"))
+    }
+    if self.blockPositions.len( ) as u64 == EPOCH_SIZE {
+      return Err(Era1Error::InvalidBlockIndex("an Era1 file can't hold more than an epoch of blocks"))
+    }
+
+    self.blockPositions.push(self.position);
+
+    let receipts: Vec<Vec<u8>>= block.receipts.iter( ).map(Receipt::encoded).collect( );
+
+    self.position += writeEntry(&mut self.writer, e2store::COMPRESSED_HEADER, &compress(&alloy_rlp::encode(&block.header))?)?;
+    self.position += writeEntry(&mut self.writer, e2store::COMPRESSED_BODY, &compress(&encodeBody(block))?)?;
+    self.position += writeEntry(&mut self.writer, e2store::COMPRESSED_RECEIPTS, &compress(&encodeEnvelopes(&receipts))?)?;
+    self.position += writeEntry(&mut self.writer, e2store::TOTAL_DIFFICULTY, &block.totalDifficulty.to_le_bytes::<32>( ))?;
+
+    self.headerRecords.push(HeaderRecord {
+      blockHash: block.header.hash( ),
+      totalDifficulty: block.totalDifficulty
+    });
+
+    Ok(( ))
+  }
+
+  // Writes the Accumulator and the BlockIndex, and returns the accumulator root.
+  pub fn finish(mut self) -> Result<B256, Era1Error> {
+    let accumulatorRoot= accumulatorRoot(&self.headerRecords);
+    self.position += writeEntry(&mut self.writer, e2store::ACCUMULATOR, accumulatorRoot.as_slice( ))?;
+
+    let indexPosition= self.position;
+    let mut blockIndex= Vec::with_capacity(16 + 8 * self.blockPositions.len( ));
+
+    blockIndex.extend_from_slice(&self.startingNumber.unwrap_or_default( ).to_le_bytes( ));
+    for blockPosition in &self.blockPositions {
+      blockIndex.extend_from_slice(&(*blockPosition as i64 - indexPosition as i64).to_le_bytes( ));
+    }
+    blockIndex.extend_from_slice(&(self.blockPositions.len( ) as u64).to_le_bytes( ));
+
+    writeEntry(&mut self.writer, e2store::BLOCK_INDEX, &blockIndex)?;
+    self.writer.flush( )?;
+
+    Ok(accumulatorRoot)
+  }
+}
+
+// Returns the name of the Era1 file of an epoch : <network>-<epoch>-<short accumulator root>.era1.
+pub fn era1FileName(network: &str, epoch: u64, accumulatorRoot: &B256) -> String {
+  format!("{}-{:05}-{}.era1", network, epoch, hex::encode(&accumulatorRoot[..4]))
+}
+
+// Parses the name of an Era1 file, returning the epoch. None is returned for any other file.
+pub fn parseEra1FileName(fileName: &str) -> Option<u64> {
+  let mut parts= fileName.strip_suffix(".era1")?.rsplitn(3, '-');
+
+  let shortAccumulatorRoot= parts.next( )?;
+  let epoch= parts.next( )?;
+  parts.next( )?;
+
+  if shortAccumulatorRoot.len( ) != 8 || !shortAccumulatorRoot.bytes( ).all(|byte| byte.is_ascii_hexdigit( )) {
+    return None
+  }
+  epoch.parse( ).ok( )
+}
+
+fn compress(data: &[u8]) -> Result<Vec<u8>, Era1Error> {
+  let mut encoder= FrameEncoder::new(Vec::new( ));
+  encoder.write_all(data)?;
+
+  encoder.into_inner( ).map_err(|error| error.into_error( ).into( ))
+}
+
+// The block body is the RLP list [transactions, ommers].
+fn encodeBody(block: &Era1Block) -> Vec<u8> {
+  let transactions: Vec<&[u8]>= block.transactions.iter( ).map(|transaction| &transaction.encoded[..]).collect( );
+  let transactions= encodeEnvelopes(&transactions);
+  let ommers= alloy_rlp::encode(&block.ommers);
+
+  let mut body= Vec::with_capacity(transactions.len( ) + ommers.len( ) + 9);
+  alloy_rlp::Header { list: true, payload_length: transactions.len( ) + ommers.len( ) }.encode(&mut body);
+  body.extend_from_slice(&transactions);
+  body.extend_from_slice(&ommers);
+  body
+}
+
+// Encodes a list of transactions (or receipts) in their canonical encoding. Legacy ones are RLP
+// lists already, and go in as they are. Typed ones get wrapped into an RLP string.
+fn encodeEnvelopes<T>(envelopes: &[T]) -> Vec<u8>
+  where
+    T: AsRef<[u8]>
+{
+  let items: Vec<Vec<u8>>= envelopes.iter( )
+    .map(|envelope| {
+      let envelope= envelope.as_ref( );
+      match envelope.first( ) {
+        Some(firstByte) if *firstByte >= alloy_rlp::EMPTY_LIST_CODE => envelope.to_vec( ),
+        _ => alloy_rlp::encode(envelope)
+      }
+    })
+    .collect( );
+  let payloadLength= items.iter( ).map(Vec::len).sum( );
+
+  let mut encoded= Vec::with_capacity(payloadLength + 9);
+  alloy_rlp::Header { list: true, payload_length: payloadLength }.encode(&mut encoded);
+  for item in items {
+    encoded.extend_from_slice(&item);
+  }
+  encoded
+}
@@ -0,0 +1,19 @@
+#![allow(non_snake_case)]
+
+/*
+  Era1 is the standardized archive format for pre-merge history. Each Era1 file holds the blocks
+  (headers, bodies and receipts) along with the total difficulties of an epoch of 8192 blocks, and
+  the accumulator root pinning them - so that history can be published, downloaded and verified
+  independently of peers.
+
+  Era1 files are exported from static files by the Era1Exporter, and imported straight into static
+  files by the Era1Importer - so that a new node can be seeded from a directory of Era1 files.
+*/
+
+pub mod e2store;
+pub mod accumulator;
+pub mod era1_writer;
+pub mod era1_reader;
+pub mod era1_exporter;
+pub mod era1_importer;
+pub mod era1_error;
@@ -0,0 +1,10 @@
+#![allow(non_snake_case)]
+
+/*
+  Part of the history lives in static files (everything up to the highest block copied to static
+  files), and the rest lives in the database. Instead of making every caller figure out where to
+  look, reads go through a Provider, which routes each query to the right place.
+*/
+
+pub mod provider;
+pub mod provider_error;
@@ -0,0 +1,469 @@
+use std::{ops::RangeInclusive, sync::Arc};
+use alloy_primitives::{Address, U256};
+use db::{
+  interfaces::{self, db::DbError, transaction::RoDbTx},
+  models::{BlockBodyIndices, BlockHash, BlockNumber, Header, Receipt, TransactionSigned, TxHash, TxNumber}
+};
+use static_files::{segment::SegmentType, static_files_error::StaticFilesError, static_files_handler::StaticFilesHander};
+use super::provider_error::ProviderError;
+
+/*
+  Reads blocks, transactions, receipts and senders, no matter whether they live in static files or
+  in the database.
+
+  For each segment, the blocks (or transaction numbers) covered by its static files are read from
+  static files, and everything else is read from the database. That's everything above the highest
+  one copied to static files, and for a senders segment added to an existing directory, everything
+  below the first one as well (see StaticFilesWriter::new). A range straddling both is split at
+  those points, so the caller gets a single contiguous result.
+
+  Range queries return the values in order, stopping at the first missing one (e.g. when the range
+  goes beyond the tip of the chain).
+*/
+pub struct Provider<Db> {
+  db: Arc<Db>,
+  staticFilesHandler: Arc<StaticFilesHander>
+}
+
+impl<Db> Provider<Db>
+  where
+    Db: interfaces::db::Db
+{
+  pub fn new(db: Arc<Db>, staticFilesHandler: Arc<StaticFilesHander>) -> Self {
+    Self { db, staticFilesHandler }
+  }
+
+  pub fn db(&self) -> &Arc<Db> {
+    &self.db
+  }
+
+  pub fn staticFilesHandler(&self) -> &Arc<StaticFilesHander> {
+    &self.staticFilesHandler
+  }
+
+  pub fn header(&self, blockNumber: BlockNumber) -> Result<Option<Header>, ProviderError> {
+    Ok(self.headersRange(blockNumber..=blockNumber)?.pop( ))
+  }
+
+  pub fn headersRange(&self, blockRange: RangeInclusive<BlockNumber>) -> Result<Vec<Header>, ProviderError> {
+    self.fetchRange(blockRange,
+                    self.blockNumbersInStaticFiles(SegmentType::BlockHeaders),
+                    |blockNumber| self.staticFilesHandler.headerByNumber(blockNumber),
+                    |roDbTx, blockNumber| roDbTx.header(blockNumber))
+  }
+
+  // The key index of every headers static file gets probed in turn (see
+  // StaticFilesHander::headerByHash), before falling back to the database. So the cost grows
+  // linearly with the number of static files.
+  pub fn headerByHash(&self, blockHash: BlockHash) -> Result<Option<Header>, ProviderError> {
+    if let Some(header)= self.staticFilesHandler.headerByHash(blockHash)? {
+      return Ok(Some(header))
+    }
+
+    match self.db.withRoDbTx(|roDbTx| roDbTx.blockNumber(blockHash))?? {
+      Some(blockNumber) => self.header(blockNumber),
+      None => Ok(None)
+    }
+  }
+
+  pub fn blockHash(&self, blockNumber: BlockNumber) -> Result<Option<BlockHash>, ProviderError> {
+    self.fetch(blockNumber,
+               self.blockNumbersInStaticFiles(SegmentType::BlockHeaders),
+               |blockNumber| self.staticFilesHandler.blockHash(blockNumber),
+               |roDbTx, blockNumber| roDbTx.blockHash(blockNumber))
+  }
+
+  // Returns the sum of difficulties of all the blocks up to (and including) the given block.
+  pub fn totalDifficulty(&self, blockNumber: BlockNumber) -> Result<Option<U256>, ProviderError> {
+    self.fetch(blockNumber,
+               self.blockNumbersInStaticFiles(SegmentType::BlockHeaders),
+               |blockNumber| self.staticFilesHandler.totalDifficulty(blockNumber),
+               |roDbTx, blockNumber| roDbTx.totalDifficulty(blockNumber))
+  }
+
+  // Block body indices aren't copied to static files, so they are always read from the database.
+  pub fn blockBodyIndices(&self, blockNumber: BlockNumber) -> Result<Option<BlockBodyIndices>, ProviderError> {
+    Ok(self.db.withRoDbTx(|roDbTx| roDbTx.blockBodyIndices(blockNumber))??)
+  }
+
+  pub fn transaction(&self, txNumber: TxNumber) -> Result<Option<TransactionSigned>, ProviderError> {
+    Ok(self.transactionsRange(txNumber..=txNumber)?.pop( ))
+  }
+
+  pub fn transactionsRange(&self, txRange: RangeInclusive<TxNumber>) -> Result<Vec<TransactionSigned>, ProviderError> {
+    self.fetchRange(txRange,
+                    self.txNumbersInStaticFiles(SegmentType::Transactions),
+                    |txNumber| self.staticFilesHandler.transactionById(txNumber),
+                    |roDbTx, txNumber| roDbTx.transaction(txNumber))
+  }
+
+  // Like headerByHash, the cost grows linearly with the number of transactions static files.
+  pub fn transactionByHash(&self, txHash: TxHash) -> Result<Option<TransactionSigned>, ProviderError> {
+    if let Some(transaction)= self.staticFilesHandler.transactionByHash(txHash)? {
+      return Ok(Some(transaction))
+    }
+
+    match self.db.withRoDbTx(|roDbTx| roDbTx.txNumber(txHash))?? {
+      Some(txNumber) => self.transaction(txNumber),
+      None => Ok(None)
+    }
+  }
+
+  // Returns the transactions of the given block, or None if the block isn't known.
+  pub fn transactionsByBlock(&self, blockNumber: BlockNumber) -> Result<Option<Vec<TransactionSigned>>, ProviderError> {
+    self.blockBodyIndices(blockNumber)?
+        .map(|blockBodyIndices| self.transactionsRange(Self::txRangeOf(&blockBodyIndices)))
+        .transpose( )
+  }
+
+  pub fn receipt(&self, txNumber: TxNumber) -> Result<Option<Receipt>, ProviderError> {
+    Ok(self.receiptsRange(txNumber..=txNumber)?.pop( ))
+  }
+
+  pub fn receiptsRange(&self, txRange: RangeInclusive<TxNumber>) -> Result<Vec<Receipt>, ProviderError> {
+    self.fetchRange(txRange,
+                    self.txNumbersInStaticFiles(SegmentType::TransactionReceipts),
+                    |txNumber| self.staticFilesHandler.receiptById(txNumber),
+                    |roDbTx, txNumber| roDbTx.receipt(txNumber))
+  }
+
+  // Returns the receipts of the transactions in the given block, or None if the block isn't known.
+  pub fn receiptsByBlock(&self, blockNumber: BlockNumber) -> Result<Option<Vec<Receipt>>, ProviderError> {
+    self.blockBodyIndices(blockNumber)?
+        .map(|blockBodyIndices| self.receiptsRange(Self::txRangeOf(&blockBodyIndices)))
+        .transpose( )
+  }
+
+  // Returns the address which signed the given transaction.
+  pub fn sender(&self, txNumber: TxNumber) -> Result<Option<Address>, ProviderError> {
+    Ok(self.sendersRange(txNumber..=txNumber)?.pop( ))
+  }
+
+  pub fn sendersRange(&self, txRange: RangeInclusive<TxNumber>) -> Result<Vec<Address>, ProviderError> {
+    self.fetchRange(txRange,
+                    self.txNumbersInStaticFiles(SegmentType::Senders),
+                    |txNumber| self.staticFilesHandler.senderById(txNumber),
+                    |roDbTx, txNumber| roDbTx.sender(txNumber))
+  }
+
+  // For a block without transactions, an empty range is returned.
+  #[allow(clippy::reversed_empty_ranges)]
+  fn txRangeOf(blockBodyIndices: &BlockBodyIndices) -> RangeInclusive<TxNumber> {
+    match blockBodyIndices.lastTxNumber( ) {
+      Some(lastTxNumber) => blockBodyIndices.firstTxNumber..=lastTxNumber,
+      None => 1..=0
+    }
+  }
+
+  // Returns the range of block numbers covered by the static files of the given segment, if any.
+  fn blockNumbersInStaticFiles(&self, segmentType: SegmentType) -> Option<RangeInclusive<BlockNumber>> {
+    Some(0..=self.staticFilesHandler.highestBlockNumber(segmentType)?)
+  }
+
+  // Returns the range of transaction numbers covered by the static files of the given transaction
+  // based segment, if any.
+  fn txNumbersInStaticFiles(&self, segmentType: SegmentType) -> Option<RangeInclusive<TxNumber>> {
+    Some(self.staticFilesHandler.lowestTxNumber(segmentType)?..=self.staticFilesHandler.highestTxNumber(segmentType)?)
+  }
+
+  fn fetch<T, S, D>(&self,
+                    number: u64,
+                    inStaticFiles: Option<RangeInclusive<u64>>,
+                    fromStaticFiles: S,
+                    fromDb: D) -> Result<Option<T>, ProviderError>
+    where
+      S: Fn(u64) -> Result<Option<T>, StaticFilesError>,
+      D: Fn(&Db::RoDbTx, u64) -> Result<Option<T>, DbError>
+  {
+    Ok(self.fetchRange(number..=number, inStaticFiles, fromStaticFiles, fromDb)?.pop( ))
+  }
+
+  // Fetches the values for the numbers (block or transaction numbers) in the given range. Numbers
+  // in inStaticFiles are fetched from static files, and the rest from the database.
+  fn fetchRange<T, S, D>(&self,
+                         range: RangeInclusive<u64>,
+                         inStaticFiles: Option<RangeInclusive<u64>>,
+                         fromStaticFiles: S,
+                         fromDb: D) -> Result<Vec<T>, ProviderError>
+    where
+      S: Fn(u64) -> Result<Option<T>, StaticFilesError>,
+      D: Fn(&Db::RoDbTx, u64) -> Result<Option<T>, DbError>
+  {
+    let (lowerDbRange, staticFilesRange, upperDbRange)= splitRange(range, inStaticFiles);
+
+    let mut values= Vec::new( );
+
+    if !self.fetchRangeFromDb(lowerDbRange, &fromDb, &mut values)? {
+      return Ok(values)
+    }
+
+    for number in staticFilesRange {
+      match fromStaticFiles(number)? {
+        Some(value) => values.push(value),
+        None => return Ok(values)
+      }
+    }
+
+    self.fetchRangeFromDb(upperDbRange, &fromDb, &mut values)?;
+    Ok(values)
+  }
+
+  // Appends the values for the numbers in the given range, fetched from the database, to values.
+  // Returns whether none of them was missing.
+  fn fetchRangeFromDb<T, D>(&self, range: RangeInclusive<u64>, fromDb: &D, values: &mut Vec<T>) -> Result<bool, ProviderError>
+    where
+      D: Fn(&Db::RoDbTx, u64) -> Result<Option<T>, DbError>
+  {
+    if range.is_empty( ) {
+      return Ok(true)
+    }
+
+    self.db.withRoDbTx(|roDbTx| -> Result<bool, ProviderError> {
+      for number in range {
+        match fromDb(roDbTx, number)? {
+          Some(value) => values.push(value),
+          None => return Ok(false)
+        }
+      }
+
+      Ok(true)
+    })?
+  }
+}
+
+// Splits the range into the part below inStaticFiles (covered by the database), the part in it
+// (covered by static files), and the part above it (covered by the database). Any part may be empty.
+#[allow(clippy::reversed_empty_ranges)]
+fn splitRange(range: RangeInclusive<u64>,
+              inStaticFiles: Option<RangeInclusive<u64>>) -> (RangeInclusive<u64>, RangeInclusive<u64>, RangeInclusive<u64>)
+{
+  let (start, end)= range.into_inner( );
+
+  let Some((lowestInStaticFiles, highestInStaticFiles))= inStaticFiles.map(RangeInclusive::into_inner) else {
+    return (start..=end, 1..=0, 1..=0)
+  };
+
+  let lowerDbRange= match lowestInStaticFiles.checked_sub(1) {
+    Some(highestInLowerDb) => start..=end.min(highestInLowerDb),
+    None => 1..=0
+  };
+  let staticFilesRange= start.max(lowestInStaticFiles)..=end.min(highestInStaticFiles);
+  let upperDbRange= match highestInStaticFiles.checked_add(1) {
+    Some(lowestInUpperDb) => start.max(lowestInUpperDb)..=end,
+    None => 1..=0
+  };
+
+  (lowerDbRange, staticFilesRange, upperDbRange)
+}
+
+#[cfg(test)]
+mod tests {
+  use alloy_primitives::{keccak256, Bytes};
+  use db::{interfaces::{db::Db, transaction::DbTx}, test_utils::MemoryDb};
+  use static_files::static_files_writer::StaticFilesWriter;
+  use super::*;
+
+  // Block n has n % 3 transactions.
+  fn block(blockNumber: BlockNumber) -> (Header, Vec<TransactionSigned>, Vec<Receipt>, Vec<Address>) {
+    let header= Header { number: blockNumber, gasUsed: blockNumber * 1000, ..Default::default( ) };
+
+    let transactions: Vec<TransactionSigned>= (0..(blockNumber % 3))
+      .map(|i| {
+        let encoded= Bytes::from(vec![blockNumber as u8, i as u8]);
+        TransactionSigned { hash: keccak256(&encoded), encoded }
+      })
+      .collect( );
+    let receipts= (0..transactions.len( ) as u64).map(|i| Receipt { cumulativeGasUsed: (i + 1) * 21_000, ..Default::default( ) })
+                                                .collect( );
+    let senders= (0..transactions.len( )).map(|i| Address::with_last_byte((blockNumber * 3) as u8 + i as u8))
+                                         .collect( );
+
+    (header, transactions, receipts, senders)
+  }
+
+  // Blocks 0..=5 are in static files, and blocks 6..=9 in the database.
+  fn provider(directory: &std::path::Path) -> Provider<MemoryDb> {
+    let db= MemoryDb::default( );
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.to_path_buf( )).unwrap( ));
+
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( );
+    let mut nextTxNumber= 0;
+    for blockNumber in 0..10 {
+      let (header, transactions, receipts, senders)= block(blockNumber);
+
+      match blockNumber <= 5 {
+        true => staticFilesWriter.appendBlock(&header, header.hash( ), U256::from(blockNumber), &transactions, &receipts, &senders).unwrap( ),
+        false => db.insertBlock(&header, U256::from(blockNumber), nextTxNumber, &transactions, &receipts, &senders)
+      }
+      nextTxNumber += transactions.len( ) as u64;
+    }
+    staticFilesWriter.commit( ).unwrap( );
+
+    // Block body indices always live in the database.
+    db.withDbTx(|dbTx| {
+      let mut firstTxNumber= 0;
+      for blockNumber in 0..=5 {
+        let txCount= blockNumber % 3;
+        dbTx.insertBlockBodyIndices(blockNumber, BlockBodyIndices { firstTxNumber, txCount }).unwrap( );
+        firstTxNumber += txCount;
+      }
+    }).unwrap( );
+
+    Provider::new(Arc::new(db), staticFilesHandler)
+  }
+
+  #[test]
+  fn rangesAreSplitBetweenStaticFilesAndTheDb( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let provider= provider(directory.path( ));
+    assert_eq!(provider.staticFilesHandler( ).highestBlockNumber(SegmentType::BlockHeaders), Some(5));
+
+    let headers: Vec<BlockNumber>= provider.headersRange(3..=8).unwrap( ).iter( ).map(|header| header.number).collect( );
+    assert_eq!(headers, vec![3, 4, 5, 6, 7, 8]);
+
+    // Goes beyond the tip of the chain.
+    assert_eq!(provider.headersRange(8..=20).unwrap( ).len( ), 2);
+    assert_eq!(provider.header(10).unwrap( ), None);
+
+    // Transactions 0..=5 are in static files (blocks 1, 2, 4 and 5), 6..=8 in the database.
+    let transactions: Vec<TransactionSigned>= (0..10).flat_map(|blockNumber| block(blockNumber).1).collect( );
+    assert_eq!(provider.transactionsRange(0..=9).unwrap( ), transactions);
+    assert_eq!(provider.transactionsRange(3..=6).unwrap( ), transactions[3..=6]);
+    assert_eq!(provider.transactionsRange(9..=12).unwrap( ), vec![ ]);
+
+    let senders: Vec<Address>= (0..10).flat_map(|blockNumber| block(blockNumber).3).collect( );
+    assert_eq!(provider.sendersRange(2..=8).unwrap( ), senders[2..=8]);
+
+    let receipts: Vec<Receipt>= (0..10).flat_map(|blockNumber| block(blockNumber).2).collect( );
+    assert_eq!(provider.receiptsRange(5..=6).unwrap( ), receipts[5..=6]);
+
+    for blockNumber in [2, 5, 8] {
+      let (header, transactions, receipts, _)= block(blockNumber);
+      assert_eq!(provider.headerByHash(header.hash( )).unwrap( ), Some(header.clone( )));
+      assert_eq!(provider.blockHash(blockNumber).unwrap( ), Some(header.hash( )));
+      assert_eq!(provider.totalDifficulty(blockNumber).unwrap( ), Some(U256::from(blockNumber)));
+      assert_eq!(provider.transactionByHash(transactions[1].hash).unwrap( ), Some(transactions[1].clone( )));
+      assert_eq!(provider.receiptsByBlock(blockNumber).unwrap( ), Some(receipts));
+    }
+    assert_eq!(provider.transactionsByBlock(3).unwrap( ), Some(vec![ ]));
+  }
+
+  #[test]
+  fn lookupsByHashAcrossSeveralStaticFilesHaveNoFalsePositives( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= MemoryDb::default( );
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    // Blocks 0..=11 are in 4 static files per segment, and blocks 12..=14 in the database.
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( )
+      .withBlocksPerStaticFile(SegmentType::BlockHeaders, 3)
+      .withBlocksPerStaticFile(SegmentType::Transactions, 3);
+    let mut nextTxNumber= 0;
+    for blockNumber in 0..15 {
+      let (header, transactions, receipts, senders)= block(blockNumber);
+
+      match blockNumber <= 11 {
+        true => staticFilesWriter.appendBlock(&header, header.hash( ), U256::from(blockNumber), &transactions, &receipts, &senders).unwrap( ),
+        false => db.insertBlock(&header, U256::from(blockNumber), nextTxNumber, &transactions, &receipts, &senders)
+      }
+      nextTxNumber += transactions.len( ) as u64;
+    }
+    staticFilesWriter.commit( ).unwrap( );
+
+    assert_eq!(staticFilesHandler.segmentHeaders(SegmentType::BlockHeaders).len( ), 4);
+    assert_eq!(staticFilesHandler.segmentHeaders(SegmentType::Transactions).len( ), 4);
+
+    let provider= Provider::new(Arc::new(db), staticFilesHandler);
+    for blockNumber in 0..15 {
+      let (header, transactions, ..)= block(blockNumber);
+      assert_eq!(provider.headerByHash(header.hash( )).unwrap( ), Some(header));
+      for transaction in transactions {
+        assert_eq!(provider.transactionByHash(transaction.hash).unwrap( ), Some(transaction));
+      }
+    }
+
+    // Hashes in neither static files nor the database.
+    for i in 0..1000u64 {
+      let hash= keccak256(i.to_be_bytes( ));
+      assert_eq!(provider.headerByHash(hash).unwrap( ), None);
+      assert_eq!(provider.transactionByHash(hash).unwrap( ), None);
+    }
+  }
+
+  #[test]
+  fn sendersBelowALaterStartingSendersSegmentAreReadFromTheDb( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= MemoryDb::default( );
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( );
+    for blockNumber in 0..10 {
+      if blockNumber == 6 {
+        // Like a directory written before the senders segment was added, whose senders are still
+        // in the database.
+        staticFilesWriter.commit( ).unwrap( );
+        for segmentHeader in staticFilesHandler.segmentHeaders(SegmentType::Senders) {
+          let fileName= SegmentType::Senders.fileName(&segmentHeader.blockRange);
+          for entry in std::fs::read_dir(directory.path( )).unwrap( ) {
+            let path= entry.unwrap( ).path( );
+            if path.file_name( ).unwrap( ).to_str( ).unwrap( ).starts_with(&fileName) {
+              std::fs::remove_file(path).unwrap( );
+            }
+          }
+        }
+        staticFilesHandler.rescan( ).unwrap( );
+        staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( );
+      }
+
+      let (header, transactions, receipts, senders)= block(blockNumber);
+      if blockNumber < 6 {
+        let firstTxNumber= staticFilesWriter.nextTxNumber( );
+        db.tables( ).senders.extend((firstTxNumber..).zip(senders.iter( ).copied( )));
+      }
+      staticFilesWriter.appendBlock(&header, header.hash( ), U256::from(blockNumber), &transactions, &receipts, &senders).unwrap( );
+    }
+    staticFilesWriter.commit( ).unwrap( );
+
+    // Transactions 0..=5 (blocks 1, 2, 4 and 5) have their senders in the database only, and 6..=8
+    // in static files only.
+    assert_eq!(staticFilesHandler.lowestTxNumber(SegmentType::Senders), Some(6));
+    assert_eq!(db.tables( ).senders.keys( ).next_back( ), Some(&5));
+
+    let provider= Provider::new(Arc::new(db), staticFilesHandler);
+    let senders: Vec<Address>= (0..10).flat_map(|blockNumber| block(blockNumber).3).collect( );
+    assert_eq!(provider.sendersRange(0..=8).unwrap( ), senders);
+    assert_eq!(provider.sendersRange(4..=7).unwrap( ), senders[4..=7]);
+    assert_eq!(provider.sender(2).unwrap( ), Some(senders[2]));
+    assert_eq!(provider.sender(7).unwrap( ), Some(senders[7]));
+    assert_eq!(provider.sender(9).unwrap( ), None);
+  }
+
+  #[test]
+  fn rangesAreSplitAtTheNumbersInStaticFiles( ) {
+    let (lowerDbRange, staticFilesRange, upperDbRange)= splitRange(3..=8, Some(0..=5));
+    assert!(lowerDbRange.is_empty( ));
+    assert_eq!((staticFilesRange, upperDbRange), (3..=5, 6..=8));
+
+    let (lowerDbRange, staticFilesRange, upperDbRange)= splitRange(3..=8, None);
+    assert_eq!(lowerDbRange, 3..=8);
+    assert!(staticFilesRange.is_empty( ) && upperDbRange.is_empty( ));
+
+    let (lowerDbRange, staticFilesRange, upperDbRange)= splitRange(6..=8, Some(0..=5));
+    assert!(lowerDbRange.is_empty( ) && staticFilesRange.is_empty( ));
+    assert_eq!(upperDbRange, 6..=8);
+
+    let (lowerDbRange, staticFilesRange, upperDbRange)= splitRange(1..=4, Some(0..=5));
+    assert!(lowerDbRange.is_empty( ) && upperDbRange.is_empty( ));
+    assert_eq!(staticFilesRange, 1..=4);
+
+    // The static files start later (e.g. a senders segment added to an existing directory).
+    assert_eq!(splitRange(1..=8, Some(3..=5)), (1..=2, 3..=5, 6..=8));
+    let (lowerDbRange, staticFilesRange, upperDbRange)= splitRange(0..=2, Some(3..=5));
+    assert_eq!(lowerDbRange, 0..=2);
+    assert!(staticFilesRange.is_empty( ) && upperDbRange.is_empty( ));
+
+    let (lowerDbRange, staticFilesRange, upperDbRange)= splitRange(u64::MAX - 1..=u64::MAX, Some(0..=u64::MAX));
+    assert!(lowerDbRange.is_empty( ) && upperDbRange.is_empty( ));
+    assert_eq!(staticFilesRange, u64::MAX - 1..=u64::MAX);
+  }
+}
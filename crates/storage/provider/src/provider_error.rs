@@ -0,0 +1,12 @@
+use db::interfaces::db::DbError;
+use static_files::static_files_error::StaticFilesError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProviderError {
+
+  #[error(transparent)]
+  Db(#[from] DbError),
+
+  #[error(transparent)]
+  StaticFiles(#[from] StaticFilesError)
+}
@@ -1,5 +1,216 @@
-use super::segment::Segment;
+use std::{ops::RangeInclusive, path::Path, time::Instant};
+use alloy_primitives::U256;
+use db::{interfaces::{self, transaction::{DbTx, RoDbTx}}, models::{BlockHash, BlockNumber, Header}};
+use super::{
+  nippy_jar::{ColumnCompression, NippyJar, NippyJarReader, NippyJarWriter},
+  segment::{newSegmentJar, Segment, SegmentHeader, SegmentType},
+  static_files_error::StaticFilesError,
+  static_files_generator_event::SegmentMetrics
+};
 
+/*
+  Each row contains 3 columns :
+
+  (1) the block header (serde bridge encoded, since most of its fields are fixed size hashes which
+      don't compress well, lz4 compressed)
+  (2) total difficulty up to (and including) the block
+  (3) the block hash, which is the key column (so that headers can be looked up by block hash)
+
+  Block numbers and timestamps only go up, and gas limits and base fees change slowly from block to
+  block. So (1) starts with those 4 fields as 8 big-endian bytes each (the base fee being 0 before
+  London), which get delta encoded (see ColumnCompression::Lz4WithDeltaFields). They are zeroed in
+  the serde bridge encoded header following them, so that they don't take space twice.
+
+  Static files written before the delta encoding was introduced have the whole header lz4 compressed
+  in (1). They can still be read (see headerAt) and appended to (see headerRow).
+
+  The row number is the block number minus the first block number of the static file.
+*/
 pub struct BlockHeadersSegment { }
 
-impl Segment for BlockHeadersSegment { }
\ No newline at end of file
+pub const HEADER_COLUMN: usize= 0;
+pub const TOTAL_DIFFICULTY_COLUMN: usize= 1;
+pub const BLOCK_HASH_COLUMN: usize= 2;
+
+// Number of delta encoded fields at the start of the header column.
+const DELTA_FIELD_COUNT: usize= 4;
+
+// Number of rows whose delta encoded fields are stored relative to the same anchor.
+pub const ROWS_PER_ANCHOR: usize= 64;
+
+// Creates the (empty) NippyJar which will contain the block headers segment.
+pub(crate) fn newJar(directory: &Path, segmentHeader: SegmentHeader) -> Result<NippyJar<SegmentHeader>, StaticFilesError> {
+  let headerCompression= ColumnCompression::Lz4WithDeltaFields { fieldCount: DELTA_FIELD_COUNT, rowsPerAnchor: ROWS_PER_ANCHOR };
+
+  Ok(newSegmentJar(directory, 3, segmentHeader)?
+       .withColumnCompressions(vec![headerCompression, ColumnCompression::None, ColumnCompression::None])
+       .withKeyColumn(BLOCK_HASH_COLUMN))
+}
+
+// Returns the row of the block, for a static file whose header column uses the given compression.
+pub(crate) fn headerRow(header: &Header,
+                        totalDifficulty: U256,
+                        blockHash: BlockHash,
+                        headerCompression: ColumnCompression) -> Result<[Vec<u8>; 3], StaticFilesError>
+{
+  let encodedHeader= match headerCompression {
+    ColumnCompression::Lz4WithDeltaFields { .. } => {
+      let remainingHeader= Header {
+        number: 0,
+        timestamp: 0,
+        gasLimit: 0,
+        baseFeePerGas: header.baseFeePerGas.map(|_| 0),
+        ..header.clone( )
+      };
+
+      let mut encodedHeader= Vec::new( );
+      for field in [header.number, header.timestamp, header.gasLimit, header.baseFeePerGas.unwrap_or_default( )] {
+        encodedHeader.extend_from_slice(&field.to_be_bytes( ));
+      }
+      encodedHeader.extend_from_slice(&compression::serde_bridge::toBytes(&remainingHeader)?);
+      encodedHeader
+    },
+
+    _ => compression::serde_bridge::toBytes(header)?
+  };
+
+  Ok([encodedHeader, totalDifficulty.to_be_bytes::<32>( ).to_vec( ), blockHash.to_vec( )])
+}
+
+// Reads the header in the given row of a static file of the block headers segment.
+pub fn headerAt(reader: &NippyJarReader<SegmentHeader>, row: usize) -> Result<Header, StaticFilesError> {
+  let encodedHeader= reader.columnValue(row, HEADER_COLUMN)?;
+
+  match reader.jar( ).columnCompressions( )[HEADER_COLUMN] {
+    ColumnCompression::Lz4WithDeltaFields { .. } => {
+      // The column compression guarantees that the value starts with the delta encoded fields.
+      let (fields, remainingHeader)= encodedHeader.split_at(DELTA_FIELD_COUNT * 8);
+      let fieldAt= |i: usize| u64::from_be_bytes(fields[(i * 8)..((i + 1) * 8)].try_into( ).unwrap( ));
+
+      let header: Header= compression::serde_bridge::fromBytes(remainingHeader)?;
+      Ok(Header {
+        number: fieldAt(0),
+        timestamp: fieldAt(1),
+        gasLimit: fieldAt(2),
+        baseFeePerGas: header.baseFeePerGas.map(|_| fieldAt(3)),
+        ..header
+      })
+    },
+
+    _ => Ok(compression::serde_bridge::fromBytes(&encodedHeader)?)
+  }
+}
+
+impl<Db> Segment<Db> for BlockHeadersSegment
+  where
+    Db: interfaces::db::Db
+{
+  fn segmentType(&self) -> SegmentType {
+    SegmentType::BlockHeaders
+  }
+
+  fn copyToStaticFiles(&self,
+                       db: &Db,
+                       directory: &Path,
+                       blockRange: RangeInclusive<BlockNumber>) -> Result<SegmentMetrics, StaticFilesError>
+  {
+    let startTime= Instant::now( );
+
+    let segmentHeader= SegmentHeader {
+      segmentType: SegmentType::BlockHeaders,
+      blockRange: blockRange.clone( ),
+      highestBlock: Some(*blockRange.end( )),
+      txRange: None
+    };
+    let mut writer= NippyJarWriter::new(newJar(directory, segmentHeader)?)?;
+
+    db.withRoDbTx(|roDbTx| -> Result<( ), StaticFilesError> {
+      for blockNumber in blockRange {
+        let header= roDbTx.header(blockNumber)?
+                          .ok_or(StaticFilesError::MissingFromDb { data: "header", key: blockNumber })?;
+        let totalDifficulty= roDbTx.totalDifficulty(blockNumber)?
+                                   .ok_or(StaticFilesError::MissingFromDb { data: "total difficulty", key: blockNumber })?;
+        let blockHash= roDbTx.blockHash(blockNumber)?
+                             .ok_or(StaticFilesError::MissingFromDb { data: "block hash", key: blockNumber })?;
+
+        writer.appendRow(headerRow(&header, totalDifficulty, blockHash, writer.jar( ).columnCompressions( )[HEADER_COLUMN])?)?;
+      }
+
+      Ok(( ))
+    })??;
+
+    writer.finish( )?;
+    Ok(SegmentMetrics::of(&writer, startTime.elapsed( )))
+  }
+
+  fn pruneDb(&self, db: &Db, blockRange: RangeInclusive<BlockNumber>) -> Result<( ), StaticFilesError> {
+    Ok(db.withDbTx(|dbTx| dbTx.deleteHeaders(blockRange))??)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Before block 50, blocks have no base fee (like before London).
+  fn header(blockNumber: BlockNumber) -> Header {
+    Header {
+      number: blockNumber,
+      timestamp: 1_700_000_000 + 12 * blockNumber,
+      gasLimit: 30_000_000 - 1_000 * (blockNumber % 5),
+      gasUsed: 21_000 * blockNumber,
+      baseFeePerGas: (blockNumber >= 50).then_some(7 + blockNumber % 3),
+      ..Default::default( )
+    }
+  }
+
+  fn appendHeaders(writer: &mut NippyJarWriter<SegmentHeader>, blockNumbers: RangeInclusive<BlockNumber>) {
+    for blockNumber in blockNumbers {
+      let header= header(blockNumber);
+      let headerCompression= writer.jar( ).columnCompressions( )[HEADER_COLUMN];
+
+      writer.appendRow(headerRow(&header, U256::from(blockNumber), header.hash( ), headerCompression).unwrap( )).unwrap( );
+    }
+    writer.commit( ).unwrap( );
+  }
+
+  fn segmentHeader( ) -> SegmentHeader {
+    SegmentHeader {
+      segmentType: SegmentType::BlockHeaders,
+      blockRange: 0..=199,
+      highestBlock: None,
+      txRange: None
+    }
+  }
+
+  #[test]
+  fn headersRoundTripThroughTheDeltaEncodedFields( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join(SegmentType::BlockHeaders.fileName(&(0..=199)));
+
+    // The second commit resumes in the middle of an anchor's rows.
+    appendHeaders(&mut NippyJarWriter::new(newJar(directory.path( ), segmentHeader( )).unwrap( )).unwrap( ), 0..=99);
+    appendHeaders(&mut NippyJarWriter::new(NippyJar::load(&path).unwrap( )).unwrap( ), 100..=149);
+
+    let reader= NippyJarReader::<SegmentHeader>::open(&path).unwrap( );
+    for blockNumber in 0..=149 {
+      assert_eq!(headerAt(&reader, blockNumber as usize).unwrap( ), header(blockNumber));
+    }
+  }
+
+  #[test]
+  fn headersOfStaticFilesWrittenBeforeTheDeltaEncodingAreRead( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join(SegmentType::BlockHeaders.fileName(&(0..=199)));
+
+    let jar= newSegmentJar(directory.path( ), 3, segmentHeader( )).unwrap( )
+      .withColumnCompressions(vec![ColumnCompression::Lz4, ColumnCompression::None, ColumnCompression::None])
+      .withKeyColumn(BLOCK_HASH_COLUMN);
+    appendHeaders(&mut NippyJarWriter::new(jar).unwrap( ), 40..=59);
+
+    let reader= NippyJarReader::<SegmentHeader>::open(&path).unwrap( );
+    for blockNumber in 40..=59 {
+      assert_eq!(headerAt(&reader, (blockNumber - 40) as usize).unwrap( ), header(blockNumber));
+    }
+  }
+}
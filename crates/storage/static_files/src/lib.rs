@@ -4,19 +4,40 @@
   Data that has reached a finalized state and won't undergo further changes (essentially frozen)
   should be read without concerns of modification. This makes it unsuitable for traditional databases.
 
-  Such type of data will be copied from the current database to multiple static files (with custom
-  file format called 'NippyJar'), aggregated by block ranges. At every 500_000th block new static
-  files are created.
+  Such type of data is stored in multiple static files (with custom file format called 'NippyJar'),
+  aggregated by block ranges. By default, new static files are created at every 500_000th block
+  (configurable per segment).
 
-  Before getting copied, the data gets divided into multiple categories - e.g. block headers /
-  transactions / transaction receipts etc. Data contained in each category is called a 'segment'.
+  Static files are either generated by copying a block range from the database at once, and then
+  pruning it from the database (see StaticFilesGenerator, run by the StaticFilesScheduler whenever a
+  whole block range gets finalized), or appended to block by block as blocks get finalized, at the
+  tip of the chain (see StaticFilesWriter). The latter means the database never has to store that
+  history.
+
+  Before getting stored, the data gets divided into multiple categories - e.g. block headers /
+  transactions / transaction receipts / transaction senders etc. Data contained in each category is
+  called a 'segment'.
+
+  Long-running readers (see StaticFilesHander) pick up static files written or truncated after they
+  started, by another StaticFilesHander of the same process or by another process altogether (see
+  StaticFilesWatcher).
+
+  A manifest listing the complete static files, along with their sizes and hashes, is kept next to
+  them (see StaticFilesManifest), so that a copied directory can be verified before it gets used.
 */
 
 pub mod segment;
 pub mod block_headers_segment;
 pub mod transactions_segment;
 pub mod transaction_receipts_segment;
+pub mod senders_segment;
 pub mod nippy_jar;
 pub mod static_files_handler;
+pub mod static_files_handler_event;
+pub mod static_files_watcher;
 pub mod static_files_generator;
-pub mod static_files_generator_event;
\ No newline at end of file
+pub mod static_files_scheduler;
+pub mod static_files_writer;
+pub mod static_files_manifest;
+pub mod static_files_generator_event;
+pub mod static_files_error;
\ No newline at end of file
@@ -0,0 +1,479 @@
+use std::{borrow::Cow, collections::BTreeMap, fs, io::Write, path::Path, sync::Mutex};
+use serde::{Deserialize, Serialize};
+use super::NippyJarError;
+
+const ZSTD_COMPRESSION_LEVEL: i32= 3;
+
+// Maximum size of a dictionary trained for a ZstdWithDictionary column.
+pub const MAX_DICTIONARY_SIZE: usize= 64 * 1024;
+
+/*
+  Different columns compress very differently - e.g. block hashes are incompressible, while
+  transactions are very compressible. So each column of a NippyJar declares how its values are
+  compressed :
+
+  (1) None : values are stored as is.
+  (2) Lz4 : fast, but with a lower compression ratio.
+  (3) Zstd : each value is compressed independently.
+  (4) ZstdWithDictionary : each value is compressed using a dictionary, trained (while writing the
+      NippyJar) from a sample of the column values. Works much better than plain zstd on small and
+      similar values. The dictionaries are stored in the dictionaries file (<path>.dict).
+  (5) Lz4WithDeltaFields : for values starting with fieldCount u64 fields (8 big-endian bytes each)
+      which change slowly from row to row, like the block number and timestamp of a header. Every
+      rowsPerAnchor-th row is an anchor, storing its fields as is. The other rows store the
+      difference of each field from the one of their anchor (zigzag and then LEB128 encoded), which
+      usually takes a byte or two. The rest of the value is lz4 compressed. Since a value only
+      depends on its anchor, any row is still read with just 2 lookups.
+
+  Zstd compressed values are prefixed with their decompressed length (4 little-endian bytes), so
+  that readers can allocate the right buffer size upfront. Lz4 does the same by itself.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColumnCompression {
+  #[default]
+  None,
+  Lz4,
+  Zstd,
+  ZstdWithDictionary,
+  Lz4WithDeltaFields {
+    fieldCount: usize,
+    rowsPerAnchor: usize
+  }
+}
+
+impl ColumnCompression {
+  // For Lz4WithDeltaFields columns, returns the anchor row of the given row.
+  pub(crate) fn anchorRowOf(&self, row: usize) -> Option<usize> {
+    match self {
+      Self::Lz4WithDeltaFields { rowsPerAnchor, .. } => Some(row - row % (*rowsPerAnchor).max(1)),
+      _ => None
+    }
+  }
+}
+
+// Trained zstd dictionaries, keyed by column index.
+pub type ColumnDictionaries= BTreeMap<usize, Vec<u8>>;
+
+// Compresses values of a column, while writing the NippyJar.
+pub(crate) enum ColumnCompressor {
+  None,
+  Lz4,
+  Zstd(zstd::bulk::Compressor<'static>),
+
+  // Fields of the anchor of the rows being appended.
+  Lz4WithDeltaFields {
+    compression: ColumnCompression,
+    fieldCount: usize,
+    anchorFields: Option<Vec<u64>>
+  }
+}
+
+impl ColumnCompressor {
+  pub(crate) fn new(column: usize,
+                    compression: ColumnCompression,
+                    dictionaries: &ColumnDictionaries) -> Result<Self, NippyJarError>
+  {
+    let columnCompressor= match compression {
+      ColumnCompression::None => Self::None,
+      ColumnCompression::Lz4 => Self::Lz4,
+      ColumnCompression::Zstd => Self::Zstd(zstd::bulk::Compressor::new(ZSTD_COMPRESSION_LEVEL)?),
+
+      ColumnCompression::ZstdWithDictionary => {
+        let dictionary= dictionaries.get(&column).ok_or(NippyJarError::DictionaryNotFound(column))?;
+        Self::Zstd(zstd::bulk::Compressor::with_dictionary(ZSTD_COMPRESSION_LEVEL, dictionary)?)
+      },
+
+      ColumnCompression::Lz4WithDeltaFields { fieldCount, .. } =>
+        Self::Lz4WithDeltaFields { compression, fieldCount, anchorFields: None }
+    };
+
+    Ok(columnCompressor)
+  }
+
+  // For Lz4WithDeltaFields columns, sets the (decompressed) value of the anchor of the next rows to
+  // be appended, when appending to a NippyJar which already has rows.
+  pub(crate) fn setAnchor(&mut self, anchorValue: &[u8]) -> Result<( ), NippyJarError> {
+    if let Self::Lz4WithDeltaFields { fieldCount, anchorFields, .. }= self {
+      *anchorFields= Some(fieldsOf(anchorValue, *fieldCount)?);
+    }
+    Ok(( ))
+  }
+
+  // Compresses the value of the column in the given row.
+  pub(crate) fn compress<'v>(&mut self, row: usize, value: &'v [u8]) -> Result<Cow<'v, [u8]>, NippyJarError> {
+    match self {
+      Self::None => Ok(Cow::Borrowed(value)),
+
+      Self::Lz4 => Ok(Cow::Owned(lz4_flex::compress_prepend_size(value))),
+
+      Self::Zstd(compressor) => {
+        let mut compressedValue= Vec::with_capacity(4 + value.len( ));
+        compressedValue.extend_from_slice(&(value.len( ) as u32).to_le_bytes( ));
+        compressedValue.extend_from_slice(&compressor.compress(value)?);
+
+        Ok(Cow::Owned(compressedValue))
+      },
+
+      Self::Lz4WithDeltaFields { compression, fieldCount, anchorFields } => {
+        let fields= fieldsOf(value, *fieldCount)?;
+        let fieldsLen= *fieldCount * 8;
+
+        let mut compressedValue= Vec::with_capacity(value.len( ));
+        match compression.anchorRowOf(row) == Some(row) {
+          true => {
+            compressedValue.extend_from_slice(&value[..fieldsLen]);
+            *anchorFields= Some(fields);
+          },
+          false => {
+            let anchorFields= anchorFields.as_ref( ).ok_or(NippyJarError::UnknownAnchor(row))?;
+            for (field, anchorField) in fields.iter( ).zip(anchorFields) {
+              encodeDelta(&mut compressedValue, field.wrapping_sub(*anchorField) as i64);
+            }
+          }
+        }
+        compressedValue.extend_from_slice(&lz4_flex::compress_prepend_size(&value[fieldsLen..]));
+
+        Ok(Cow::Owned(compressedValue))
+      }
+    }
+  }
+}
+
+/*
+  Decompresses values of a column, while reading the NippyJar.
+
+  Compressed values are prefixed with their decompressed length. The decompressed length read from
+  disk is bounded by the size of the largest row (if the NippyJar records it), so that a corrupted
+  value can't make us allocate up to 4 GiB.
+*/
+pub(crate) enum ColumnDecompressor {
+  None,
+
+  Lz4 {
+    maxDecompressedLen: Option<usize>
+  },
+
+  // The decompressor (and the dictionary loaded into it) is reused across values. Readers can be
+  // shared between threads, so a value gets its own decompressor while the cached one is busy.
+  Zstd {
+    decompressor: Mutex<zstd::bulk::Decompressor<'static>>,
+    dictionary: Option<Vec<u8>>,
+    maxDecompressedLen: Option<usize>
+  },
+
+  Lz4WithDeltaFields {
+    fieldCount: usize,
+    maxDecompressedLen: Option<usize>
+  }
+}
+
+impl ColumnDecompressor {
+  pub(crate) fn new(column: usize,
+                    compression: ColumnCompression,
+                    dictionaries: &ColumnDictionaries,
+                    maxDecompressedLen: Option<usize>) -> Result<Self, NippyJarError>
+  {
+    let zstd= |dictionary: Option<Vec<u8>>| -> Result<Self, NippyJarError> {
+      let decompressor= newZstdDecompressor(dictionary.as_deref( ))?;
+      Ok(Self::Zstd { decompressor: Mutex::new(decompressor), dictionary, maxDecompressedLen })
+    };
+
+    let columnDecompressor= match compression {
+      ColumnCompression::None => Self::None,
+      ColumnCompression::Lz4 => Self::Lz4 { maxDecompressedLen },
+      ColumnCompression::Zstd => zstd(None)?,
+
+      ColumnCompression::ZstdWithDictionary => {
+        let dictionary= dictionaries.get(&column).ok_or(NippyJarError::DictionaryNotFound(column))?;
+        zstd(Some(dictionary.clone( )))?
+      },
+
+      ColumnCompression::Lz4WithDeltaFields { fieldCount, .. } => Self::Lz4WithDeltaFields { fieldCount, maxDecompressedLen }
+    };
+
+    Ok(columnDecompressor)
+  }
+
+  // Decompresses a value of the column. For Lz4WithDeltaFields columns, the (stored) value of the
+  // row's anchor must be given, unless the row is an anchor itself.
+  pub(crate) fn decompress<'v>(&self, value: &'v [u8], anchorValue: Option<&[u8]>) -> Result<Cow<'v, [u8]>, NippyJarError> {
+    match self {
+      Self::None => Ok(Cow::Borrowed(value)),
+
+      Self::Lz4 { maxDecompressedLen } => Ok(Cow::Owned(decompressLz4(value, *maxDecompressedLen)?)),
+
+      Self::Zstd { decompressor, dictionary, maxDecompressedLen } => {
+        if value.len( ) < 4 {
+          return Err(NippyJarError::CorruptedValue)
+        }
+        let decompressedLen= u32::from_le_bytes(value[..4].try_into( ).unwrap( )) as usize;
+        checkDecompressedLen(decompressedLen, *maxDecompressedLen)?;
+
+        let decompressedValue= match decompressor.try_lock( ) {
+          Ok(mut decompressor) => decompressor.decompress(&value[4..], decompressedLen)?,
+          Err(_) => newZstdDecompressor(dictionary.as_deref( ))?.decompress(&value[4..], decompressedLen)?
+        };
+
+        Ok(Cow::Owned(decompressedValue))
+      },
+
+      Self::Lz4WithDeltaFields { fieldCount, maxDecompressedLen } => {
+        let (fields, rest)= match anchorValue {
+          None => (fieldsOf(value, *fieldCount).map_err(|_| NippyJarError::CorruptedValue)?, &value[(*fieldCount * 8)..]),
+
+          Some(anchorValue) => {
+            let anchorFields= fieldsOf(anchorValue, *fieldCount).map_err(|_| NippyJarError::CorruptedValue)?;
+            let mut rest= value;
+            let fields= anchorFields.into_iter( )
+              .map(|anchorField| Ok(anchorField.wrapping_add(decodeDelta(&mut rest)? as u64)))
+              .collect::<Result<Vec<_>, NippyJarError>>( )?;
+            (fields, rest)
+          }
+        };
+
+        // The fields are part of the row as well.
+        let rest= decompressLz4(rest, maxDecompressedLen.map(|maxDecompressedLen| maxDecompressedLen.saturating_sub(fields.len( ) * 8)))?;
+        let mut decompressedValue= Vec::with_capacity(fields.len( ) * 8 + rest.len( ));
+        for field in fields {
+          decompressedValue.extend_from_slice(&field.to_be_bytes( ));
+        }
+        decompressedValue.extend_from_slice(&rest);
+
+        Ok(Cow::Owned(decompressedValue))
+      }
+    }
+  }
+}
+
+// Decompresses a lz4 value prefixed with its decompressed length, which mustn't exceed
+// maxDecompressedLen.
+fn decompressLz4(value: &[u8], maxDecompressedLen: Option<usize>) -> Result<Vec<u8>, NippyJarError> {
+  let (decompressedLen, compressedValue)= lz4_flex::block::uncompressed_size(value)?;
+  checkDecompressedLen(decompressedLen, maxDecompressedLen)?;
+
+  Ok(lz4_flex::block::decompress(compressedValue, decompressedLen)?)
+}
+
+fn checkDecompressedLen(decompressedLen: usize, maxDecompressedLen: Option<usize>) -> Result<( ), NippyJarError> {
+  match maxDecompressedLen.is_some_and(|maxDecompressedLen| decompressedLen > maxDecompressedLen) {
+    true => Err(NippyJarError::CorruptedValue),
+    false => Ok(( ))
+  }
+}
+
+fn newZstdDecompressor(dictionary: Option<&[u8]>) -> Result<zstd::bulk::Decompressor<'static>, NippyJarError> {
+  let decompressor= match dictionary {
+    Some(dictionary) => zstd::bulk::Decompressor::with_dictionary(dictionary)?,
+    None => zstd::bulk::Decompressor::new( )?
+  };
+  Ok(decompressor)
+}
+
+// Parses the fields at the start of a value of a Lz4WithDeltaFields column.
+fn fieldsOf(value: &[u8], fieldCount: usize) -> Result<Vec<u64>, NippyJarError> {
+  if value.len( ) < fieldCount * 8 {
+    return Err(NippyJarError::InvalidDeltaFields { fieldCount, valueLen: value.len( ) })
+  }
+
+  Ok(value[..(fieldCount * 8)].chunks_exact(8)
+       .map(|field| u64::from_be_bytes(field.try_into( ).unwrap( )))
+       .collect( ))
+}
+
+// Zigzag encodes the delta (so that small negative deltas stay small), and then LEB128 encodes it :
+// 7 bits per byte, least significant first, with the high bit set on all bytes but the last.
+fn encodeDelta(buffer: &mut Vec<u8>, delta: i64) {
+  let mut zigzagEncodedDelta= ((delta << 1) ^ (delta >> 63)) as u64;
+
+  while zigzagEncodedDelta >= 0x80 {
+    buffer.push((zigzagEncodedDelta as u8) | 0x80);
+    zigzagEncodedDelta >>= 7;
+  }
+  buffer.push(zigzagEncodedDelta as u8);
+}
+
+// Decodes the delta at the start of the buffer, advancing the buffer past it.
+fn decodeDelta(buffer: &mut &[u8]) -> Result<i64, NippyJarError> {
+  let mut zigzagEncodedDelta= 0u64;
+
+  for (i, byte) in buffer.iter( ).enumerate( ).take(10) {
+    zigzagEncodedDelta |= ((byte & 0x7f) as u64) << (7 * i);
+
+    if byte & 0x80 == 0 {
+      *buffer= &buffer[(i + 1)..];
+      return Ok(((zigzagEncodedDelta >> 1) as i64) ^ -((zigzagEncodedDelta & 1) as i64))
+    }
+  }
+
+  Err(NippyJarError::CorruptedValue)
+}
+
+// Trains a zstd dictionary for a column, using the given sample of its values.
+pub(crate) fn trainDictionary(column: usize, samples: &[Vec<u8>]) -> Result<Vec<u8>, NippyJarError> {
+  zstd::dict::from_samples(samples, MAX_DICTIONARY_SIZE)
+    .map_err(|error| NippyJarError::DictionaryTrainingFailed { column, error })
+}
+
+/*
+  Layout of the dictionaries file - for each dictionary :
+
+    | column index (4 bytes) | dictionary length (4 bytes) | dictionary |
+
+  All integers are little-endian.
+*/
+pub(crate) fn saveDictionaries(path: &Path, dictionaries: &ColumnDictionaries) -> Result<( ), NippyJarError> {
+  let mut buffer= Vec::new( );
+  for (column, dictionary) in dictionaries {
+    buffer.extend_from_slice(&(*column as u32).to_le_bytes( ));
+    buffer.extend_from_slice(&(dictionary.len( ) as u32).to_le_bytes( ));
+    buffer.extend_from_slice(dictionary);
+  }
+
+  let temporaryPath= path.with_extension("dict.tmp");
+
+  let mut temporaryFile= fs::File::create(&temporaryPath)?;
+  temporaryFile.write_all(&buffer)?;
+  temporaryFile.sync_all( )?;
+
+  fs::rename(&temporaryPath, path)?;
+
+  Ok(( ))
+}
+
+pub(crate) fn loadDictionaries(path: &Path) -> Result<ColumnDictionaries, NippyJarError> {
+  let mut dictionaries= ColumnDictionaries::new( );
+  if !path.exists( ) {
+    return Ok(dictionaries)
+  }
+
+  let buffer= fs::read(path)?;
+  let mut buffer= buffer.as_slice( );
+
+  while !buffer.is_empty( ) {
+    if buffer.len( ) < 8 {
+      return Err(NippyJarError::CorruptedDictionaries)
+    }
+    let column= u32::from_le_bytes(buffer[..4].try_into( ).unwrap( )) as usize;
+    let dictionaryLen= u32::from_le_bytes(buffer[4..8].try_into( ).unwrap( )) as usize;
+    buffer= &buffer[8..];
+
+    if buffer.len( ) < dictionaryLen {
+      return Err(NippyJarError::CorruptedDictionaries)
+    }
+    dictionaries.insert(column, buffer[..dictionaryLen].to_vec( ));
+    buffer= &buffer[dictionaryLen..];
+  }
+
+  Ok(dictionaries)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // A value of a Lz4WithDeltaFields column, with the given fields.
+  fn value(fields: &[u64]) -> Vec<u8> {
+    let mut value: Vec<u8>= fields.iter( ).flat_map(|field| field.to_be_bytes( )).collect( );
+    value.extend_from_slice(b"rest of the value");
+    value
+  }
+
+  #[test]
+  fn deltaFieldsRoundTrip( ) {
+    let compression= ColumnCompression::Lz4WithDeltaFields { fieldCount: 3, rowsPerAnchor: 4 };
+    let mut compressor= ColumnCompressor::new(0, compression, &ColumnDictionaries::new( )).unwrap( );
+    let decompressor= ColumnDecompressor::new(0, compression, &ColumnDictionaries::new( ), None).unwrap( );
+
+    // Increasing, decreasing and wrapping around fields.
+    let values: Vec<Vec<u8>>= (0..10u64).map(|i| value(&[1_000_000 + i, 5_000 - 3 * i, u64::MAX.wrapping_add(i)])).collect( );
+    let storedValues: Vec<Vec<u8>>= values.iter( ).enumerate( )
+      .map(|(row, value)| compressor.compress(row, value).unwrap( ).into_owned( ))
+      .collect( );
+
+    for (row, value) in values.iter( ).enumerate( ) {
+      let anchorRow= compression.anchorRowOf(row).unwrap( );
+      let anchorValue= (anchorRow != row).then(|| storedValues[anchorRow].as_slice( ));
+
+      assert_eq!(decompressor.decompress(&storedValues[row], anchorValue).unwrap( ), value.as_slice( ));
+    }
+
+    // Anchors store their fields as is, while the other rows take a byte per field here.
+    assert_eq!(storedValues[4][..24], values[4][..24]);
+    assert_eq!(storedValues[5].len( ), storedValues[4].len( ) - 24 + 3);
+
+    // Appending to a NippyJar which already has rows, starts from the anchor's value.
+    let mut compressor= ColumnCompressor::new(0, compression, &ColumnDictionaries::new( )).unwrap( );
+    assert!(matches!(compressor.compress(6, &values[6]), Err(NippyJarError::UnknownAnchor(6))));
+    compressor.setAnchor(&values[4]).unwrap( );
+    assert_eq!(compressor.compress(6, &values[6]).unwrap( ), storedValues[6].as_slice( ));
+  }
+
+  #[test]
+  fn zstdValuesAreBoundedByTheLargestRow( ) {
+    let mut compressor= ColumnCompressor::new(0, ColumnCompression::Zstd, &ColumnDictionaries::new( )).unwrap( );
+    let decompressor= ColumnDecompressor::new(0, ColumnCompression::Zstd, &ColumnDictionaries::new( ), Some(100)).unwrap( );
+
+    // The cached decompressor is reused across values.
+    for value in [vec![7u8; 100], b"short".to_vec( )] {
+      let storedValue= compressor.compress(0, &value).unwrap( ).into_owned( );
+      assert_eq!(decompressor.decompress(&storedValue, None).unwrap( ), value.as_slice( ));
+    }
+
+    // A corrupted decompressed length.
+    let mut storedValue= compressor.compress(0, &[7u8; 100]).unwrap( ).into_owned( );
+    storedValue[..4].copy_from_slice(&u32::MAX.to_le_bytes( ));
+    assert!(matches!(decompressor.decompress(&storedValue, None), Err(NippyJarError::CorruptedValue)));
+  }
+
+  #[test]
+  fn lz4ValuesAreBoundedByTheLargestRow( ) {
+    let mut compressor= ColumnCompressor::new(0, ColumnCompression::Lz4, &ColumnDictionaries::new( )).unwrap( );
+    let decompressor= ColumnDecompressor::new(0, ColumnCompression::Lz4, &ColumnDictionaries::new( ), Some(100)).unwrap( );
+
+    let mut storedValue= compressor.compress(0, &[7u8; 100]).unwrap( ).into_owned( );
+    assert_eq!(decompressor.decompress(&storedValue, None).unwrap( ), [7u8; 100].as_slice( ));
+
+    // A corrupted decompressed length.
+    storedValue[..4].copy_from_slice(&u32::MAX.to_le_bytes( ));
+    assert!(matches!(decompressor.decompress(&storedValue, None), Err(NippyJarError::CorruptedValue)));
+
+    // The fields count towards the bound too (an anchor's value holds 24 bytes of fields here).
+    let compression= ColumnCompression::Lz4WithDeltaFields { fieldCount: 3, rowsPerAnchor: 4 };
+    let mut compressor= ColumnCompressor::new(0, compression, &ColumnDictionaries::new( )).unwrap( );
+    let value= value(&[1, 2, 3]);
+
+    let storedValue= compressor.compress(0, &value).unwrap( ).into_owned( );
+    let decompressor= ColumnDecompressor::new(0, compression, &ColumnDictionaries::new( ), Some(value.len( ))).unwrap( );
+    assert_eq!(decompressor.decompress(&storedValue, None).unwrap( ), value.as_slice( ));
+
+    let decompressor= ColumnDecompressor::new(0, compression, &ColumnDictionaries::new( ), Some(value.len( ) - 1)).unwrap( );
+    assert!(matches!(decompressor.decompress(&storedValue, None), Err(NippyJarError::CorruptedValue)));
+  }
+
+  #[test]
+  fn deltasRoundTrip( ) {
+    for (delta, encodedLen) in [(0, 1), (1, 1), (-1, 1), (63, 1), (-64, 1), (64, 2), (-65, 2), (i64::MAX, 10), (i64::MIN, 10)] {
+      let mut buffer= Vec::new( );
+      encodeDelta(&mut buffer, delta);
+      assert_eq!(buffer.len( ), encodedLen);
+
+      buffer.push(0xFF);
+      let mut remaining= buffer.as_slice( );
+      assert_eq!(decodeDelta(&mut remaining).unwrap( ), delta);
+      assert_eq!(remaining, [0xFF]);
+    }
+  }
+
+  #[test]
+  fn invalidDeltaFieldsAreErrors( ) {
+    let compression= ColumnCompression::Lz4WithDeltaFields { fieldCount: 3, rowsPerAnchor: 4 };
+    let mut compressor= ColumnCompressor::new(0, compression, &ColumnDictionaries::new( )).unwrap( );
+    let decompressor= ColumnDecompressor::new(0, compression, &ColumnDictionaries::new( ), None).unwrap( );
+
+    assert!(matches!(compressor.compress(0, &[0; 20]), Err(NippyJarError::InvalidDeltaFields { fieldCount: 3, valueLen: 20 })));
+
+    // A delta which never ends, and a value too short to hold the anchor's fields.
+    assert!(matches!(decodeDelta(&mut &[0x80; 12][..]), Err(NippyJarError::CorruptedValue)));
+    assert!(matches!(decompressor.decompress(&[0; 20], None), Err(NippyJarError::CorruptedValue)));
+  }
+}
@@ -0,0 +1,459 @@
+use std::{fs::{self, OpenOptions}, io::{self, Read, Seek, SeekFrom}, path::{Path, PathBuf}};
+use serde::{de::DeserializeOwned, Serialize};
+use super::{index::KeyIndex, key_table::KeyTable, offsets::OffsetsLayout, xor_filter::hashKey, KeyIndexKind, NippyJar, NippyJarError,
+            NippyJarReader};
+
+/*
+  If the node dies while a NippyJar is being written, the data file and the offsets file can
+  disagree with each other and with the config :
+
+  (1) The data file may have (partially written) values of rows that were never committed.
+  (2) The offsets file may have offsets of rows that were never committed.
+  (3) In case of disk corruption, the offsets may point beyond the data file, or the data may not
+      match the checksums (only verified for the last row group, see checkConsistency).
+  (4) If the node died while the NippyJar was being upgraded (see NippyJar::upgrade), the offsets
+      file may already be in the new format version, but not the config.
+
+  NippyJar::checkConsistency detects all of these, truncates the data and offsets files back to the
+  last fully written (and valid) row, completes the upgrade, and reports what was dropped. It runs
+  whenever a NippyJar is opened for writing (see NippyJarWriter::consistencyReport). Like
+  NippyJar::truncate, it replaces the files instead of truncating them in place, so readers which
+  have them memory-mapped are unaffected.
+
+  Checksums : the rows are split into groups of rowsPerChecksum rows (for the block headers segment,
+  that's a block range), and the CRC32 checksum of the data of each group is stored in the config.
+  The checksum of the last (partially filled) group is updated on every commit.
+*/
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ConsistencyReport {
+  // Committed rows that had to be dropped (since they were torn or corrupted).
+  pub rowsDropped: usize,
+
+  // Bytes dropped from the end of the data file.
+  pub dataBytesDropped: u64,
+
+  // Bytes dropped from the end of the offsets file.
+  pub offsetsBytesDropped: u64,
+
+  // Row groups whose data didn't match their checksums.
+  pub corruptedRowGroups: Vec<usize>
+}
+
+impl ConsistencyReport {
+  // Whether or not the NippyJar was consistent (nothing was dropped).
+  pub fn isClean(&self) -> bool {
+    *self == Self::default( )
+  }
+}
+
+impl<H> NippyJar<H>
+  where
+    H: Serialize + DeserializeOwned
+{
+  // Truncates the data and offsets files back to the last fully written and valid row. The config
+  // (and the key index / key table, if any) are updated if committed rows had to be dropped.
+  pub fn checkConsistency(&mut self) -> Result<ConsistencyReport, NippyJarError> {
+    let mut report= ConsistencyReport::default( );
+
+    // Leftovers from an interrupted config / dictionaries / index write, or truncation.
+    for temporaryPath in [self.configPath( ).with_extension("conf.tmp"),
+                          self.dictionariesPath( ).with_extension("dict.tmp"),
+                          self.indexPath( ).with_extension("idx.tmp"),
+                          self.keysPath( ).with_extension("keys.tmp"),
+                          temporaryPathOf(self.dataPath( )),
+                          temporaryPathOf(&self.offsetsPath( ))]
+    {
+      if temporaryPath.exists( ) {
+        fs::remove_file(temporaryPath)?;
+      }
+    }
+
+    // Leftover of an interrupted NippyJarWriter::finish.
+    if self.keyIndexKind == KeyIndexKind::KeyIndex && self.keysPath( ).exists( ) {
+      fs::remove_file(self.keysPath( ))?;
+    }
+
+    let mut dataFile= OpenOptions::new( ).read(true).write(true).create(true).truncate(false)
+                                         .open(self.dataPath( ))?;
+    let dataFileLen= dataFile.metadata( )?.len( );
+
+    let mut offsetsFile= OpenOptions::new( ).read(true).write(true).create(true).truncate(false)
+                                            .open(self.offsetsPath( ))?;
+    let offsetsFileLen= offsetsFile.metadata( )?.len( );
+
+    let offsetsLayout= OffsetsLayout::detectFromFile(&mut offsetsFile, self.version)?;
+    let isUpgradeInterrupted= offsetsLayout.version( ) != self.version;
+    self.version= offsetsLayout.version( );
+
+    /*
+      Only the last row group is checked (its offsets being present, increasing and within the data
+      file, and its checksum), since that's where the rows written after the last commit end up.
+      Everything is checked only when the committed offsets or data aren't all there (e.g. after
+      disk corruption), since then any row may be affected. The CLI verify command checks every
+      row group (see NippyJarReader::verifyChecksums).
+    */
+    let committedOffsetCount= self.rowCount * self.columnCount + 1;
+
+    let mut firstCheckedRow= (self.rowCount.saturating_sub(1) / self.rowsPerChecksum) * self.rowsPerChecksum;
+    let mut offsets= offsetsLayout.readOffsetsFrom(&mut offsetsFile,
+                                                   firstCheckedRow * self.columnCount,
+                                                   committedOffsetCount - firstCheckedRow * self.columnCount)?;
+
+    let isTailPresent= offsets.len( ) == committedOffsetCount - firstCheckedRow * self.columnCount &&
+                       offsets.first( ).is_some_and(|offset| *offset <= dataFileLen);
+    if !isTailPresent {
+      firstCheckedRow= 0;
+      offsets= offsetsLayout.readOffsets(&mut offsetsFile, committedOffsetCount)?;
+    }
+
+    let firstOffsetIndex= firstCheckedRow * self.columnCount;
+    let offsetAt= |index: usize| offsets[index - firstOffsetIndex];
+
+    // Rows whose offsets are present, increasing, and within the data file.
+    let firstInvalidOffsetIndex= firstOffsetIndex + offsets.iter( ).enumerate( )
+      .position(|(i, offset)| {
+        let isDecreasing= i > 0 && *offset < offsets[i - 1];
+        *offset > dataFileLen || isDecreasing || (firstOffsetIndex + i == 0 && *offset != 0)
+      })
+      .unwrap_or(offsets.len( ));
+    let mut validRowCount= match (firstInvalidOffsetIndex, self.columnCount) {
+      (0, _) | (_, 0) => 0,
+      (firstInvalidOffsetIndex, columnCount) => (firstInvalidOffsetIndex - 1) / columnCount
+    };
+    validRowCount= validRowCount.min(self.rowCount);
+
+    // Verify the checksums of the checked row groups which are still fully present.
+    let rowDataRange= |startRow: usize, endRow: usize|
+      (offsetAt(startRow * self.columnCount) as usize)..(offsetAt(endRow * self.columnCount) as usize);
+
+    let mut data= Vec::new( );
+    for (rowGroup, expectedChecksum) in self.checksums.iter( ).enumerate( ).skip(firstCheckedRow / self.rowsPerChecksum) {
+      let (startRow, endRow)= self.rowGroupBounds(rowGroup);
+      if endRow > validRowCount {
+        break
+      }
+
+      let dataRange= rowDataRange(startRow, endRow);
+      data.resize(dataRange.len( ), 0);
+      dataFile.seek(SeekFrom::Start(dataRange.start as u64))?;
+      dataFile.read_exact(&mut data)?;
+
+      if crc32fast::hash(&data) != *expectedChecksum {
+        report.corruptedRowGroups.push(rowGroup);
+        validRowCount= startRow;
+        break
+      }
+    }
+
+    report.rowsDropped= self.rowCount - validRowCount;
+
+    // Truncate the data and offsets files, by replacing them (see truncate). dataFile keeps
+    // referring to the replaced data file, whose valid rows are the same.
+    let validDataLen= if validRowCount == 0 { 0 } else { offsetAt(validRowCount * self.columnCount) };
+    if dataFileLen > validDataLen {
+      replaceWithPrefix(self.dataPath( ), validDataLen)?;
+    }
+    report.dataBytesDropped= dataFileLen - validDataLen;
+
+    // The last valid offset is the length of the valid data. If not even the first offset is valid
+    // (e.g. the offsets file is empty), the offsets file is rewritten.
+    let validOffsetCount= validRowCount * self.columnCount + 1;
+    let validOffsetsFileLen= offsetsLayout.fileLen(validOffsetCount);
+    if offsetsFileLen != validOffsetsFileLen {
+      match firstInvalidOffsetIndex >= validOffsetCount {
+        true => replaceWithPrefix(&self.offsetsPath( ), validOffsetsFileLen)?,
+        false => offsetsLayout.writeOffsetsFile(&self.offsetsPath( ), std::iter::once(0))?
+      }
+    }
+    report.offsetsBytesDropped= offsetsFileLen.saturating_sub(validOffsetsFileLen);
+
+    if report.rowsDropped > 0 {
+      self.rowCount= validRowCount;
+
+      // The checksum of the (now) last row group needs to be recalculated.
+      let completeRowGroupCount= validRowCount / self.rowsPerChecksum;
+      self.checksums.truncate(completeRowGroupCount);
+      if validRowCount % self.rowsPerChecksum != 0 {
+        let dataRange= rowDataRange(completeRowGroupCount * self.rowsPerChecksum, validRowCount);
+        data.resize(dataRange.len( ), 0);
+        dataFile.seek(SeekFrom::Start(dataRange.start as u64))?;
+        dataFile.read_exact(&mut data)?;
+
+        self.checksums.push(crc32fast::hash(&data));
+      }
+
+      self.saveConfig( )?;
+
+      self.rebuildKeyIndex( )?;
+    }
+    else if isUpgradeInterrupted {
+      self.saveConfig( )?;
+    }
+
+    Ok(report)
+  }
+
+  /*
+    Removes the rows after the first rowCount rows. The user header is persisted as well, so that it
+    can be updated to describe the remaining rows.
+
+    The config is saved first - that's the point at which the rows are gone for readers. The data
+    and offsets files are truncated afterwards. If the node dies in between, checkConsistency drops
+    the leftover data, since it's beyond the row count in the config.
+
+    The data and offsets files aren't truncated in place, but replaced by truncated copies, since
+    readers (e.g. of another process, which hasn't noticed the truncation yet) may still have them
+    memory-mapped. Accessing a memory-mapped page beyond the end of a file crashes the process,
+    whereas a replaced file lives on until it's unmapped.
+  */
+  pub fn truncate(&mut self, rowCount: usize) -> Result<( ), NippyJarError> {
+    let rowCount= rowCount.min(self.rowCount);
+
+    let mut dataFile= fs::File::open(self.dataPath( ))?;
+    let mut offsetsFile= fs::File::open(self.offsetsPath( ))?;
+
+    let offsetsLayout= OffsetsLayout::detectFromFile(&mut offsetsFile, self.version)?;
+
+    // Only the offsets of the (now) last row group are needed, to recalculate its checksum.
+    let completeRowGroupCount= rowCount / self.rowsPerChecksum;
+    let firstOffsetIndex= completeRowGroupCount * self.rowsPerChecksum * self.columnCount;
+    let offsetCount= rowCount * self.columnCount + 1 - firstOffsetIndex;
+
+    let offsets= offsetsLayout.readOffsetsFrom(&mut offsetsFile, firstOffsetIndex, offsetCount)?;
+    if offsets.len( ) != offsetCount {
+      return Err(NippyJarError::OffsetsMismatch {
+        expected: rowCount * self.columnCount + 1,
+        found: firstOffsetIndex + offsets.len( )
+      })
+    }
+    let dataEnd= offsets[offsetCount - 1];
+
+    self.checksums.truncate(completeRowGroupCount);
+    if !rowCount.is_multiple_of(self.rowsPerChecksum) {
+      let dataStart= offsets[0];
+
+      let mut data= vec![0u8; (dataEnd - dataStart) as usize];
+      dataFile.seek(SeekFrom::Start(dataStart))?;
+      dataFile.read_exact(&mut data)?;
+
+      self.checksums.push(crc32fast::hash(&data));
+    }
+
+    self.rowCount= rowCount;
+    self.saveConfig( )?;
+
+    replaceWithPrefix(self.dataPath( ), dataEnd)?;
+    replaceWithPrefix(&self.offsetsPath( ), offsetsLayout.fileLen(rowCount * self.columnCount + 1))?;
+
+    self.checkConsistency( )?;
+
+    self.rebuildKeyIndex( )
+  }
+
+  // Rebuilds the key index or the key table (depending on keyIndexKind), if any, from the rows
+  // committed to the config on disk.
+  pub(crate) fn rebuildKeyIndex(&self) -> Result<( ), NippyJarError> {
+    let Some(keyColumn)= self.keyColumn else {
+      return Ok(( ))
+    };
+
+    let reader= match self.rowCount {
+      0 => None,
+      _ => Some(NippyJarReader::<H>::openWithoutKeys(self.dataPath( ))?)
+    };
+
+    match (self.keyIndexKind, &reader) {
+      (KeyIndexKind::KeyTable, _) => {
+        let keys= reader.iter( ).flat_map(|reader| (0..reader.rowCount( )).map(move |row| reader.columnValue(row, keyColumn)));
+        let entries= keys.enumerate( ).map(|(row, key)| Ok((hashKey(&key?), row)))
+                         .collect::<Result<Vec<_>, NippyJarError>>( )?;
+        KeyTable::create(&self.keysPath( ), entries.into_iter( ))?;
+      },
+
+      // The keys are read straight from the memory-mapped data file.
+      (KeyIndexKind::KeyIndex, Some(reader)) =>
+        KeyIndex::new(reader.rowCount( ), |row| reader.columnValue(row, keyColumn))?.save(&self.indexPath( ))?,
+
+      (KeyIndexKind::KeyIndex, None) => { }
+    }
+
+    Ok(( ))
+  }
+
+  // Returns the (start, end) rows (end exclusive) of the given row group.
+  pub(crate) fn rowGroupBounds(&self, rowGroup: usize) -> (usize, usize) {
+    let startRow= rowGroup * self.rowsPerChecksum;
+    (startRow, (startRow + self.rowsPerChecksum).min(self.rowCount))
+  }
+}
+
+// Replaces the file by a copy of its first len bytes.
+fn replaceWithPrefix(path: &Path, len: u64) -> Result<( ), NippyJarError> {
+  let temporaryPath= temporaryPathOf(path);
+
+  let mut temporaryFile= fs::File::create(&temporaryPath)?;
+  io::copy(&mut fs::File::open(path)?.take(len), &mut temporaryFile)?;
+  temporaryFile.sync_all( )?;
+
+  fs::rename(&temporaryPath, path)?;
+  Ok(( ))
+}
+
+// Returns the path where the given file is written to, before replacing it.
+fn temporaryPathOf(path: &Path) -> PathBuf {
+  let mut temporaryPath= path.as_os_str( ).to_owned( );
+  temporaryPath.push(".tmp");
+  temporaryPath.into( )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Write;
+  use super::*;
+  use crate::nippy_jar::NippyJarWriter;
+
+  fn row(i: usize) -> [Vec<u8>; 2] {
+    [format!("value {i}").into_bytes( ), vec![i as u8; 32]]
+  }
+
+  // Writes a NippyJar with 10 committed rows, in row groups of 4 rows.
+  fn writeJar(path: &Path) -> NippyJar {
+    let mut writer= NippyJarWriter::new(NippyJar::new(2, path, ( )).withRowsPerChecksum(4).withKeyColumn(1)).unwrap( );
+    for i in 0..10 {
+      writer.appendRow(row(i)).unwrap( );
+    }
+    writer.commit( ).unwrap( );
+
+    NippyJar::load(path).unwrap( )
+  }
+
+  fn appendBytes(path: &Path, bytes: &[u8]) {
+    OpenOptions::new( ).append(true).open(path).unwrap( ).write_all(bytes).unwrap( );
+  }
+
+  #[test]
+  fn tornTailIsDropped( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("jar");
+    let jar= writeJar(&path);
+
+    // Uncommitted rows, partially written when the node died.
+    appendBytes(jar.dataPath( ), &[1; 100]);
+    appendBytes(&jar.offsetsPath( ), &[0xFF; 5]);
+
+    let mut writer= NippyJarWriter::new(NippyJar::<( )>::load(&path).unwrap( )).unwrap( );
+    assert_eq!(*writer.consistencyReport( ), ConsistencyReport { dataBytesDropped: 100, offsetsBytesDropped: 5, ..Default::default( ) });
+
+    writer.appendRow(row(10)).unwrap( );
+    writer.commit( ).unwrap( );
+
+    let reader= NippyJarReader::<( )>::open(&path).unwrap( );
+    assert_eq!(reader.rowCount( ), 11);
+    for i in 0..11 {
+      assert_eq!(reader.row(i).unwrap( ), row(i));
+    }
+    assert!(reader.verifyChecksums( ).unwrap( ).is_empty( ));
+
+    // Nothing left to drop.
+    let mut jar= NippyJar::<( )>::load(&path).unwrap( );
+    assert!(jar.checkConsistency( ).unwrap( ).isClean( ));
+  }
+
+  #[test]
+  fn missingCommittedDataIsDropped( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("jar");
+    let mut jar= writeJar(&path);
+
+    // Cut in the middle of row 7.
+    let reader= NippyJarReader::<( )>::open(&path).unwrap( );
+    let row7Start= reader.offsetAt(7 * 2) as u64;
+    drop(reader);
+    OpenOptions::new( ).write(true).open(jar.dataPath( )).unwrap( ).set_len(row7Start + 3).unwrap( );
+
+    let report= jar.checkConsistency( ).unwrap( );
+    assert_eq!((report.rowsDropped, report.dataBytesDropped), (3, 3));
+    assert_eq!((jar.rowCount( ), jar.checksums( ).len( )), (7, 2));
+
+    let reader= NippyJarReader::<( )>::open(&path).unwrap( );
+    assert_eq!(reader.rowCount( ), 7);
+    assert_eq!(reader.rowByKey(&[6; 32]).unwrap( ), Some(6));
+    assert_eq!(reader.rowByKey(&[8; 32]).unwrap( ), None);
+    assert!(reader.verifyChecksums( ).unwrap( ).is_empty( ));
+  }
+
+  #[test]
+  fn corruptedRowGroupIsDroppedWithoutAffectingReaders( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("jar");
+    let mut jar= writeJar(&path);
+
+    let reader= NippyJarReader::<( )>::open(&path).unwrap( );
+
+    // Corrupt row 8, in the last row group.
+    let mut dataFile= OpenOptions::new( ).write(true).open(jar.dataPath( )).unwrap( );
+    dataFile.seek(SeekFrom::Start(reader.offsetAt(8 * 2) as u64)).unwrap( );
+    dataFile.write_all(b"X").unwrap( );
+
+    let report= jar.checkConsistency( ).unwrap( );
+    assert_eq!((report.rowsDropped, report.corruptedRowGroups.clone( )), (2, vec![2]));
+
+    // The reader opened before still has the replaced files memory-mapped.
+    assert_eq!(reader.row(9).unwrap( ), row(9));
+
+    let reader= NippyJarReader::<( )>::open(&path).unwrap( );
+    assert_eq!(reader.rowCount( ), 8);
+    assert_eq!(reader.rowByKey(&[7; 32]).unwrap( ), Some(7));
+    assert_eq!(reader.rowByKey(&[8; 32]).unwrap( ), None);
+  }
+
+  #[test]
+  fn rowGroupsBeforeTheLastOneAreOnlyVerifiedByReaders( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("jar");
+    let mut jar= writeJar(&path);
+
+    // Corrupt row 5, in the second row group.
+    let reader= NippyJarReader::<( )>::open(&path).unwrap( );
+    let mut dataFile= OpenOptions::new( ).write(true).open(jar.dataPath( )).unwrap( );
+    dataFile.seek(SeekFrom::Start(reader.offsetAt(5 * 2) as u64)).unwrap( );
+    dataFile.write_all(b"X").unwrap( );
+
+    assert!(jar.checkConsistency( ).unwrap( ).isClean( ));
+    assert_eq!(reader.verifyChecksums( ).unwrap( ), vec![1]);
+  }
+
+  #[test]
+  fn truncateRemovesTheLastRows( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("jar");
+    let mut jar= writeJar(&path);
+
+    let reader= NippyJarReader::<( )>::open(&path).unwrap( );
+    jar.truncate(6).unwrap( );
+
+    assert_eq!(reader.row(9).unwrap( ), row(9));
+
+    let reader= NippyJarReader::<( )>::open(&path).unwrap( );
+    assert_eq!((reader.rowCount( ), reader.jar( ).checksums( ).len( )), (6, 2));
+    assert!(reader.verifyChecksums( ).unwrap( ).is_empty( ));
+    assert_eq!(reader.rowByKey(&[5; 32]).unwrap( ), Some(5));
+    assert_eq!(reader.rowByKey(&[6; 32]).unwrap( ), None);
+
+    // Appending continues after the remaining rows.
+    let mut writer= NippyJarWriter::new(NippyJar::<( )>::load(&path).unwrap( )).unwrap( );
+    assert!(writer.consistencyReport( ).isClean( ));
+    writer.appendRow(row(20)).unwrap( );
+    writer.commit( ).unwrap( );
+
+    let reader= NippyJarReader::<( )>::open(&path).unwrap( );
+    assert_eq!((reader.rowCount( ), reader.row(6).unwrap( )), (7, row(20).map(Into::into).to_vec( )));
+    assert!(reader.verifyChecksums( ).unwrap( ).is_empty( ));
+
+    // Truncating everything.
+    let mut jar= NippyJar::<( )>::load(&path).unwrap( );
+    jar.truncate(0).unwrap( );
+    assert_eq!((jar.rowCount( ), fs::metadata(jar.dataPath( )).unwrap( ).len( )), (0, 0));
+  }
+}
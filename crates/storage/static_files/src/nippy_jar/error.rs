@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum NippyJarError {
+
+  #[error("NippyJar config not found at {0}")]
+  ConfigNotFound(PathBuf),
+
+  #[error("NippyJar format version {0} is not supported")]
+  UnsupportedVersion(usize),
+
+  #[error("Expected {expected} column values in the row, but got {got}")]
+  ColumnCountMismatch {
+    expected: usize,
+    got: usize
+  },
+
+  #[error("Row {row} is out of bounds (row count is {rowCount})")]
+  RowOutOfBounds {
+    row: usize,
+    rowCount: usize
+  },
+
+  #[error("Column {column} is out of bounds (column count is {columnCount})")]
+  ColumnOutOfBounds {
+    column: usize,
+    columnCount: usize
+  },
+
+  #[error("Offsets file starts with an invalid offset size")]
+  CorruptedOffsetsHeader,
+
+  #[error("Offsets file is inconsistent with the config : expected {expected} offsets, found {found}")]
+  OffsetsMismatch {
+    expected: usize,
+    found: usize
+  },
+
+  #[error("No zstd dictionary found for column {0}")]
+  DictionaryNotFound(usize),
+
+  #[error("No samples provided to train the zstd dictionary for column {0}")]
+  NotEnoughSamples(usize),
+
+  // zstd refuses to train a dictionary when the samples are too few / too small.
+  #[error("Failed to train the zstd dictionary for column {column} : {error}")]
+  DictionaryTrainingFailed {
+    column: usize,
+    error: std::io::Error
+  },
+
+  #[error("Dictionaries file is corrupted")]
+  CorruptedDictionaries,
+
+  #[error("Compressed column value is corrupted")]
+  CorruptedValue,
+
+  #[error("Value of {valueLen} bytes is too short to start with {fieldCount} u64 fields")]
+  InvalidDeltaFields {
+    fieldCount: usize,
+    valueLen: usize
+  },
+
+  #[error("Anchor of row {0} is unknown")]
+  UnknownAnchor(usize),
+
+  #[error("NippyJar doesn't have a key column")]
+  NoKeyColumn,
+
+  #[error("Failed to build the key index")]
+  IndexConstructionFailed,
+
+  #[error("Index file is corrupted")]
+  CorruptedIndex,
+
+  #[error(transparent)]
+  Lz4Decompression(#[from] lz4_flex::block::DecompressError),
+
+  #[error("Offsets of the value at row {row}, column {column} are corrupted")]
+  CorruptedOffsets {
+    row: usize,
+    column: usize
+  },
+
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+
+  #[error(transparent)]
+  Config(#[from] serde_json::Error)
+}
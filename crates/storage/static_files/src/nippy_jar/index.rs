@@ -0,0 +1,171 @@
+use std::{borrow::Cow, collections::HashSet, fs, io::Write, path::Path};
+use ph::fmph::{self, keyset::DynamicKeySet};
+use super::{xor_filter::{hashKey, XorFilter}, NippyJarError};
+
+/*
+  Maps values of a key column (e.g. block hashes / transaction hashes) to row numbers, without
+  going through the database.
+
+  (1) A minimal perfect hash function (MPHF) maps each of the n keys to a distinct slot in 0..n.
+      slotToRow then gives the row number for that slot. An MPHF takes only ~3 bits per key, since
+      it doesn't store the keys themselves.
+  (2) For a key which is not present, the MPHF returns an arbitrary slot. An xor filter rejects
+      (~99.6% of) such keys upfront. For the rest, the caller must compare the key with the key
+      column value of the returned row.
+
+  Everything is kept in memory, so a lookup takes O(1) time and the only disk read is for the row
+  itself.
+
+  Layout of the index file (<path>.idx) :
+
+    | key count (8 bytes) | slotToRow (8 bytes per key) | xor filter | MPHF |
+
+  All integers are little-endian.
+*/
+pub(crate) struct KeyIndex {
+  mphf: fmph::Function,
+  slotToRow: Vec<u64>,
+  filter: XorFilter
+}
+
+impl KeyIndex {
+  /*
+    Builds the index from the key column values of the given rows, given by keyAt. If a key is
+    present multiple times, it's mapped to the first row containing it.
+
+    Keys aren't collected, but read again (through keyAt) whenever needed, since there may be
+    hundreds of millions of them. Only their hashes are kept in memory, to find the duplicated keys.
+  */
+  pub(crate) fn new<'k, F>(rowCount: usize, keyAt: F) -> Result<Self, NippyJarError>
+    where
+      F: Fn(usize) -> Result<Cow<'k, [u8]>, NippyJarError>
+  {
+    let mut keyHashes= (0..rowCount).map(|row| Ok((hashKey(&keyAt(row)?), row)))
+                                    .collect::<Result<Vec<(u64, usize)>, NippyJarError>>( )?;
+    keyHashes.sort_unstable( );
+
+    // Keys with the same hash are compared, to tell duplicates apart from hash collisions. Rows
+    // with the same hash are sorted, so the first row containing a key comes first.
+    let mut duplicateRows= HashSet::new( );
+    for sameHashRows in keyHashes.chunk_by(|(keyHash, _), (otherKeyHash, _)| keyHash == otherKeyHash).filter(|rows| rows.len( ) > 1) {
+      for (i, (_, row)) in sameHashRows.iter( ).enumerate( ).skip(1) {
+        let key= keyAt(*row)?;
+        for (_, earlierRow) in &sameHashRows[..i] {
+          if !duplicateRows.contains(earlierRow) && keyAt(*earlierRow)? == key {
+            duplicateRows.insert(*row);
+            break
+          }
+        }
+      }
+    }
+
+    let mut uniqueKeyHashes: Vec<u64>= keyHashes.into_iter( ).map(|(keyHash, _)| keyHash).collect( );
+    uniqueKeyHashes.dedup( );
+    let filter= XorFilter::new(&uniqueKeyHashes).ok_or(NippyJarError::IndexConstructionFailed)?;
+    drop(uniqueKeyHashes);
+
+    // Every key has been read once already, so reading them again doesn't fail.
+    let uniqueRows= || (0..rowCount).filter(|row| !duplicateRows.contains(row));
+    let uniqueKeyCount= rowCount - duplicateRows.len( );
+    let mphf= fmph::Function::new(DynamicKeySet::with_len(|| uniqueRows( ).map(|row| keyAt(row).unwrap_or_default( )), uniqueKeyCount));
+
+    let mut slotToRow= vec![0u64; uniqueKeyCount];
+    for row in uniqueRows( ) {
+      let slot= mphf.get(keyAt(row)?.as_ref( )).ok_or(NippyJarError::IndexConstructionFailed)?;
+      slotToRow[slot as usize]= row as u64;
+    }
+
+    Ok(Self { mphf, slotToRow, filter })
+  }
+
+  // Returns the row which may contain the key. None means that the key is definitely absent.
+  pub(crate) fn candidateRow(&self, key: &[u8]) -> Option<usize> {
+    if !self.filter.contains(hashKey(key)) {
+      return None
+    }
+
+    let slot= self.mphf.get(key)? as usize;
+    self.slotToRow.get(slot).map(|row| *row as usize)
+  }
+
+  // Writes the index to a temporary file first, and then renames it.
+  pub(crate) fn save(&self, path: &Path) -> Result<( ), NippyJarError> {
+    let mut buffer= Vec::new( );
+
+    buffer.extend_from_slice(&(self.slotToRow.len( ) as u64).to_le_bytes( ));
+    for row in &self.slotToRow {
+      buffer.extend_from_slice(&row.to_le_bytes( ));
+    }
+    self.filter.toBytes(&mut buffer);
+    self.mphf.write(&mut buffer)?;
+
+    let temporaryPath= path.with_extension("idx.tmp");
+
+    let mut temporaryFile= fs::File::create(&temporaryPath)?;
+    temporaryFile.write_all(&buffer)?;
+    temporaryFile.sync_all( )?;
+
+    fs::rename(&temporaryPath, path)?;
+
+    Ok(( ))
+  }
+
+  pub(crate) fn load(path: &Path) -> Result<Self, NippyJarError> {
+    let buffer= fs::read(path)?;
+
+    if buffer.len( ) < 8 {
+      return Err(NippyJarError::CorruptedIndex)
+    }
+    let keyCount= u64::from_le_bytes(buffer[..8].try_into( ).unwrap( )) as usize;
+
+    let buffer= &buffer[8..];
+    let slotToRowLen= keyCount.checked_mul(8).filter(|slotToRowLen| *slotToRowLen <= buffer.len( ))
+                              .ok_or(NippyJarError::CorruptedIndex)?;
+    let slotToRow= buffer[..slotToRowLen].chunks_exact(8)
+                                         .map(|row| u64::from_le_bytes(row.try_into( ).unwrap( )))
+                                         .collect( );
+
+    let (filter, mut buffer)= XorFilter::fromBytes(&buffer[slotToRowLen..]).ok_or(NippyJarError::CorruptedIndex)?;
+    let mphf= fmph::Function::read(&mut buffer)?;
+
+    Ok(Self { mphf, slotToRow, filter })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn duplicatedKeysAreMappedToTheirFirstRow( ) {
+    let keys: Vec<Vec<u8>>= (0..1000u64).map(|row| (row % 700).to_le_bytes( ).to_vec( )).collect( );
+    let index= KeyIndex::new(keys.len( ), |row| Ok(Cow::Borrowed(&keys[row]))).unwrap( );
+
+    assert_eq!(index.slotToRow.len( ), 700);
+    for (row, key) in keys.iter( ).enumerate( ) {
+      assert_eq!(index.candidateRow(key), Some(row % 700));
+    }
+  }
+
+  #[test]
+  fn corruptedLengthsAreAnError( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("jar.idx");
+
+    let keys: Vec<Vec<u8>>= (0..100u64).map(|key| key.to_le_bytes( ).to_vec( )).collect( );
+    KeyIndex::new(keys.len( ), |row| Ok(Cow::Borrowed(&keys[row]))).unwrap( ).save(&path).unwrap( );
+    let index= fs::read(&path).unwrap( );
+    assert!(KeyIndex::load(&path).is_ok( ));
+
+    // A key count overflowing once multiplied by 8, and then a block length of the xor filter doing
+    // so once multiplied by 3, or being 0.
+    let blockLengthPosition= 8 + 100 * 8 + 8;
+    for (position, length) in [(0, u64::MAX / 4), (blockLengthPosition, u64::MAX / 2), (blockLengthPosition, 0)] {
+      let mut corruptedIndex= index.clone( );
+      corruptedIndex[position..(position + 8)].copy_from_slice(&length.to_le_bytes( ));
+      fs::write(&path, &corruptedIndex).unwrap( );
+
+      assert!(matches!(KeyIndex::load(&path), Err(NippyJarError::CorruptedIndex)));
+    }
+  }
+}
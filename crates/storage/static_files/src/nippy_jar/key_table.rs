@@ -0,0 +1,210 @@
+use std::{fs::{self, OpenOptions}, path::Path};
+use memmap2::{Mmap, MmapMut};
+use super::NippyJarError;
+
+/*
+  Maps values of the key column to row numbers, while the NippyJar is still being appended to. The
+  key index (see the index module) can only be built once all the keys are known, so it's built
+  when the NippyJar gets finished (see NippyJarWriter::finish), and replaces the key table.
+
+  The key table is an open addressing hash table with linear probing, in the key table file
+  (<path>.keys). It's a sequence of slots, each of them being :
+
+    | key hash (8 bytes) | row + 1 (8 bytes) |
+
+  A row of 0 marks an empty slot. The slot count is a power of 2, derived from the file length. All
+  integers are little-endian.
+
+  On every commit, the new keys are inserted in place, so a commit takes time proportional to the
+  rows appended since the previous one. Slots are only ever filled, never cleared, so readers which
+  have the file memory-mapped can keep using it meanwhile (a slot being filled was empty, hence it
+  isn't part of the probe sequence of any committed key). Once the table is half full, it's rebuilt
+  with twice as many slots, in a new file which replaces the current one.
+
+  Like with the key index, the caller must compare the key with the key column value of each
+  candidate row. Keys of rows which were never committed (e.g. when the node crashed in between)
+  may be left behind.
+*/
+pub(crate) struct KeyTable {
+  slots: MmapMut,
+  entryCount: usize
+}
+
+const SLOT_SIZE: usize= 16;
+
+const MIN_SLOT_COUNT: usize= 1024;
+
+impl KeyTable {
+  // Builds the key table from the key hashes of the given rows, and saves it in a new file, which
+  // replaces the current one (if any).
+  pub(crate) fn create<I>(path: &Path, entries: I) -> Result<Self, NippyJarError>
+    where
+      I: ExactSizeIterator<Item = (u64, usize)>
+  {
+    Self::createWithSlotCount(path, slotCountFor(entries.len( )), entries)
+  }
+
+  pub(crate) fn open(path: &Path) -> Result<Self, NippyJarError> {
+    let file= OpenOptions::new( ).read(true).write(true).open(path)?;
+
+    // SAFETY : The file is only modified through this mapping (see the module comment).
+    let slots= unsafe { MmapMut::map_mut(&file)? };
+    validate(&slots)?;
+
+    let entryCount= slots.chunks_exact(SLOT_SIZE).filter(|slot| rowOf(slot).is_some( )).count( );
+    Ok(Self { slots, entryCount })
+  }
+
+  // Inserts the key hashes of the given rows, and makes them durable.
+  pub(crate) fn insert(&mut self, path: &Path, entries: &[(u64, usize)]) -> Result<( ), NippyJarError> {
+    if (self.entryCount + entries.len( )) * 2 > self.slotCount( ) {
+      let existingEntries: Vec<(u64, usize)>= self.slots.chunks_exact(SLOT_SIZE)
+        .filter_map(|slot| Some((keyHashOf(slot), rowOf(slot)?)))
+        .collect( );
+
+      // Rows were inserted in order, so they are sorted again for the duplicate keys to keep
+      // resolving to the first row containing them.
+      let mut allEntries: Vec<(u64, usize)>= existingEntries.into_iter( ).chain(entries.iter( ).copied( )).collect( );
+      allEntries.sort_unstable_by_key(|(_, row)| *row);
+
+      *self= Self::createWithSlotCount(path, slotCountFor(allEntries.len( )), allEntries.into_iter( ))?;
+      return Ok(( ))
+    }
+
+    for (keyHash, row) in entries {
+      self.insertSlot(*keyHash, *row);
+    }
+    self.slots.flush( )?;
+
+    Ok(( ))
+  }
+
+  fn createWithSlotCount<I>(path: &Path, slotCount: usize, entries: I) -> Result<Self, NippyJarError>
+    where
+      I: Iterator<Item = (u64, usize)>
+  {
+    let temporaryPath= path.with_extension("keys.tmp");
+
+    let file= OpenOptions::new( ).read(true).write(true).create(true).truncate(true).open(&temporaryPath)?;
+    file.set_len((slotCount * SLOT_SIZE) as u64)?;
+
+    // SAFETY : Nobody else knows about the temporary file.
+    let mut keyTable= Self { slots: unsafe { MmapMut::map_mut(&file)? }, entryCount: 0 };
+    for (keyHash, row) in entries {
+      keyTable.insertSlot(keyHash, row);
+    }
+    keyTable.slots.flush( )?;
+
+    fs::rename(&temporaryPath, path)?;
+    Ok(keyTable)
+  }
+
+  fn insertSlot(&mut self, keyHash: u64, row: usize) {
+    let mask= self.slotCount( ) - 1;
+
+    let mut slot= keyHash as usize & mask;
+    while rowOf(&self.slots[(slot * SLOT_SIZE)..]).is_some( ) {
+      slot= (slot + 1) & mask;
+    }
+
+    let slotBytes= &mut self.slots[(slot * SLOT_SIZE)..((slot + 1) * SLOT_SIZE)];
+    slotBytes[..8].copy_from_slice(&keyHash.to_le_bytes( ));
+    slotBytes[8..].copy_from_slice(&(row as u64 + 1).to_le_bytes( ));
+
+    self.entryCount += 1;
+  }
+
+  fn slotCount(&self) -> usize {
+    self.slots.len( ) / SLOT_SIZE
+  }
+}
+
+// Read-only view of a key table file, for readers.
+pub(crate) struct KeyTableReader {
+  slots: Mmap
+}
+
+impl KeyTableReader {
+  pub(crate) fn open(path: &Path) -> Result<Self, NippyJarError> {
+    // SAFETY : See the module comment.
+    let slots= unsafe { Mmap::map(&fs::File::open(path)?)? };
+    validate(&slots)?;
+
+    Ok(Self { slots })
+  }
+
+  // Returns the rows which may contain the key with the given hash, in the order they were inserted.
+  pub(crate) fn candidateRows(&self, keyHash: u64) -> impl Iterator<Item = usize> + '_ {
+    let slotCount= self.slots.len( ) / SLOT_SIZE;
+    let firstSlot= keyHash as usize & (slotCount - 1);
+
+    (0..slotCount).map(move |probe| (firstSlot + probe) & (slotCount - 1))
+                  .map(|slot| &self.slots[(slot * SLOT_SIZE)..((slot + 1) * SLOT_SIZE)])
+                  .map_while(|slot| rowOf(slot).map(|row| (keyHashOf(slot), row)))
+                  .filter(move |(slotKeyHash, _)| *slotKeyHash == keyHash)
+                  .map(|(_, row)| row)
+  }
+}
+
+// At least twice as many slots as entries.
+fn slotCountFor(entryCount: usize) -> usize {
+  (entryCount * 2).next_power_of_two( ).max(MIN_SLOT_COUNT)
+}
+
+fn validate(slots: &[u8]) -> Result<( ), NippyJarError> {
+  let slotCount= slots.len( ) / SLOT_SIZE;
+  if !slots.len( ).is_multiple_of(SLOT_SIZE) || !slotCount.is_power_of_two( ) {
+    return Err(NippyJarError::CorruptedIndex)
+  }
+  Ok(( ))
+}
+
+fn keyHashOf(slot: &[u8]) -> u64 {
+  u64::from_le_bytes(slot[..8].try_into( ).unwrap( ))
+}
+
+fn rowOf(slot: &[u8]) -> Option<usize> {
+  match u64::from_le_bytes(slot[8..16].try_into( ).unwrap( )) {
+    0 => None,
+    rowPlusOne => Some((rowPlusOne - 1) as usize)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn insertedRowsAreFound( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("jar.keys");
+
+    // Key hashes 0..3000 (with hash 7 present twice), which end up in neighbouring slots.
+    let mut keyTable= KeyTable::create(&path, (0..10).map(|row| (row as u64, row))).unwrap( );
+    for rows in [10..600, 600..3000] {
+      let entries: Vec<(u64, usize)>= rows.map(|row| (row as u64, row)).collect( );
+      keyTable.insert(&path, &entries).unwrap( );
+    }
+    keyTable.insert(&path, &[(7, 3000)]).unwrap( );
+
+    // Grown twice, and at most half full.
+    assert_eq!((keyTable.entryCount, keyTable.slotCount( )), (3001, 8192));
+    assert_eq!(KeyTable::open(&path).unwrap( ).entryCount, 3001);
+
+    let reader= KeyTableReader::open(&path).unwrap( );
+    for row in 0..3000 {
+      let candidateRows: Vec<usize>= reader.candidateRows(row as u64).collect( );
+      assert_eq!(candidateRows, if row == 7 { vec![7, 3000] } else { vec![row] });
+    }
+    assert_eq!(reader.candidateRows(1 << 40).count( ), 0);
+  }
+
+  #[test]
+  fn corruptedKeyTableIsAnError( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("jar.keys");
+
+    fs::write(&path, [0; 3 * SLOT_SIZE]).unwrap( );
+    assert!(matches!(KeyTableReader::open(&path), Err(NippyJarError::CorruptedIndex)));
+  }
+}
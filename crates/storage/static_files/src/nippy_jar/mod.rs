@@ -1,4 +1,405 @@
+use std::{fs, io::Write, path::{Path, PathBuf}};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+mod error;
+mod compression;
+mod xor_filter;
+mod index;
+mod key_table;
+mod writer;
+mod reader;
+mod consistency;
+mod offsets;
+mod upgrade;
+
+pub use error::NippyJarError;
+pub use compression::{ColumnCompression, ColumnDictionaries, MAX_DICTIONARY_SIZE};
+pub use writer::NippyJarWriter;
+pub use reader::NippyJarReader;
+pub use consistency::ConsistencyReport;
+
 /*
+  NippyJar is the file format used for static files. Data is stored as rows, and each row is split
+  into a fixed number of columns (e.g. a block header and the block hash). Every column value is an
+  opaque byte array.
+
+  A NippyJar consists of up to 6 files :
+
+  (1) Data file (at the given path) : the column values, written one after the other, row by row.
+
+  (2) Offsets file (<path>.off) : offset of each column value in the data file (see the offsets
+      module for how they're encoded). The offset of the value at (row, column) is at index
+      (row * columnCount + column). The last offset is the length of the data file, so the length of
+      any value can be calculated by looking at the next offset.
+
+  (3) Config file (<path>.conf) : this struct, JSON encoded. It contains the format version, row
+      and column count, compression used by each column, checksums of the data, and a custom header
+      (e.g. the block range that the static file contains).
+
+  (4) Dictionaries file (<path>.dict) : zstd dictionaries trained for the columns using
+      ColumnCompression::ZstdWithDictionary (see the compression module). Only present if there's
+      any such column.
+
+  (5) Index file (<path>.idx) : maps values of the key column (if any) to row numbers, so that rows
+      can be looked up by e.g. block hash (see the index module). Built once the NippyJar is
+      finished (see NippyJarWriter::finish).
+
+  (6) Key table file (<path>.keys) : maps values of the key column to row numbers, while the
+      NippyJar is still being appended to (see the key_table module). Replaced by the index file
+      once the NippyJar is finished.
+
+  Rows are appended using a NippyJarWriter, and removed from the end using NippyJar::truncate. After
+  a crash, NippyJar::checkConsistency brings the files back to a consistent state (see the
+  consistency module). A NippyJarReader memory-maps the data and offsets files,
+  so that any column value can be fetched by (row, column) index with no more than 2 lookups.
 
+  Format versions : NippyJars of all the versions from MIN_NIPPY_JAR_VERSION up to
+  NIPPY_JAR_VERSION can be read and appended to (in their own version). New NippyJars get the
+  latest version, and older ones can be upgraded to it in place (see NippyJar::upgrade), so that
+  the history never needs to be synced again after a format change.
+
+  (1) version 1 : the initial format.
+  (2) version 2 : offsets take as few bytes as the length of the data file allows, instead of 8.
 */
-pub struct NippyJar { }
\ No newline at end of file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NippyJar<H = ( )> {
+  // Version of the file format.
+  version: usize,
+
+  // Custom header set by the user of the NippyJar.
+  userHeader: H,
+
+  columnCount: usize,
+  rowCount: usize,
+
+  // Compression used by each column.
+  columnCompressions: Vec<ColumnCompression>,
+
+  // Column whose values are indexed, so that rows can be looked up by them.
+  keyColumn: Option<usize>,
+
+  // Whether the values of the key column are mapped to rows by the key index or the key table.
+  // NippyJars written before key tables existed always have a key index.
+  #[serde(default)]
+  keyIndexKind: KeyIndexKind,
+
+  // Size of the largest row, in bytes.
+  maxRowSize: usize,
+
+  // Size of the largest row before compression, which bounds the decompressed length of its values.
+  // Unknown for NippyJars written before it was recorded.
+  #[serde(default)]
+  maxUncompressedRowSize: Option<usize>,
+
+  // CRC32 checksum of the data of each group of rowsPerChecksum rows. The last group may be
+  // partially filled.
+  rowsPerChecksum: usize,
+  checksums: Vec<u32>,
+
+  // Path of the data file.
+  #[serde(skip)]
+  path: PathBuf,
+
+  // Loaded from the dictionaries file.
+  #[serde(skip)]
+  dictionaries: ColumnDictionaries
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyIndexKind {
+  // Built once the NippyJar is finished (see the index module).
+  #[default]
+  KeyIndex,
+
+  // Updated on every commit, while the NippyJar is being appended to (see the key_table module).
+  KeyTable
+}
+
+pub const NIPPY_JAR_VERSION: usize= 2;
+
+// Oldest format version which can still be read.
+pub const MIN_NIPPY_JAR_VERSION: usize= 1;
+
+pub const DEFAULT_ROWS_PER_CHECKSUM: usize= 8192;
+
+impl<H> NippyJar<H>
+  where
+    H: Serialize + DeserializeOwned
+{
+  pub fn new(columnCount: usize, path: &Path, userHeader: H) -> Self {
+    Self {
+      version: NIPPY_JAR_VERSION,
+      userHeader,
+      columnCount,
+      rowCount: 0,
+      columnCompressions: vec![ColumnCompression::None; columnCount],
+      keyColumn: None,
+      keyIndexKind: KeyIndexKind::KeyTable,
+      maxRowSize: 0,
+      maxUncompressedRowSize: Some(0),
+      rowsPerChecksum: DEFAULT_ROWS_PER_CHECKSUM,
+      checksums: Vec::new( ),
+      path: path.to_path_buf( ),
+      dictionaries: ColumnDictionaries::new( )
+    }
+  }
+
+  // Sets the compression used by each column. Must be called before writing any row.
+  pub fn withColumnCompressions(mut self, columnCompressions: Vec<ColumnCompression>) -> Self {
+    self.columnCompressions= columnCompressions;
+    self
+  }
+
+  // Sets the number of rows covered by each checksum. Must be called before writing any row.
+  pub fn withRowsPerChecksum(mut self, rowsPerChecksum: usize) -> Self {
+    self.rowsPerChecksum= rowsPerChecksum.max(1);
+    self
+  }
+
+  // Makes the NippyJar build an index over the values of the given column. Must be called before
+  // writing any row.
+  pub fn withKeyColumn(mut self, keyColumn: usize) -> Self {
+    self.keyColumn= Some(keyColumn);
+    self
+  }
+
+  /*
+    Trains the dictionaries for the columns using ColumnCompression::ZstdWithDictionary and saves
+    them in the dictionaries file. Must be called before writing any row.
+
+    columnSamples contains a sample of values for each column (values for the other columns are
+    ignored, and can be left empty).
+  */
+  pub fn prepareCompression(&mut self, columnSamples: &[Vec<Vec<u8>>]) -> Result<( ), NippyJarError> {
+    let mut dictionaries= ColumnDictionaries::new( );
+
+    for (column, compression) in self.columnCompressions.iter( ).enumerate( ) {
+      if *compression != ColumnCompression::ZstdWithDictionary {
+        continue
+      }
+
+      let samples= columnSamples.get(column).filter(|samples| !samples.is_empty( ))
+                                            .ok_or(NippyJarError::NotEnoughSamples(column))?;
+      dictionaries.insert(column, compression::trainDictionary(column, samples)?);
+    }
+
+    compression::saveDictionaries(&self.dictionariesPath( ), &dictionaries)?;
+    self.dictionaries= dictionaries;
+
+    Ok(( ))
+  }
+
+  // Loads the config of the NippyJar whose data file is at the given path.
+  pub fn load(path: &Path) -> Result<Self, NippyJarError> {
+    let configPath= Self::configPathOf(path);
+    if !configPath.exists( ) {
+      return Err(NippyJarError::ConfigNotFound(configPath))
+    }
+
+    let mut jar: Self= serde_json::from_slice(&fs::read(&configPath)?)?;
+    if !(MIN_NIPPY_JAR_VERSION..=NIPPY_JAR_VERSION).contains(&jar.version) {
+      return Err(NippyJarError::UnsupportedVersion(jar.version))
+    }
+    jar.path= path.to_path_buf( );
+    jar.dictionaries= compression::loadDictionaries(&jar.dictionariesPath( ))?;
+
+    Ok(jar)
+  }
+
+  // Writes the config to a temporary file first, and then renames it. This way, a crash never
+  // leaves us with a partially written config.
+  pub(crate) fn saveConfig(&self) -> Result<( ), NippyJarError> {
+    let configPath= self.configPath( );
+    let temporaryConfigPath= configPath.with_extension("conf.tmp");
+
+    let mut temporaryConfigFile= fs::File::create(&temporaryConfigPath)?;
+    temporaryConfigFile.write_all(&serde_json::to_vec(self)?)?;
+    temporaryConfigFile.sync_all( )?;
+
+    fs::rename(&temporaryConfigPath, &configPath)?;
+
+    Ok(( ))
+  }
+
+  pub fn version(&self) -> usize {
+    self.version
+  }
+
+  pub fn userHeader(&self) -> &H {
+    &self.userHeader
+  }
+
+  // The user header gets persisted on the next truncate.
+  pub fn userHeaderMut(&mut self) -> &mut H {
+    &mut self.userHeader
+  }
+
+  pub fn columnCount(&self) -> usize {
+    self.columnCount
+  }
+
+  pub fn rowCount(&self) -> usize {
+    self.rowCount
+  }
+
+  pub fn columnCompressions(&self) -> &[ColumnCompression] {
+    &self.columnCompressions
+  }
+
+  pub fn keyColumn(&self) -> Option<usize> {
+    self.keyColumn
+  }
+
+  pub fn keyIndexKind(&self) -> KeyIndexKind {
+    self.keyIndexKind
+  }
+
+  pub fn maxRowSize(&self) -> usize {
+    self.maxRowSize
+  }
+
+  pub fn rowsPerChecksum(&self) -> usize {
+    self.rowsPerChecksum
+  }
+
+  pub fn checksums(&self) -> &[u32] {
+    &self.checksums
+  }
+
+  pub fn dataPath(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn offsetsPath(&self) -> PathBuf {
+    self.path.with_extension("off")
+  }
+
+  pub fn dictionariesPath(&self) -> PathBuf {
+    self.path.with_extension("dict")
+  }
+
+  pub fn indexPath(&self) -> PathBuf {
+    self.path.with_extension("idx")
+  }
+
+  pub fn keysPath(&self) -> PathBuf {
+    self.path.with_extension("keys")
+  }
+
+  pub fn configPath(&self) -> PathBuf {
+    Self::configPathOf(&self.path)
+  }
+
+  pub(crate) fn configPathOf(path: &Path) -> PathBuf {
+    path.with_extension("conf")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn row(i: usize) -> [Vec<u8>; 3] {
+    [format!("header {i}").repeat(i % 5 + 1).into_bytes( ), vec![i as u8; 32], (i as u64).to_le_bytes( ).to_vec( )]
+  }
+
+  #[test]
+  fn writtenRowsCanBeReadAndLookedUpByKey( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("headers");
+
+    let jar= NippyJar::new(3, &path, 7u64).withColumnCompressions(vec![ColumnCompression::Lz4, ColumnCompression::None, ColumnCompression::Zstd])
+                                          .withRowsPerChecksum(4)
+                                          .withKeyColumn(1);
+    let mut writer= NippyJarWriter::new(jar).unwrap( );
+    for i in 0..10 {
+      writer.appendRow(row(i)).unwrap( );
+    }
+    assert_eq!(writer.rowCount( ), 10);
+
+    // Nothing is visible before the commit.
+    assert!(matches!(NippyJarReader::<u64>::open(&path), Err(NippyJarError::ConfigNotFound(_))));
+    writer.commit( ).unwrap( );
+
+    // Append to the existing NippyJar.
+    let mut writer= NippyJarWriter::new(NippyJar::<u64>::load(&path).unwrap( )).unwrap( );
+    for i in 10..15 {
+      writer.appendRow(row(i)).unwrap( );
+    }
+    *writer.userHeaderMut( )= 8;
+    writer.commit( ).unwrap( );
+
+    let reader= NippyJarReader::<u64>::open(&path).unwrap( );
+    assert_eq!((reader.rowCount( ), *reader.jar( ).userHeader( ), reader.jar( ).checksums( ).len( )), (15, 8, 4));
+
+    for i in 0..15 {
+      assert_eq!(reader.row(i).unwrap( ), row(i));
+      assert_eq!(reader.rowByKey(&[i as u8; 32]).unwrap( ), Some(i));
+    }
+    assert_eq!(reader.rowByKey(&[99; 32]).unwrap( ), None);
+    assert!(reader.verifyChecksums( ).unwrap( ).is_empty( ));
+
+    assert!(matches!(reader.columnValue(15, 0), Err(NippyJarError::RowOutOfBounds { row: 15, rowCount: 15 })));
+    assert!(matches!(reader.columnValue(0, 3), Err(NippyJarError::ColumnOutOfBounds { column: 3, .. })));
+  }
+
+  #[test]
+  fn keyTableIsReplacedByTheKeyIndexOnFinish( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("transactions");
+
+    let assertKeysAreFound= |rowCount: usize, keyIndexKind: KeyIndexKind| {
+      let reader= NippyJarReader::<( )>::open(&path).unwrap( );
+      assert_eq!((reader.rowCount( ), reader.jar( ).keyIndexKind( )), (rowCount, keyIndexKind));
+      for i in 0..rowCount {
+        assert_eq!(reader.rowByKey(&[i as u8; 32]).unwrap( ), Some(i));
+      }
+      assert_eq!(reader.rowByKey(&[rowCount as u8; 32]).unwrap( ), None);
+    };
+
+    let mut writer= NippyJarWriter::new(NippyJar::new(3, &path, ( )).withKeyColumn(1)).unwrap( );
+    for i in 0..5 {
+      writer.appendRow(row(i)).unwrap( );
+      writer.commit( ).unwrap( );
+      assertKeysAreFound(i + 1, KeyIndexKind::KeyTable);
+    }
+
+    writer.finish( ).unwrap( );
+    assertKeysAreFound(5, KeyIndexKind::KeyIndex);
+    assert!(!writer.jar( ).keysPath( ).exists( ));
+
+    // Appending to a finished NippyJar brings the key table back.
+    let mut writer= NippyJarWriter::new(NippyJar::<( )>::load(&path).unwrap( )).unwrap( );
+    writer.appendRow(row(5)).unwrap( );
+    writer.commit( ).unwrap( );
+    assertKeysAreFound(6, KeyIndexKind::KeyTable);
+
+    // Truncating a finished NippyJar rebuilds its key index, without bringing the key table back.
+    writer.finish( ).unwrap( );
+    let mut jar= NippyJar::<( )>::load(&path).unwrap( );
+    jar.truncate(3).unwrap( );
+    assertKeysAreFound(3, KeyIndexKind::KeyIndex);
+  }
+
+  #[test]
+  fn nippyJarsWithoutRowsCanBeFinished( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("transactions");
+
+    let mut writer= NippyJarWriter::new(NippyJar::new(3, &path, ( )).withKeyColumn(1)).unwrap( );
+    writer.commit( ).unwrap( );
+    writer.finish( ).unwrap( );
+
+    let reader= NippyJarReader::<( )>::open(&path).unwrap( );
+    assert_eq!((reader.rowCount( ), reader.jar( ).keyIndexKind( )), (0, KeyIndexKind::KeyIndex));
+    assert_eq!(reader.rowByKey(&[0; 32]).unwrap( ), None);
+  }
+
+  #[test]
+  fn rowsMustHaveAValuePerColumn( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let mut writer= NippyJarWriter::new(NippyJar::new(2, &directory.path( ).join("jar"), ( ))).unwrap( );
+
+    assert!(matches!(writer.appendRow([[1u8]]), Err(NippyJarError::ColumnCountMismatch { expected: 2, got: 1 })));
+  }
+}
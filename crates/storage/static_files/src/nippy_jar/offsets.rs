@@ -0,0 +1,155 @@
+use std::{fs::{self, File}, io::{Read, Seek, SeekFrom, Write}, path::Path};
+use super::NippyJarError;
+
+/*
+  Layout of the offsets file (<path>.off), by format version of the NippyJar :
+
+  (1) version 1 : each offset as 8 little-endian bytes.
+  (2) version 2 : a byte holding the offset size, followed by each offset as that many little-endian
+      bytes. The offset size is the smallest one which can hold the length of the data file (so
+      4 bytes for a data file of less than 4 GiB, halving the offsets file). It grows along with the
+      data file, by rewriting the offsets file (see NippyJarWriter::commit).
+
+  The first offset is always 0. So a version 1 offsets file starts with a 0 byte, whereas a version
+  2 one starts with the (non-zero) offset size. Readers detect the layout that way, rather than
+  trusting the version in the config, which may not have been updated yet (the offsets file gets
+  replaced first, when a NippyJar is upgraded or its offsets get wider).
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct OffsetsLayout {
+  // Length of the header preceding the offsets.
+  headerLen: usize,
+
+  offsetSize: usize
+}
+
+impl OffsetsLayout {
+  pub(crate) const V1: Self= Self { headerLen: 0, offsetSize: 8 };
+
+  // Returns the version 2 layout, for a data file of the given length.
+  pub(crate) fn v2(dataLen: u64) -> Self {
+    let offsetSize= (u64::BITS - dataLen.leading_zeros( )).div_ceil(8).max(1);
+    Self { headerLen: 1, offsetSize: offsetSize as usize }
+  }
+
+  // Returns the layout of a new (empty) offsets file of a NippyJar with the given format version.
+  pub(crate) fn ofVersion(version: usize) -> Self {
+    match version {
+      1 => Self::V1,
+      _ => Self::v2(0)
+    }
+  }
+
+  // Detects the layout from the start of the offsets file (None if the offsets file is empty).
+  pub(crate) fn detect(offsetsFileStart: &[u8]) -> Result<Option<Self>, NippyJarError> {
+    match offsetsFileStart.first( ) {
+      None => Ok(None),
+      Some(0) => Ok(Some(Self::V1)),
+      Some(offsetSize @ 1..=8) => Ok(Some(Self { headerLen: 1, offsetSize: *offsetSize as usize })),
+      Some(_) => Err(NippyJarError::CorruptedOffsetsHeader)
+    }
+  }
+
+  // Detects the layout of the given offsets file, falling back to the one of the given format
+  // version if it's empty.
+  pub(crate) fn detectFromFile(offsetsFile: &mut File, version: usize) -> Result<Self, NippyJarError> {
+    let mut offsetsFileStart= Vec::with_capacity(1);
+    offsetsFile.seek(SeekFrom::Start(0))?;
+    Read::by_ref(offsetsFile).take(1).read_to_end(&mut offsetsFileStart)?;
+
+    Ok(Self::detect(&offsetsFileStart)?.unwrap_or(Self::ofVersion(version)))
+  }
+
+  // Format version of the NippyJar the layout belongs to.
+  pub(crate) fn version(&self) -> usize {
+    match self.headerLen {
+      0 => 1,
+      _ => 2
+    }
+  }
+
+  pub(crate) fn offsetSize(&self) -> usize {
+    self.offsetSize
+  }
+
+  pub(crate) fn header(&self) -> Vec<u8> {
+    match self.headerLen {
+      0 => Vec::new( ),
+      _ => vec![self.offsetSize as u8]
+    }
+  }
+
+  // Whether offsets can go up to the given data file length.
+  pub(crate) fn canHold(&self, dataLen: u64) -> bool {
+    self.offsetSize == 8 || dataLen < (1 << (8 * self.offsetSize))
+  }
+
+  // Returns the position of the offset with the given index, in the offsets file.
+  pub(crate) fn positionOf(&self, index: usize) -> u64 {
+    (self.headerLen + index * self.offsetSize) as u64
+  }
+
+  // Returns the length of an offsets file with the given number of offsets.
+  pub(crate) fn fileLen(&self, offsetCount: usize) -> u64 {
+    self.positionOf(offsetCount)
+  }
+
+  // Returns the number of (complete) offsets in an offsets file of the given length.
+  pub(crate) fn offsetCount(&self, fileLen: u64) -> usize {
+    (fileLen as usize).saturating_sub(self.headerLen) / self.offsetSize
+  }
+
+  pub(crate) fn encode(&self, offset: u64, buffer: &mut Vec<u8>) {
+    buffer.extend_from_slice(&offset.to_le_bytes( )[..self.offsetSize]);
+  }
+
+  // Decodes the offset with the given index, from the (whole) offsets file.
+  pub(crate) fn decode(&self, offsetsFile: &[u8], index: usize) -> u64 {
+    let position= self.positionOf(index) as usize;
+
+    let mut offsetAsBytes= [0u8; 8];
+    offsetAsBytes[..self.offsetSize].copy_from_slice(&offsetsFile[position..(position + self.offsetSize)]);
+    u64::from_le_bytes(offsetAsBytes)
+  }
+
+  // Reads at most maxOffsetCount offsets from the offsets file.
+  pub(crate) fn readOffsets(&self, offsetsFile: &mut File, maxOffsetCount: usize) -> Result<Vec<u64>, NippyJarError> {
+    self.readOffsetsFrom(offsetsFile, 0, maxOffsetCount)
+  }
+
+  // Reads at most maxOffsetCount offsets from the offsets file, starting with the one with the given
+  // index.
+  pub(crate) fn readOffsetsFrom(&self,
+                                offsetsFile: &mut File,
+                                firstIndex: usize,
+                                maxOffsetCount: usize) -> Result<Vec<u64>, NippyJarError>
+  {
+    let mut buffer= self.header( );
+    offsetsFile.seek(SeekFrom::Start(self.positionOf(firstIndex)))?;
+    Read::by_ref(offsetsFile).take((maxOffsetCount * self.offsetSize) as u64).read_to_end(&mut buffer)?;
+
+    let offsetCount= self.offsetCount(buffer.len( ) as u64);
+    Ok((0..offsetCount).map(|index| self.decode(&buffer, index)).collect( ))
+  }
+
+  /*
+    Writes a whole offsets file with the given offsets. It's written to a temporary file first,
+    which then replaces the offsets file. So a crash never leaves us with a partially written
+    offsets file, and readers which have the old one memory-mapped can keep using it.
+  */
+  pub(crate) fn writeOffsetsFile(&self, path: &Path, offsets: impl IntoIterator<Item = u64>) -> Result<( ), NippyJarError> {
+    let mut buffer= self.header( );
+    for offset in offsets {
+      self.encode(offset, &mut buffer);
+    }
+
+    let temporaryPath= path.with_extension("off.tmp");
+
+    let mut temporaryFile= File::create(&temporaryPath)?;
+    temporaryFile.write_all(&buffer)?;
+    temporaryFile.sync_all( )?;
+
+    fs::rename(&temporaryPath, path)?;
+    Ok(( ))
+  }
+}
@@ -0,0 +1,195 @@
+use std::{borrow::Cow, fs::File, io, path::Path};
+use memmap2::Mmap;
+use serde::{de::DeserializeOwned, Serialize};
+use super::{compression::ColumnDecompressor, index::KeyIndex, key_table::KeyTableReader, offsets::OffsetsLayout,
+            xor_filter::hashKey, KeyIndexKind, NippyJar, NippyJarError};
+
+// How many times opening a NippyJar is attempted, while it's being truncated.
+const MAX_OPEN_ATTEMPTS: usize= 3;
+
+// Reads rows from a NippyJar. The data and offsets files are memory-mapped, so that fetching a
+// column value doesn't require any syscall. Column values are transparently decompressed.
+pub struct NippyJarReader<H = ( )> {
+  jar: NippyJar<H>,
+
+  columnDecompressors: Vec<ColumnDecompressor>,
+
+  // Depending on the KeyIndexKind of the NippyJar.
+  keyIndex: Option<KeyIndex>,
+  keyTable: Option<KeyTableReader>,
+
+  data: Mmap,
+  offsets: Mmap,
+
+  // Detected from the offsets file itself, so files of any supported format version can be read.
+  offsetsLayout: OffsetsLayout
+}
+
+impl<H> NippyJarReader<H>
+  where
+    H: Serialize + DeserializeOwned
+{
+  /*
+    Opens the NippyJar whose data file is at the given path.
+
+    The config is loaded before the data and offsets files get memory-mapped. If the NippyJar gets
+    truncated in between (see NippyJar::truncate), the files no longer contain all the rows of the
+    config, and opening is retried (with the new config). Same if it gets finished in between, and
+    the key table is gone (see NippyJarWriter::finish).
+  */
+  pub fn open(path: &Path) -> Result<Self, NippyJarError> {
+    Self::openWithRetries(path, true)
+  }
+
+  // Opens the NippyJar, without its key index / key table. Used to rebuild them.
+  pub(crate) fn openWithoutKeys(path: &Path) -> Result<Self, NippyJarError> {
+    Self::openWithRetries(path, false)
+  }
+
+  fn openWithRetries(path: &Path, withKeys: bool) -> Result<Self, NippyJarError> {
+    let mut attempt= 1;
+    loop {
+      match Self::tryOpen(path, withKeys) {
+        Err(NippyJarError::OffsetsMismatch { .. } | NippyJarError::CorruptedOffsets { .. }) if attempt < MAX_OPEN_ATTEMPTS => attempt += 1,
+        Err(NippyJarError::Io(error)) if error.kind( ) == io::ErrorKind::NotFound && attempt < MAX_OPEN_ATTEMPTS => attempt += 1,
+        result => return result
+      }
+    }
+  }
+
+  fn tryOpen(path: &Path, withKeys: bool) -> Result<Self, NippyJarError> {
+    let jar= NippyJar::<H>::load(path)?;
+
+    // SAFETY : The bytes the reader looks at never change while being memory-mapped - rows are only
+    // appended after the committed ones, and truncation replaces the files (see NippyJar::truncate).
+    // Offsets files are replaced as well, when widened or upgraded. The only bytes overwritten in
+    // place are the last offset of the offsets file (see NippyJarWriter::commit), with the very
+    // same value : the data file length at the previous commit is where the next row starts.
+    let data= unsafe { Mmap::map(&File::open(jar.dataPath( ))?)? };
+    let offsets= unsafe { Mmap::map(&File::open(jar.offsetsPath( ))?)? };
+
+    let offsetsLayout= OffsetsLayout::detect(&offsets)?.unwrap_or(OffsetsLayout::ofVersion(jar.version));
+
+    let expectedOffsetCount= jar.rowCount * jar.columnCount + 1;
+    let offsetCount= offsetsLayout.offsetCount(offsets.len( ) as u64);
+    if offsetCount < expectedOffsetCount {
+      return Err(NippyJarError::OffsetsMismatch { expected: expectedOffsetCount, found: offsetCount })
+    }
+
+    // The last offset of the rows is where their data ends.
+    if offsetsLayout.decode(&offsets, expectedOffsetCount - 1) > data.len( ) as u64 {
+      return Err(NippyJarError::CorruptedOffsets { row: jar.rowCount, column: 0 })
+    }
+
+    let columnDecompressors= jar.columnCompressions.iter( ).enumerate( )
+      .map(|(column, compression)| ColumnDecompressor::new(column, *compression, &jar.dictionaries, jar.maxUncompressedRowSize))
+      .collect::<Result<Vec<_>, _>>( )?;
+
+    let (keyIndex, keyTable)= match (jar.keyColumn, jar.keyIndexKind) {
+      (Some(_), _) if !withKeys || jar.rowCount == 0 => (None, None),
+      (Some(_), KeyIndexKind::KeyIndex) => (Some(KeyIndex::load(&jar.indexPath( ))?), None),
+      (Some(_), KeyIndexKind::KeyTable) => (None, Some(KeyTableReader::open(&jar.keysPath( ))?)),
+      (None, _) => (None, None)
+    };
+
+    Ok(Self { jar, columnDecompressors, keyIndex, keyTable, data, offsets, offsetsLayout })
+  }
+
+  pub fn jar(&self) -> &NippyJar<H> {
+    &self.jar
+  }
+
+  pub fn rowCount(&self) -> usize {
+    self.jar.rowCount
+  }
+
+  // Returns the value of the given column in the given row.
+  pub fn columnValue(&self, row: usize, column: usize) -> Result<Cow<'_, [u8]>, NippyJarError> {
+    if row >= self.jar.rowCount {
+      return Err(NippyJarError::RowOutOfBounds { row, rowCount: self.jar.rowCount })
+    }
+    if column >= self.jar.columnCount {
+      return Err(NippyJarError::ColumnOutOfBounds { column, columnCount: self.jar.columnCount })
+    }
+
+    // Fields of Lz4WithDeltaFields columns are stored relative to the ones of their anchor row.
+    let anchorValue= match self.jar.columnCompressions[column].anchorRowOf(row) {
+      Some(anchorRow) if anchorRow != row => Some(self.storedValue(anchorRow, column)?),
+      _ => None
+    };
+
+    self.columnDecompressors[column].decompress(self.storedValue(row, column)?, anchorValue)
+  }
+
+  // Returns the values of all the columns in the given row.
+  pub fn row(&self, row: usize) -> Result<Vec<Cow<'_, [u8]>>, NippyJarError> {
+    (0..self.jar.columnCount).map(|column| self.columnValue(row, column))
+                             .collect( )
+  }
+
+  // Returns the row groups whose data doesn't match their checksums.
+  pub fn verifyChecksums(&self) -> Result<Vec<usize>, NippyJarError> {
+    let mut corruptedRowGroups= Vec::new( );
+
+    for (rowGroup, expectedChecksum) in self.jar.checksums.iter( ).enumerate( ) {
+      let (startRow, endRow)= self.jar.rowGroupBounds(rowGroup);
+
+      let start= self.offsetAt(startRow * self.jar.columnCount);
+      let end= self.offsetAt(endRow * self.jar.columnCount);
+      if start > end || end > self.data.len( ) {
+        return Err(NippyJarError::CorruptedOffsets { row: startRow, column: 0 })
+      }
+
+      if crc32fast::hash(&self.data[start..end]) != *expectedChecksum {
+        corruptedRowGroups.push(rowGroup);
+      }
+    }
+
+    Ok(corruptedRowGroups)
+  }
+
+  // Returns the row whose key column value is the given key, if any.
+  pub fn rowByKey(&self, key: &[u8]) -> Result<Option<usize>, NippyJarError> {
+    let keyColumn= self.jar.keyColumn.ok_or(NippyJarError::NoKeyColumn)?;
+
+    // The index / table may return a row for a key which is absent, or a row which was committed to
+    // it but not to the config (when the node crashed in between). So the key is verified.
+    let isRowOf= |row: usize| -> Result<bool, NippyJarError> {
+      Ok(row < self.jar.rowCount && *self.columnValue(row, keyColumn)? == *key)
+    };
+
+    if let Some(keyIndex)= &self.keyIndex {
+      return match keyIndex.candidateRow(key) {
+        Some(row) if isRowOf(row)? => Ok(Some(row)),
+        _ => Ok(None)
+      }
+    }
+
+    if let Some(keyTable)= &self.keyTable {
+      for row in keyTable.candidateRows(hashKey(key)) {
+        if isRowOf(row)? {
+          return Ok(Some(row))
+        }
+      }
+    }
+
+    Ok(None)
+  }
+
+  // Returns the value of the given column in the given row, as stored in the data file.
+  fn storedValue(&self, row: usize, column: usize) -> Result<&[u8], NippyJarError> {
+    let offsetIndex= row * self.jar.columnCount + column;
+    let (start, end)= (self.offsetAt(offsetIndex), self.offsetAt(offsetIndex + 1));
+
+    // Never panic because of a corrupted offsets file.
+    if start > end || end > self.data.len( ) {
+      return Err(NippyJarError::CorruptedOffsets { row, column })
+    }
+
+    Ok(&self.data[start..end])
+  }
+
+  pub(crate) fn offsetAt(&self, index: usize) -> usize {
+    self.offsetsLayout.decode(&self.offsets, index) as usize
+  }
+}
@@ -0,0 +1,121 @@
+use std::{fs, path::Path};
+use serde::{de::DeserializeOwned, Serialize};
+use super::{offsets::OffsetsLayout, NippyJar, NippyJarError, NIPPY_JAR_VERSION};
+
+impl<H> NippyJar<H>
+  where
+    H: Serialize + DeserializeOwned
+{
+  /*
+    Upgrades the NippyJar whose data file is at the given path to the latest format version, in
+    place. Returns whether it had to be upgraded.
+
+    Every file whose layout changes is written to a temporary file, fsynced, and then renamed over
+    the original one. The config (with the new version) is saved last. So :
+
+    (1) If the node dies in the middle of an upgrade, the NippyJar is still readable, and
+        checkConsistency completes the upgrade the next time it's opened for writing (readers
+        detect the layout of each file by themselves, regardless of the version in the config).
+
+    (2) Readers (of this or another process) can keep using the NippyJar while it's being upgraded.
+        It must not be appended to or truncated meanwhile though.
+
+    The data file is the same in all the format versions, so it's never rewritten.
+  */
+  pub fn upgrade(path: &Path) -> Result<bool, NippyJarError> {
+    let mut jar= Self::load(path)?;
+    if jar.version == NIPPY_JAR_VERSION {
+      return Ok(false)
+    }
+
+    // Leftovers of a crash would otherwise end up in the upgraded files.
+    jar.checkConsistency( )?;
+    if jar.version == NIPPY_JAR_VERSION {
+      return Ok(true)
+    }
+
+    // Version 1 -> 2 : offsets as small as the data file allows.
+    let mut offsetsFile= fs::File::open(jar.offsetsPath( ))?;
+    let offsetsLayout= OffsetsLayout::detectFromFile(&mut offsetsFile, jar.version)?;
+
+    let offsetCount= jar.rowCount * jar.columnCount + 1;
+    let offsets= offsetsLayout.readOffsets(&mut offsetsFile, offsetCount)?;
+    if offsets.len( ) != offsetCount {
+      return Err(NippyJarError::OffsetsMismatch { expected: offsetCount, found: offsets.len( ) })
+    }
+
+    let dataLen= offsets[offsetCount - 1];
+    OffsetsLayout::v2(dataLen).writeOffsetsFile(&jar.offsetsPath( ), offsets)?;
+
+    jar.version= NIPPY_JAR_VERSION;
+    jar.saveConfig( )?;
+
+    Ok(true)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::nippy_jar::{ColumnCompression, NippyJarReader, NippyJarWriter};
+
+  fn row(i: usize) -> [Vec<u8>; 2] {
+    [format!("value {i}").repeat(i % 3 + 1).into_bytes( ), vec![i as u8; 32]]
+  }
+
+  fn assertRows(reader: &NippyJarReader<u64>, rowCount: usize) {
+    assert_eq!(reader.rowCount( ), rowCount);
+    for i in 0..rowCount {
+      assert_eq!(reader.row(i).unwrap( ), row(i));
+      assert_eq!(reader.rowByKey(&[i as u8; 32]).unwrap( ), Some(i));
+    }
+  }
+
+  #[test]
+  fn version1NippyJarsAreUpgradedInPlace( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let path= directory.path( ).join("jar");
+
+    let jar= NippyJar::new(2, &path, 0u64).withColumnCompressions(vec![ColumnCompression::Lz4, ColumnCompression::None])
+                                          .withKeyColumn(1);
+    let mut writer= NippyJarWriter::new(jar).unwrap( );
+    for i in 0..20 {
+      writer.appendRow(row(i)).unwrap( );
+    }
+    writer.finish( ).unwrap( );
+    drop(writer);
+
+    // New NippyJars always get the latest version, so this one is rewritten the way version 1 did :
+    // 8 bytes per offset.
+    let mut jar= NippyJar::<u64>::load(&path).unwrap( );
+    let offsetsPath= jar.offsetsPath( );
+    let mut offsetsFile= fs::File::open(&offsetsPath).unwrap( );
+    let offsets= OffsetsLayout::detectFromFile(&mut offsetsFile, jar.version).unwrap( ).readOffsets(&mut offsetsFile, 41).unwrap( );
+    OffsetsLayout::V1.writeOffsetsFile(&offsetsPath, offsets).unwrap( );
+    jar.version= 1;
+    jar.saveConfig( ).unwrap( );
+    assert_eq!(fs::metadata(&offsetsPath).unwrap( ).len( ), 41 * 8);
+
+    let readerBeforeUpgrade= NippyJarReader::<u64>::open(&path).unwrap( );
+    assert_eq!(readerBeforeUpgrade.jar( ).version( ), 1);
+
+    assert!(NippyJar::<u64>::upgrade(&path).unwrap( ));
+    assert!(!NippyJar::<u64>::upgrade(&path).unwrap( ));
+
+    // The data file is less than 64 KiB long, so offsets take 2 bytes (after the offset size).
+    assert_eq!(NippyJar::<u64>::load(&path).unwrap( ).version( ), NIPPY_JAR_VERSION);
+    assert_eq!(fs::metadata(&offsetsPath).unwrap( ).len( ), 1 + 41 * 2);
+
+    // Readers opened before the upgrade keep working.
+    assertRows(&readerBeforeUpgrade, 20);
+    assertRows(&NippyJarReader::<u64>::open(&path).unwrap( ), 20);
+
+    // The upgraded NippyJar can be appended to.
+    let mut writer= NippyJarWriter::new(NippyJar::<u64>::load(&path).unwrap( )).unwrap( );
+    for i in 20..25 {
+      writer.appendRow(row(i)).unwrap( );
+    }
+    writer.commit( ).unwrap( );
+    assertRows(&NippyJarReader::<u64>::open(&path).unwrap( ), 25);
+  }
+}
@@ -0,0 +1,345 @@
+use std::{fs::{self, File, OpenOptions}, io::{BufWriter, Read, Seek, SeekFrom, Write}};
+use serde::{de::DeserializeOwned, Serialize};
+use super::{compression::ColumnCompressor, key_table::KeyTable, offsets::OffsetsLayout, xor_filter::hashKey, ConsistencyReport,
+            KeyIndexKind, NippyJar, NippyJarError, NippyJarReader, NIPPY_JAR_VERSION};
+
+/*
+  Appends rows to a NippyJar.
+
+  Rows appended are not visible to readers until commit is called. Commit first makes sure that the
+  data file is on disk, then writes the offsets and the key table, and finally the config (which has
+  the new row count and checksums). So whatever happens, the config never refers to rows that aren't
+  fully written. And whatever was written after the last commit, gets truncated the next time the
+  NippyJar is opened for writing (see NippyJar::checkConsistency).
+
+  Once no more rows are going to be appended, finish replaces the key table by the key index.
+*/
+pub struct NippyJarWriter<H = ( )> {
+  jar: NippyJar<H>,
+
+  columnCompressors: Vec<ColumnCompressor>,
+
+  dataFile: BufWriter<File>,
+  offsetsFile: File,
+  offsetsLayout: OffsetsLayout,
+
+  dataFileLen: u64,
+
+  // What NippyJar::checkConsistency dropped, when the NippyJar got opened.
+  consistencyReport: ConsistencyReport,
+
+  // Size of the column values appended through this writer, before and after compression.
+  uncompressedBytesAppended: u64,
+  compressedBytesAppended: u64,
+
+  // Opened on the first commit, if the NippyJar has a key column.
+  keyTable: Option<KeyTable>,
+
+  // Hashes of the key column values appended since the last commit, along with their rows.
+  uncommittedKeyHashes: Vec<(u64, usize)>,
+
+  // Checksum of the data of the last (partially filled) row group, so far.
+  rowGroupHasher: crc32fast::Hasher,
+
+  // Offsets of the column values appended since the last commit.
+  uncommittedOffsets: Vec<u64>,
+  uncommittedRowCount: usize
+}
+
+impl<H> NippyJarWriter<H>
+  where
+    H: Serialize + DeserializeOwned
+{
+  // Opens the NippyJar for appending. The data and offsets files are created if they don't exist.
+  pub fn new(mut jar: NippyJar<H>) -> Result<Self, NippyJarError> {
+    if jar.columnCompressions.len( ) != jar.columnCount {
+      return Err(NippyJarError::ColumnCountMismatch {
+        expected: jar.columnCount,
+        got: jar.columnCompressions.len( )
+      })
+    }
+
+    let mut columnCompressors= jar.columnCompressions.iter( ).enumerate( )
+      .map(|(column, compression)| ColumnCompressor::new(column, *compression, &jar.dictionaries))
+      .collect::<Result<Vec<_>, _>>( )?;
+
+    let isNewJar= !jar.dataPath( ).exists( ) || jar.rowCount == 0;
+    let consistencyReport= match isNewJar {
+      // Rows of a config without data files are gone.
+      true => {
+        jar.rowCount= 0;
+        ConsistencyReport::default( )
+      },
+      false => jar.checkConsistency( )?
+    };
+
+    if let Some(keyColumn)= jar.keyColumn.filter(|keyColumn| *keyColumn >= jar.columnCount) {
+      return Err(NippyJarError::ColumnOutOfBounds { column: keyColumn, columnCount: jar.columnCount })
+    }
+    // The consistency check may have truncated every row.
+    let reader= match jar.rowCount == 0 {
+      true => None,
+      false => Some(NippyJarReader::<H>::openWithoutKeys(jar.dataPath( ))?)
+    };
+
+    // Lz4WithDeltaFields columns continue from the anchor of the last row.
+    if let Some(reader)= &reader {
+      for (column, columnCompressor) in columnCompressors.iter_mut( ).enumerate( ) {
+        if let Some(anchorRow)= jar.columnCompressions[column].anchorRowOf(jar.rowCount) {
+          if anchorRow < jar.rowCount {
+            columnCompressor.setAnchor(&reader.columnValue(anchorRow, column)?)?;
+          }
+        }
+      }
+    }
+
+    let mut dataFile= OpenOptions::new( ).read(true).write(true).create(true)
+                                         .truncate(isNewJar)
+                                         .open(jar.dataPath( ))?;
+    let dataFileLen= dataFile.seek(SeekFrom::End(0))?;
+
+    let mut offsetsFile= OpenOptions::new( ).read(true).write(true).create(true)
+                                            .truncate(isNewJar)
+                                            .open(jar.offsetsPath( ))?;
+
+    // New NippyJars get the latest format version. Existing ones keep theirs, until upgraded.
+    let offsetsLayout= match isNewJar {
+      true => {
+        jar.version= NIPPY_JAR_VERSION;
+        jar.keyIndexKind= KeyIndexKind::KeyTable;
+        jar.checksums.clear( );
+
+        let offsetsLayout= OffsetsLayout::ofVersion(jar.version);
+        offsetsFile.write_all(&offsetsLayout.header( ))?;
+        offsetsLayout
+      },
+      false => OffsetsLayout::detectFromFile(&mut offsetsFile, jar.version)?
+    };
+
+    // Rehash the data of the last (partially filled) row group.
+    let mut rowGroupHasher= crc32fast::Hasher::new( );
+    if !jar.rowCount.is_multiple_of(jar.rowsPerChecksum) {
+      let rowGroupStartRow= (jar.rowCount / jar.rowsPerChecksum) * jar.rowsPerChecksum;
+
+      let mut rowGroupStartOffset= [0u8; 8];
+      offsetsFile.seek(SeekFrom::Start(offsetsLayout.positionOf(rowGroupStartRow * jar.columnCount)))?;
+      offsetsFile.read_exact(&mut rowGroupStartOffset[..offsetsLayout.offsetSize( )])?;
+
+      let mut rowGroupData= Vec::new( );
+      dataFile.seek(SeekFrom::Start(u64::from_le_bytes(rowGroupStartOffset)))?;
+      dataFile.read_to_end(&mut rowGroupData)?;
+
+      rowGroupHasher.update(&rowGroupData);
+    }
+
+    Ok(Self {
+      jar,
+      columnCompressors,
+      keyTable: None,
+      uncommittedKeyHashes: Vec::new( ),
+      rowGroupHasher,
+      dataFile: BufWriter::new(dataFile),
+      offsetsFile,
+      offsetsLayout,
+      dataFileLen,
+      consistencyReport,
+      uncompressedBytesAppended: 0,
+      compressedBytesAppended: 0,
+      uncommittedOffsets: Vec::new( ),
+      uncommittedRowCount: 0
+    })
+  }
+
+  pub fn jar(&self) -> &NippyJar<H> {
+    &self.jar
+  }
+
+  // Returns what had to be dropped from the NippyJar (e.g. the torn tail left by a crash) when it got
+  // opened.
+  pub fn consistencyReport(&self) -> &ConsistencyReport {
+    &self.consistencyReport
+  }
+
+  // The user header gets persisted on the next commit.
+  pub fn userHeaderMut(&mut self) -> &mut H {
+    &mut self.jar.userHeader
+  }
+
+  // Returns the row count, including the rows which haven't been committed yet.
+  pub fn rowCount(&self) -> usize {
+    self.jar.rowCount + self.uncommittedRowCount
+  }
+
+  // Returns the size of the column values appended through this writer (committed or not), before
+  // compression.
+  pub fn uncompressedBytesAppended(&self) -> u64 {
+    self.uncompressedBytesAppended
+  }
+
+  // Returns the size of the column values appended through this writer (committed or not), after
+  // compression.
+  pub fn compressedBytesAppended(&self) -> u64 {
+    self.compressedBytesAppended
+  }
+
+  // Appends a row, with a value for each column. Values get compressed as per the column
+  // compression.
+  pub fn appendRow<I, V>(&mut self, row: I) -> Result<( ), NippyJarError>
+    where
+      I: IntoIterator<Item = V>,
+      V: AsRef<[u8]>
+  {
+    let columnValues: Vec<V>= row.into_iter( ).collect( );
+    if columnValues.len( ) != self.jar.columnCount {
+      return Err(NippyJarError::ColumnCountMismatch {
+        expected: self.jar.columnCount,
+        got: columnValues.len( )
+      })
+    }
+
+    let row= self.rowCount( );
+    let (mut rowSize, mut uncompressedRowSize)= (0, 0);
+    for (columnValue, columnCompressor) in columnValues.iter( ).zip(&mut self.columnCompressors) {
+      uncompressedRowSize += columnValue.as_ref( ).len( );
+      self.uncompressedBytesAppended += columnValue.as_ref( ).len( ) as u64;
+      let columnValue= columnCompressor.compress(row, columnValue.as_ref( ))?;
+      self.compressedBytesAppended += columnValue.len( ) as u64;
+
+      self.uncommittedOffsets.push(self.dataFileLen);
+      self.dataFile.write_all(&columnValue)?;
+      self.rowGroupHasher.update(&columnValue);
+
+      self.dataFileLen += columnValue.len( ) as u64;
+      rowSize += columnValue.len( );
+    }
+
+    if let Some(keyColumn)= self.jar.keyColumn {
+      self.uncommittedKeyHashes.push((hashKey(columnValues[keyColumn].as_ref( )), row));
+    }
+
+    self.jar.maxRowSize= self.jar.maxRowSize.max(rowSize);
+    if let Some(maxUncompressedRowSize)= &mut self.jar.maxUncompressedRowSize {
+      *maxUncompressedRowSize= (*maxUncompressedRowSize).max(uncompressedRowSize);
+    }
+    self.uncommittedRowCount += 1;
+
+    // The row group got filled.
+    if self.rowCount( ).is_multiple_of(self.jar.rowsPerChecksum) {
+      let rowGroup= (self.rowCount( ) / self.jar.rowsPerChecksum) - 1;
+      let rowGroupHasher= std::mem::replace(&mut self.rowGroupHasher, crc32fast::Hasher::new( ));
+
+      self.jar.checksums.truncate(rowGroup);
+      self.jar.checksums.push(rowGroupHasher.finalize( ));
+    }
+
+    Ok(( ))
+  }
+
+  // Makes the appended rows durable and visible to readers.
+  pub fn commit(&mut self) -> Result<( ), NippyJarError> {
+    self.dataFile.flush( )?;
+    self.dataFile.get_ref( ).sync_all( )?;
+
+    let committedOffsetCount= self.jar.rowCount * self.jar.columnCount;
+    let newOffsets= self.uncommittedOffsets.iter( ).copied( ).chain(std::iter::once(self.dataFileLen));
+
+    if self.offsetsLayout.canHold(self.dataFileLen) {
+      // The last offset (length of the data file) written during the previous commit gets
+      // overwritten, by the offset of the first appended row : the same value, so readers which
+      // have the offsets file memory-mapped are unaffected.
+      self.offsetsFile.seek(SeekFrom::Start(self.offsetsLayout.positionOf(committedOffsetCount)))?;
+
+      let mut offsetsBuffer= Vec::with_capacity((self.uncommittedOffsets.len( ) + 1) * self.offsetsLayout.offsetSize( ));
+      for offset in newOffsets {
+        self.offsetsLayout.encode(offset, &mut offsetsBuffer);
+      }
+      self.offsetsFile.write_all(&offsetsBuffer)?;
+
+      let offsetsFileLen= self.offsetsLayout.fileLen(committedOffsetCount + self.uncommittedOffsets.len( ) + 1);
+      self.offsetsFile.set_len(offsetsFileLen)?;
+      self.offsetsFile.sync_all( )?;
+    }
+    else {
+      // The data file outgrew the offset size : the whole offsets file gets rewritten with wider
+      // offsets. It replaces the current one, since readers may have it memory-mapped.
+      let committedOffsets= self.offsetsLayout.readOffsets(&mut self.offsetsFile, committedOffsetCount)?;
+
+      let widerOffsetsLayout= OffsetsLayout::v2(self.dataFileLen);
+      widerOffsetsLayout.writeOffsetsFile(&self.jar.offsetsPath( ), committedOffsets.into_iter( ).chain(newOffsets))?;
+
+      self.offsetsFile= OpenOptions::new( ).read(true).write(true).open(self.jar.offsetsPath( ))?;
+      self.offsetsLayout= widerOffsetsLayout;
+    }
+
+    if !self.uncommittedKeyHashes.is_empty( ) {
+      self.insertUncommittedKeys( )?;
+    }
+
+    self.jar.rowCount += self.uncommittedRowCount;
+
+    // Checksum of the last (partially filled) row group.
+    if !self.jar.rowCount.is_multiple_of(self.jar.rowsPerChecksum) {
+      let rowGroup= self.jar.rowCount / self.jar.rowsPerChecksum;
+
+      self.jar.checksums.truncate(rowGroup);
+      self.jar.checksums.push(self.rowGroupHasher.clone( ).finalize( ));
+    }
+
+    self.jar.saveConfig( )?;
+
+    self.uncommittedOffsets.clear( );
+    self.uncommittedKeyHashes.clear( );
+    self.uncommittedRowCount= 0;
+
+    Ok(( ))
+  }
+
+  /*
+    Commits, and then builds the key index (if the NippyJar has a key column) out of all the keys,
+    replacing the key table. To be called once no more rows are going to be appended (e.g. the
+    static file contains its whole block range).
+
+    Rows can still be appended afterwards, but the next commit has to rebuild the key table.
+  */
+  pub fn finish(&mut self) -> Result<( ), NippyJarError> {
+    self.commit( )?;
+
+    if self.jar.keyColumn.is_none( ) || self.jar.keyIndexKind == KeyIndexKind::KeyIndex {
+      return Ok(( ))
+    }
+
+    // The key index is saved before the config, and the key table removed after it. So there's
+    // always a valid one for the config.
+    self.keyTable= None;
+    self.jar.keyIndexKind= KeyIndexKind::KeyIndex;
+    self.jar.rebuildKeyIndex( )?;
+    self.jar.saveConfig( )?;
+
+    // There's no key table if no row was ever committed.
+    if self.jar.keysPath( ).exists( ) {
+      fs::remove_file(self.jar.keysPath( ))?;
+    }
+    Ok(( ))
+  }
+
+  // Inserts the keys of the rows appended since the last commit into the key table. The key table
+  // gets (re)built from the committed rows first, if the NippyJar was finished or is new.
+  fn insertUncommittedKeys(&mut self) -> Result<( ), NippyJarError> {
+    let keysPath= self.jar.keysPath( );
+
+    if self.keyTable.is_none( ) {
+      let hasKeyTable= self.jar.keyIndexKind == KeyIndexKind::KeyTable && self.jar.rowCount > 0 && keysPath.exists( );
+
+      self.keyTable= Some(match hasKeyTable {
+        true => KeyTable::open(&keysPath)?,
+        false => {
+          self.jar.keyIndexKind= KeyIndexKind::KeyTable;
+          self.jar.rebuildKeyIndex( )?;
+          KeyTable::open(&keysPath)?
+        }
+      });
+    }
+
+    self.keyTable.as_mut( ).unwrap( ).insert(&keysPath, &self.uncommittedKeyHashes)
+  }
+}
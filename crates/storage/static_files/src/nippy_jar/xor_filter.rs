@@ -0,0 +1,146 @@
+/*
+  Xor filter (https://arxiv.org/abs/1912.08258) : a compact and immutable structure, which tells
+  whether a key is (probably) present in a set. With 8-bit fingerprints, it takes ~9.84 bits per key
+  and has a false positive rate of ~0.4%. It never gives false negatives.
+
+  Each key is mapped to 3 slots (one in each third of the fingerprints array). The filter is built
+  such that, for every key in the set, XOR of the fingerprints at those 3 slots equals the
+  fingerprint of the key.
+*/
+pub(crate) struct XorFilter {
+  seed: u64,
+  blockLength: usize,
+  fingerprints: Vec<u8>
+}
+
+// Number of seeds to try before giving up. Construction fails with a tiny probability for a given
+// seed, so this is only hit when the same key hash is present multiple times.
+const MAX_CONSTRUCTION_ATTEMPTS: u64= 100;
+
+impl XorFilter {
+  // Builds the filter from hashes of the keys (see hashKey). The hashes must be unique.
+  pub(crate) fn new(keyHashes: &[u64]) -> Option<Self> {
+    let capacity= (32 + (1.23 * keyHashes.len( ) as f64).ceil( ) as usize) / 3 * 3;
+    let blockLength= capacity / 3;
+
+    for seed in 0..MAX_CONSTRUCTION_ATTEMPTS {
+      // For each slot, XOR of the (seeded) hashes mapped to it and their count.
+      let mut slots= vec![(0u64, 0u32); capacity];
+      for keyHash in keyHashes {
+        let hash= mix(*keyHash, seed);
+        for slot in slotsOf(hash, blockLength) {
+          slots[slot].0 ^= hash;
+          slots[slot].1 += 1;
+        }
+      }
+
+      // Peel off slots which have a single hash mapped to them, until no slot is left.
+      let mut singleHashSlots: Vec<usize>= (0..capacity).filter(|slot| slots[*slot].1 == 1).collect( );
+      let mut peeledHashes= Vec::with_capacity(keyHashes.len( ));
+
+      while let Some(slot)= singleHashSlots.pop( ) {
+        if slots[slot].1 != 1 {
+          continue
+        }
+
+        let hash= slots[slot].0;
+        peeledHashes.push((hash, slot));
+
+        for otherSlot in slotsOf(hash, blockLength) {
+          slots[otherSlot].0 ^= hash;
+          slots[otherSlot].1 -= 1;
+
+          if slots[otherSlot].1 == 1 {
+            singleHashSlots.push(otherSlot);
+          }
+        }
+      }
+
+      if peeledHashes.len( ) != keyHashes.len( ) {
+        continue
+      }
+
+      // Assign fingerprints in the reverse order of peeling.
+      let mut fingerprints= vec![0u8; capacity];
+      for (hash, slot) in peeledHashes.into_iter( ).rev( ) {
+        let [slot0, slot1, slot2]= slotsOf(hash, blockLength);
+        fingerprints[slot]= 0;
+        fingerprints[slot]= fingerprint(hash) ^ fingerprints[slot0] ^ fingerprints[slot1] ^ fingerprints[slot2];
+      }
+
+      return Some(Self { seed, blockLength, fingerprints })
+    }
+
+    None
+  }
+
+  pub(crate) fn contains(&self, keyHash: u64) -> bool {
+    let hash= mix(keyHash, self.seed);
+    let [slot0, slot1, slot2]= slotsOf(hash, self.blockLength);
+
+    fingerprint(hash) == self.fingerprints[slot0] ^ self.fingerprints[slot1] ^ self.fingerprints[slot2]
+  }
+
+  // Layout : | seed (8 bytes) | block length (8 bytes) | fingerprints |
+  // All integers are little-endian.
+  pub(crate) fn toBytes(&self, buffer: &mut Vec<u8>) {
+    buffer.extend_from_slice(&self.seed.to_le_bytes( ));
+    buffer.extend_from_slice(&(self.blockLength as u64).to_le_bytes( ));
+    buffer.extend_from_slice(&self.fingerprints);
+  }
+
+  // Returns the filter and the remaining bytes.
+  pub(crate) fn fromBytes(buffer: &[u8]) -> Option<(Self, &[u8])> {
+    if buffer.len( ) < 16 {
+      return None
+    }
+    let seed= u64::from_le_bytes(buffer[..8].try_into( ).unwrap( ));
+    let blockLength= u64::from_le_bytes(buffer[8..16].try_into( ).unwrap( )) as usize;
+
+    // An empty filter would have no slot to map keys to.
+    let buffer= &buffer[16..];
+    let fingerprintsLen= blockLength.checked_mul(3).filter(|fingerprintsLen| (1..=buffer.len( )).contains(fingerprintsLen))?;
+    let fingerprints= buffer[..fingerprintsLen].to_vec( );
+
+    Some((Self { seed, blockLength, fingerprints }, &buffer[fingerprintsLen..]))
+  }
+}
+
+// Hashes a key (FNV-1a, followed by the MurmurHash3 finalizer). Unlike the hashers in std, the
+// output is guaranteed to never change, which is a must since the filter is persisted.
+pub(crate) fn hashKey(key: &[u8]) -> u64 {
+  let mut hash: u64= 0xcbf2_9ce4_8422_2325;
+  for byte in key {
+    hash ^= *byte as u64;
+    hash= hash.wrapping_mul(0x0000_0100_0000_01b3);
+  }
+
+  murmur64(hash)
+}
+
+fn mix(keyHash: u64, seed: u64) -> u64 {
+  murmur64(keyHash.wrapping_add(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15)))
+}
+
+fn murmur64(mut hash: u64) -> u64 {
+  hash ^= hash >> 33;
+  hash= hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+  hash ^= hash >> 33;
+  hash= hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+  hash ^ (hash >> 33)
+}
+
+fn fingerprint(hash: u64) -> u8 {
+  (hash ^ (hash >> 32)) as u8
+}
+
+// Returns the 3 slots (one in each block) which the hash maps to.
+fn slotsOf(hash: u64, blockLength: usize) -> [usize; 3] {
+  let reduce= |hash: u64| (((hash as u32) as u64 * blockLength as u64) >> 32) as usize;
+
+  [
+    reduce(hash),
+    reduce(hash.rotate_left(21)) + blockLength,
+    reduce(hash.rotate_left(42)) + 2 * blockLength
+  ]
+}
@@ -1,16 +1,322 @@
-use std::ops::RangeInclusive;
-use ethers_core::types::BlockNumber;
+use std::{fs, io, ops::RangeInclusive, path::Path};
+use db::{interfaces::{self, transaction::RoDbTx}, models::{BlockNumber, TxNumber}};
+use serde::{Deserialize, Serialize};
+use super::{
+  nippy_jar::{ColumnCompression, NippyJar, NippyJarError},
+  static_files_error::StaticFilesError,
+  static_files_generator_event::SegmentMetrics
+};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SegmentType {
   BlockHeaders,
   Transactions,
-  TransactionReceipts
+  TransactionReceipts,
+  Senders
 }
 
-pub trait Segment
+impl SegmentType {
+  pub fn asStr(&self) -> &'static str {
+    match self {
+      Self::BlockHeaders => "headers",
+      Self::Transactions => "transactions",
+      Self::TransactionReceipts => "receipts",
+      Self::Senders => "senders"
+    }
+  }
+
+  // Returns name of the static file containing this segment for the given block range.
+  pub fn fileName(&self, blockRange: &RangeInclusive<BlockNumber>) -> String {
+    format!("static_file_{}_{}_{}", self.asStr( ), blockRange.start( ), blockRange.end( ))
+  }
+
+  // Parses the name of a static file, returning the segment and the block range it contains. None
+  // is returned for any other file (e.g. the config or offsets file of a NippyJar).
+  pub fn parseFileName(fileName: &str) -> Option<(Self, RangeInclusive<BlockNumber>)> {
+    let mut parts= fileName.strip_prefix("static_file_")?.split('_');
+
+    let segmentType= match parts.next( )? {
+      "headers" => Self::BlockHeaders,
+      "transactions" => Self::Transactions,
+      "receipts" => Self::TransactionReceipts,
+      "senders" => Self::Senders,
+      _ => return None
+    };
+    let start= parts.next( )?.parse( ).ok( )?;
+    let end= parts.next( )?.parse( ).ok( )?;
+
+    if parts.next( ).is_some( ) || start > end {
+      return None
+    }
+    Some((segmentType, start..=end))
+  }
+
+  // Whether rows of this segment are numbered by transaction number (instead of block number).
+  pub fn isTxBased(&self) -> bool {
+    matches!(self, Self::Transactions | Self::TransactionReceipts | Self::Senders)
+  }
+}
+
+// All the segments. The block headers segment comes first.
+pub const SEGMENT_TYPES: [SegmentType; 4]= [
+  SegmentType::BlockHeaders,
+  SegmentType::Transactions,
+  SegmentType::TransactionReceipts,
+  SegmentType::Senders
+];
+
+// Default number of blocks in each static file, produced at the tip of the chain (see
+// StaticFilesWriter::withBlocksPerStaticFile).
+pub const DEFAULT_BLOCKS_PER_STATIC_FILE: u64= 500_000;
+
+// Custom header of the NippyJar containing a segment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentHeader {
+  pub segmentType: SegmentType,
+  // Block range the static file is meant for (its name is derived from it). Its size may differ
+  // across static files, even for the same segment.
+  pub blockRange: RangeInclusive<BlockNumber>,
+
+  // The last block the static file actually contains, if any. It's behind the end of blockRange
+  // while the static file is still being appended to, at the tip of the chain.
+  pub highestBlock: Option<BlockNumber>,
+
+  // Transaction numbers of the first and the last transaction in the block range. It's None for
+  // the block headers segment, or if there's no transaction in the block range.
+  pub txRange: Option<RangeInclusive<TxNumber>>
+}
+
+impl SegmentHeader {
+  // Returns the range of blocks the static file actually contains.
+  pub fn containedBlockRange(&self) -> Option<RangeInclusive<BlockNumber>> {
+    self.highestBlock.map(|highestBlock| *self.blockRange.start( )..=highestBlock)
+  }
+}
+
+pub trait Segment<Db>
   : Send + Sync
+  where
+    Db: interfaces::db::Db
 {
-  // Copies segment (within the given block-range) to static files.
+  fn segmentType(&self) -> SegmentType;
+
+  // Copies segment (within the given block-range) from the database to a static file in the given
+  // directory, returning metrics of the copy.
   fn copyToStaticFiles(&self,
-                       blockRange: RangeInclusive<BlockNumber>);
-}
\ No newline at end of file
+                       db: &Db,
+                       directory: &Path,
+                       blockRange: RangeInclusive<BlockNumber>) -> Result<SegmentMetrics, StaticFilesError>;
+
+  // Removes this segment (within the given block-range) from the database, once it has been copied
+  // to static files.
+  fn pruneDb(&self, db: &Db, blockRange: RangeInclusive<BlockNumber>) -> Result<( ), StaticFilesError>;
+
+  /*
+    Removes everything after the given block from the static files of this segment in the given
+    directory (e.g. when the chain gets rolled back). Static files starting after the block are
+    deleted. Without a block, all the static files of this segment are deleted.
+
+    Rows of the transaction based segments don't tell which block they belong to, so the block body
+    indices are looked up in the database.
+  */
+  fn truncateToBlock(&self,
+                     db: &Db,
+                     directory: &Path,
+                     blockNumber: Option<BlockNumber>) -> Result<( ), StaticFilesError>
+  {
+    let segmentType= self.segmentType( );
+
+    // Transaction number of the first transaction after the block.
+    let nextTxNumber= match (segmentType.isTxBased( ), blockNumber) {
+      (false, _) | (true, None) => 0,
+      (true, Some(blockNumber)) => {
+        let blockBodyIndices= db.withRoDbTx(|roDbTx| roDbTx.blockBodyIndices(blockNumber))??
+                                .ok_or(StaticFilesError::MissingFromDb { data: "block body indices", key: blockNumber })?;
+        blockBodyIndices.txNumbers( ).end
+      }
+    };
+
+    for entry in fs::read_dir(directory)? {
+      let fileName= entry?.file_name( );
+      match fileName.to_str( ).and_then(SegmentType::parseFileName) {
+        Some((fileSegmentType, blockRange)) if fileSegmentType == segmentType =>
+          truncateStaticFile(&directory.join(&fileName), &blockRange, blockNumber, nextTxNumber)?,
+
+        _ => continue
+      }
+    }
+
+    Ok(( ))
+  }
+}
+
+// Truncates the static file (containing the given block range) to the given block. It's removed if
+// it starts after the block, or if there's no block.
+fn truncateStaticFile(path: &Path,
+                      blockRange: &RangeInclusive<BlockNumber>,
+                      blockNumber: Option<BlockNumber>,
+                      nextTxNumber: TxNumber) -> Result<( ), StaticFilesError>
+{
+  let mut jar= match NippyJar::<SegmentHeader>::load(path) {
+    Ok(jar) => jar,
+
+    // The static file was never committed to, so there's nothing to truncate.
+    Err(NippyJarError::ConfigNotFound(_)) => return Ok(( )),
+
+    Err(error) => return Err(error.into( ))
+  };
+
+  // The config is removed first, so that the static file is ignored from then on, even if the node
+  // dies before the rest is removed.
+  let Some(blockNumber)= blockNumber.filter(|blockNumber| blockRange.start( ) <= blockNumber) else {
+    for path in [jar.configPath( ), jar.dataPath( ).to_path_buf( ), jar.offsetsPath( ), jar.dictionariesPath( ), jar.indexPath( ),
+                 jar.keysPath( )]
+    {
+      match fs::remove_file(&path) {
+        Err(error) if error.kind( ) != io::ErrorKind::NotFound => return Err(error.into( )),
+        _ => { }
+      }
+    }
+    return Ok(( ))
+  };
+
+  let segmentHeader= jar.userHeader( );
+  if segmentHeader.highestBlock.is_none_or(|highestBlock| highestBlock <= blockNumber) {
+    return Ok(( ))
+  }
+
+  let (rowCount, txRange)= match (segmentHeader.segmentType.isTxBased( ), &segmentHeader.txRange) {
+    (false, _) => ((blockNumber - blockRange.start( ) + 1) as usize, None),
+    (true, None) => (0, None),
+    (true, Some(txRange)) => {
+      let rowCount= nextTxNumber.saturating_sub(*txRange.start( ));
+      let txRange= (rowCount > 0).then(|| *txRange.start( )..=(nextTxNumber - 1));
+      (rowCount as usize, txRange)
+    }
+  };
+
+  let segmentHeader= jar.userHeaderMut( );
+  segmentHeader.highestBlock= Some(blockNumber);
+  segmentHeader.txRange= txRange;
+
+  Ok(jar.truncate(rowCount)?)
+}
+
+// Returns the transaction numbers of the first and the last transaction in the block range.
+pub(crate) fn txRangeOf<T>(roDbTx: &T,
+                           blockRange: &RangeInclusive<BlockNumber>) -> Result<Option<RangeInclusive<TxNumber>>, StaticFilesError>
+  where
+    T: RoDbTx
+{
+  let blockBodyIndicesOf= |blockNumber: BlockNumber|
+    roDbTx.blockBodyIndices(blockNumber)?
+          .ok_or(StaticFilesError::MissingFromDb { data: "block body indices", key: blockNumber });
+
+  let firstTxNumber= blockBodyIndicesOf(*blockRange.start( ))?.firstTxNumber;
+  let lastBlockBodyIndices= blockBodyIndicesOf(*blockRange.end( ))?;
+
+  let txRange= (lastBlockBodyIndices.firstTxNumber + lastBlockBodyIndices.txCount > firstTxNumber)
+                 .then(|| firstTxNumber..=(lastBlockBodyIndices.firstTxNumber + lastBlockBodyIndices.txCount - 1));
+  Ok(txRange)
+}
+
+// Number of column values used to train a zstd dictionary.
+pub(crate) const DICTIONARY_SAMPLE_COUNT: usize= 1000;
+
+/*
+  Makes the given column use ColumnCompression::ZstdWithDictionary, training the dictionary from the
+  given samples.
+
+  zstd refuses to train a dictionary when the samples are too few / too small (e.g. for a block
+  range with only a handful of transactions). In that case, plain ColumnCompression::Zstd is used.
+  Any other error (e.g. failing to save the dictionaries file) is returned.
+*/
+pub(crate) fn useDictionaryCompression(jar: NippyJar<SegmentHeader>,
+                                       column: usize,
+                                       samples: Vec<Vec<u8>>) -> Result<NippyJar<SegmentHeader>, StaticFilesError>
+{
+  let columnCompressions= |columnCompression: ColumnCompression| {
+    let mut columnCompressions= jar.columnCompressions( ).to_vec( );
+    columnCompressions[column]= columnCompression;
+    columnCompressions
+  };
+  let withDictionary= columnCompressions(ColumnCompression::ZstdWithDictionary);
+  let withoutDictionary= columnCompressions(ColumnCompression::Zstd);
+
+  let mut columnSamples= vec![Vec::new( ); jar.columnCount( )];
+  columnSamples[column]= samples;
+
+  let mut jar= jar.withColumnCompressions(withDictionary);
+  match jar.prepareCompression(&columnSamples) {
+    Ok(( )) => Ok(jar),
+    Err(NippyJarError::NotEnoughSamples(_) | NippyJarError::DictionaryTrainingFailed { .. }) =>
+      Ok(jar.withColumnCompressions(withoutDictionary)),
+    Err(error) => Err(error.into( ))
+  }
+}
+
+
+/*
+  Creates the (empty) NippyJar which will contain the given segment for the given block range.
+
+  Leftovers of a previous attempt at generating the same static file are removed first, otherwise
+  the rows would get appended to them.
+*/
+pub(crate) fn newSegmentJar(directory: &Path,
+                            columnCount: usize,
+                            segmentHeader: SegmentHeader) -> Result<NippyJar<SegmentHeader>, StaticFilesError>
+{
+  let path= directory.join(segmentHeader.segmentType.fileName(&segmentHeader.blockRange));
+  let jar= NippyJar::new(columnCount, &path, segmentHeader);
+
+  for leftoverPath in [jar.dataPath( ).to_path_buf( ), jar.offsetsPath( ), jar.configPath( ),
+                       jar.dictionariesPath( ), jar.indexPath( ), jar.keysPath( )]
+  {
+    match fs::remove_file(&leftoverPath) {
+      Err(error) if error.kind( ) != io::ErrorKind::NotFound =>
+        return Err(error.into( )),
+      _ => { }
+    }
+  }
+
+  Ok(jar)
+}
+
+// Returns (at most DICTIONARY_SAMPLE_COUNT) numbers evenly spread over the given range, to pick
+// the dictionary training samples from.
+pub(crate) fn sampleNumbersOf(range: &RangeInclusive<u64>) -> impl Iterator<Item = u64> {
+  let step= ((range.end( ) - range.start( ) + 1) / DICTIONARY_SAMPLE_COUNT as u64).max(1);
+  range.clone( ).step_by(step as usize).take(DICTIONARY_SAMPLE_COUNT)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn jarIn(directory: &Path) -> NippyJar<SegmentHeader> {
+    let segmentHeader= SegmentHeader {
+      segmentType: SegmentType::Transactions,
+      blockRange: 0..=99,
+      highestBlock: None,
+      txRange: None
+    };
+    NippyJar::new(2, &directory.join("static_file_transactions_0_99"), segmentHeader)
+  }
+
+  #[test]
+  fn dictionaryCompressionFallsBackToZstdOnlyWhenTrainingFails( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+
+    let jar= useDictionaryCompression(jarIn(directory.path( )), 1, vec![b"a".to_vec( ); 3]).unwrap( );
+    assert_eq!(jar.columnCompressions( ), [ColumnCompression::None, ColumnCompression::Zstd]);
+
+    let samples: Vec<Vec<u8>>= (0..DICTIONARY_SAMPLE_COUNT).map(|i| format!("transaction {i} from {}", i * 7919).into_bytes( )).collect( );
+    let jar= useDictionaryCompression(jarIn(directory.path( )), 1, samples.clone( )).unwrap( );
+    assert_eq!(jar.columnCompressions( ), [ColumnCompression::None, ColumnCompression::ZstdWithDictionary]);
+
+    // The dictionaries file can't be saved.
+    let missingDirectory= directory.path( ).join("missing");
+    assert!(matches!(useDictionaryCompression(jarIn(&missingDirectory), 1, samples),
+                     Err(StaticFilesError::NippyJar(NippyJarError::Io(_)))));
+  }
+}
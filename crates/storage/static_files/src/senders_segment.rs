@@ -0,0 +1,118 @@
+use std::{ops::RangeInclusive, path::Path, time::Instant};
+use db::{interfaces::{self, transaction::{DbTx, RoDbTx}}, models::BlockNumber};
+use super::{
+  nippy_jar::{ColumnCompression, NippyJar, NippyJarWriter},
+  segment::{newSegmentJar, txRangeOf, Segment, SegmentHeader, SegmentType},
+  static_files_error::StaticFilesError,
+  static_files_generator_event::SegmentMetrics
+};
+
+/*
+  Each row contains a single column : the address which signed the transaction (as recovered from
+  its signature), uncompressed - addresses barely compress, and recovering them again is costly.
+
+  The row number is the transaction number minus the first transaction number of the static file.
+*/
+pub struct SendersSegment { }
+
+pub const SENDER_COLUMN: usize= 0;
+
+// Creates the (empty) NippyJar which will contain the senders segment.
+pub(crate) fn newJar(directory: &Path, segmentHeader: SegmentHeader) -> Result<NippyJar<SegmentHeader>, StaticFilesError> {
+  Ok(newSegmentJar(directory, 1, segmentHeader)?.withColumnCompressions(vec![ColumnCompression::None]))
+}
+
+impl<Db> Segment<Db> for SendersSegment
+  where
+    Db: interfaces::db::Db
+{
+  fn segmentType(&self) -> SegmentType {
+    SegmentType::Senders
+  }
+
+  fn copyToStaticFiles(&self,
+                       db: &Db,
+                       directory: &Path,
+                       blockRange: RangeInclusive<BlockNumber>) -> Result<SegmentMetrics, StaticFilesError>
+  {
+    let startTime= Instant::now( );
+
+    db.withRoDbTx(|roDbTx| -> Result<SegmentMetrics, StaticFilesError> {
+      let txRange= txRangeOf(roDbTx, &blockRange)?;
+
+      let segmentHeader= SegmentHeader {
+        segmentType: SegmentType::Senders,
+        blockRange: blockRange.clone( ),
+        highestBlock: Some(*blockRange.end( )),
+        txRange: txRange.clone( )
+      };
+
+      let mut writer= NippyJarWriter::new(newJar(directory, segmentHeader)?)?;
+      for txNumber in txRange.into_iter( ).flatten( ) {
+        let sender= roDbTx.sender(txNumber)?
+                          .ok_or(StaticFilesError::MissingFromDb { data: "sender", key: txNumber })?;
+        writer.appendRow([sender.as_slice( )])?;
+      }
+      writer.finish( )?;
+
+      Ok(SegmentMetrics::of(&writer, startTime.elapsed( )))
+    })?
+  }
+
+  fn pruneDb(&self, db: &Db, blockRange: RangeInclusive<BlockNumber>) -> Result<( ), StaticFilesError> {
+    db.withDbTx(|dbTx| -> Result<( ), StaticFilesError> {
+      if let Some(txRange)= txRangeOf(dbTx, &blockRange)? {
+        dbTx.deleteSenders(txRange)?;
+      }
+      Ok(( ))
+    })?
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use alloy_primitives::{Address, U256};
+  use db::{models::Header, test_utils::{signedTransaction, MemoryDb}};
+  use crate::nippy_jar::NippyJarReader;
+  use super::*;
+
+  #[test]
+  fn sendersAreCopiedToStaticFilesAndPrunedFromTheDb( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= MemoryDb::default( );
+
+    // Block n has n % 3 transactions.
+    let mut nextTxNumber= 0;
+    for blockNumber in 0..10 {
+      let (transactions, senders): (Vec<_>, Vec<_>)= (0..(blockNumber % 3)).map(|nonce| signedTransaction(blockNumber, nonce)).unzip( );
+      db.insertBlock(&Header { number: blockNumber, ..Default::default( ) }, U256::ZERO, nextTxNumber, &transactions, &[ ], &senders);
+      nextTxNumber += transactions.len( ) as u64;
+    }
+    let senders: Vec<Address>= db.tables( ).senders.values( ).copied( ).collect( );
+
+    // Blocks 4..=7 contain transactions 3..=6.
+    let metrics= SendersSegment { }.copyToStaticFiles(&db, directory.path( ), 4..=7).unwrap( );
+    assert_eq!(metrics.rowCount, 4);
+
+    let reader= NippyJarReader::<SegmentHeader>::open(&directory.path( ).join(SegmentType::Senders.fileName(&(4..=7)))).unwrap( );
+    assert_eq!(reader.jar( ).userHeader( ).txRange, Some(3..=6));
+    assert_eq!(reader.jar( ).userHeader( ).highestBlock, Some(7));
+    for row in 0..4 {
+      assert_eq!(&*reader.columnValue(row, SENDER_COLUMN).unwrap( ), senders[3 + row].as_slice( ));
+    }
+
+    Segment::<MemoryDb>::pruneDb(&SendersSegment { }, &db, 4..=7).unwrap( );
+    let remainingTxNumbers: Vec<u64>= db.tables( ).senders.keys( ).copied( ).collect( );
+    assert_eq!(remainingTxNumbers, vec![0, 1, 2, 7, 8]);
+
+    // A block range without transactions.
+    let metrics= SendersSegment { }.copyToStaticFiles(&db, directory.path( ), 3..=3).unwrap( );
+    assert_eq!(metrics.rowCount, 0);
+    let reader= NippyJarReader::<SegmentHeader>::open(&directory.path( ).join(SegmentType::Senders.fileName(&(3..=3)))).unwrap( );
+    assert_eq!(reader.jar( ).userHeader( ).txRange, None);
+
+    // The senders were already pruned.
+    assert!(matches!(SendersSegment { }.copyToStaticFiles(&db, directory.path( ), 4..=4),
+                     Err(StaticFilesError::MissingFromDb { data: "sender", key: 3 })));
+  }
+}
@@ -0,0 +1,52 @@
+use compression::serde_bridge::SerdeBridgeError;
+use db::interfaces::db::DbError;
+use super::nippy_jar::NippyJarError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StaticFilesError {
+
+  #[error("{data} for {key} not found in the database")]
+  MissingFromDb {
+    data: &'static str,
+    key: u64
+  },
+
+  #[error("Expected block {expected} to be appended next, but got block {got}")]
+  UnexpectedBlock {
+    expected: u64,
+    got: u64
+  },
+
+  #[error("Got {receipts} receipts for {transactions} transactions")]
+  ReceiptCountMismatch {
+    transactions: usize,
+    receipts: usize
+  },
+
+  #[error("Got {senders} senders for {transactions} transactions")]
+  SenderCountMismatch {
+    transactions: usize,
+    senders: usize
+  },
+
+  #[error("Appending or committing failed midway, the static files writer must be reopened")]
+  WriterPoisoned,
+
+  #[error("Static file {0} is corrupted")]
+  CorruptedStaticFile(String),
+
+  #[error(transparent)]
+  NippyJar(#[from] NippyJarError),
+
+  #[error(transparent)]
+  Db(#[from] DbError),
+
+  #[error(transparent)]
+  Encoding(#[from] SerdeBridgeError),
+
+  #[error(transparent)]
+  Json(#[from] serde_json::Error),
+
+  #[error(transparent)]
+  Io(#[from] std::io::Error)
+}
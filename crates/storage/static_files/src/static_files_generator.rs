@@ -1,16 +1,23 @@
-use std::{ops::RangeInclusive, time::Instant};
-use ethers_core::types::BlockNumber;
+use std::{fs, ops::RangeInclusive, sync::{Arc, Mutex}, time::Instant};
+use db::{interfaces, models::BlockNumber};
+use tokio::sync::mpsc::UnboundedReceiver;
 use utils::event_emitters::EventEmitters;
-use db::interfaces;
 use super::{
   static_files_generator_event::StaticFilesGeneratorEvent,
   static_files_handler::StaticFilesHander,
-  block_headers_segment::BlockHeadersSegment, segment::Segment
+  static_files_manifest::StaticFilesManifest,
+  block_headers_segment::BlockHeadersSegment,
+  transactions_segment::TransactionsSegment,
+  transaction_receipts_segment::TransactionReceiptsSegment,
+  senders_segment::SendersSegment,
+  segment::Segment,
+  static_files_error::StaticFilesError
 };
 use rayon::prelude::*;
 
 pub struct StaticFilesGenerator<Db> {
-  staticFilesHandler: StaticFilesHander,
+  db: Arc<Db>,
+  staticFilesHandler: Arc<StaticFilesHander>,
   eventEmitters: EventEmitters<StaticFilesGeneratorEvent>
 }
 
@@ -18,41 +25,280 @@ impl<Db> StaticFilesGenerator<Db>
   where
     Db: interfaces::db::Db
 {
-  pub fn run(&mut self, blockRangeOfSegments: BlockRangeOfSegments) {
-    self.eventEmitters.emit(StaticFilesGeneratorEvent::Started { blockRangeOfSegments });
+  pub fn new(db: Arc<Db>, staticFilesHandler: Arc<StaticFilesHander>) -> Self {
+    Self {
+      db,
+      staticFilesHandler,
+      eventEmitters: EventEmitters::default( )
+    }
+  }
+
+  // Returns a receiver, through which StaticFilesGeneratorEvents will be received.
+  pub fn subscribe(&mut self) -> UnboundedReceiver<StaticFilesGeneratorEvent> {
+    self.eventEmitters.subscribe( )
+  }
+
+  /*
+    Copies each segment (within its block range) from the database to static files. The segments
+    are copied in parallel.
+
+    Events are emitted as each segment starts, finishes (along with its metrics) or fails. If any
+    segment fails, the others are still copied, and the first error is returned. If the generated
+    static files can't be made visible afterwards, every segment is reported as failed.
+  */
+  pub fn run(&mut self, blockRangeOfSegments: BlockRangeOfSegments) -> Result<( ), StaticFilesError> {
+    self.eventEmitters.emit(StaticFilesGeneratorEvent::Started {
+      blockRangeOfSegments: blockRangeOfSegments.clone( )
+    });
 
     let generatorStartTime= Instant::now( );
 
-    let mut segments= Vec::<(Box<dyn Segment>, RangeInclusive<BlockNumber>)>::new( );
+    let segments= segmentsOf::<Db>(&blockRangeOfSegments);
+
+    let directory= self.staticFilesHandler.directory( );
+    if let Err(error)= fs::create_dir_all(directory) {
+      return Err(self.segmentsFailed(&segments, error.into( )))
+    }
 
-    if let Some(blockRange)= blockRangeOfSegments.headers {
-      segments.push((Box::new(BlockHeadersSegment { }), blockRange));}
-    if let Some(blockRange)= blockRangeOfSegments.transactions {
-      segments.push((Box::new(BlockHeadersSegment { }), blockRange));}
-    if let Some(blockRange)= blockRangeOfSegments.transactionReceipts {
-      segments.push((Box::new(BlockHeadersSegment { }), blockRange));}
+    // Shared by the threads copying the segments.
+    let eventEmitters= Mutex::new(&mut self.eventEmitters);
+    let emit= |event| eventEmitters.lock( ).unwrap( ).emit(event);
 
-    segments.par_iter( ).try_for_each(
-      |(segment, blockRange)| -> Result<( ), ( )> {
+    let results: Vec<Result<( ), StaticFilesError>>= segments.par_iter( ).map(
+      |(segment, blockRange)| {
+        let segmentType= segment.segmentType( );
+        emit(StaticFilesGeneratorEvent::SegmentStarted { segmentType, blockRange: blockRange.clone( ) });
 
-        // TODO: Track time taken for static files generation for each specific segment as metrics.
+        match segment.copyToStaticFiles(&self.db, directory, blockRange.clone( )) {
+          Ok(metrics) => {
+            emit(StaticFilesGeneratorEvent::SegmentFinished { segmentType, blockRange: blockRange.clone( ), metrics });
+            Ok(( ))
+          },
 
-        Ok(( ))
+          Err(error) => {
+            emit(StaticFilesGeneratorEvent::SegmentFailed { segmentType, blockRange: blockRange.clone( ), error: error.to_string( ) });
+            Err(error)
+          }
+        }
       }
-    );
+    ).collect( );
+    results.into_iter( ).collect::<Result<( ), _>>( )?;
 
-    unimplemented!( );
+    // Make the generated static files visible to the readers, and list them in the manifest.
+    let madeVisible= self.staticFilesHandler.rescan( )
+                       .and_then(|_| StaticFilesManifest::update(&self.staticFilesHandler));
+    if let Err(error)= madeVisible {
+      return Err(self.segmentsFailed(&segments, error))
+    }
 
     self.eventEmitters.emit(StaticFilesGeneratorEvent::Finished {
       blockRangeOfSegments,
       timeTaken: generatorStartTime.elapsed( )
     });
+
+    Ok(( ))
+  }
+
+  /*
+    Removes each segment (within its block range) from the database. Must only be called once the
+    segments have been copied to static files (see run), since reads of the pruned rows are served
+    from static files from then on.
+
+    Block body indices aren't pruned, since they aren't copied to static files. A segment failing to
+    be pruned is reported as failed, and the segments after it aren't pruned.
+  */
+  pub fn pruneDb(&mut self, blockRangeOfSegments: &BlockRangeOfSegments) -> Result<( ), StaticFilesError> {
+    for (segment, blockRange) in segmentsOf::<Db>(blockRangeOfSegments) {
+      if let Err(error)= segment.pruneDb(&self.db, blockRange.clone( )) {
+        return Err(self.segmentsFailed(&[(segment, blockRange)], error))
+      }
+    }
+
+    Ok(( ))
+  }
+
+  // Reports each of the segments as failed, and returns the error back.
+  fn segmentsFailed(&mut self,
+                    segments: &[SegmentAndBlockRange<Db>],
+                    error: StaticFilesError) -> StaticFilesError
+  {
+    for (segment, blockRange) in segments {
+      self.eventEmitters.emit(StaticFilesGeneratorEvent::SegmentFailed {
+        segmentType: segment.segmentType( ),
+        blockRange: blockRange.clone( ),
+        error: error.to_string( )
+      });
+    }
+
+    error
   }
 }
 
+type SegmentAndBlockRange<Db>= (Box<dyn Segment<Db>>, RangeInclusive<BlockNumber>);
+
+fn segmentsOf<Db>(blockRangeOfSegments: &BlockRangeOfSegments) -> Vec<SegmentAndBlockRange<Db>>
+  where
+    Db: interfaces::db::Db
+{
+  let mut segments= Vec::<SegmentAndBlockRange<Db>>::new( );
+
+  if let Some(blockRange)= blockRangeOfSegments.headers.clone( ) {
+    segments.push((Box::new(BlockHeadersSegment { }), blockRange));}
+  if let Some(blockRange)= blockRangeOfSegments.transactions.clone( ) {
+    segments.push((Box::new(TransactionsSegment { }), blockRange));}
+  if let Some(blockRange)= blockRangeOfSegments.transactionReceipts.clone( ) {
+    segments.push((Box::new(TransactionReceiptsSegment { }), blockRange));}
+  if let Some(blockRange)= blockRangeOfSegments.senders.clone( ) {
+    segments.push((Box::new(SendersSegment { }), blockRange));}
+
+  segments
+}
+
 // Specifies the block range for each segment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct BlockRangeOfSegments {
-  headers: Option<RangeInclusive<BlockNumber>>,
-  transactions: Option<RangeInclusive<BlockNumber>>,
-  transactionReceipts: Option<RangeInclusive<BlockNumber>>
-}
\ No newline at end of file
+  pub headers: Option<RangeInclusive<BlockNumber>>,
+  pub transactions: Option<RangeInclusive<BlockNumber>>,
+  pub transactionReceipts: Option<RangeInclusive<BlockNumber>>,
+  pub senders: Option<RangeInclusive<BlockNumber>>
+}
+
+impl BlockRangeOfSegments {
+  // Whether there's nothing to copy, for any segment.
+  pub fn isEmpty(&self) -> bool {
+    self.headers.is_none( ) && self.transactions.is_none( ) && self.transactionReceipts.is_none( ) &&
+    self.senders.is_none( )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use alloy_primitives::{Address, U256};
+  use db::{
+    models::{Header, Receipt, TransactionSigned},
+    test_utils::{signedTransaction, MemoryDb}
+  };
+  use crate::{segment::SegmentType, static_files_manifest::MANIFEST_FILE_NAME};
+  use super::*;
+
+  // Block n has n % 3 transactions.
+  fn block(blockNumber: BlockNumber) -> (Header, Vec<TransactionSigned>, Vec<Receipt>, Vec<Address>) {
+    let header= Header { number: blockNumber, gasUsed: blockNumber * 1000, ..Default::default( ) };
+    let (transactions, senders): (Vec<_>, Vec<_>)= (0..(blockNumber % 3)).map(|nonce| signedTransaction(blockNumber, nonce)).unzip( );
+    let receipts= (0..transactions.len( ) as u64).map(|i| Receipt { cumulativeGasUsed: (i + 1) * 21_000, ..Default::default( ) })
+                                                .collect( );
+
+    (header, transactions, receipts, senders)
+  }
+
+  // Blocks 0..=7 in the database, containing transactions 0..=6.
+  fn dbWithBlocks( ) -> MemoryDb {
+    let db= MemoryDb::default( );
+    let mut nextTxNumber= 0;
+    for blockNumber in 0..8 {
+      let (header, transactions, receipts, senders)= block(blockNumber);
+      db.insertBlock(&header, U256::from(blockNumber), nextTxNumber, &transactions, &receipts, &senders);
+      nextTxNumber += transactions.len( ) as u64;
+    }
+    db
+  }
+
+  fn allSegments(blockRange: RangeInclusive<BlockNumber>) -> BlockRangeOfSegments {
+    BlockRangeOfSegments {
+      headers: Some(blockRange.clone( )),
+      transactions: Some(blockRange.clone( )),
+      transactionReceipts: Some(blockRange.clone( )),
+      senders: Some(blockRange)
+    }
+  }
+
+  fn eventsOf(events: &mut UnboundedReceiver<StaticFilesGeneratorEvent>) -> Vec<StaticFilesGeneratorEvent> {
+    std::iter::from_fn(|| events.try_recv( ).ok( )).collect( )
+  }
+
+  #[test]
+  fn everySegmentIsCopiedToStaticFilesAndPrunedFromTheDb( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= Arc::new(dbWithBlocks( ));
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    let mut staticFilesGenerator= StaticFilesGenerator::new(db.clone( ), staticFilesHandler.clone( ));
+    let mut events= staticFilesGenerator.subscribe( );
+    staticFilesGenerator.run(allSegments(0..=7)).unwrap( );
+
+    let mut txNumber= 0;
+    for blockNumber in 0..8 {
+      let (header, transactions, receipts, senders)= block(blockNumber);
+      assert_eq!(staticFilesHandler.headerByNumber(blockNumber).unwrap( ), Some(header.clone( )));
+      assert_eq!(staticFilesHandler.blockHash(blockNumber).unwrap( ), Some(header.hash( )));
+      assert_eq!(staticFilesHandler.totalDifficulty(blockNumber).unwrap( ), Some(U256::from(blockNumber)));
+
+      for i in 0..transactions.len( ) {
+        assert_eq!(staticFilesHandler.transactionById(txNumber).unwrap( ), Some(transactions[i].clone( )));
+        assert_eq!(staticFilesHandler.receiptById(txNumber).unwrap( ), Some(receipts[i].clone( )));
+        assert_eq!(staticFilesHandler.senderById(txNumber).unwrap( ), Some(senders[i]));
+        txNumber += 1;
+      }
+    }
+    assert_eq!(txNumber, 7);
+    assert!(StaticFilesManifest::load(&directory.path( ).join(MANIFEST_FILE_NAME)).is_ok( ));
+
+    let events= eventsOf(&mut events);
+    assert!(matches!(events.first( ), Some(StaticFilesGeneratorEvent::Started { .. })));
+    assert!(matches!(events.last( ), Some(StaticFilesGeneratorEvent::Finished { .. })));
+    let finishedSegments: Vec<(SegmentType, usize)>= events.iter( )
+      .filter_map(|event| match event {
+        StaticFilesGeneratorEvent::SegmentFinished { segmentType, metrics, .. } => Some((*segmentType, metrics.rowCount)),
+        _ => None
+      })
+      .collect( );
+    assert_eq!(finishedSegments.len( ), 4);
+    for (segmentType, rowCount) in finishedSegments {
+      let expectedRowCount= match segmentType.isTxBased( ) {
+        true => 7,
+        false => 8
+      };
+      assert_eq!(rowCount, expectedRowCount, "{:?}", segmentType);
+    }
+
+    // Block body indices are kept, since they aren't copied to static files.
+    staticFilesGenerator.pruneDb(&allSegments(0..=7)).unwrap( );
+    let tables= db.tables( );
+    assert!(tables.headers.is_empty( ) && tables.totalDifficulties.is_empty( ) && tables.blockHashes.is_empty( ));
+    assert!(tables.transactions.is_empty( ) && tables.receipts.is_empty( ) && tables.senders.is_empty( ));
+    assert_eq!(tables.blockBodyIndices.len( ), 8);
+  }
+
+  #[test]
+  fn failingSegmentsDontStopTheOthersAndTheFirstErrorIsReturned( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= Arc::new(dbWithBlocks( ));
+    db.tables( ).receipts.remove(&3);
+    db.tables( ).senders.remove(&5);
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    let mut staticFilesGenerator= StaticFilesGenerator::new(db, staticFilesHandler.clone( ));
+    let mut events= staticFilesGenerator.subscribe( );
+
+    // Receipts come before senders.
+    assert!(matches!(staticFilesGenerator.run(allSegments(0..=7)),
+                     Err(StaticFilesError::MissingFromDb { data: "receipt", key: 3 })));
+
+    let events= eventsOf(&mut events);
+    let mut failedSegments: Vec<SegmentType>= events.iter( )
+      .filter_map(|event| match event {
+        StaticFilesGeneratorEvent::SegmentFailed { segmentType, .. } => Some(*segmentType),
+        _ => None
+      })
+      .collect( );
+    failedSegments.sort_by_key(|segmentType| segmentType.asStr( ));
+    assert_eq!(failedSegments, vec![SegmentType::TransactionReceipts, SegmentType::Senders]);
+    assert!(!events.iter( ).any(|event| matches!(event, StaticFilesGeneratorEvent::Finished { .. })));
+
+    // The other segments were copied anyway, but aren't visible until the next rescan.
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::BlockHeaders), None);
+    staticFilesHandler.rescan( ).unwrap( );
+    assert_eq!(staticFilesHandler.headerByNumber(7).unwrap( ), Some(block(7).0));
+    assert_eq!(staticFilesHandler.transactionById(6).unwrap( ), Some(block(7).1[0].clone( )));
+  }
+}
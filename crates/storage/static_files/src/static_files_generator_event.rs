@@ -1,15 +1,101 @@
-use std::time::Duration;
-use super::static_files_generator::BlockRangeOfSegments;
+use std::{ops::RangeInclusive, time::Duration};
+use db::models::BlockNumber;
+use super::{
+  nippy_jar::NippyJarWriter,
+  segment::{SegmentHeader, SegmentType},
+  static_files_generator::BlockRangeOfSegments
+};
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub enum StaticFilesGeneratorEvent {
 
   Started {
     blockRangeOfSegments: BlockRangeOfSegments
   },
 
+  // Emitted right before a segment starts getting copied to static files.
+  SegmentStarted {
+    segmentType: SegmentType,
+    blockRange: RangeInclusive<BlockNumber>
+  },
+
+  // Emitted once a segment has been copied to static files.
+  SegmentFinished {
+    segmentType: SegmentType,
+    blockRange: RangeInclusive<BlockNumber>,
+    metrics: SegmentMetrics
+  },
+
+  // Emitted when copying a segment to static files (or pruning it from the database) fails. The
+  // generator run (or pruning) fails as well.
+  SegmentFailed {
+    segmentType: SegmentType,
+    blockRange: RangeInclusive<BlockNumber>,
+    error: String
+  },
+
   Finished {
     blockRangeOfSegments: BlockRangeOfSegments,
     timeTaken: Duration
   }
-}
\ No newline at end of file
+}
+
+// Metrics of copying a segment (within a block range) to static files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SegmentMetrics {
+  pub rowCount: usize,
+
+  // Size of the column values written, before and after compression, in bytes.
+  pub uncompressedSize: u64,
+  pub compressedSize: u64,
+
+  pub timeTaken: Duration
+}
+
+impl SegmentMetrics {
+  pub(crate) fn of(writer: &NippyJarWriter<SegmentHeader>, timeTaken: Duration) -> Self {
+    Self {
+      rowCount: writer.rowCount( ),
+      uncompressedSize: writer.uncompressedBytesAppended( ),
+      compressedSize: writer.compressedBytesAppended( ),
+      timeTaken
+    }
+  }
+
+  // The rates below are 0 when there's nothing to divide by (e.g. nothing got copied).
+
+  pub fn rowsPerSecond(&self) -> f64 {
+    ratioOf(self.rowCount as f64, self.timeTaken.as_secs_f64( ))
+  }
+
+  // Throughput, in (uncompressed) bytes per second.
+  pub fn bytesPerSecond(&self) -> f64 {
+    ratioOf(self.uncompressedSize as f64, self.timeTaken.as_secs_f64( ))
+  }
+
+  // How many times smaller the values got after compression.
+  pub fn compressionRatio(&self) -> f64 {
+    ratioOf(self.uncompressedSize as f64, self.compressedSize as f64)
+  }
+}
+
+fn ratioOf(numerator: f64, denominator: f64) -> f64 {
+  match denominator == 0.0 {
+    true => 0.0,
+    false => numerator / denominator
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn ratesAreZeroWithoutADivisor( ) {
+    let metrics= SegmentMetrics { rowCount: 4, uncompressedSize: 100, compressedSize: 0, timeTaken: Duration::ZERO };
+    assert_eq!((metrics.rowsPerSecond( ), metrics.bytesPerSecond( ), metrics.compressionRatio( )), (0.0, 0.0, 0.0));
+
+    let metrics= SegmentMetrics { compressedSize: 25, timeTaken: Duration::from_secs(2), ..metrics };
+    assert_eq!((metrics.rowsPerSecond( ), metrics.bytesPerSecond( ), metrics.compressionRatio( )), (2.0, 50.0, 4.0));
+  }
+}
@@ -1 +1,565 @@
-pub struct StaticFilesHander { }
\ No newline at end of file
+use std::{
+  collections::{BTreeMap, HashMap},
+  fs, io,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex, RwLock},
+  time::SystemTime
+};
+use alloy_primitives::{Address, U256};
+use db::models::{BlockHash, BlockNumber, Header, Receipt, TransactionSigned, TxHash, TxNumber};
+use tokio::sync::mpsc::UnboundedReceiver;
+use utils::event_emitters::EventEmitters;
+use super::{
+  block_headers_segment::{headerAt, BLOCK_HASH_COLUMN, TOTAL_DIFFICULTY_COLUMN},
+  nippy_jar::{NippyJar, NippyJarError, NippyJarReader},
+  segment::{SegmentHeader, SegmentType},
+  static_files_error::StaticFilesError,
+  static_files_handler_event::StaticFilesHandlerEvent,
+  transaction_receipts_segment::RECEIPT_COLUMN,
+  senders_segment::SENDER_COLUMN,
+  transactions_segment::{TRANSACTION_COLUMN, TX_HASH_COLUMN}
+};
+
+type StaticFileReader= NippyJarReader<SegmentHeader>;
+
+/*
+  Serves reads from the static files in a directory.
+
+  The static files are discovered when the handler is created (and whenever rescan is called). For
+  each segment, the handler knows which static file contains a given block number (or transaction
+  number, for the segments whose rows are transactions), just by looking at the SegmentHeader of
+  each static file.
+
+  Static files are opened (memory-mapped) lazily, on the first read from them, and the readers are
+  cached.
+
+  The discovered static files and their readers form a StaticFileSet, which a rescan replaces as a
+  whole. Each read works on the StaticFileSet current when it started, so it never sees a mix of the
+  old and new static files, and a rescan never waits for reads in progress (they keep the mmaps of
+  the old StaticFileSet alive until they're done).
+
+  Subscribers get notified of every rescan (see subscribe). Static files written by another process
+  can be picked up by calling rescanIfChanged periodically (see StaticFilesWatcher).
+*/
+pub struct StaticFilesHander {
+  // Directory containing the static files.
+  directory: PathBuf,
+
+  staticFiles: RwLock<Arc<StaticFileSet>>,
+
+  // Fingerprint of the directory, as of the last rescan. Also makes sure rescans don't run
+  // concurrently, so that an older StaticFileSet never replaces a newer one.
+  lastFingerprint: Mutex<DirectoryFingerprint>,
+
+  eventEmitters: Mutex<EventEmitters<StaticFilesHandlerEvent>>
+}
+
+#[derive(Default)]
+struct StaticFileSet {
+  // For each segment, headers of the static files containing it, keyed by the last block number of
+  // the static file.
+  byBlockNumber: HashMap<SegmentType, BTreeMap<BlockNumber, SegmentHeader>>,
+
+  // For each transaction based segment, headers of the static files containing it (and containing
+  // any transaction), keyed by the last transaction number of the static file.
+  byTxNumber: HashMap<SegmentType, BTreeMap<TxNumber, SegmentHeader>>,
+
+  // Readers of the static files opened so far, keyed by static file name.
+  readers: RwLock<HashMap<String, Arc<StaticFileReader>>>
+}
+
+/*
+  Changes whenever a static file is committed to or truncated : for each static file, the length
+  of its offsets file (which grows on every commit and shrinks on every truncation), and the
+  length and modification time of its config (which gets rewritten on both).
+*/
+type DirectoryFingerprint= BTreeMap<String, (Option<u64>, Option<(u64, SystemTime)>)>;
+
+impl StaticFilesHander {
+  // Creates the directory (if it doesn't exist), and discovers the static files in it.
+  pub fn new(directory: PathBuf) -> Result<Self, StaticFilesError> {
+    fs::create_dir_all(&directory)?;
+
+    let staticFilesHandler= Self {
+      directory,
+      staticFiles: RwLock::default( ),
+      lastFingerprint: Mutex::default( ),
+      eventEmitters: Mutex::default( )
+    };
+    staticFilesHandler.rescan( )?;
+
+    Ok(staticFilesHandler)
+  }
+
+  pub fn directory(&self) -> &Path {
+    &self.directory
+  }
+
+  // Returns a receiver, through which StaticFilesHandlerEvents will be received.
+  pub fn subscribe(&self) -> UnboundedReceiver<StaticFilesHandlerEvent> {
+    self.eventEmitters.lock( ).unwrap( ).subscribe( )
+  }
+
+  pub(crate) fn emit(&self, event: StaticFilesHandlerEvent) {
+    self.eventEmitters.lock( ).unwrap( ).emit(event);
+  }
+
+  // Discovers the static files in the directory again, so that newly generated static files are
+  // visible. Cached readers are dropped, since a static file may have been regenerated.
+  pub fn rescan(&self) -> Result<( ), StaticFilesError> {
+    let mut lastFingerprint= self.lastFingerprint.lock( ).unwrap( );
+    self.rescanWithLock(&mut lastFingerprint)
+  }
+
+  // Rescans, only if the static files changed since the last rescan. Returns whether it did.
+  pub fn rescanIfChanged(&self) -> Result<bool, StaticFilesError> {
+    let mut lastFingerprint= self.lastFingerprint.lock( ).unwrap( );
+    if self.fingerprint( )? == *lastFingerprint {
+      return Ok(false)
+    }
+
+    self.rescanWithLock(&mut lastFingerprint)?;
+    Ok(true)
+  }
+
+  fn rescanWithLock(&self, lastFingerprint: &mut DirectoryFingerprint) -> Result<( ), StaticFilesError> {
+    // Taken before scanning, so that a static file changing in the meantime triggers another rescan.
+    let fingerprint= self.fingerprint( )?;
+
+    let mut staticFiles= StaticFileSet::default( );
+
+    for fileName in fingerprint.keys( ) {
+      let Some((segmentType, blockRange))= SegmentType::parseFileName(fileName) else {
+        continue
+      };
+
+      // The static file was created at the tip of the chain, but not committed to even once.
+      let segmentHeader= match NippyJar::<SegmentHeader>::load(&self.directory.join(fileName)) {
+        Ok(jar) => jar.userHeader( ).clone( ),
+        Err(NippyJarError::ConfigNotFound(_)) => continue,
+        Err(error) => return Err(error.into( ))
+      };
+      if segmentHeader.segmentType != segmentType || segmentHeader.blockRange != blockRange {
+        return Err(StaticFilesError::CorruptedStaticFile(segmentType.fileName(&blockRange)))
+      }
+
+      if let Some(txRange)= &segmentHeader.txRange {
+        staticFiles.byTxNumber.entry(segmentType).or_default( )
+                              .insert(*txRange.end( ), segmentHeader.clone( ));
+      }
+      staticFiles.byBlockNumber.entry(segmentType).or_default( )
+                               .insert(*blockRange.end( ), segmentHeader);
+    }
+
+    let highestBlockNumbers= staticFiles.byBlockNumber.keys( )
+      .filter_map(|segmentType| Some((*segmentType, staticFiles.highestBlockNumber(*segmentType)?)))
+      .collect( );
+
+    *self.staticFiles.write( ).unwrap( )= Arc::new(staticFiles);
+    *lastFingerprint= fingerprint;
+
+    self.eventEmitters.lock( ).unwrap( ).emit(StaticFilesHandlerEvent::Rescanned { highestBlockNumbers });
+    Ok(( ))
+  }
+
+  // Returns the fingerprint of the static files in the directory.
+  fn fingerprint(&self) -> Result<DirectoryFingerprint, StaticFilesError> {
+    let mut fingerprint= DirectoryFingerprint::new( );
+
+    for entry in fs::read_dir(&self.directory)? {
+      let fileName= entry?.file_name( );
+      let Some(fileName)= fileName.to_str( ).filter(|fileName| SegmentType::parseFileName(fileName).is_some( )) else {
+        continue
+      };
+
+      // Either may be missing, while the static file is being created or removed.
+      let path= self.directory.join(fileName);
+      let offsetsLen= fs::metadata(path.with_extension("off")).ok( ).map(|metadata| metadata.len( ));
+      let config= fs::metadata(NippyJar::<SegmentHeader>::configPathOf(&path)).ok( )
+                    .map(|metadata| Ok::<_, StaticFilesError>((metadata.len( ), metadata.modified( )?)))
+                    .transpose( )?;
+
+      fingerprint.insert(fileName.to_string( ), (offsetsLen, config));
+    }
+
+    Ok(fingerprint)
+  }
+
+  // Returns the current StaticFileSet.
+  fn staticFiles(&self) -> Arc<StaticFileSet> {
+    self.staticFiles.read( ).unwrap( ).clone( )
+  }
+
+  // Returns headers of the static files containing the given segment, ordered by block range.
+  pub fn segmentHeaders(&self, segmentType: SegmentType) -> Vec<SegmentHeader> {
+    self.staticFiles( ).segmentHeaders(segmentType)
+  }
+
+  /*
+    Returns the header of the static file of the given segment, whose block range contains the given
+    block (the block may not have been appended to it yet).
+
+    Static files are keyed by the end of their block range, so the first one ending at or after the
+    block is the only candidate, whatever the size of the block ranges is.
+  */
+  pub fn segmentHeaderOf(&self, segmentType: SegmentType, blockNumber: BlockNumber) -> Option<SegmentHeader> {
+    self.staticFiles( ).segmentHeaderOf(segmentType, blockNumber)
+  }
+
+  // Returns the last block number contained in the static files of the given segment, if any.
+  pub fn highestBlockNumber(&self, segmentType: SegmentType) -> Option<BlockNumber> {
+    self.staticFiles( ).highestBlockNumber(segmentType)
+  }
+
+  // Returns the last transaction number contained in the static files of the given transaction
+  // based segment, if any.
+  pub fn highestTxNumber(&self, segmentType: SegmentType) -> Option<TxNumber> {
+    self.staticFiles( ).byTxNumber.get(&segmentType)
+      .and_then(|segmentHeaders| segmentHeaders.keys( ).next_back( ).copied( ))
+  }
+
+  // Returns the first transaction number contained in the static files of the given transaction
+  // based segment, if any. It isn't 0 for a senders segment added to an existing directory (see
+  // StaticFilesWriter::new).
+  pub fn lowestTxNumber(&self, segmentType: SegmentType) -> Option<TxNumber> {
+    self.staticFiles( ).byTxNumber.get(&segmentType)
+      .and_then(|segmentHeaders| segmentHeaders.values( ).next( ))
+      .and_then(|segmentHeader| segmentHeader.txRange.as_ref( ).map(|txRange| *txRange.start( )))
+  }
+
+  pub fn headerByNumber(&self, blockNumber: BlockNumber) -> Result<Option<Header>, StaticFilesError> {
+    self.withBlockRow(blockNumber, headerAt)
+  }
+
+  // There's no index of the hashes across static files : the key index of each static file gets
+  // probed in turn, starting from the most recent one.
+  pub fn headerByHash(&self, blockHash: BlockHash) -> Result<Option<Header>, StaticFilesError> {
+    let staticFiles= self.staticFiles( );
+
+    for segmentHeader in staticFiles.segmentHeaders(SegmentType::BlockHeaders).iter( ).rev( ) {
+      let Some(reader)= staticFiles.reader(&self.directory, segmentHeader)? else {
+        continue
+      };
+
+      if let Some(row)= reader.rowByKey(blockHash.as_slice( ))? {
+        return Ok(Some(headerAt(&reader, row)?))
+      }
+    }
+
+    Ok(None)
+  }
+
+  pub fn blockHash(&self, blockNumber: BlockNumber) -> Result<Option<BlockHash>, StaticFilesError> {
+    self.withBlockRow(blockNumber, |reader, row| {
+      BlockHash::try_from(&*reader.columnValue(row, BLOCK_HASH_COLUMN)?)
+        .map_err(|_| StaticFilesError::CorruptedStaticFile(Self::fileNameOf(reader)))
+    })
+  }
+
+  // Returns the sum of difficulties of all the blocks up to (and including) the given block.
+  pub fn totalDifficulty(&self, blockNumber: BlockNumber) -> Result<Option<U256>, StaticFilesError> {
+    self.withBlockRow(blockNumber, |reader, row| {
+      U256::try_from_be_slice(&reader.columnValue(row, TOTAL_DIFFICULTY_COLUMN)?)
+        .ok_or_else(|| StaticFilesError::CorruptedStaticFile(Self::fileNameOf(reader)))
+    })
+  }
+
+  pub fn transactionById(&self, txNumber: TxNumber) -> Result<Option<TransactionSigned>, StaticFilesError> {
+    self.withTxRow(SegmentType::Transactions, txNumber, Self::transactionAt)
+  }
+
+  // Probes the key index of each static file in turn, like headerByHash.
+  pub fn transactionByHash(&self, txHash: TxHash) -> Result<Option<TransactionSigned>, StaticFilesError> {
+    let staticFiles= self.staticFiles( );
+
+    for segmentHeader in staticFiles.segmentHeaders(SegmentType::Transactions).iter( ).rev( ) {
+      let Some(reader)= staticFiles.reader(&self.directory, segmentHeader)? else {
+        continue
+      };
+
+      if let Some(row)= reader.rowByKey(txHash.as_slice( ))? {
+        return Ok(Some(Self::transactionAt(&reader, row)?))
+      }
+    }
+
+    Ok(None)
+  }
+
+  pub fn receiptById(&self, txNumber: TxNumber) -> Result<Option<Receipt>, StaticFilesError> {
+    self.withTxRow(SegmentType::TransactionReceipts, txNumber, |reader, row| {
+      Ok(compression::serde_bridge::fromBytes(&reader.columnValue(row, RECEIPT_COLUMN)?)?)
+    })
+  }
+
+  // Returns the address which signed the given transaction.
+  pub fn senderById(&self, txNumber: TxNumber) -> Result<Option<Address>, StaticFilesError> {
+    self.withTxRow(SegmentType::Senders, txNumber, |reader, row| {
+      Address::try_from(&*reader.columnValue(row, SENDER_COLUMN)?)
+        .map_err(|_| StaticFilesError::CorruptedStaticFile(Self::fileNameOf(reader)))
+    })
+  }
+
+  fn transactionAt(reader: &StaticFileReader, row: usize) -> Result<TransactionSigned, StaticFilesError> {
+    let hash= TxHash::try_from(&*reader.columnValue(row, TX_HASH_COLUMN)?)
+                .map_err(|_| StaticFilesError::CorruptedStaticFile(Self::fileNameOf(reader)))?;
+    let encoded= reader.columnValue(row, TRANSACTION_COLUMN)?.to_vec( ).into( );
+
+    Ok(TransactionSigned { hash, encoded })
+  }
+
+  // Finds the static file of the block headers segment containing the given block, and executes
+  // the function by passing its reader and the block's row to it.
+  fn withBlockRow<T, F>(&self, blockNumber: BlockNumber, f: F) -> Result<Option<T>, StaticFilesError>
+    where
+      F: FnOnce(&StaticFileReader, usize) -> Result<T, StaticFilesError>
+  {
+    let staticFiles= self.staticFiles( );
+
+    let Some(segmentHeader)= staticFiles.segmentHeaderOf(SegmentType::BlockHeaders, blockNumber)
+                                        .filter(|segmentHeader| segmentHeader.highestBlock >= Some(blockNumber))
+    else {
+      return Ok(None)
+    };
+    self.withRow(&staticFiles, &segmentHeader, blockNumber - segmentHeader.blockRange.start( ), f)
+  }
+
+  // Finds the static file of the given transaction based segment containing the given
+  // transaction, and executes the function by passing its reader and the transaction's row to it.
+  fn withTxRow<T, F>(&self, segmentType: SegmentType, txNumber: TxNumber, f: F) -> Result<Option<T>, StaticFilesError>
+    where
+      F: FnOnce(&StaticFileReader, usize) -> Result<T, StaticFilesError>
+  {
+    let staticFiles= self.staticFiles( );
+
+    let segmentHeader= staticFiles.byTxNumber.get(&segmentType)
+      .and_then(|segmentHeaders| segmentHeaders.range(txNumber..).next( ))
+      .map(|(_, segmentHeader)| segmentHeader.clone( ));
+
+    let Some(txRange)= segmentHeader.as_ref( ).and_then(|segmentHeader| segmentHeader.txRange.clone( ))
+                                                  .filter(|txRange| txRange.contains(&txNumber))
+    else {
+      return Ok(None)
+    };
+    self.withRow(&staticFiles, &segmentHeader.unwrap( ), txNumber - txRange.start( ), f)
+  }
+
+  fn withRow<T, F>(&self,
+                   staticFiles: &StaticFileSet,
+                   segmentHeader: &SegmentHeader,
+                   row: u64,
+                   f: F) -> Result<Option<T>, StaticFilesError>
+    where
+      F: FnOnce(&StaticFileReader, usize) -> Result<T, StaticFilesError>
+  {
+    let Some(reader)= staticFiles.reader(&self.directory, segmentHeader)? else {
+      return Ok(None)
+    };
+
+    // Guards against a static file having less rows than its range claims.
+    if row as usize >= reader.rowCount( ) {
+      return Ok(None)
+    }
+    f(&reader, row as usize).map(Some)
+  }
+
+  fn fileNameOf(reader: &StaticFileReader) -> String {
+    let segmentHeader= reader.jar( ).userHeader( );
+    segmentHeader.segmentType.fileName(&segmentHeader.blockRange)
+  }
+}
+
+impl StaticFileSet {
+  fn segmentHeaders(&self, segmentType: SegmentType) -> Vec<SegmentHeader> {
+    self.byBlockNumber.get(&segmentType)
+      .map(|segmentHeaders| segmentHeaders.values( ).cloned( ).collect( ))
+      .unwrap_or_default( )
+  }
+
+  fn segmentHeaderOf(&self, segmentType: SegmentType, blockNumber: BlockNumber) -> Option<SegmentHeader> {
+    self.byBlockNumber.get(&segmentType)
+      .and_then(|segmentHeaders| segmentHeaders.range(blockNumber..).next( ))
+      .map(|(_, segmentHeader)| segmentHeader)
+      .filter(|segmentHeader| segmentHeader.blockRange.contains(&blockNumber))
+      .cloned( )
+  }
+
+  fn highestBlockNumber(&self, segmentType: SegmentType) -> Option<BlockNumber> {
+    self.byBlockNumber.get(&segmentType)
+      .and_then(|segmentHeaders| segmentHeaders.values( ).rev( ).find_map(|segmentHeader| segmentHeader.highestBlock))
+  }
+
+  /*
+    Returns the (cached) reader of the static file with the given header.
+
+    None if the static file got removed (by truncation) since it was discovered. The StaticFileSet
+    is about to be replaced then.
+  */
+  fn reader(&self, directory: &Path, segmentHeader: &SegmentHeader) -> Result<Option<Arc<StaticFileReader>>, StaticFilesError> {
+    let fileName= segmentHeader.segmentType.fileName(&segmentHeader.blockRange);
+
+    if let Some(reader)= self.readers.read( ).unwrap( ).get(&fileName) {
+      return Ok(Some(reader.clone( )))
+    }
+
+    let reader= match NippyJarReader::open(&directory.join(&fileName)) {
+      Ok(reader) => Arc::new(reader),
+      Err(NippyJarError::ConfigNotFound(_)) => return Ok(None),
+      Err(NippyJarError::Io(error)) if error.kind( ) == io::ErrorKind::NotFound => return Ok(None),
+      Err(error) => return Err(error.into( ))
+    };
+    Ok(Some(self.readers.write( ).unwrap( ).entry(fileName).or_insert(reader).clone( )))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::ops::RangeInclusive;
+  use db::test_utils::{signedTransaction, MemoryDb};
+  use crate::{segment::SEGMENT_TYPES, static_files_writer::StaticFilesWriter};
+  use super::*;
+
+  // Block n has n % 3 transactions.
+  fn block(blockNumber: BlockNumber) -> (Header, Vec<TransactionSigned>, Vec<Receipt>, Vec<Address>) {
+    let header= Header { number: blockNumber, gasUsed: blockNumber * 1000, ..Default::default( ) };
+    let (transactions, senders): (Vec<_>, Vec<_>)= (0..(blockNumber % 3)).map(|nonce| signedTransaction(blockNumber, nonce)).unzip( );
+    let receipts= (0..transactions.len( ) as u64).map(|i| Receipt { cumulativeGasUsed: (i + 1) * 21_000, ..Default::default( ) })
+                                                .collect( );
+
+    (header, transactions, receipts, senders)
+  }
+
+  // Appends the given blocks, to static files of blocksPerStaticFile blocks each.
+  fn appendBlocks(staticFilesHandler: &Arc<StaticFilesHander>, blockNumbers: std::ops::Range<BlockNumber>, blocksPerStaticFile: u64) {
+    let mut staticFilesWriter= SEGMENT_TYPES.into_iter( )
+      .fold(StaticFilesWriter::new(staticFilesHandler.clone( ), &MemoryDb::default( )).unwrap( ),
+            |staticFilesWriter, segmentType| staticFilesWriter.withBlocksPerStaticFile(segmentType, blocksPerStaticFile));
+
+    for blockNumber in blockNumbers {
+      let (header, transactions, receipts, senders)= block(blockNumber);
+      staticFilesWriter.appendBlock(&header, header.hash( ), U256::from(blockNumber), &transactions, &receipts, &senders).unwrap( );
+    }
+    staticFilesWriter.commit( ).unwrap( );
+  }
+
+  // Blocks 0..=9 in static files of 4 blocks each : 0..=3, 4..=7 and 8..=11 (being appended to).
+  fn staticFilesHandlerIn(directory: &Path) -> Arc<StaticFilesHander> {
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.to_path_buf( )).unwrap( ));
+    appendBlocks(&staticFilesHandler, 0..10, 4);
+    staticFilesHandler
+  }
+
+  #[test]
+  fn lookupsAreServedFromTheStaticFileContainingTheNumber( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let staticFilesHandler= staticFilesHandlerIn(directory.path( ));
+
+    assert_eq!(staticFilesHandler.segmentHeaders(SegmentType::BlockHeaders).len( ), 3);
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::BlockHeaders), Some(9));
+    assert_eq!(staticFilesHandler.lowestTxNumber(SegmentType::Transactions), Some(0));
+    assert_eq!(staticFilesHandler.highestTxNumber(SegmentType::Transactions), Some(8));
+
+    let mut txNumber= 0;
+    for blockNumber in 0..10 {
+      let (header, transactions, receipts, senders)= block(blockNumber);
+      assert_eq!(staticFilesHandler.headerByNumber(blockNumber).unwrap( ), Some(header.clone( )));
+      assert_eq!(staticFilesHandler.blockHash(blockNumber).unwrap( ), Some(header.hash( )));
+      assert_eq!(staticFilesHandler.totalDifficulty(blockNumber).unwrap( ), Some(U256::from(blockNumber)));
+
+      for i in 0..transactions.len( ) {
+        assert_eq!(staticFilesHandler.transactionById(txNumber).unwrap( ), Some(transactions[i].clone( )));
+        assert_eq!(staticFilesHandler.receiptById(txNumber).unwrap( ), Some(receipts[i].clone( )));
+        assert_eq!(staticFilesHandler.senderById(txNumber).unwrap( ), Some(senders[i]));
+        txNumber += 1;
+      }
+    }
+
+    // Block 10 belongs to the last static file, but hasn't been appended yet.
+    assert_eq!(staticFilesHandler.segmentHeaderOf(SegmentType::BlockHeaders, 10).unwrap( ).blockRange, 8..=11);
+    assert_eq!(staticFilesHandler.headerByNumber(10).unwrap( ), None);
+    assert_eq!(staticFilesHandler.segmentHeaderOf(SegmentType::BlockHeaders, 12), None);
+    assert_eq!(staticFilesHandler.blockHash(12).unwrap( ), None);
+    assert_eq!(staticFilesHandler.transactionById(9).unwrap( ), None);
+    assert_eq!(staticFilesHandler.senderById(9).unwrap( ), None);
+  }
+
+  #[test]
+  fn staticFilesOfDifferentBlockRangeSizesCoexist( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    // New static files are aligned to the number of blocks they're meant for, so the first one of
+    // each run gets cut short.
+    appendBlocks(&staticFilesHandler, 0..4, 4);
+    appendBlocks(&staticFilesHandler, 4..10, 10);
+    appendBlocks(&staticFilesHandler, 10..15, 3);
+
+    let blockRanges: Vec<RangeInclusive<BlockNumber>>= staticFilesHandler.segmentHeaders(SegmentType::BlockHeaders).into_iter( )
+                                                                          .map(|segmentHeader| segmentHeader.blockRange)
+                                                                          .collect( );
+    assert_eq!(blockRanges, vec![0..=3, 4..=9, 10..=11, 12..=14]);
+
+    let mut txNumber= 0;
+    for blockNumber in 0..15 {
+      let blockRange= blockRanges.iter( ).find(|blockRange| blockRange.contains(&blockNumber)).unwrap( );
+      for segmentType in SEGMENT_TYPES {
+        assert_eq!(&staticFilesHandler.segmentHeaderOf(segmentType, blockNumber).unwrap( ).blockRange, blockRange);
+      }
+
+      let (header, transactions, _, senders)= block(blockNumber);
+      assert_eq!(staticFilesHandler.headerByNumber(blockNumber).unwrap( ), Some(header));
+      for (transaction, sender) in transactions.into_iter( ).zip(senders) {
+        assert_eq!(staticFilesHandler.transactionById(txNumber).unwrap( ), Some(transaction));
+        assert_eq!(staticFilesHandler.senderById(txNumber).unwrap( ), Some(sender));
+        txNumber += 1;
+      }
+    }
+    assert_eq!(staticFilesHandler.segmentHeaderOf(SegmentType::BlockHeaders, 15), None);
+  }
+
+  #[test]
+  fn lookupsByHashSearchEveryStaticFile( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let staticFilesHandler= staticFilesHandlerIn(directory.path( ));
+
+    for blockNumber in [1, 5, 8] {
+      let (header, transactions, _, _)= block(blockNumber);
+      assert_eq!(staticFilesHandler.headerByHash(header.hash( )).unwrap( ), Some(header));
+      assert_eq!(staticFilesHandler.transactionByHash(transactions[0].hash).unwrap( ), Some(transactions[0].clone( )));
+    }
+
+    // Blocks (and transactions) which were never appended.
+    let (header, transactions, _, _)= block(11);
+    assert_eq!(staticFilesHandler.headerByHash(header.hash( )).unwrap( ), None);
+    assert_eq!(staticFilesHandler.transactionByHash(transactions[0].hash).unwrap( ), None);
+    assert_eq!(staticFilesHandler.headerByHash(BlockHash::ZERO).unwrap( ), None);
+
+    let emptyDirectory= tempfile::tempdir( ).unwrap( );
+    let emptyStaticFilesHandler= StaticFilesHander::new(emptyDirectory.path( ).to_path_buf( )).unwrap( );
+    assert_eq!(emptyStaticFilesHandler.headerByHash(block(1).0.hash( )).unwrap( ), None);
+    assert_eq!(emptyStaticFilesHandler.transactionByHash(transactions[0].hash).unwrap( ), None);
+  }
+
+  #[test]
+  fn staticFilesWithoutAConfigAreSkipped( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let staticFilesHandler= staticFilesHandlerIn(directory.path( ));
+
+    // Like a static file created at the tip of the chain, but never committed to.
+    let path= directory.path( ).join(SegmentType::BlockHeaders.fileName(&(8..=11)));
+    fs::remove_file(NippyJar::<SegmentHeader>::configPathOf(&path)).unwrap( );
+    fs::write(directory.path( ).join("notes.txt"), b"not a static file").unwrap( );
+    staticFilesHandler.rescan( ).unwrap( );
+
+    assert_eq!(staticFilesHandler.segmentHeaders(SegmentType::BlockHeaders).len( ), 2);
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::BlockHeaders), Some(7));
+    assert_eq!(staticFilesHandler.headerByNumber(9).unwrap( ), None);
+    assert_eq!(staticFilesHandler.headerByHash(block(9).0.hash( )).unwrap( ), None);
+    assert_eq!(staticFilesHandler.headerByNumber(5).unwrap( ), Some(block(5).0));
+
+    // The other segments are left alone.
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::Transactions), Some(9));
+
+    let reopenedStaticFilesHandler= StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( );
+    assert_eq!(reopenedStaticFilesHandler.highestBlockNumber(SegmentType::BlockHeaders), Some(7));
+  }
+}
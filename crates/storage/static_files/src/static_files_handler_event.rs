@@ -0,0 +1,22 @@
+use std::{collections::HashMap, ops::RangeInclusive};
+use db::models::BlockNumber;
+use super::{nippy_jar::ConsistencyReport, segment::SegmentType};
+
+#[derive(Debug, Clone)]
+pub enum StaticFilesHandlerEvent {
+
+  // Emitted once the StaticFilesHander has swapped in the static files it just discovered.
+  Rescanned {
+    // Highest block number in the static files of each segment (segments without any static file
+    // are left out).
+    highestBlockNumbers: HashMap<SegmentType, BlockNumber>
+  },
+
+  // Emitted when a static file opened for appending wasn't consistent (e.g. the node crashed while
+  // writing it), and had to be truncated (see NippyJar::checkConsistency).
+  Recovered {
+    segmentType: SegmentType,
+    blockRange: RangeInclusive<BlockNumber>,
+    consistencyReport: ConsistencyReport
+  }
+}
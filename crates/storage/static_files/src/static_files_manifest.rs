@@ -0,0 +1,366 @@
+use std::{
+  collections::BTreeMap,
+  fs::{self, File},
+  io::{self, Write},
+  ops::RangeInclusive,
+  path::Path
+};
+use alloy_primitives::B256;
+use db::models::BlockNumber;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use super::{
+  nippy_jar::NippyJar,
+  segment::{SegmentHeader, SegmentType, SEGMENT_TYPES},
+  static_files_error::StaticFilesError,
+  static_files_handler::StaticFilesHander
+};
+
+// Name of the manifest file, in the directory containing the static files.
+pub const MANIFEST_FILE_NAME: &str= "manifest.json";
+
+pub const MANIFEST_VERSION: usize= 1;
+
+/*
+  Lists the files making up the static files in a directory (the data file of each NippyJar, along
+  with its offsets, config, dictionaries and index files), with their size and sha256 hash. It's
+  kept next to the static files, so that a copy of the directory can be verified against it before
+  the node uses it (see verify).
+
+  Only complete static files (containing their whole block range) are listed. Static files still
+  being appended to at the tip of the chain change on every commit, so they'd have to be hashed over
+  and over again.
+
+  The manifest is maintained by the StaticFilesWriter and the StaticFilesGenerator, whenever they
+  change the static files (see update). Entries of complete static files whose files haven't
+  changed are kept as they are, so that the (large) data files don't get re-hashed every time.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StaticFilesManifest {
+  pub version: usize,
+
+  // Keyed by file name.
+  pub files: BTreeMap<String, ManifestEntry>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+  // Segment and block range of the static file the file belongs to.
+  pub segmentType: SegmentType,
+  pub blockRange: RangeInclusive<BlockNumber>,
+
+  pub size: u64,
+  pub sha256: B256
+}
+
+// Result of verifying a directory against a manifest. Each list contains file names.
+#[derive(Debug, Default)]
+pub struct ManifestVerificationReport {
+  pub verifiedFiles: usize,
+
+  // Listed in the manifest, but not in the directory.
+  pub missingFiles: Vec<String>,
+
+  // Files of complete static files in the directory, not listed in the manifest.
+  pub extraFiles: Vec<String>,
+
+  // Differing in size or hash from the manifest.
+  pub alteredFiles: Vec<String>,
+
+  // Files of static files still being appended to, which the manifest doesn't list.
+  pub unverifiedFiles: Vec<String>
+}
+
+impl ManifestVerificationReport {
+  pub fn isOk(&self) -> bool {
+    self.missingFiles.is_empty( ) && self.extraFiles.is_empty( ) && self.alteredFiles.is_empty( )
+  }
+}
+
+impl Default for StaticFilesManifest {
+  fn default( ) -> Self {
+    Self {
+      version: MANIFEST_VERSION,
+      files: BTreeMap::new( )
+    }
+  }
+}
+
+impl StaticFilesManifest {
+  // Loads the manifest from the given path. An empty manifest is returned if there's none yet.
+  pub fn load(path: &Path) -> Result<Self, StaticFilesError> {
+    match fs::read(path) {
+      Ok(manifest) => Ok(serde_json::from_slice(&manifest)?),
+      Err(error) if error.kind( ) == io::ErrorKind::NotFound => Ok(Self::default( )),
+      Err(error) => Err(error.into( ))
+    }
+  }
+
+  // Writes the manifest to a temporary file first, and then renames it, so that a crash never
+  // leaves us with a partially written manifest.
+  pub fn save(&self, path: &Path) -> Result<( ), StaticFilesError> {
+    let temporaryPath= path.with_extension("json.tmp");
+
+    let mut temporaryFile= File::create(&temporaryPath)?;
+    temporaryFile.write_all(&serde_json::to_vec_pretty(self)?)?;
+    temporaryFile.sync_all( )?;
+
+    fs::rename(&temporaryPath, path)?;
+
+    Ok(( ))
+  }
+
+  /*
+    Brings the manifest in the directory of the StaticFilesHandler up to date with the static files
+    it knows about : entries of static files which got truncated or removed are dropped, and the
+    files of newly completed static files are hashed.
+
+    Called after the static files change, and the StaticFilesHandler is rescanned.
+  */
+  pub fn update(staticFilesHandler: &StaticFilesHander) -> Result<( ), StaticFilesError> {
+    let directory= staticFilesHandler.directory( );
+    let path= directory.join(MANIFEST_FILE_NAME);
+
+    let manifest= Self::load(&path)?;
+    let mut updatedManifest= Self::default( );
+
+    for segmentHeader in SEGMENT_TYPES.into_iter( ).flat_map(|segmentType| staticFilesHandler.segmentHeaders(segmentType)) {
+      if !isComplete(&segmentHeader) {
+        continue
+      }
+
+      /*
+        A file may change without changing size (e.g. the static file gets regenerated, or upgraded
+        in place). The config holds the checksums of the data and the format version, so it changes
+        along with any of the other files. Being small, it's re-hashed every time.
+      */
+      let fileNames= fileNamesOf(directory, &segmentHeader);
+      let configFileName= format!("{}.conf", segmentHeader.segmentType.fileName(&segmentHeader.blockRange));
+      let unchanged= fileNames.iter( ).all(|fileName| {
+        manifest.files.get(fileName).is_some_and(|entry| fileSize(&directory.join(fileName)).ok( ) == Some(entry.size))
+      }) &&
+      manifest.files.get(&configFileName).is_some_and(|entry| {
+        hashFile(&directory.join(&configFileName)).ok( ) == Some((entry.size, entry.sha256))
+      });
+
+      for fileName in fileNames {
+        let entry= match unchanged {
+          true => manifest.files[&fileName].clone( ),
+          false => {
+            let (size, sha256)= hashFile(&directory.join(&fileName))?;
+            ManifestEntry {
+              segmentType: segmentHeader.segmentType,
+              blockRange: segmentHeader.blockRange.clone( ),
+              size,
+              sha256
+            }
+          }
+        };
+        updatedManifest.files.insert(fileName, entry);
+      }
+    }
+
+    if updatedManifest != manifest || !path.exists( ) {
+      updatedManifest.save(&path)?;
+    }
+    Ok(( ))
+  }
+
+  /*
+    Verifies the static files in the directory against the manifest, re-hashing every file listed
+    in it. Files of static files not listed in the manifest are reported as extra - unless the
+    static file is still being appended to, in which case they are reported as unverified.
+  */
+  pub fn verify(&self, directory: &Path) -> Result<ManifestVerificationReport, StaticFilesError> {
+    let mut report= ManifestVerificationReport::default( );
+
+    for (fileName, entry) in &self.files {
+      let path= directory.join(fileName);
+      if !path.exists( ) {
+        report.missingFiles.push(fileName.clone( ));
+        continue
+      }
+
+      let altered= fileSize(&path)? != entry.size || hashFile(&path)? != (entry.size, entry.sha256);
+      match altered {
+        true => report.alteredFiles.push(fileName.clone( )),
+        false => report.verifiedFiles += 1
+      }
+    }
+
+    for dirEntry in fs::read_dir(directory)? {
+      let fileName= dirEntry?.file_name( ).to_string_lossy( ).into_owned( );
+      if self.files.contains_key(&fileName) {
+        continue
+      }
+      let Some(dataFileName)= dataFileNameOf(&fileName) else {
+        continue
+      };
+
+      let beingAppendedTo= NippyJar::<SegmentHeader>::load(&directory.join(dataFileName))
+        .is_ok_and(|jar| !isComplete(jar.userHeader( )));
+      match beingAppendedTo {
+        true => report.unverifiedFiles.push(fileName),
+        false => report.extraFiles.push(fileName)
+      }
+    }
+    report.extraFiles.sort( );
+    report.unverifiedFiles.sort( );
+
+    Ok(report)
+  }
+}
+
+fn isComplete(segmentHeader: &SegmentHeader) -> bool {
+  segmentHeader.highestBlock == Some(*segmentHeader.blockRange.end( ))
+}
+
+// Extensions of the files making up a NippyJar, besides the data file (see the nippy_jar module).
+const NIPPY_JAR_FILE_EXTENSIONS: [&str; 5]= ["off", "conf", "dict", "idx", "keys"];
+
+// Returns the names of the files making up the NippyJar of a static file, which exist.
+fn fileNamesOf(directory: &Path, segmentHeader: &SegmentHeader) -> Vec<String> {
+  let dataFileName= segmentHeader.segmentType.fileName(&segmentHeader.blockRange);
+
+  std::iter::once(dataFileName.clone( ))
+    .chain(NIPPY_JAR_FILE_EXTENSIONS.map(|extension| format!("{}.{}", dataFileName, extension)))
+    .filter(|fileName| directory.join(fileName).exists( ))
+    .collect( )
+}
+
+// If the file is one of the files making up the NippyJar of a static file, returns the name of the
+// data file.
+fn dataFileNameOf(fileName: &str) -> Option<&str> {
+  let dataFileName= NIPPY_JAR_FILE_EXTENSIONS.into_iter( )
+    .find_map(|extension| fileName.strip_suffix(extension)?.strip_suffix('.'))
+    .unwrap_or(fileName);
+
+  SegmentType::parseFileName(dataFileName).map(|_| dataFileName)
+}
+
+fn fileSize(path: &Path) -> Result<u64, io::Error> {
+  Ok(fs::metadata(path)?.len( ))
+}
+
+// Returns the size and the sha256 hash of the file.
+fn hashFile(path: &Path) -> Result<(u64, B256), io::Error> {
+  let mut hasher= Sha256::new( );
+  let size= io::copy(&mut File::open(path)?, &mut hasher)?;
+
+  Ok((size, B256::from_slice(&hasher.finalize( ))))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+  use alloy_primitives::U256;
+  use db::{models::Header, test_utils::MemoryDb};
+  use crate::static_files_writer::StaticFilesWriter;
+  use super::*;
+
+  // Writes blocks 0..=5 into static files of 4 blocks : those of blocks 0..=3 are complete, and
+  // those of blocks 4..=7 are being appended to.
+  fn writeBlocks(directory: &Path) -> Arc<StaticFilesHander> {
+    let db= MemoryDb::default( );
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.to_path_buf( )).unwrap( ));
+
+    let mut staticFilesWriter= SEGMENT_TYPES.into_iter( ).fold(StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( ),
+                                                               |staticFilesWriter, segmentType| staticFilesWriter.withBlocksPerStaticFile(segmentType, 4));
+    for blockNumber in 0..6 {
+      let header= Header { number: blockNumber, ..Default::default( ) };
+      staticFilesWriter.appendBlock(&header, header.hash( ), U256::ZERO, &[ ], &[ ], &[ ]).unwrap( );
+    }
+    staticFilesWriter.commit( ).unwrap( );
+
+    staticFilesHandler
+  }
+
+  #[test]
+  fn unchangedFilesArentRehashedOnUpdate( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let staticFilesHandler= writeBlocks(directory.path( ));
+    let manifestPath= directory.path( ).join(MANIFEST_FILE_NAME);
+
+    // Only the complete static files are listed.
+    let mut manifest= StaticFilesManifest::load(&manifestPath).unwrap( );
+    assert!(!manifest.files.is_empty( ));
+    assert!(manifest.files.values( ).all(|entry| entry.blockRange == (0..=3)));
+
+    // A wrong hash goes unnoticed, as long as the size of the file and the config don't change.
+    let headersFileName= SegmentType::BlockHeaders.fileName(&(0..=3));
+    manifest.files.get_mut(&headersFileName).unwrap( ).sha256= B256::ZERO;
+    manifest.save(&manifestPath).unwrap( );
+
+    StaticFilesManifest::update(&staticFilesHandler).unwrap( );
+    assert_eq!(StaticFilesManifest::load(&manifestPath).unwrap( ), manifest);
+
+    // The config changed, without changing size (like after the static file got regenerated).
+    let headersConfigPath= directory.path( ).join(format!("{}.conf", headersFileName));
+    let mut headersConfig= fs::read(&headersConfigPath).unwrap( );
+    let lastByte= headersConfig.len( ) - 1;
+    headersConfig[lastByte]= match headersConfig[lastByte] {
+      b' ' => b'\n',
+      _ => b' '
+    };
+    fs::write(&headersConfigPath, &headersConfig).unwrap( );
+
+    StaticFilesManifest::update(&staticFilesHandler).unwrap( );
+    let manifest= StaticFilesManifest::load(&manifestPath).unwrap( );
+    assert_eq!(manifest.files[&headersFileName].sha256, hashFile(&directory.path( ).join(&headersFileName)).unwrap( ).1);
+    assert_eq!(manifest.verify(directory.path( )).unwrap( ).alteredFiles, Vec::<String>::new( ));
+
+    let mut headersFile= fs::OpenOptions::new( ).append(true).open(directory.path( ).join(&headersFileName)).unwrap( );
+    headersFile.write_all(&[0]).unwrap( );
+
+    StaticFilesManifest::update(&staticFilesHandler).unwrap( );
+    let updatedManifest= StaticFilesManifest::load(&manifestPath).unwrap( );
+    assert_eq!(updatedManifest.files[&headersFileName], ManifestEntry {
+      segmentType: SegmentType::BlockHeaders,
+      blockRange: 0..=3,
+      size: manifest.files[&headersFileName].size + 1,
+      sha256: hashFile(&directory.path( ).join(&headersFileName)).unwrap( ).1
+    });
+  }
+
+  #[test]
+  fn verificationReportsMissingExtraAlteredAndUnverifiedFiles( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    writeBlocks(directory.path( ));
+
+    let mut manifest= StaticFilesManifest::load(&directory.path( ).join(MANIFEST_FILE_NAME)).unwrap( );
+    let report= manifest.verify(directory.path( )).unwrap( );
+    assert!(report.isOk( ));
+    assert_eq!(report.verifiedFiles, manifest.files.len( ));
+
+    // The files of the static files being appended to (only the block headers have a key table).
+    let mut beingAppendedTo: Vec<String>= SEGMENT_TYPES.into_iter( )
+      .flat_map(|segmentType| {
+        let dataFileName= segmentType.fileName(&(4..=7));
+        [dataFileName.clone( ), format!("{}.off", dataFileName), format!("{}.conf", dataFileName), format!("{}.keys", dataFileName)]
+      })
+      .filter(|fileName| directory.path( ).join(fileName).exists( ))
+      .collect( );
+    assert_eq!(beingAppendedTo.len( ), 4 * 3 + 1);
+    beingAppendedTo.sort( );
+    assert_eq!(report.unverifiedFiles, beingAppendedTo);
+
+    let headersFileName= SegmentType::BlockHeaders.fileName(&(0..=3));
+    let mut headers= fs::read(directory.path( ).join(&headersFileName)).unwrap( );
+    headers[0] ^= 1;
+    fs::write(directory.path( ).join(&headersFileName), headers).unwrap( );
+
+    let sendersConfigFileName= format!("{}.conf", SegmentType::Senders.fileName(&(0..=3)));
+    fs::remove_file(directory.path( ).join(&sendersConfigFileName)).unwrap( );
+
+    let transactionsOffsetsFileName= format!("{}.off", SegmentType::Transactions.fileName(&(0..=3)));
+    manifest.files.remove(&transactionsOffsetsFileName);
+
+    let report= manifest.verify(directory.path( )).unwrap( );
+    assert!(!report.isOk( ));
+    assert_eq!(report.missingFiles, vec![sendersConfigFileName]);
+    assert_eq!(report.extraFiles, vec![transactionsOffsetsFileName]);
+    assert_eq!(report.alteredFiles, vec![headersFileName]);
+    assert_eq!(report.unverifiedFiles, beingAppendedTo);
+    assert_eq!(report.verifiedFiles, manifest.files.len( ) - 2);
+  }
+}
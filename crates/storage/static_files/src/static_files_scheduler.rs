@@ -0,0 +1,298 @@
+use std::{collections::HashMap, ops::RangeInclusive, panic, sync::{Arc, Mutex}};
+use db::{interfaces, models::BlockNumber};
+use tokio::{
+  sync::{mpsc::UnboundedReceiver, oneshot, watch},
+  task::{self, JoinHandle}
+};
+use super::{
+  segment::{SegmentType, DEFAULT_BLOCKS_PER_STATIC_FILE, SEGMENT_TYPES},
+  static_files_error::StaticFilesError,
+  static_files_generator::{BlockRangeOfSegments, StaticFilesGenerator},
+  static_files_generator_event::StaticFilesGeneratorEvent,
+  static_files_handler::StaticFilesHander
+};
+
+/*
+  Decides when the StaticFilesGenerator runs : it watches the finalized block number, and as soon
+  as a whole block range (of the configured size, aligned to it) is finalized but not yet in static
+  files, copies it from the database to static files and then prunes it from the database.
+
+  The generator runs on a blocking thread, so that it never blocks sync. Block ranges are copied one
+  at a time - if the finalized block number jumps over several block ranges, they get copied one
+  after the other.
+
+  A failed generation (e.g. due to a transient IO error) is reported through SegmentFailed events
+  (see subscribe), and retried once the finalized block number changes again.
+
+  On shutdown, a generation in progress is completed before the task returns (it can't be
+  interrupted midway). If the node dies between copying and pruning a block range, the copied rows
+  stay in the database. They are harmless, since reads of them are served from static files.
+
+  Segments whose next block falls into a static file appended to at the tip of the chain (by the
+  StaticFilesWriter) are left alone.
+*/
+pub struct StaticFilesScheduler<Db> {
+  staticFilesHandler: Arc<StaticFilesHander>,
+
+  // Shared with the blocking thread the generator runs on.
+  staticFilesGenerator: Arc<Mutex<StaticFilesGenerator<Db>>>,
+
+  // Number of blocks in each generated static file, for each segment.
+  blocksPerStaticFile: HashMap<SegmentType, u64>,
+
+  // Latest finalized block number (None until a block gets finalized).
+  finalizedBlockNumber: watch::Receiver<Option<BlockNumber>>
+}
+
+impl<Db> StaticFilesScheduler<Db>
+  where
+    Db: interfaces::db::Db + 'static
+{
+  pub fn new(db: Arc<Db>,
+             staticFilesHandler: Arc<StaticFilesHander>,
+             finalizedBlockNumber: watch::Receiver<Option<BlockNumber>>) -> Self
+  {
+    Self {
+      staticFilesGenerator: Arc::new(Mutex::new(StaticFilesGenerator::new(db, staticFilesHandler.clone( )))),
+      staticFilesHandler,
+      blocksPerStaticFile: SEGMENT_TYPES.map(|segmentType| (segmentType, DEFAULT_BLOCKS_PER_STATIC_FILE)).into( ),
+      finalizedBlockNumber
+    }
+  }
+
+  // Sets the number of blocks in each static file generated for the given segment.
+  pub fn withBlocksPerStaticFile(mut self, segmentType: SegmentType, blocksPerStaticFile: u64) -> Self {
+    self.blocksPerStaticFile.insert(segmentType, blocksPerStaticFile.max(1));
+    self
+  }
+
+  // Returns a receiver, through which StaticFilesGeneratorEvents (of each generation) will be
+  // received.
+  pub fn subscribe(&mut self) -> UnboundedReceiver<StaticFilesGeneratorEvent> {
+    self.staticFilesGenerator.lock( ).unwrap( ).subscribe( )
+  }
+
+  // Runs the scheduler as a background task, until the shutdown signal is received (or the sender
+  // of the finalized block number is dropped).
+  pub fn spawn(self, shutdownSignal: oneshot::Receiver<( )>) -> JoinHandle<Result<( ), StaticFilesError>> {
+    tokio::spawn(self.run(shutdownSignal))
+  }
+
+  pub async fn run(mut self, mut shutdownSignal: oneshot::Receiver<( )>) -> Result<( ), StaticFilesError> {
+    loop {
+      let finalizedBlockNumber= *self.finalizedBlockNumber.borrow_and_update( );
+
+      if let Some(finalizedBlockNumber)= finalizedBlockNumber {
+        let blockRangeOfSegments= self.blockRangeOfSegments(finalizedBlockNumber);
+
+        // More block ranges may have been finalized in the meantime. A failed generation waits
+        // for the next change of the finalized block number instead.
+        if !blockRangeOfSegments.isEmpty( ) && self.generate(blockRangeOfSegments).await.is_ok( ) {
+          match shutdownSignal.try_recv( ) {
+            Err(oneshot::error::TryRecvError::Empty) => continue,
+            _ => return Ok(( ))
+          }
+        }
+      }
+
+      tokio::select! {
+        changed= self.finalizedBlockNumber.changed( ) => {
+          if changed.is_err( ) {
+            return Ok(( ))
+          }
+        },
+
+        _= &mut shutdownSignal => return Ok(( ))
+      }
+    }
+  }
+
+  // Copies the block ranges to static files and prunes them from the database, on a blocking
+  // thread.
+  async fn generate(&self, blockRangeOfSegments: BlockRangeOfSegments) -> Result<( ), StaticFilesError> {
+    let staticFilesGenerator= self.staticFilesGenerator.clone( );
+
+    let generation= task::spawn_blocking(move || {
+      let mut staticFilesGenerator= staticFilesGenerator.lock( ).unwrap( );
+
+      staticFilesGenerator.run(blockRangeOfSegments.clone( ))?;
+      staticFilesGenerator.pruneDb(&blockRangeOfSegments)
+    });
+
+    match generation.await {
+      Ok(result) => result,
+      Err(joinError) => panic::resume_unwind(joinError.into_panic( ))
+    }
+  }
+
+  /*
+    Computes, for each segment, the next block range to be copied to static files : it starts right
+    after the highest block in the static files of the segment, and ends at the end of the aligned
+    block range containing that block. It's None until that whole block range is finalized.
+  */
+  fn blockRangeOfSegments(&self, finalizedBlockNumber: BlockNumber) -> BlockRangeOfSegments {
+    BlockRangeOfSegments {
+      headers: self.nextBlockRangeOf(SegmentType::BlockHeaders, finalizedBlockNumber),
+      transactions: self.nextBlockRangeOf(SegmentType::Transactions, finalizedBlockNumber),
+      transactionReceipts: self.nextBlockRangeOf(SegmentType::TransactionReceipts, finalizedBlockNumber),
+      senders: self.nextBlockRangeOf(SegmentType::Senders, finalizedBlockNumber)
+    }
+  }
+
+  fn nextBlockRangeOf(&self, segmentType: SegmentType, finalizedBlockNumber: BlockNumber) -> Option<RangeInclusive<BlockNumber>> {
+    let firstBlockNumber= self.staticFilesHandler.highestBlockNumber(segmentType)
+                                                 .map_or(0, |highestBlockNumber| highestBlockNumber + 1);
+
+    // The static file containing the block is being appended to at the tip of the chain.
+    if self.staticFilesHandler.segmentHeaderOf(segmentType, firstBlockNumber).is_some( ) {
+      return None
+    }
+
+    let blocksPerStaticFile= self.blocksPerStaticFile[&segmentType];
+    let lastBlockNumber= (firstBlockNumber / blocksPerStaticFile + 1) * blocksPerStaticFile - 1;
+
+    (lastBlockNumber <= finalizedBlockNumber).then_some(firstBlockNumber..=lastBlockNumber)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+  use alloy_primitives::U256;
+  use db::{models::{Header, Receipt}, test_utils::{signedTransaction, MemoryDb}};
+  use tokio::time;
+  use crate::static_files_writer::StaticFilesWriter;
+  use super::*;
+
+  // Block n has a single transaction, whose number is n.
+  fn dbWithBlocks(blockNumbers: std::ops::Range<BlockNumber>) -> Arc<MemoryDb> {
+    let db= Arc::new(MemoryDb::default( ));
+    for blockNumber in blockNumbers {
+      let (transaction, sender)= signedTransaction(blockNumber, 0);
+      db.insertBlock(&Header { number: blockNumber, ..Default::default( ) }, U256::from(blockNumber), blockNumber,
+                     &[transaction], &[Receipt::default( )], &[sender]);
+    }
+    db
+  }
+
+  // Waits for an event matching the predicate, and returns it.
+  async fn eventMatching<P>(events: &mut UnboundedReceiver<StaticFilesGeneratorEvent>, predicate: P) -> StaticFilesGeneratorEvent
+    where
+      P: Fn(&StaticFilesGeneratorEvent) -> bool
+  {
+    let matching= async {
+      loop {
+        let event= events.recv( ).await.unwrap( );
+        if predicate(&event) {
+          return event
+        }
+      }
+    };
+    time::timeout(Duration::from_secs(10), matching).await.unwrap( )
+  }
+
+  fn schedulerOf(db: Arc<MemoryDb>,
+                 staticFilesHandler: Arc<StaticFilesHander>,
+                 finalizedBlockNumber: watch::Receiver<Option<BlockNumber>>) -> StaticFilesScheduler<MemoryDb>
+  {
+    SEGMENT_TYPES.into_iter( ).fold(StaticFilesScheduler::new(db, staticFilesHandler, finalizedBlockNumber),
+                                    |staticFilesScheduler, segmentType| staticFilesScheduler.withBlocksPerStaticFile(segmentType, 4))
+  }
+
+  #[test]
+  fn nextBlockRangesAreAlignedAndSkipSegmentsAppendedToAtTheTip( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= Arc::new(MemoryDb::default( ));
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+    let (_, finalizedBlockNumber)= watch::channel(None);
+    let staticFilesScheduler= schedulerOf(db.clone( ), staticFilesHandler.clone( ), finalizedBlockNumber);
+
+    // Only whole block ranges get copied.
+    assert_eq!(staticFilesScheduler.nextBlockRangeOf(SegmentType::BlockHeaders, 2), None);
+    assert_eq!(staticFilesScheduler.nextBlockRangeOf(SegmentType::BlockHeaders, 3), Some(0..=3));
+    assert_eq!(staticFilesScheduler.nextBlockRangeOf(SegmentType::Senders, 10), Some(0..=3));
+
+    // After a static file with a different block range, the next block range is cut short, to get
+    // back in line.
+    let mut staticFilesWriter= SEGMENT_TYPES.into_iter( ).fold(StaticFilesWriter::new(staticFilesHandler.clone( ), db.as_ref( )).unwrap( ),
+                                                               |staticFilesWriter, segmentType| staticFilesWriter.withBlocksPerStaticFile(segmentType, 3));
+    let appendBlock= |staticFilesWriter: &mut StaticFilesWriter, blockNumber: BlockNumber| {
+      let header= Header { number: blockNumber, ..Default::default( ) };
+      staticFilesWriter.appendBlock(&header, header.hash( ), U256::ZERO, &[ ], &[ ], &[ ]).unwrap( );
+    };
+    for blockNumber in 0..3 {
+      appendBlock(&mut staticFilesWriter, blockNumber);
+    }
+    staticFilesWriter.commit( ).unwrap( );
+    assert_eq!(staticFilesScheduler.nextBlockRangeOf(SegmentType::Transactions, 10), Some(3..=3));
+
+    // The static file of block 3 is then being appended to at the tip of the chain.
+    appendBlock(&mut staticFilesWriter, 3);
+    staticFilesWriter.commit( ).unwrap( );
+    assert_eq!(staticFilesScheduler.nextBlockRangeOf(SegmentType::Transactions, 10), None);
+    assert!(staticFilesScheduler.blockRangeOfSegments(10).isEmpty( ));
+  }
+
+  #[tokio::test]
+  async fn shutdownCompletesTheGenerationInProgress( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= dbWithBlocks(0..8);
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    // Two block ranges are finalized, but the shutdown signal is already there.
+    let (_finalizedBlockNumberSender, finalizedBlockNumber)= watch::channel(Some(7));
+    let (shutdownSignalSender, shutdownSignal)= oneshot::channel( );
+    shutdownSignalSender.send(( )).unwrap( );
+
+    let staticFilesScheduler= schedulerOf(db.clone( ), staticFilesHandler.clone( ), finalizedBlockNumber);
+    staticFilesScheduler.spawn(shutdownSignal).await.unwrap( ).unwrap( );
+
+    for segmentType in SEGMENT_TYPES {
+      assert_eq!(staticFilesHandler.highestBlockNumber(segmentType), Some(3));
+    }
+    assert_eq!(staticFilesHandler.highestTxNumber(SegmentType::Senders), Some(3));
+
+    let tables= db.tables( );
+    assert_eq!(tables.headers.keys( ).copied( ).collect::<Vec<_>>( ), (4..8).collect::<Vec<_>>( ));
+    assert_eq!(tables.transactions.keys( ).copied( ).collect::<Vec<_>>( ), (4..8).collect::<Vec<_>>( ));
+    assert_eq!(tables.senders.len( ), 4);
+  }
+
+  #[tokio::test]
+  async fn failedGenerationsAreReportedAndRetriedOnTheNextFinalizedBlock( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= dbWithBlocks(0..8);
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    // The receipt of block 2 is missing at first.
+    let missingReceipt= db.tables( ).receipts.remove(&2).unwrap( );
+
+    let (finalizedBlockNumberSender, finalizedBlockNumber)= watch::channel(Some(3));
+    let (shutdownSignalSender, shutdownSignal)= oneshot::channel( );
+    let mut staticFilesScheduler= schedulerOf(db.clone( ), staticFilesHandler.clone( ), finalizedBlockNumber);
+    let mut events= staticFilesScheduler.subscribe( );
+    let staticFilesScheduler= staticFilesScheduler.spawn(shutdownSignal);
+
+    let failed= eventMatching(&mut events, |event| matches!(event, StaticFilesGeneratorEvent::SegmentFailed { .. })).await;
+    assert!(matches!(failed, StaticFilesGeneratorEvent::SegmentFailed { segmentType: SegmentType::TransactionReceipts, blockRange, .. }
+                               if blockRange == (0..=3)));
+    assert!(!staticFilesScheduler.is_finished( ));
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::BlockHeaders), None);
+    assert_eq!(db.tables( ).headers.len( ), 8);
+
+    // Retried once the next block gets finalized.
+    db.tables( ).receipts.insert(2, missingReceipt);
+    finalizedBlockNumberSender.send(Some(4)).unwrap( );
+
+    eventMatching(&mut events, |event| matches!(event, StaticFilesGeneratorEvent::Finished { .. })).await;
+    shutdownSignalSender.send(( )).unwrap( );
+    staticFilesScheduler.await.unwrap( ).unwrap( );
+
+    for segmentType in SEGMENT_TYPES {
+      assert_eq!(staticFilesHandler.highestBlockNumber(segmentType), Some(3));
+    }
+    let tables= db.tables( );
+    assert_eq!(tables.headers.keys( ).copied( ).collect::<Vec<_>>( ), (4..8).collect::<Vec<_>>( ));
+    assert_eq!(tables.receipts.keys( ).copied( ).collect::<Vec<_>>( ), (4..8).collect::<Vec<_>>( ));
+  }
+}
\ No newline at end of file
@@ -0,0 +1,233 @@
+use std::{panic, sync::Arc, time::Duration};
+use tokio::{
+  sync::{mpsc::UnboundedReceiver, oneshot},
+  task::{self, JoinHandle},
+  time::{self, MissedTickBehavior}
+};
+use super::{
+  static_files_error::StaticFilesError,
+  static_files_handler::StaticFilesHander,
+  static_files_handler_event::StaticFilesHandlerEvent
+};
+
+pub const DEFAULT_POLL_INTERVAL: Duration= Duration::from_secs(5);
+
+/*
+  Keeps a long-running StaticFilesHander up to date, when the static files in its directory get
+  written or truncated by someone else :
+
+  (1) in the same process, by a StaticFilesWriter or StaticFilesGenerator using another
+      StaticFilesHander : that one notifies each of its rescans (see withSource), and this one
+      rescans right away.
+  (2) in another process (e.g. the node, while this handler serves a separate RPC process) : the
+      directory gets polled every pollInterval, and the handler rescans if a static file changed
+      since its last rescan (see StaticFilesHander::rescanIfChanged). Polling only looks at file
+      metadata, so it's cheap.
+
+  Rescans run on a blocking thread, and never block reads (see StaticFilesHander).
+*/
+pub struct StaticFilesWatcher {
+  staticFilesHandler: Arc<StaticFilesHander>,
+
+  // Rescans of the source StaticFilesHander, if any.
+  rescans: Option<UnboundedReceiver<StaticFilesHandlerEvent>>,
+
+  pollInterval: Duration
+}
+
+impl StaticFilesWatcher {
+  pub fn new(staticFilesHandler: Arc<StaticFilesHander>) -> Self {
+    Self {
+      staticFilesHandler,
+      rescans: None,
+      pollInterval: DEFAULT_POLL_INTERVAL
+    }
+  }
+
+  // Rescans whenever the given StaticFilesHander (of the same directory) rescans.
+  pub fn withSource(mut self, staticFilesHandler: &StaticFilesHander) -> Self {
+    self.rescans= Some(staticFilesHandler.subscribe( ));
+    self
+  }
+
+  pub fn withPollInterval(mut self, pollInterval: Duration) -> Self {
+    self.pollInterval= pollInterval.max(Duration::from_millis(1));
+    self
+  }
+
+  // Runs the watcher as a background task, until the shutdown signal is received.
+  pub fn spawn(self, shutdownSignal: oneshot::Receiver<( )>) -> JoinHandle<Result<( ), StaticFilesError>> {
+    tokio::spawn(self.run(shutdownSignal))
+  }
+
+  pub async fn run(mut self, mut shutdownSignal: oneshot::Receiver<( )>) -> Result<( ), StaticFilesError> {
+    let mut pollTimer= time::interval(self.pollInterval);
+    pollTimer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+      tokio::select! {
+        _= pollTimer.tick( ) => {
+          self.rescan(false).await?;
+        },
+
+        rescan= recvRescan(&mut self.rescans) => {
+          match rescan {
+            Some(StaticFilesHandlerEvent::Rescanned { .. }) => self.rescan(true).await?,
+
+            // Other events don't change which static files there are.
+            Some(_) => { },
+
+            // The source StaticFilesHander is gone. Polling goes on.
+            None => self.rescans= None
+          }
+        },
+
+        _= &mut shutdownSignal => return Ok(( ))
+      }
+    }
+  }
+
+  // Rescans (unconditionally if forced, or only if the static files changed otherwise) on a
+  // blocking thread.
+  async fn rescan(&self, force: bool) -> Result<( ), StaticFilesError> {
+    let staticFilesHandler= self.staticFilesHandler.clone( );
+
+    let rescan= task::spawn_blocking(move || {
+      match force {
+        true => staticFilesHandler.rescan( ),
+        false => staticFilesHandler.rescanIfChanged( ).map(|_| ( ))
+      }
+    });
+
+    match rescan.await {
+      Ok(result) => result,
+      Err(joinError) => panic::resume_unwind(joinError.into_panic( ))
+    }
+  }
+}
+
+// Receives the next rescan of the source StaticFilesHander (never, if there's none).
+async fn recvRescan(rescans: &mut Option<UnboundedReceiver<StaticFilesHandlerEvent>>) -> Option<StaticFilesHandlerEvent> {
+  match rescans {
+    Some(rescans) => rescans.recv( ).await,
+    None => std::future::pending( ).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use alloy_primitives::U256;
+  use db::{models::{BlockNumber, Header}, test_utils::MemoryDb};
+  use crate::{nippy_jar::ConsistencyReport, segment::SegmentType, static_files_writer::StaticFilesWriter};
+  use super::*;
+
+  fn appendBlocks(staticFilesWriter: &mut StaticFilesWriter, blockNumbers: std::ops::Range<BlockNumber>) {
+    for blockNumber in blockNumbers {
+      let header= Header { number: blockNumber, ..Default::default( ) };
+      staticFilesWriter.appendBlock(&header, header.hash( ), U256::ZERO, &[ ], &[ ], &[ ]).unwrap( );
+    }
+    staticFilesWriter.commit( ).unwrap( );
+  }
+
+  // Waits until the StaticFilesHander rescans, and finds the given highest block.
+  async fn rescannedUpTo(events: &mut UnboundedReceiver<StaticFilesHandlerEvent>, blockNumber: BlockNumber) {
+    let rescanned= async {
+      while let Some(event)= events.recv( ).await {
+        if let StaticFilesHandlerEvent::Rescanned { highestBlockNumbers }= event {
+          if highestBlockNumbers.get(&SegmentType::BlockHeaders) == Some(&blockNumber) {
+            return
+          }
+        }
+      }
+    };
+    time::timeout(Duration::from_secs(10), rescanned).await.unwrap( );
+  }
+
+  #[test]
+  fn commitsOfAnotherHandlerArePickedUpOnlyOnce( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= MemoryDb::default( );
+    let writerHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+    let readerHandler= StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( );
+
+    let mut staticFilesWriter= StaticFilesWriter::new(writerHandler, &db).unwrap( );
+    assert!(!readerHandler.rescanIfChanged( ).unwrap( ));
+
+    appendBlocks(&mut staticFilesWriter, 0..3);
+    assert_eq!(readerHandler.highestBlockNumber(SegmentType::BlockHeaders), None);
+    assert!(readerHandler.rescanIfChanged( ).unwrap( ));
+    assert_eq!(readerHandler.highestBlockNumber(SegmentType::BlockHeaders), Some(2));
+    assert!(!readerHandler.rescanIfChanged( ).unwrap( ));
+
+    appendBlocks(&mut staticFilesWriter, 3..5);
+    assert!(readerHandler.rescanIfChanged( ).unwrap( ));
+    assert_eq!(readerHandler.headerByNumber(4).unwrap( ).map(|header| header.number), Some(4));
+  }
+
+  #[tokio::test]
+  async fn watchersPickUpCommitsThroughTheSourceOrByPolling( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= MemoryDb::default( );
+    let writerHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    // One watcher only hears from the source, the other one only polls.
+    let notifiedHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+    let mut notifiedHandlerEvents= notifiedHandler.subscribe( );
+    let (notifiedShutdownSignalSender, notifiedShutdownSignal)= oneshot::channel( );
+    let notifiedWatcher= StaticFilesWatcher::new(notifiedHandler.clone( ))
+      .withSource(&writerHandler)
+      .withPollInterval(Duration::from_secs(3600))
+      .spawn(notifiedShutdownSignal);
+
+    let pollingHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+    let mut pollingHandlerEvents= pollingHandler.subscribe( );
+    let (pollingShutdownSignalSender, pollingShutdownSignal)= oneshot::channel( );
+    let pollingWatcher= StaticFilesWatcher::new(pollingHandler.clone( ))
+      .withPollInterval(Duration::from_millis(10))
+      .spawn(pollingShutdownSignal);
+
+    let mut staticFilesWriter= StaticFilesWriter::new(writerHandler, &db).unwrap( );
+    appendBlocks(&mut staticFilesWriter, 0..3);
+
+    rescannedUpTo(&mut notifiedHandlerEvents, 2).await;
+    rescannedUpTo(&mut pollingHandlerEvents, 2).await;
+    assert_eq!(notifiedHandler.headerByNumber(2).unwrap( ).map(|header| header.number), Some(2));
+    assert_eq!(pollingHandler.headerByNumber(2).unwrap( ).map(|header| header.number), Some(2));
+
+    notifiedShutdownSignalSender.send(( )).unwrap( );
+    pollingShutdownSignalSender.send(( )).unwrap( );
+    notifiedWatcher.await.unwrap( ).unwrap( );
+    pollingWatcher.await.unwrap( ).unwrap( );
+  }
+
+  #[tokio::test]
+  async fn onlyRescansOfTheSourceTriggerARescan( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let sourceHandler= StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( );
+
+    let watchedHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+    let mut watchedHandlerEvents= watchedHandler.subscribe( );
+    let (shutdownSignalSender, shutdownSignal)= oneshot::channel( );
+    let watcher= StaticFilesWatcher::new(watchedHandler)
+      .withSource(&sourceHandler)
+      .withPollInterval(Duration::from_secs(3600))
+      .spawn(shutdownSignal);
+
+    // Events are handled in order, so a rescan due to the Recovered event would be seen first.
+    sourceHandler.emit(StaticFilesHandlerEvent::Recovered {
+      segmentType: SegmentType::BlockHeaders,
+      blockRange: 0..=499_999,
+      consistencyReport: ConsistencyReport::default( )
+    });
+    sourceHandler.rescan( ).unwrap( );
+
+    let event= time::timeout(Duration::from_secs(10), watchedHandlerEvents.recv( )).await.unwrap( );
+    assert!(matches!(event, Some(StaticFilesHandlerEvent::Rescanned { .. })));
+
+    // Gives the watcher time to handle a second rescan, if there were one.
+    time::sleep(Duration::from_millis(100)).await;
+    shutdownSignalSender.send(( )).unwrap( );
+    watcher.await.unwrap( ).unwrap( );
+    assert!(watchedHandlerEvents.try_recv( ).is_err( ));
+  }
+}
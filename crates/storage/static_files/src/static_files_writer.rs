@@ -0,0 +1,548 @@
+use std::{collections::HashMap, ops::RangeInclusive, path::Path, sync::Arc};
+use alloy_primitives::{Address, U256};
+use db::{interfaces, models::{BlockHash, BlockNumber, Header, Receipt, TransactionSigned, TxNumber}};
+use super::{
+  block_headers_segment::{self, BlockHeadersSegment},
+  transactions_segment::{self, TransactionsSegment},
+  transaction_receipts_segment::{self, TransactionReceiptsSegment},
+  senders_segment::{self, SendersSegment},
+  nippy_jar::{NippyJar, NippyJarError, NippyJarWriter},
+  segment::{Segment, SegmentHeader, SegmentType, DEFAULT_BLOCKS_PER_STATIC_FILE, SEGMENT_TYPES},
+  static_files_error::StaticFilesError,
+  static_files_handler::StaticFilesHander,
+  static_files_handler_event::StaticFilesHandlerEvent,
+  static_files_manifest::StaticFilesManifest
+};
+
+/*
+  Appends finalized blocks (their headers, transactions, receipts and transaction senders) directly
+  to static files, at the tip of the chain.
+
+  For each segment, blocks are appended to the static file of the block range containing them.
+  Once a block beyond that range is appended, the static files get committed and a new one is
+  created for the next block range. The size of the block ranges is configurable per segment (see
+  withBlocksPerStaticFile), and new block ranges are aligned to it. A block range may be shorter
+  than that, if it continues after a static file with a different block range (e.g. produced by the
+  StaticFilesGenerator, or before the size was changed). Since each static file has its block range
+  in its SegmentHeader, static files with different block range sizes coexist.
+
+  Appended blocks become durable and visible to readers (of the StaticFilesHander) only on commit.
+  The StaticFilesManifest gets updated on commit (and truncation) as well.
+  The segments are committed one by one, with the block headers segment last. So the highest block
+  in the block headers segment is always fully present in the other segments.
+
+  Since dictionaries can't be trained before the values are known, the transactions and
+  transaction receipts columns are zstd compressed without a dictionary.
+*/
+pub struct StaticFilesWriter {
+  staticFilesHandler: Arc<StaticFilesHander>,
+
+  // Number of blocks in each new static file, for each segment.
+  blocksPerStaticFile: HashMap<SegmentType, u64>,
+
+  // Writers of the static files currently being appended to, for each segment.
+  segmentWriters: HashMap<SegmentType, SegmentWriter>,
+
+  // Block number and transaction number, which the next block and its first transaction will get.
+  nextBlockNumber: BlockNumber,
+  nextTxNumber: TxNumber,
+
+  // Set when appending or committing fails midway, leaving the segment writers with a partially
+  // appended block or partially committed. The StaticFilesWriter then refuses to do anything, until
+  // it's reopened (which recovers the static files from what was committed).
+  isPoisoned: bool
+}
+
+struct SegmentWriter {
+  blockRange: RangeInclusive<BlockNumber>,
+  writer: NippyJarWriter<SegmentHeader>
+}
+
+impl StaticFilesWriter {
+  /*
+    Continues after the highest block found in the static files (or from the genesis block, if
+    there are no static files yet).
+
+    If the node died while committing (or truncating), some segments may end after the others. They
+    are truncated back to the block where all the segments end. If some segment has no committed
+    block at all (the node died during the very first commit), the static files of all the
+    segments are removed.
+
+    Directories written before the senders segment was added have no static files of it. The
+    senders segment then starts from the next block appended, and is left out of the check above.
+  */
+  pub fn new<Db>(staticFilesHandler: Arc<StaticFilesHander>, db: &Db) -> Result<Self, StaticFilesError>
+    where
+      Db: interfaces::db::Db
+  {
+    let hasSenders= !staticFilesHandler.segmentHeaders(SegmentType::Senders).is_empty( );
+    let highestBlockNumbers: Vec<Option<BlockNumber>>= SEGMENT_TYPES.into_iter( )
+      .filter(|segmentType| *segmentType != SegmentType::Senders || hasSenders)
+      .map(|segmentType| staticFilesHandler.highestBlockNumber(segmentType))
+      .collect( );
+
+    if highestBlockNumbers.iter( ).any(|highestBlockNumber| *highestBlockNumber != highestBlockNumbers[0]) {
+      // None (no committed block) is the minimum of Options.
+      let highestCommonBlockNumber= highestBlockNumbers.iter( ).min( ).copied( ).flatten( );
+      truncateSegmentsToBlock(db, staticFilesHandler.directory( ), highestCommonBlockNumber)?;
+      staticFilesHandler.rescan( )?;
+      StaticFilesManifest::update(&staticFilesHandler)?;
+    }
+
+    let mut staticFilesWriter= Self {
+      staticFilesHandler,
+      blocksPerStaticFile: SEGMENT_TYPES.map(|segmentType| (segmentType, DEFAULT_BLOCKS_PER_STATIC_FILE)).into( ),
+      segmentWriters: HashMap::new( ),
+      nextBlockNumber: 0,
+      nextTxNumber: 0,
+      isPoisoned: false
+    };
+    staticFilesWriter.resetNextNumbers( );
+
+    Ok(staticFilesWriter)
+  }
+
+  // Sets the number of blocks in each new static file of the given segment. Static files which
+  // already exist keep their block range.
+  pub fn withBlocksPerStaticFile(mut self, segmentType: SegmentType, blocksPerStaticFile: u64) -> Self {
+    self.blocksPerStaticFile.insert(segmentType, blocksPerStaticFile.max(1));
+    self
+  }
+
+  pub fn nextBlockNumber(&self) -> BlockNumber {
+    self.nextBlockNumber
+  }
+
+  pub fn nextTxNumber(&self) -> TxNumber {
+    self.nextTxNumber
+  }
+
+  // Appends a block. Blocks must be appended in order, and the receipts and senders must correspond
+  // to the transactions. The transactions get numbered sequentially, starting from nextTxNumber.
+  // If it fails after rows got appended, the StaticFilesWriter is poisoned (see isPoisoned).
+  pub fn appendBlock(&mut self,
+                     header: &Header,
+                     blockHash: BlockHash,
+                     totalDifficulty: U256,
+                     transactions: &[TransactionSigned],
+                     receipts: &[Receipt],
+                     senders: &[Address]) -> Result<( ), StaticFilesError>
+  {
+    if self.isPoisoned {
+      return Err(StaticFilesError::WriterPoisoned)
+    }
+    if header.number != self.nextBlockNumber {
+      return Err(StaticFilesError::UnexpectedBlock { expected: self.nextBlockNumber, got: header.number })
+    }
+    if transactions.len( ) != receipts.len( ) {
+      return Err(StaticFilesError::ReceiptCountMismatch {
+        transactions: transactions.len( ),
+        receipts: receipts.len( )
+      })
+    }
+    if transactions.len( ) != senders.len( ) {
+      return Err(StaticFilesError::SenderCountMismatch {
+        transactions: transactions.len( ),
+        senders: senders.len( )
+      })
+    }
+
+    // Everything is encoded upfront, so that a failure doesn't leave the block partially appended.
+    let encodedReceipts= receipts.iter( )
+                                 .map(compression::serde_bridge::toBytes)
+                                 .collect::<Result<Vec<_>, _>>( )?;
+
+    // Roll over to the next static file, for the segments whose static file doesn't cover the block.
+    // Everything is committed first, so that the segments stay in sync on disk.
+    let segmentTypesToRollOver: Vec<SegmentType>= SEGMENT_TYPES.into_iter( )
+      .filter(|segmentType| {
+        !self.segmentWriters.get(segmentType)
+                            .is_some_and(|segmentWriter| segmentWriter.blockRange.contains(&header.number))
+      })
+      .collect( );
+    if !segmentTypesToRollOver.is_empty( ) {
+      self.commit( )?;
+    }
+    for segmentType in segmentTypesToRollOver {
+      let segmentWriter= self.openSegmentWriter(segmentType, header.number)?;
+      self.segmentWriters.insert(segmentType, segmentWriter);
+    }
+
+    // The header row depends on the compression of the header column of the static file (see
+    // block_headers_segment), so it's only encoded now. Nothing got appended yet, if it fails.
+    let headersWriter= self.writerOf(SegmentType::BlockHeaders);
+    let headerRow= block_headers_segment::headerRow(header, totalDifficulty, blockHash,
+                                                    headersWriter.jar( ).columnCompressions( )[block_headers_segment::HEADER_COLUMN])?;
+
+    let result= self.appendRows(headerRow, transactions, encodedReceipts, senders);
+    self.poisonOnError(result)?;
+
+    let txRange= (!transactions.is_empty( ))
+                   .then(|| self.nextTxNumber..=(self.nextTxNumber + transactions.len( ) as u64 - 1));
+
+    for SegmentWriter { writer, .. } in self.segmentWriters.values_mut( ) {
+      let segmentHeader= writer.userHeaderMut( );
+      segmentHeader.highestBlock= Some(header.number);
+
+      if let (true, Some(txRange))= (segmentHeader.segmentType.isTxBased( ), &txRange) {
+        let firstTxNumber= segmentHeader.txRange.as_ref( ).map_or(*txRange.start( ), |range| *range.start( ));
+        segmentHeader.txRange= Some(firstTxNumber..=*txRange.end( ));
+      }
+    }
+
+    self.nextBlockNumber += 1;
+    self.nextTxNumber += transactions.len( ) as u64;
+
+    Ok(( ))
+  }
+
+  // Makes the appended blocks durable and visible to readers.
+  pub fn commit(&mut self) -> Result<( ), StaticFilesError> {
+    if self.isPoisoned {
+      return Err(StaticFilesError::WriterPoisoned)
+    }
+    if self.segmentWriters.is_empty( ) {
+      return Ok(( ))
+    }
+
+    let result= self.commitSegments( );
+    self.poisonOnError(result)
+  }
+
+  fn commitSegments(&mut self) -> Result<( ), StaticFilesError> {
+    // The block headers segment is committed last. Static files containing their whole block range
+    // won't be appended to anymore, so they get finished (their key index gets built).
+    for segmentType in SEGMENT_TYPES.iter( ).rev( ) {
+      if let Some(SegmentWriter { blockRange, writer })= self.segmentWriters.get_mut(segmentType) {
+        match writer.jar( ).userHeader( ).highestBlock == Some(*blockRange.end( )) {
+          true => writer.finish( )?,
+          false => writer.commit( )?
+        }
+      }
+    }
+
+    self.staticFilesHandler.rescan( )?;
+    StaticFilesManifest::update(&self.staticFilesHandler)
+  }
+
+  /*
+    Removes everything after the given block from the static files (e.g. when the chain gets rolled
+    back), so that they never hold non-canonical data. The next block to be appended is the one
+    after the given block.
+
+    Blocks appended but not yet committed, are committed first.
+  */
+  pub fn truncateToBlock<Db>(&mut self, db: &Db, blockNumber: BlockNumber) -> Result<( ), StaticFilesError>
+    where
+      Db: interfaces::db::Db
+  {
+    if blockNumber >= self.nextBlockNumber {
+      return Ok(( ))
+    }
+
+    self.commit( )?;
+    self.segmentWriters.clear( );
+
+    truncateSegmentsToBlock(db, self.staticFilesHandler.directory( ), Some(blockNumber))?;
+    self.staticFilesHandler.rescan( )?;
+    StaticFilesManifest::update(&self.staticFilesHandler)?;
+
+    self.resetNextNumbers( );
+    Ok(( ))
+  }
+
+  // Appends the rows of a block to the segment writers. Nothing can be assumed about the rows
+  // appended, if it fails.
+  fn appendRows(&mut self,
+                headerRow: [Vec<u8>; 3],
+                transactions: &[TransactionSigned],
+                encodedReceipts: Vec<Vec<u8>>,
+                senders: &[Address]) -> Result<( ), StaticFilesError>
+  {
+    self.writerOf(SegmentType::BlockHeaders).appendRow(headerRow)?;
+
+    for transaction in transactions {
+      self.writerOf(SegmentType::Transactions).appendRow([transaction.hash.as_slice( ), &transaction.encoded])?;
+    }
+    for encodedReceipt in encodedReceipts {
+      self.writerOf(SegmentType::TransactionReceipts).appendRow([encodedReceipt])?;
+    }
+    for sender in senders {
+      self.writerOf(SegmentType::Senders).appendRow([sender.as_slice( )])?;
+    }
+
+    Ok(( ))
+  }
+
+  fn poisonOnError<T>(&mut self, result: Result<T, StaticFilesError>) -> Result<T, StaticFilesError> {
+    self.isPoisoned |= result.is_err( );
+    result
+  }
+
+  fn resetNextNumbers(&mut self) {
+    self.nextBlockNumber= self.staticFilesHandler.highestBlockNumber(SegmentType::BlockHeaders)
+                                                 .map_or(0, |highestBlockNumber| highestBlockNumber + 1);
+    self.nextTxNumber= self.staticFilesHandler.highestTxNumber(SegmentType::Transactions)
+                                              .map_or(0, |highestTxNumber| highestTxNumber + 1);
+  }
+
+  // Returns the writer of the static file currently being appended to, for the given segment.
+  fn writerOf(&mut self, segmentType: SegmentType) -> &mut NippyJarWriter<SegmentHeader> {
+    &mut self.segmentWriters.get_mut(&segmentType).unwrap( ).writer
+  }
+
+  /*
+    Opens the static file of the given segment, for the block range containing the given block. If
+    there's no such static file yet, it's created for a new block range starting at the block.
+  */
+  fn openSegmentWriter(&self, segmentType: SegmentType, blockNumber: BlockNumber) -> Result<SegmentWriter, StaticFilesError> {
+    let blocksPerStaticFile= self.blocksPerStaticFile[&segmentType];
+
+    let blockRange= self.staticFilesHandler.segmentHeaderOf(segmentType, blockNumber)
+      .map(|segmentHeader| segmentHeader.blockRange)
+      .unwrap_or(blockNumber..=((blockNumber / blocksPerStaticFile + 1) * blocksPerStaticFile - 1));
+
+    let writer= self.openNippyJarWriter(segmentType, &blockRange)?;
+    Ok(SegmentWriter { blockRange, writer })
+  }
+
+  fn openNippyJarWriter(&self,
+                        segmentType: SegmentType,
+                        blockRange: &RangeInclusive<BlockNumber>) -> Result<NippyJarWriter<SegmentHeader>, StaticFilesError>
+  {
+    let directory= self.staticFilesHandler.directory( );
+
+    match NippyJar::load(&directory.join(segmentType.fileName(blockRange))) {
+      Ok(jar) => {
+        let writer= NippyJarWriter::new(jar)?;
+        if !writer.consistencyReport( ).isClean( ) {
+          self.staticFilesHandler.emit(StaticFilesHandlerEvent::Recovered {
+            segmentType,
+            blockRange: blockRange.clone( ),
+            consistencyReport: writer.consistencyReport( ).clone( )
+          });
+        }
+        return Ok(writer)
+      },
+      Err(NippyJarError::ConfigNotFound(_)) => { },
+      Err(error) => return Err(error.into( ))
+    }
+
+    let segmentHeader= SegmentHeader {
+      segmentType,
+      blockRange: blockRange.clone( ),
+      highestBlock: None,
+      txRange: None
+    };
+    let jar= match segmentType {
+      SegmentType::BlockHeaders => block_headers_segment::newJar(directory, segmentHeader)?,
+      SegmentType::Transactions => transactions_segment::newJar(directory, segmentHeader)?,
+      SegmentType::TransactionReceipts => transaction_receipts_segment::newJar(directory, segmentHeader)?,
+      SegmentType::Senders => senders_segment::newJar(directory, segmentHeader)?
+    };
+    Ok(NippyJarWriter::new(jar)?)
+  }
+}
+
+// Truncates the static files of all the segments to the given block (or removes them, without a
+// block). The block headers segment is truncated first (the reverse of the commit order), so that
+// the highest block in it is always fully present in the other segments.
+fn truncateSegmentsToBlock<Db>(db: &Db, directory: &Path, blockNumber: Option<BlockNumber>) -> Result<( ), StaticFilesError>
+  where
+    Db: interfaces::db::Db
+{
+  let segments: [Box<dyn Segment<Db>>; 4]= [
+    Box::new(BlockHeadersSegment { }),
+    Box::new(TransactionsSegment { }),
+    Box::new(TransactionReceiptsSegment { }),
+    Box::new(SendersSegment { })
+  ];
+
+  for segment in segments {
+    segment.truncateToBlock(db, directory, blockNumber)?;
+  }
+
+  Ok(( ))
+}
+
+#[cfg(test)]
+mod tests {
+  use alloy_primitives::{keccak256, Bytes};
+  use db::{models::BlockBodyIndices, test_utils::MemoryDb};
+  use crate::nippy_jar::KeyIndexKind;
+  use super::*;
+
+  // Block n has n % 3 transactions. Block body indices always live in the database.
+  fn appendBlock(staticFilesWriter: &mut StaticFilesWriter, db: &MemoryDb, blockNumber: BlockNumber) {
+    let header= Header { number: blockNumber, ..Default::default( ) };
+    let transactions: Vec<TransactionSigned>= (0..(blockNumber % 3))
+      .map(|i| {
+        let encoded= Bytes::from(vec![blockNumber as u8, i as u8]);
+        TransactionSigned { hash: keccak256(&encoded), encoded }
+      })
+      .collect( );
+    let receipts= vec![Receipt::default( ); transactions.len( )];
+    let senders= vec![Address::with_last_byte(blockNumber as u8); transactions.len( )];
+
+    let blockBodyIndices= BlockBodyIndices { firstTxNumber: staticFilesWriter.nextTxNumber( ), txCount: transactions.len( ) as u64 };
+    db.tables( ).blockBodyIndices.insert(blockNumber, blockBodyIndices);
+
+    staticFilesWriter.appendBlock(&header, header.hash( ), U256::from(blockNumber), &transactions, &receipts, &senders).unwrap( );
+  }
+
+  fn keyIndexKindOf(directory: &Path, segmentType: SegmentType, blockRange: RangeInclusive<BlockNumber>) -> KeyIndexKind {
+    NippyJar::<SegmentHeader>::load(&directory.join(segmentType.fileName(&blockRange))).unwrap( ).keyIndexKind( )
+  }
+
+  #[test]
+  fn blocksRollOverToNewStaticFilesAndCanBeTruncated( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= MemoryDb::default( );
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    let mut staticFilesWriter= SEGMENT_TYPES.into_iter( ).fold(StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( ),
+                                                               |staticFilesWriter, segmentType| staticFilesWriter.withBlocksPerStaticFile(segmentType, 4));
+    for blockNumber in 0..10 {
+      appendBlock(&mut staticFilesWriter, &db, blockNumber);
+    }
+    staticFilesWriter.commit( ).unwrap( );
+
+    assert_eq!(staticFilesHandler.segmentHeaders(SegmentType::BlockHeaders).len( ), 3);
+    assert_eq!(staticFilesHandler.highestTxNumber(SegmentType::Transactions), Some(8));
+
+    // Complete static files are finished, the one being appended to isn't.
+    assert_eq!(keyIndexKindOf(directory.path( ), SegmentType::Transactions, 0..=3), KeyIndexKind::KeyIndex);
+    assert_eq!(keyIndexKindOf(directory.path( ), SegmentType::BlockHeaders, 4..=7), KeyIndexKind::KeyIndex);
+    assert_eq!(keyIndexKindOf(directory.path( ), SegmentType::BlockHeaders, 8..=11), KeyIndexKind::KeyTable);
+
+    let header9= Header { number: 9, ..Default::default( ) };
+    assert_eq!(staticFilesHandler.headerByHash(header9.hash( )).unwrap( ), Some(header9.clone( )));
+
+    // Rolling back to block 5 removes the last static file, and truncates the previous one.
+    staticFilesWriter.truncateToBlock(&db, 5).unwrap( );
+    assert_eq!((staticFilesWriter.nextBlockNumber( ), staticFilesWriter.nextTxNumber( )), (6, 6));
+    assert_eq!(staticFilesHandler.segmentHeaders(SegmentType::BlockHeaders).len( ), 2);
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::Senders), Some(5));
+    assert_eq!(staticFilesHandler.headerByNumber(6).unwrap( ), None);
+    assert_eq!(staticFilesHandler.headerByHash(header9.hash( )).unwrap( ), None);
+
+    for blockNumber in 6..9 {
+      appendBlock(&mut staticFilesWriter, &db, blockNumber);
+    }
+    staticFilesWriter.commit( ).unwrap( );
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::BlockHeaders), Some(8));
+    assert_eq!(staticFilesHandler.highestTxNumber(SegmentType::TransactionReceipts), Some(8));
+  }
+
+  #[test]
+  fn segmentsAreRemovedIfTheNodeDiedDuringTheFirstCommit( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= MemoryDb::default( );
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( );
+    for blockNumber in 0..5 {
+      appendBlock(&mut staticFilesWriter, &db, blockNumber);
+    }
+
+    // Like the node dying right after committing the senders segment (which is committed first).
+    staticFilesWriter.writerOf(SegmentType::Senders).commit( ).unwrap( );
+    drop(staticFilesWriter);
+    staticFilesHandler.rescan( ).unwrap( );
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::Senders), Some(4));
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::BlockHeaders), None);
+
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( );
+    assert_eq!((staticFilesWriter.nextBlockNumber( ), staticFilesWriter.nextTxNumber( )), (0, 0));
+    assert!(staticFilesHandler.segmentHeaders(SegmentType::Senders).is_empty( ));
+
+    for blockNumber in 0..3 {
+      appendBlock(&mut staticFilesWriter, &db, blockNumber);
+    }
+    staticFilesWriter.commit( ).unwrap( );
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::Senders), Some(2));
+    assert_eq!(staticFilesHandler.highestTxNumber(SegmentType::Senders), Some(2));
+    assert_eq!(staticFilesHandler.senderById(2).unwrap( ), Some(Address::with_last_byte(2)));
+  }
+
+  #[test]
+  fn failedCommitPoisonsTheWriterUntilItsReopened( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= MemoryDb::default( );
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( );
+    for blockNumber in 0..3 {
+      appendBlock(&mut staticFilesWriter, &db, blockNumber);
+    }
+    staticFilesWriter.commit( ).unwrap( );
+    for blockNumber in 3..5 {
+      appendBlock(&mut staticFilesWriter, &db, blockNumber);
+    }
+
+    // The config of the block headers static file (committed last) can't be saved anymore.
+    let headersPath= directory.path( ).join(SegmentType::BlockHeaders.fileName(&(0..=(DEFAULT_BLOCKS_PER_STATIC_FILE - 1))));
+    let temporaryConfigPath= NippyJar::<SegmentHeader>::configPathOf(&headersPath).with_extension("conf.tmp");
+    std::fs::create_dir(&temporaryConfigPath).unwrap( );
+
+    assert!(staticFilesWriter.commit( ).is_err( ));
+    assert!(matches!(staticFilesWriter.commit( ), Err(StaticFilesError::WriterPoisoned)));
+
+    let header5= Header { number: 5, ..Default::default( ) };
+    assert!(matches!(staticFilesWriter.appendBlock(&header5, header5.hash( ), U256::from(5), &[ ], &[ ], &[ ]),
+                     Err(StaticFilesError::WriterPoisoned)));
+    drop(staticFilesWriter);
+
+    // Reopening recovers from the partial commit.
+    std::fs::remove_dir(&temporaryConfigPath).unwrap( );
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( );
+    assert_eq!((staticFilesWriter.nextBlockNumber( ), staticFilesWriter.nextTxNumber( )), (3, 3));
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::Senders), Some(2));
+
+    for blockNumber in 3..6 {
+      appendBlock(&mut staticFilesWriter, &db, blockNumber);
+    }
+    staticFilesWriter.commit( ).unwrap( );
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::BlockHeaders), Some(5));
+  }
+
+  #[test]
+  fn directoriesWithoutSendersGetASendersSegmentFromTheNextBlock( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= MemoryDb::default( );
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( );
+    for blockNumber in 0..5 {
+      appendBlock(&mut staticFilesWriter, &db, blockNumber);
+    }
+    staticFilesWriter.commit( ).unwrap( );
+    drop(staticFilesWriter);
+
+    // Like a directory written before the senders segment was added.
+    let sendersFilePrefix= SegmentType::Senders.fileName(&(0..=0));
+    let sendersFilePrefix= sendersFilePrefix.trim_end_matches("_0_0");
+    for entry in std::fs::read_dir(directory.path( )).unwrap( ) {
+      let path= entry.unwrap( ).path( );
+      if path.file_name( ).unwrap( ).to_str( ).unwrap( ).starts_with(sendersFilePrefix) {
+        std::fs::remove_file(path).unwrap( );
+      }
+    }
+    staticFilesHandler.rescan( ).unwrap( );
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::Senders), None);
+
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), &db).unwrap( );
+    assert_eq!((staticFilesWriter.nextBlockNumber( ), staticFilesWriter.nextTxNumber( )), (5, 4));
+
+    for blockNumber in 5..7 {
+      appendBlock(&mut staticFilesWriter, &db, blockNumber);
+    }
+    staticFilesWriter.commit( ).unwrap( );
+
+    let sendersSegmentHeaders= staticFilesHandler.segmentHeaders(SegmentType::Senders);
+    assert_eq!(sendersSegmentHeaders.len( ), 1);
+    assert_eq!(*sendersSegmentHeaders[0].blockRange.start( ), 5);
+    assert_eq!(sendersSegmentHeaders[0].txRange, Some(4..=5));
+    assert_eq!(staticFilesHandler.senderById(5).unwrap( ), Some(Address::with_last_byte(5)));
+  }
+}
@@ -1,5 +1,81 @@
-use super::segment::Segment;
+use std::{ops::RangeInclusive, path::Path, time::Instant};
+use db::{interfaces::{self, transaction::{DbTx, RoDbTx}}, models::BlockNumber};
+use super::{
+  nippy_jar::{ColumnCompression, NippyJar, NippyJarWriter},
+  segment::{newSegmentJar, sampleNumbersOf, txRangeOf, useDictionaryCompression, Segment, SegmentHeader, SegmentType},
+  static_files_error::StaticFilesError,
+  static_files_generator_event::SegmentMetrics
+};
 
+/*
+  Each row contains a single column : the transaction receipt (serde bridge encoded), zstd
+  compressed (with a dictionary trained from receipts of the block range, when the whole block range
+  is copied at once).
+
+  The row number is the transaction number minus the first transaction number of the static file.
+*/
 pub struct TransactionReceiptsSegment { }
 
-impl Segment for TransactionReceiptsSegment { }
\ No newline at end of file
+pub const RECEIPT_COLUMN: usize= 0;
+
+// Creates the (empty) NippyJar which will contain the transaction receipts segment.
+pub(crate) fn newJar(directory: &Path, segmentHeader: SegmentHeader) -> Result<NippyJar<SegmentHeader>, StaticFilesError> {
+  Ok(newSegmentJar(directory, 1, segmentHeader)?.withColumnCompressions(vec![ColumnCompression::Zstd]))
+}
+
+impl<Db> Segment<Db> for TransactionReceiptsSegment
+  where
+    Db: interfaces::db::Db
+{
+  fn segmentType(&self) -> SegmentType {
+    SegmentType::TransactionReceipts
+  }
+
+  fn copyToStaticFiles(&self,
+                       db: &Db,
+                       directory: &Path,
+                       blockRange: RangeInclusive<BlockNumber>) -> Result<SegmentMetrics, StaticFilesError>
+  {
+    let startTime= Instant::now( );
+
+    db.withRoDbTx(|roDbTx| -> Result<SegmentMetrics, StaticFilesError> {
+      let txRange= txRangeOf(roDbTx, &blockRange)?;
+
+      let segmentHeader= SegmentHeader {
+        segmentType: SegmentType::TransactionReceipts,
+        blockRange: blockRange.clone( ),
+        highestBlock: Some(*blockRange.end( )),
+        txRange: txRange.clone( )
+      };
+      let mut jar= newJar(directory, segmentHeader)?;
+
+      let encodedReceiptOf= |txNumber| -> Result<Vec<u8>, StaticFilesError> {
+        let receipt= roDbTx.receipt(txNumber)?
+                           .ok_or(StaticFilesError::MissingFromDb { data: "receipt", key: txNumber })?;
+        Ok(compression::serde_bridge::toBytes(&receipt)?)
+      };
+
+      if let Some(txRange)= &txRange {
+        let samples= sampleNumbersOf(txRange).map(encodedReceiptOf).collect::<Result<Vec<_>, _>>( )?;
+        jar= useDictionaryCompression(jar, RECEIPT_COLUMN, samples)?;
+      }
+
+      let mut writer= NippyJarWriter::new(jar)?;
+      for txNumber in txRange.into_iter( ).flatten( ) {
+        writer.appendRow([encodedReceiptOf(txNumber)?])?;
+      }
+      writer.finish( )?;
+
+      Ok(SegmentMetrics::of(&writer, startTime.elapsed( )))
+    })?
+  }
+
+  fn pruneDb(&self, db: &Db, blockRange: RangeInclusive<BlockNumber>) -> Result<( ), StaticFilesError> {
+    db.withDbTx(|dbTx| -> Result<( ), StaticFilesError> {
+      if let Some(txRange)= txRangeOf(dbTx, &blockRange)? {
+        dbTx.deleteReceipts(txRange)?;
+      }
+      Ok(( ))
+    })?
+  }
+}
@@ -1,5 +1,87 @@
-use super::segment::Segment;
+use std::{ops::RangeInclusive, path::Path, time::Instant};
+use db::{interfaces::{self, transaction::{DbTx, RoDbTx}}, models::BlockNumber};
+use super::{
+  nippy_jar::{ColumnCompression, NippyJar, NippyJarWriter},
+  segment::{newSegmentJar, sampleNumbersOf, txRangeOf, useDictionaryCompression, Segment, SegmentHeader, SegmentType},
+  static_files_error::StaticFilesError,
+  static_files_generator_event::SegmentMetrics
+};
 
+/*
+  Each row contains 2 columns :
+
+  (1) the transaction hash, which is the key column (so that transactions can be looked up by hash)
+  (2) the EIP 2718 encoded transaction, zstd compressed (with a dictionary trained from transactions
+      of the block range, when the whole block range is copied at once)
+
+  The row number is the transaction number minus the first transaction number of the static file.
+*/
 pub struct TransactionsSegment { }
 
-impl Segment for TransactionsSegment { }
\ No newline at end of file
+pub const TX_HASH_COLUMN: usize= 0;
+pub const TRANSACTION_COLUMN: usize= 1;
+
+// Creates the (empty) NippyJar which will contain the transactions segment.
+pub(crate) fn newJar(directory: &Path, segmentHeader: SegmentHeader) -> Result<NippyJar<SegmentHeader>, StaticFilesError> {
+  Ok(newSegmentJar(directory, 2, segmentHeader)?
+       .withColumnCompressions(vec![ColumnCompression::None, ColumnCompression::Zstd])
+       .withKeyColumn(TX_HASH_COLUMN))
+}
+
+impl<Db> Segment<Db> for TransactionsSegment
+  where
+    Db: interfaces::db::Db
+{
+  fn segmentType(&self) -> SegmentType {
+    SegmentType::Transactions
+  }
+
+  fn copyToStaticFiles(&self,
+                       db: &Db,
+                       directory: &Path,
+                       blockRange: RangeInclusive<BlockNumber>) -> Result<SegmentMetrics, StaticFilesError>
+  {
+    let startTime= Instant::now( );
+
+    db.withRoDbTx(|roDbTx| -> Result<SegmentMetrics, StaticFilesError> {
+      let txRange= txRangeOf(roDbTx, &blockRange)?;
+
+      let segmentHeader= SegmentHeader {
+        segmentType: SegmentType::Transactions,
+        blockRange: blockRange.clone( ),
+        highestBlock: Some(*blockRange.end( )),
+        txRange: txRange.clone( )
+      };
+      let mut jar= newJar(directory, segmentHeader)?;
+
+      let transactionOf= |txNumber|
+        roDbTx.transaction(txNumber)?
+              .ok_or(StaticFilesError::MissingFromDb { data: "transaction", key: txNumber });
+
+      if let Some(txRange)= &txRange {
+        let samples= sampleNumbersOf(txRange)
+                       .map(|txNumber| transactionOf(txNumber).map(|transaction| transaction.encoded.to_vec( )))
+                       .collect::<Result<Vec<_>, _>>( )?;
+        jar= useDictionaryCompression(jar, TRANSACTION_COLUMN, samples)?;
+      }
+
+      let mut writer= NippyJarWriter::new(jar)?;
+      for txNumber in txRange.into_iter( ).flatten( ) {
+        let transaction= transactionOf(txNumber)?;
+        writer.appendRow([transaction.hash.as_slice( ), &transaction.encoded])?;
+      }
+      writer.finish( )?;
+
+      Ok(SegmentMetrics::of(&writer, startTime.elapsed( )))
+    })?
+  }
+
+  fn pruneDb(&self, db: &Db, blockRange: RangeInclusive<BlockNumber>) -> Result<( ), StaticFilesError> {
+    db.withDbTx(|dbTx| -> Result<( ), StaticFilesError> {
+      if let Some(txRange)= txRangeOf(dbTx, &blockRange)? {
+        dbTx.deleteTransactions(txRange)?;
+      }
+      Ok(( ))
+    })?
+  }
+}
@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+use compression::serde_bridge::SerdeBridgeError;
+use static_files::{nippy_jar::NippyJarError, static_files_error::StaticFilesError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+
+  #[error("Directory {0} doesn't exist")]
+  DirectoryNotFound(PathBuf),
+
+  #[error("Manifest {0} doesn't exist")]
+  ManifestNotFound(PathBuf),
+
+  #[error("{0} isn't a static file in the directory")]
+  UnknownStaticFile(String),
+
+  #[error(transparent)]
+  StaticFiles(#[from] StaticFilesError),
+
+  #[error(transparent)]
+  NippyJar(#[from] NippyJarError),
+
+  #[error(transparent)]
+  Encoding(#[from] SerdeBridgeError),
+
+  #[error(transparent)]
+  Json(#[from] serde_json::Error),
+
+  #[error(transparent)]
+  Io(#[from] std::io::Error)
+}
@@ -0,0 +1,83 @@
+use alloy_primitives::{Address, Bytes, B256, U256};
+use db::models::Receipt;
+use serde_json::json;
+use static_files::{
+  block_headers_segment::{headerAt, BLOCK_HASH_COLUMN, TOTAL_DIFFICULTY_COLUMN},
+  nippy_jar::NippyJarReader,
+  segment::{SegmentHeader, SegmentType},
+  static_files_handler::StaticFilesHander,
+  transaction_receipts_segment::RECEIPT_COLUMN,
+  senders_segment::SENDER_COLUMN,
+  transactions_segment::{TRANSACTION_COLUMN, TX_HASH_COLUMN}
+};
+use super::cli_error::CliError;
+
+/*
+  Prints the rows of the given static file, starting from the given row, as JSON (one line per
+  row). Each row is printed along with the block number (or transaction number) it belongs to.
+
+  Column values are printed decoded, except for the transactions, which are printed in their
+  canonical encoding.
+*/
+pub fn dump(staticFilesHandler: &StaticFilesHander,
+            fileName: &str,
+            fromRow: usize,
+            rowCount: Option<usize>) -> Result<( ), CliError>
+{
+  let Some((segmentType, _))= SegmentType::parseFileName(fileName) else {
+    return Err(CliError::UnknownStaticFile(fileName.to_string( )))
+  };
+  let path= staticFilesHandler.directory( ).join(fileName);
+  if !path.exists( ) {
+    return Err(CliError::UnknownStaticFile(fileName.to_string( )))
+  }
+
+  let reader= NippyJarReader::<SegmentHeader>::open(&path)?;
+  let segmentHeader= reader.jar( ).userHeader( );
+
+  let firstNumber= match &segmentHeader.txRange {
+    Some(txRange) if segmentType.isTxBased( ) => *txRange.start( ),
+    _ => *segmentHeader.blockRange.start( )
+  };
+  let toRow= rowCount.map_or(reader.rowCount( ), |rowCount| fromRow.saturating_add(rowCount))
+                     .min(reader.rowCount( ));
+
+  for row in fromRow..toRow {
+    let number= firstNumber + row as u64;
+
+    let value= match segmentType {
+      SegmentType::BlockHeaders => {
+        let header= headerAt(&reader, row)?;
+        json!({
+          "blockNumber": number,
+          "blockHash": B256::try_from(&*reader.columnValue(row, BLOCK_HASH_COLUMN)?).ok( ),
+          "totalDifficulty": U256::try_from_be_slice(&reader.columnValue(row, TOTAL_DIFFICULTY_COLUMN)?),
+          "header": header
+        })
+      },
+
+      SegmentType::Transactions => json!({
+        "txNumber": number,
+        "hash": B256::try_from(&*reader.columnValue(row, TX_HASH_COLUMN)?).ok( ),
+        "encoded": Bytes::from(reader.columnValue(row, TRANSACTION_COLUMN)?.to_vec( ))
+      }),
+
+      SegmentType::TransactionReceipts => {
+        let receipt: Receipt= compression::serde_bridge::fromBytes(&reader.columnValue(row, RECEIPT_COLUMN)?)?;
+        json!({
+          "txNumber": number,
+          "receipt": receipt
+        })
+      },
+
+      SegmentType::Senders => json!({
+        "txNumber": number,
+        "sender": Address::try_from(&*reader.columnValue(row, SENDER_COLUMN)?).ok( )
+      })
+    };
+
+    println!("{}", serde_json::to_string(&value)?);
+  }
+
+  Ok(( ))
+}
@@ -0,0 +1,64 @@
+use std::{fs, ops::RangeInclusive};
+use static_files::{
+  nippy_jar::NippyJar,
+  segment::{SegmentHeader, SEGMENT_TYPES},
+  static_files_handler::StaticFilesHander
+};
+use super::cli_error::CliError;
+
+// Prints a line for each static file, with the segment and the block range it contains, its row
+// count, the compression used by each column, its size on disk (summed over all its files) and its
+// NippyJar format version.
+pub fn list(staticFilesHandler: &StaticFilesHander) -> Result<( ), CliError> {
+  println!("{:<48} {:<12} {:<24} {:<12} {:<24} {:>10} {:<40} {:>10} {:>7}",
+           "FILE", "SEGMENT", "BLOCK RANGE", "HIGHEST", "TX RANGE", "ROWS", "COLUMN COMPRESSIONS", "SIZE", "VERSION");
+
+  for segmentType in SEGMENT_TYPES {
+    for segmentHeader in staticFilesHandler.segmentHeaders(segmentType) {
+      let fileName= segmentType.fileName(&segmentHeader.blockRange);
+      let jar= NippyJar::<SegmentHeader>::load(&staticFilesHandler.directory( ).join(&fileName))?;
+
+      let mut size= 0;
+      for path in [jar.dataPath( ).to_path_buf( ), jar.offsetsPath( ), jar.configPath( ), jar.dictionariesPath( ), jar.indexPath( ),
+                   jar.keysPath( )]
+      {
+        if path.exists( ) {
+          size += fs::metadata(&path)?.len( );
+        }
+      }
+
+      println!("{:<48} {:<12} {:<24} {:<12} {:<24} {:>10} {:<40} {:>10} {:>7}",
+               fileName,
+               segmentType.asStr( ),
+               formatRange(Some(&segmentHeader.blockRange)),
+               segmentHeader.highestBlock.map_or("-".to_string( ), |highestBlock| highestBlock.to_string( )),
+               formatRange(segmentHeader.txRange.as_ref( )),
+               jar.rowCount( ),
+               format!("{:?}", jar.columnCompressions( )),
+               formatSize(size),
+               jar.version( ));
+    }
+  }
+
+  Ok(( ))
+}
+
+fn formatRange(range: Option<&RangeInclusive<u64>>) -> String {
+  range.map_or("-".to_string( ), |range| format!("{}..={}", range.start( ), range.end( )))
+}
+
+fn formatSize(size: u64) -> String {
+  const UNITS: [&str; 4]= ["B", "KiB", "MiB", "GiB"];
+
+  let mut size= size as f64;
+  let mut unit= 0;
+  while size >= 1024.0 && unit < UNITS.len( ) - 1 {
+    size /= 1024.0;
+    unit += 1;
+  }
+
+  match unit {
+    0 => format!("{} {}", size, UNITS[unit]),
+    _ => format!("{:.1} {}", size, UNITS[unit])
+  }
+}
@@ -0,0 +1,151 @@
+#![allow(non_snake_case)]
+
+/*
+  Command line tool, to inspect and verify the static files in a directory, without running the
+  node :
+
+  (1) list : lists the static files, with the segment and block range each one contains, row counts,
+      column compressions and sizes.
+  (2) dump : prints the rows of a static file as JSON.
+  (3) verify : re-hashes the block headers and transactions, and checks the transactions of each
+      block against the transactions root in its header. Exits with a non-zero code if there's any
+      mismatch.
+  (4) verify-manifest : verifies the files in the directory against a manifest (the one in the
+      directory by default), reporting missing, extra and altered files. Meant to be run on a copied
+      directory, before the node uses it. Exits with a non-zero code if there's any. Static files
+      still being appended to can't be verified, and are only reported.
+  (5) update-manifest : brings the manifest in the directory up to date (e.g. for static files
+      produced before manifests were maintained).
+  (6) upgrade : upgrades the static files written in an older NippyJar format version to the latest
+      one, in place. Older versions can still be read, so this is optional, but it's the only way
+      to get the benefits of the newer format (e.g. smaller offsets files) for the existing history.
+      The node must not be running meanwhile.
+*/
+
+mod cli_error;
+mod list;
+mod dump;
+mod verify;
+mod upgrade;
+
+use std::{path::PathBuf, process::ExitCode};
+use clap::{Parser, Subcommand};
+use static_files::{
+  static_files_handler::StaticFilesHander,
+  static_files_manifest::{StaticFilesManifest, MANIFEST_FILE_NAME}
+};
+use cli_error::CliError;
+
+#[derive(Parser)]
+#[command(about = "Inspects and verifies static files")]
+struct Cli {
+  #[arg(long, help = "Directory containing the static files")]
+  directory: PathBuf,
+
+  #[command(subcommand)]
+  command: Command
+}
+
+#[derive(Subcommand)]
+enum Command {
+  #[command(about = "Lists the static files")]
+  List,
+
+  #[command(about = "Prints the rows of a static file as JSON, one line per row")]
+  Dump {
+    #[arg(help = "Name of the static file (e.g. static_file_headers_0_499999)")]
+    fileName: String,
+
+    #[arg(long, default_value_t = 0, help = "Row to start from")]
+    fromRow: usize,
+
+    #[arg(long, help = "Maximum number of rows to print (all the rows by default)")]
+    rowCount: Option<usize>
+  },
+
+  #[command(about = "Verifies the static files, exiting with a non-zero code on any mismatch")]
+  Verify,
+
+  #[command(about = "Verifies the files against a manifest, exiting with a non-zero code on any missing, extra or altered file")]
+  VerifyManifest {
+    #[arg(long, help = "Path of the manifest (the manifest in the directory by default)")]
+    manifest: Option<PathBuf>
+  },
+
+  #[command(about = "Brings the manifest in the directory up to date")]
+  UpdateManifest,
+
+  #[command(about = "Upgrades the static files to the latest format version, in place (the node must not be running)")]
+  Upgrade
+}
+
+fn main( ) -> ExitCode {
+  match run(Cli::parse( )) {
+    Ok(exitCode) => exitCode,
+    Err(error) => {
+      eprintln!("Error : {}", error);
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn run(cli: Cli) -> Result<ExitCode, CliError> {
+  // The handler would create the directory otherwise.
+  if !cli.directory.is_dir( ) {
+    return Err(CliError::DirectoryNotFound(cli.directory))
+  }
+  let staticFilesHandler= StaticFilesHander::new(cli.directory)?;
+
+  match cli.command {
+    Command::List => list::list(&staticFilesHandler)?,
+
+    Command::Dump { fileName, fromRow, rowCount } => dump::dump(&staticFilesHandler, &fileName, fromRow, rowCount)?,
+
+    Command::Verify => {
+      let report= verify::verify(&staticFilesHandler)?;
+
+      for problem in &report.problems {
+        println!("{}", problem);
+      }
+      println!("Verified {} static files and the transactions roots of {} blocks : {} problems found",
+               report.verifiedStaticFiles, report.verifiedTransactionsRoots, report.problems.len( ));
+
+      if !report.problems.is_empty( ) {
+        return Ok(ExitCode::FAILURE)
+      }
+    },
+
+    Command::VerifyManifest { manifest } => {
+      let manifestPath= manifest.unwrap_or_else(|| staticFilesHandler.directory( ).join(MANIFEST_FILE_NAME));
+      if !manifestPath.is_file( ) {
+        return Err(CliError::ManifestNotFound(manifestPath))
+      }
+
+      let report= StaticFilesManifest::load(&manifestPath)?.verify(staticFilesHandler.directory( ))?;
+
+      for (problem, fileNames) in [("missing", &report.missingFiles), ("extra", &report.extraFiles), ("altered", &report.alteredFiles),
+                                   ("not verified, still being appended to", &report.unverifiedFiles)]
+      {
+        for fileName in fileNames {
+          println!("{} : {}", fileName, problem);
+        }
+      }
+      println!("Verified {} files : {} missing, {} extra, {} altered ({} not verified)",
+               report.verifiedFiles, report.missingFiles.len( ), report.extraFiles.len( ), report.alteredFiles.len( ),
+               report.unverifiedFiles.len( ));
+
+      if !report.isOk( ) {
+        return Ok(ExitCode::FAILURE)
+      }
+    },
+
+    Command::UpdateManifest => StaticFilesManifest::update(&staticFilesHandler)?,
+
+    Command::Upgrade => {
+      let report= upgrade::upgrade(&staticFilesHandler)?;
+      println!("Upgraded {} static files ({} already up to date)", report.upgradedStaticFiles, report.upToDateStaticFiles);
+    }
+  }
+
+  Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,41 @@
+use static_files::{
+  nippy_jar::NippyJar,
+  segment::{SegmentHeader, SEGMENT_TYPES},
+  static_files_handler::StaticFilesHander,
+  static_files_manifest::StaticFilesManifest
+};
+use super::cli_error::CliError;
+
+#[derive(Default)]
+pub struct UpgradeReport {
+  pub upgradedStaticFiles: usize,
+  pub upToDateStaticFiles: usize
+}
+
+/*
+  Upgrades all the static files to the latest NippyJar format version, in place (see
+  NippyJar::upgrade). The node must not be appending to them meanwhile.
+
+  Upgraded files no longer match their manifest entries, so the manifest is brought up to date
+  afterwards.
+*/
+pub fn upgrade(staticFilesHandler: &StaticFilesHander) -> Result<UpgradeReport, CliError> {
+  let mut report= UpgradeReport::default( );
+
+  for segmentType in SEGMENT_TYPES {
+    for segmentHeader in staticFilesHandler.segmentHeaders(segmentType) {
+      let path= staticFilesHandler.directory( ).join(segmentType.fileName(&segmentHeader.blockRange));
+
+      match NippyJar::<SegmentHeader>::upgrade(&path)? {
+        true => report.upgradedStaticFiles += 1,
+        false => report.upToDateStaticFiles += 1
+      }
+    }
+  }
+
+  if report.upgradedStaticFiles > 0 {
+    StaticFilesManifest::update(staticFilesHandler)?;
+  }
+
+  Ok(report)
+}
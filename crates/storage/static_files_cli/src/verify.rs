@@ -0,0 +1,373 @@
+use std::fmt;
+use alloy_primitives::{keccak256, B256};
+use db::models::{orderedTrieRoot, BlockNumber, Receipt, TransactionSigned, TxNumber};
+use static_files::{
+  block_headers_segment::{headerAt, BLOCK_HASH_COLUMN},
+  nippy_jar::NippyJarReader,
+  segment::{SegmentHeader, SegmentType, SEGMENT_TYPES},
+  static_files_error::StaticFilesError,
+  static_files_handler::StaticFilesHander,
+  senders_segment::SENDER_COLUMN,
+  transactions_segment::{TRANSACTION_COLUMN, TX_HASH_COLUMN}
+};
+use super::cli_error::CliError;
+
+// A problem found in a static file.
+pub struct Problem {
+  pub fileName: String,
+  pub description: String
+}
+
+impl fmt::Display for Problem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} : {}", self.fileName, self.description)
+  }
+}
+
+#[derive(Default)]
+pub struct VerificationReport {
+  pub verifiedStaticFiles: usize,
+
+  // Blocks whose transactions were checked against the transactions root in their header.
+  pub verifiedTransactionsRoots: u64,
+
+  pub problems: Vec<Problem>
+}
+
+/*
+  Verifies the static files in the directory, reporting every problem found :
+
+  (1) data not matching its checksums, or row counts not matching the ranges in the SegmentHeader
+  (2) block headers not hashing to the block hash stored next to them, or out of order
+  (3) transactions not hashing to the transaction hash stored next to them
+  (4) transactions of a block not matching the transactions root in its header
+  (5) senders not matching the address recovered from the signature of their transaction
+
+  Static files don't tell which transactions belong to which block. For (4), the transactions of a
+  block are found using the receipts : the cumulative gas used by the block's last transaction is
+  the gas used by the block. Not being able to check any block is a problem too.
+*/
+pub fn verify(staticFilesHandler: &StaticFilesHander) -> Result<VerificationReport, CliError> {
+  let mut report= VerificationReport::default( );
+
+  for segmentType in SEGMENT_TYPES {
+    for segmentHeader in staticFilesHandler.segmentHeaders(segmentType) {
+      let fileName= segmentType.fileName(&segmentHeader.blockRange);
+      let reader= NippyJarReader::<SegmentHeader>::open(&staticFilesHandler.directory( ).join(&fileName))?;
+
+      let mut problems= verifyRowCount(&reader);
+      problems.extend(verifyChecksums(&reader)?);
+      problems.extend(match segmentType {
+        SegmentType::BlockHeaders => verifyHeaders(&reader)?,
+        SegmentType::Transactions => verifyTransactionHashes(&reader)?,
+        SegmentType::TransactionReceipts => Vec::new( ),
+        SegmentType::Senders => verifySenders(staticFilesHandler, &reader)?
+      });
+
+      report.problems.extend(problems.into_iter( ).map(|description| Problem { fileName: fileName.clone( ), description }));
+      report.verifiedStaticFiles += 1;
+    }
+  }
+
+  verifyTransactionsRoots(staticFilesHandler, &mut report);
+
+  // Otherwise, a directory whose segments don't line up would pass without anything being checked.
+  if report.verifiedTransactionsRoots == 0 {
+    report.problems.push(Problem {
+      fileName: staticFilesHandler.directory( ).display( ).to_string( ),
+      description: "no block could have its transactions checked against its transactions root".to_string( )
+    });
+  }
+
+  Ok(report)
+}
+
+fn verifyRowCount(reader: &NippyJarReader<SegmentHeader>) -> Vec<String> {
+  let segmentHeader= reader.jar( ).userHeader( );
+
+  let range= match segmentHeader.segmentType.isTxBased( ) {
+    true => segmentHeader.txRange.clone( ),
+    false => segmentHeader.containedBlockRange( )
+  };
+  let expectedRowCount= range.map_or(0, |range| range.end( ) - range.start( ) + 1);
+
+  match reader.rowCount( ) as u64 == expectedRowCount {
+    true => Vec::new( ),
+    false => vec![format!("contains {} rows, but its SegmentHeader claims {}", reader.rowCount( ), expectedRowCount)]
+  }
+}
+
+fn verifyChecksums(reader: &NippyJarReader<SegmentHeader>) -> Result<Vec<String>, CliError> {
+  let rowsPerChecksum= reader.jar( ).rowsPerChecksum( );
+
+  Ok(reader.verifyChecksums( )?.into_iter( )
+    .map(|rowGroup| {
+      let startRow= rowGroup * rowsPerChecksum;
+      format!("rows {}..{} don't match their checksum", startRow, (startRow + rowsPerChecksum).min(reader.rowCount( )))
+    })
+    .collect( ))
+}
+
+fn verifyHeaders(reader: &NippyJarReader<SegmentHeader>) -> Result<Vec<String>, CliError> {
+  let firstBlockNumber= *reader.jar( ).userHeader( ).blockRange.start( );
+  let mut problems= Vec::new( );
+
+  for row in 0..reader.rowCount( ) {
+    let blockNumber= firstBlockNumber + row as u64;
+
+    let header= match headerAt(reader, row) {
+      Ok(header) => header,
+      Err(error) => {
+        problems.push(format!("header of block {} can't be decoded : {}", blockNumber, error));
+        continue
+      }
+    };
+    if header.number != blockNumber {
+      problems.push(format!("row of block {} contains the header of block {}", blockNumber, header.number));
+    }
+
+    let blockHash= header.hash( );
+    if *reader.columnValue(row, BLOCK_HASH_COLUMN)? != *blockHash {
+      problems.push(format!("header of block {} hashes to {}, not to the stored block hash", blockNumber, blockHash));
+    }
+  }
+
+  Ok(problems)
+}
+
+fn verifyTransactionHashes(reader: &NippyJarReader<SegmentHeader>) -> Result<Vec<String>, CliError> {
+  let firstTxNumber= reader.jar( ).userHeader( ).txRange.as_ref( ).map_or(0, |txRange| *txRange.start( ));
+  let mut problems= Vec::new( );
+
+  for row in 0..reader.rowCount( ) {
+    let txHash= keccak256(reader.columnValue(row, TRANSACTION_COLUMN)?);
+
+    if *reader.columnValue(row, TX_HASH_COLUMN)? != *txHash {
+      problems.push(format!("transaction {} hashes to {}, not to the stored transaction hash", firstTxNumber + row as u64, txHash));
+    }
+  }
+
+  Ok(problems)
+}
+
+fn verifySenders(staticFilesHandler: &StaticFilesHander, reader: &NippyJarReader<SegmentHeader>) -> Result<Vec<String>, CliError> {
+  let firstTxNumber= reader.jar( ).userHeader( ).txRange.as_ref( ).map_or(0, |txRange| *txRange.start( ));
+  let mut problems= Vec::new( );
+
+  for row in 0..reader.rowCount( ) {
+    let txNumber= firstTxNumber + row as u64;
+
+    let recoveredSender= match staticFilesHandler.transactionById(txNumber) {
+      Ok(Some(transaction)) => transaction.recoverSender( ),
+      Ok(None) => {
+        problems.push(format!("transaction {} of the sender is missing", txNumber));
+        continue
+      },
+      Err(error) => {
+        problems.push(format!("transaction {} of the sender can't be read : {}", txNumber, error));
+        continue
+      }
+    };
+
+    match recoveredSender {
+      Some(recoveredSender) if *reader.columnValue(row, SENDER_COLUMN)? == *recoveredSender => { },
+      Some(recoveredSender) =>
+        problems.push(format!("transaction {} is signed by {}, not by the stored sender", txNumber, recoveredSender)),
+      None =>
+        problems.push(format!("sender of transaction {} can't be recovered from its signature", txNumber))
+    }
+  }
+
+  Ok(problems)
+}
+
+/*
+  Walks the blocks contained in all the segments, checking the transactions of each block against
+  the transactions root in its header. Stops at the first block whose transactions can't be told
+  apart (or read), since the transactions of the following blocks can't be found either.
+*/
+fn verifyTransactionsRoots(staticFilesHandler: &StaticFilesHander, report: &mut VerificationReport) {
+  let Some(firstBlockNumber)= staticFilesHandler.segmentHeaders(SegmentType::BlockHeaders).first( )
+                                                .map(|segmentHeader| *segmentHeader.blockRange.start( ))
+  else {
+    return
+  };
+  // Senders aren't needed, so they don't limit the blocks walked (and directories written before
+  // the senders segment was added have none).
+  let Some(Some(lastBlockNumber))= [SegmentType::BlockHeaders, SegmentType::Transactions, SegmentType::TransactionReceipts]
+    .map(|segmentType| staticFilesHandler.highestBlockNumber(segmentType))
+    .into_iter( )
+    .min( )
+  else {
+    return
+  };
+
+  // The transactions segment must start at the same block, for the first transaction number to be
+  // known.
+  let transactionsSegmentHeaders= staticFilesHandler.segmentHeaders(SegmentType::Transactions);
+  if transactionsSegmentHeaders.first( ).map(|segmentHeader| *segmentHeader.blockRange.start( )) != Some(firstBlockNumber) {
+    return
+  }
+  let mut nextTxNumber= transactionsSegmentHeaders.iter( )
+                                                  .find_map(|segmentHeader| segmentHeader.txRange.as_ref( ).map(|txRange| *txRange.start( )))
+                                                  .unwrap_or(0);
+
+  for blockNumber in firstBlockNumber..=lastBlockNumber {
+    let problem= |segmentType: SegmentType, description: String| Problem {
+      fileName: fileNameOf(staticFilesHandler, segmentType, blockNumber),
+      description
+    };
+
+    let header= match staticFilesHandler.headerByNumber(blockNumber) {
+      Ok(Some(header)) => header,
+      Ok(None) => {
+        report.problems.push(problem(SegmentType::BlockHeaders, format!("header of block {} is missing", blockNumber)));
+        return
+      },
+      Err(error) => {
+        report.problems.push(problem(SegmentType::BlockHeaders, format!("header of block {} can't be read : {}", blockNumber, error)));
+        return
+      }
+    };
+
+    // Every transaction uses some gas, so the block's transactions end when the cumulative gas used
+    // reaches the gas used by the block.
+    let mut transactions= Vec::new( );
+    let mut cumulativeGasUsed= 0;
+
+    while cumulativeGasUsed < header.gasUsed {
+      let (transaction, receipt)= match transactionAndReceipt(staticFilesHandler, nextTxNumber) {
+        Ok(Some(transactionAndReceipt)) => transactionAndReceipt,
+        Ok(None) => {
+          report.problems.push(problem(SegmentType::Transactions, format!("transactions of block {} are missing", blockNumber)));
+          return
+        },
+        Err(error) => {
+          report.problems.push(problem(SegmentType::Transactions, format!("transactions of block {} can't be read : {}", blockNumber, error)));
+          return
+        }
+      };
+      if receipt.cumulativeGasUsed <= cumulativeGasUsed {
+        break
+      }
+
+      cumulativeGasUsed= receipt.cumulativeGasUsed;
+      transactions.push(transaction.encoded);
+      nextTxNumber += 1;
+    }
+
+    if cumulativeGasUsed != header.gasUsed {
+      report.problems.push(problem(SegmentType::TransactionReceipts,
+                                   format!("receipts of block {} don't add up to the gas used by the block", blockNumber)));
+      return
+    }
+
+    let transactionsRoot: B256= orderedTrieRoot(&transactions);
+    if transactionsRoot != header.transactionsRoot {
+      report.problems.push(problem(SegmentType::Transactions, format!("transactions of block {} have root {}, but the header has {}",
+                                                                      blockNumber, transactionsRoot, header.transactionsRoot)));
+    }
+    report.verifiedTransactionsRoots += 1;
+  }
+}
+
+fn transactionAndReceipt(staticFilesHandler: &StaticFilesHander,
+                         txNumber: TxNumber) -> Result<Option<(TransactionSigned, Receipt)>, StaticFilesError>
+{
+  let transaction= staticFilesHandler.transactionById(txNumber)?;
+  let receipt= staticFilesHandler.receiptById(txNumber)?;
+
+  Ok(transaction.zip(receipt))
+}
+
+fn fileNameOf(staticFilesHandler: &StaticFilesHander, segmentType: SegmentType, blockNumber: BlockNumber) -> String {
+  staticFilesHandler.segmentHeaderOf(segmentType, blockNumber)
+    .map_or_else(|| format!("block {}", blockNumber), |segmentHeader| segmentType.fileName(&segmentHeader.blockRange))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{path::Path, sync::Arc};
+  use alloy_primitives::{Address, U256};
+  use db::{models::Header, test_utils::{signedTransaction, MemoryDb}};
+  use static_files::static_files_writer::StaticFilesWriter;
+  use super::*;
+
+  /*
+    Writes blocks 0..=9 to static files in the directory. Block n has n % 3 transactions.
+
+    The block hash of the block numbered invalidBlockHashAt (if any) doesn't match its header, and
+    the transaction hash of the transaction numbered invalidTxHashAt (if any) doesn't match the
+    transaction.
+  */
+  fn writeBlocks(directory: &Path, invalidBlockHashAt: Option<BlockNumber>, invalidTxHashAt: Option<TxNumber>) -> Arc<StaticFilesHander> {
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.to_path_buf( )).unwrap( ));
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), &MemoryDb::default( )).unwrap( );
+
+    for blockNumber in 0..10 {
+      let firstTxNumber= staticFilesWriter.nextTxNumber( );
+      let (mut transactions, senders): (Vec<TransactionSigned>, Vec<Address>)= (firstTxNumber..(firstTxNumber + blockNumber % 3))
+        .map(|txNumber| signedTransaction(txNumber, 0))
+        .unzip( );
+      let receipts: Vec<Receipt>= (0..transactions.len( ) as u64)
+        .map(|i| Receipt { cumulativeGasUsed: 21_000 * (i + 1), ..Default::default( ) })
+        .collect( );
+
+      let encodedTransactions: Vec<&[u8]>= transactions.iter( ).map(|transaction| &transaction.encoded[..]).collect( );
+      let header= Header {
+        number: blockNumber,
+        gasUsed: 21_000 * transactions.len( ) as u64,
+        transactionsRoot: orderedTrieRoot(&encodedTransactions),
+        ..Default::default( )
+      };
+      let blockHash= match invalidBlockHashAt == Some(blockNumber) {
+        true => B256::ZERO,
+        false => header.hash( )
+      };
+      for (txNumber, transaction) in (firstTxNumber..).zip(&mut transactions) {
+        if invalidTxHashAt == Some(txNumber) {
+          transaction.hash= B256::ZERO;
+        }
+      }
+
+      staticFilesWriter.appendBlock(&header, blockHash, U256::from(blockNumber), &transactions, &receipts, &senders).unwrap( );
+    }
+    staticFilesWriter.commit( ).unwrap( );
+
+    staticFilesHandler
+  }
+
+  #[test]
+  fn intactStaticFilesHaveNoProblems( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let report= verify(&writeBlocks(directory.path( ), None, None)).unwrap( );
+
+    let problems: Vec<String>= report.problems.iter( ).map(Problem::to_string).collect( );
+    assert!(problems.is_empty( ), "{:?}", problems);
+    assert_eq!(report.verifiedStaticFiles, 4);
+    assert_eq!(report.verifiedTransactionsRoots, 10);
+  }
+
+  #[test]
+  fn headersNotMatchingTheirBlockHashAreReported( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let report= verify(&writeBlocks(directory.path( ), Some(4), None)).unwrap( );
+
+    assert_eq!(report.problems.len( ), 1);
+    assert_eq!(report.problems[0].fileName, SegmentType::BlockHeaders.fileName(&(0..=499_999)));
+    assert!(report.problems[0].description.starts_with("header of block 4 hashes to"));
+  }
+
+  #[test]
+  fn transactionsNotMatchingTheirHashAreReported( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let report= verify(&writeBlocks(directory.path( ), None, Some(3))).unwrap( );
+
+    assert_eq!(report.problems.len( ), 1);
+    assert_eq!(report.problems[0].fileName, SegmentType::Transactions.fileName(&(0..=499_999)));
+    assert!(report.problems[0].description.starts_with("transaction 3 hashes to"));
+
+    // The transactions themselves are intact, so the transactions roots still match.
+    assert_eq!(report.verifiedTransactionsRoots, 10);
+  }
+}
@@ -1,9 +1,11 @@
+use std::sync::Arc;
 use ethers_core::types::BlockNumber;
 use tokio::sync::mpsc::UnboundedSender;
 use db::interfaces;
+use static_files::static_files_writer::StaticFilesWriter;
 use super::{
   metrics::MetricEvent, pipeline_control_flow::PipelineControlFlow, pipeline_error::PipelineError,
-  pipeline_event::PipelineEvent, stage::Stage
+  pipeline_event::PipelineEvent, stage::{DbTransaction, Stage, StageRollbackInput}
 };
 use tracing::warn;
 
@@ -16,7 +18,8 @@ use tracing::warn;
 
   In case of a state validation error (determined by the consensus engine) in one of the stages, the
   pipeline will rollback the stages in reverse order of execution.
-  It's also possible to manually request for a rollback.
+  It's also possible to manually request for a rollback. Static files are truncated as well during a
+  rollback, so that they never hold non-canonical data.
 
   After the entire pipeline has been run, it will run again unless asked to stop (see
   self.stopSyncAfterReachingBlock).
@@ -26,8 +29,13 @@ pub struct Pipeline<Db>
     Db: interfaces::db::Db
 {
 
+  db: Arc<Db>,
+
   stages: Vec<Box<dyn Stage<Db>>>,
 
+  // Appends finalized blocks to static files, if they are enabled.
+  staticFilesWriter: Option<StaticFilesWriter>,
+
   // The synchronization process will stop after reaching this block.
   stopSyncAfterReachingBlock: Option<BlockNumber>,
 
@@ -63,6 +71,37 @@ impl<Db> Pipeline<Db>
     unimplemented!( )
   }
 
+  /*
+    Rolls the pipeline back to the target block.
+
+    The static files get truncated first, and then the stages get rolled back in reverse order of
+    execution. If the node dies in between, the rollback is requested again at startup, and the
+    static files are already free of the blocks being rolled back. The other way round, static files
+    would end up holding non-canonical data.
+  */
+  pub async fn rollback(&mut self,
+                        dbTransaction: &mut dyn DbTransaction,
+                        input: StageRollbackInput) -> Result<( ), PipelineError>
+  {
+    let targetBlock= input.targetBlock.as_number( )
+                       .ok_or(PipelineError::InvalidRollbackTarget(input.targetBlock))?;
+
+    if let Some(staticFilesWriter)= &mut self.staticFilesWriter {
+      staticFilesWriter.truncateToBlock(self.db.as_ref( ), targetBlock.as_u64( ))?;
+    }
+
+    for stage in self.stages.iter_mut( ).rev( ) {
+      let input= StageRollbackInput {
+        currentBlock: input.currentBlock,
+        targetBlock: input.targetBlock,
+        responsibleBadBlock: input.responsibleBadBlock
+      };
+      stage.rollback(dbTransaction, input).await.map_err(PipelineError::StageRollback)?;
+    }
+
+    Ok(( ))
+  }
+
   async fn executeStage(&mut self,
                         blockReachedByPreviousStage: Option<BlockNumber>,
                         stageIndex: usize)
@@ -86,7 +125,7 @@ impl<Db> Pipeline<Db>
         warn!(
           target: "sync::pipeline",
           stage= %stageId,
-          stopSyncAfterReachingBlock= self.stopSyncAfterReachingBlock,
+          stopSyncAfterReachingBlock= ?self.stopSyncAfterReachingBlock,
           "Skipping Stage, since it reached 'stopSyncAfterReachingBlock'"
         );
 
@@ -122,4 +161,101 @@ impl<Db> Pipeline<Db>
 
     Ok(( ))
   }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex;
+  use alloy_primitives::U256;
+  use async_trait::async_trait;
+  use db::{models::{BlockBodyIndices, Header}, test_utils::MemoryDb};
+  use static_files::{segment::SegmentType, static_files_handler::StaticFilesHander};
+  use crate::{stage::{StageExecutionError, StageExecutionInput, StageExecutionOutput, StageRollbackOutput}, stage_id::StageId};
+  use super::*;
+
+  struct TestDbTransaction;
+
+  impl DbTransaction for TestDbTransaction { }
+
+  // Records the highest block in the static files, at the time it gets rolled back.
+  struct TestStage {
+    staticFilesHandler: Arc<StaticFilesHander>,
+    highestBlocksAtRollback: Arc<Mutex<Vec<Option<u64>>>>
+  }
+
+  #[async_trait]
+  impl Stage<MemoryDb> for TestStage {
+    fn id(&self) -> StageId {
+      unimplemented!( )
+    }
+
+    async fn execute(&mut self,
+                     _: &mut dyn DbTransaction,
+                     _: StageExecutionInput) -> Result<StageExecutionOutput, StageExecutionError>
+    {
+      unimplemented!( )
+    }
+
+    async fn rollback(&mut self,
+                      _: &mut dyn DbTransaction,
+                      input: StageRollbackInput) -> Result<StageRollbackOutput, Box<dyn std::error::Error>>
+    {
+      let highestBlock= self.staticFilesHandler.highestBlockNumber(SegmentType::BlockHeaders);
+      self.highestBlocksAtRollback.lock( ).unwrap( ).push(highestBlock);
+      Ok(StageRollbackOutput { blockReached: input.targetBlock })
+    }
+  }
+
+  #[tokio::test]
+  async fn staticFilesAreTruncatedBeforeTheStagesGetRolledBack( ) {
+    let directory= tempfile::tempdir( ).unwrap( );
+    let db= Arc::new(MemoryDb::default( ));
+    let staticFilesHandler= Arc::new(StaticFilesHander::new(directory.path( ).to_path_buf( )).unwrap( ));
+
+    let mut staticFilesWriter= StaticFilesWriter::new(staticFilesHandler.clone( ), db.as_ref( )).unwrap( );
+    // Block body indices always live in the database.
+    for blockNumber in 0..10 {
+      let header= Header { number: blockNumber, ..Default::default( ) };
+      db.tables( ).blockBodyIndices.insert(blockNumber, BlockBodyIndices { firstTxNumber: 0, txCount: 0 });
+      staticFilesWriter.appendBlock(&header, header.hash( ), U256::ZERO, &[ ], &[ ], &[ ]).unwrap( );
+    }
+    staticFilesWriter.commit( ).unwrap( );
+
+    let highestBlocksAtRollback= Arc::new(Mutex::new(Vec::new( )));
+    let stages: Vec<Box<dyn Stage<MemoryDb>>>= (0..2).map(|_| {
+      Box::new(TestStage {
+        staticFilesHandler: staticFilesHandler.clone( ),
+        highestBlocksAtRollback: highestBlocksAtRollback.clone( )
+      }) as Box<dyn Stage<MemoryDb>>
+    }).collect( );
+
+    let mut pipeline= Pipeline {
+      db,
+      stages,
+      staticFilesWriter: Some(staticFilesWriter),
+      stopSyncAfterReachingBlock: None,
+      eventEmitters: Vec::new( ),
+      metricEventsEmitter: None
+    };
+
+    let input= StageRollbackInput {
+      currentBlock: BlockNumber::Number(9.into( )),
+      targetBlock: BlockNumber::Number(5.into( )),
+      responsibleBadBlock: None
+    };
+    pipeline.rollback(&mut TestDbTransaction, input).await.unwrap( );
+
+    assert_eq!(staticFilesHandler.highestBlockNumber(SegmentType::BlockHeaders), Some(5));
+    assert_eq!(*highestBlocksAtRollback.lock( ).unwrap( ), vec![Some(5), Some(5)]);
+    assert_eq!(pipeline.staticFilesWriter.as_ref( ).unwrap( ).nextBlockNumber( ), 6);
+
+    // Only block numbers can be rolled back to.
+    let input= StageRollbackInput {
+      currentBlock: BlockNumber::Number(5.into( )),
+      targetBlock: BlockNumber::Latest,
+      responsibleBadBlock: None
+    };
+    assert!(matches!(pipeline.rollback(&mut TestDbTransaction, input).await,
+                     Err(PipelineError::InvalidRollbackTarget(BlockNumber::Latest))));
+  }
 }
\ No newline at end of file
@@ -1 +1,15 @@
-pub enum PipelineError { }
\ No newline at end of file
+use ethers_core::types::BlockNumber;
+use static_files::static_files_error::StaticFilesError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineError {
+
+  #[error("Can't rollback to {0:?}, since it isn't a block number")]
+  InvalidRollbackTarget(BlockNumber),
+
+  #[error(transparent)]
+  StaticFiles(#[from] StaticFilesError),
+
+  #[error(transparent)]
+  StageRollback(Box<dyn std::error::Error>)
+}
\ No newline at end of file
@@ -1,12 +1,26 @@
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
 pub struct EventEmitters<T> {
   emitters: Vec<UnboundedSender<T>>
 }
 
+impl<T> Default for EventEmitters<T> {
+  fn default( ) -> Self {
+    Self { emitters: Vec::new( ) }
+  }
+}
+
 impl<T: Clone> EventEmitters<T> {
   // Makes all the emitters emit the given event.
   pub fn emit(&mut self, event: T) {
     self.emitters.retain(|emitter| emitter.send(event.clone( )).is_ok( ))
   }
-}
\ No newline at end of file
+
+  // Adds a new emitter, and returns the receiving end of it.
+  pub fn subscribe(&mut self) -> UnboundedReceiver<T> {
+    let (emitter, receiver)= mpsc::unbounded_channel( );
+    self.emitters.push(emitter);
+
+    receiver
+  }
+}